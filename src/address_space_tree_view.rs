@@ -1,10 +1,15 @@
-use crate::model::ModelMessage;
+use crate::model::{node_class_from_i32, ModelMessage};
 use gtk::prelude::{BuilderExtManual, ToValue, TreeStoreExtManual};
-use gtk::{TreeIter, TreeModelExt, TreePath, TreeStoreExt};
+use gtk::{
+    TreeIter, TreeModelExt, TreePath, TreeSelectionExt, TreeStoreExt, TreeViewExt, WidgetExt,
+};
 use opcua_client::prelude::*;
 use riker::actor::{ActorRef, Tell};
 use std::{collections::HashMap, rc::Rc, str::FromStr};
 
+/// The drag target of nodes dragged from the address space tree into other panes
+const NODES_DRAG_TARGET: &str = "application/x-opcua-nodes";
+
 pub struct AddressSpaceTreeView {
     address_space_tree: Rc<gtk::TreeView>,
     address_space_model: Rc<gtk::TreeStore>,
@@ -22,6 +27,21 @@ impl AddressSpaceTreeView {
         let address_space_model: Rc<gtk::TreeStore> =
             Rc::new(builder.get_object("address_space_model").unwrap());
 
+        // The selected nodes can be dragged into other panes, e.g. to monitor them
+        address_space_tree.enable_model_drag_source(
+            gdk::ModifierType::BUTTON1_MASK,
+            &[Self::nodes_drag_target()],
+            gdk::DragAction::COPY,
+        );
+        address_space_tree.connect_drag_data_get(|tree, _, selection_data, _, _| {
+            let data = Self::nodes_from_selection(&tree.get_selection())
+                .iter()
+                .map(|(node_id, display_name)| format!("{}\t{}", node_id, display_name))
+                .collect::<Vec<_>>()
+                .join("\n");
+            selection_data.set(&selection_data.get_target(), 8, data.as_bytes());
+        });
+
         AddressSpaceTreeView {
             address_space_tree,
             model,
@@ -45,6 +65,7 @@ impl AddressSpaceTreeView {
     const COL_BROWSE_NAME: u32 = 2;
     const COL_DISPLAY_NAME: u32 = 3;
     const COL_REFERENCE_TYPE_ID: u32 = 4;
+    const COL_NODE_CLASS: u32 = 5;
 
     pub fn on_browse_node_result(
         &mut self,
@@ -105,6 +126,114 @@ impl AddressSpaceTreeView {
         false
     }
 
+    /// Selects the row under the pointer, e.g. before showing a context menu for it. A row
+    /// that is already part of a multiple selection leaves the selection as it is.
+    pub fn select_at_pos(&self, x: f64, y: f64) {
+        if let Some((Some(path), _, _, _)) =
+            self.address_space_tree.get_path_at_pos(x as i32, y as i32)
+        {
+            let selection = self.address_space_tree.get_selection();
+            if !selection.path_is_selected(&path) {
+                selection.unselect_all();
+                selection.select_path(&path);
+            }
+        }
+    }
+
+    /// Returns the node id of the selected row, if just one is selected
+    pub fn selected_node_id(&self) -> Option<NodeId> {
+        Self::node_id_from_selection(&self.address_space_tree.get_selection())
+    }
+
+    /// Returns the node class of the selected row, if just one is selected
+    pub fn selected_node_class(&self) -> Option<NodeClass> {
+        let (model, iter) = Self::single_selected(&self.address_space_tree.get_selection())?;
+        let v = model.get_value(&iter, Self::COL_NODE_CLASS as i32);
+        match v.get::<i32>() {
+            Ok(Some(node_class)) => Some(node_class_from_i32(node_class)),
+            _ => None,
+        }
+    }
+
+    /// Returns the node id of the parent of the selected row, if just one is selected. This is
+    /// the object a method was browsed from.
+    pub fn selected_parent_node_id(&self) -> Option<NodeId> {
+        let (model, iter) = Self::single_selected(&self.address_space_tree.get_selection())?;
+        let parent = model.iter_parent(&iter)?;
+        let v = model.get_value(&parent, Self::COL_NODE_ID as i32);
+        match v.get::<String>() {
+            Ok(Some(node_id)) if !node_id.is_empty() => NodeId::from_str(&node_id).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the node ids and display names of every selected row
+    pub fn selected_nodes(&self) -> Vec<(NodeId, String)> {
+        Self::nodes_from_selection(&self.address_space_tree.get_selection())
+    }
+
+    /// The drag target for panes that accept nodes dragged from the tree
+    pub fn nodes_drag_target() -> gtk::TargetEntry {
+        gtk::TargetEntry::new(NODES_DRAG_TARGET, gtk::TargetFlags::SAME_APP, 0)
+    }
+
+    /// Gets the node ids and display names from the data of nodes dragged from the tree
+    pub fn nodes_from_drag_data(data: &[u8]) -> Vec<(NodeId, String)> {
+        String::from_utf8_lossy(data)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, '\t');
+                let node_id = NodeId::from_str(parts.next()?).ok()?;
+                Some((node_id, parts.next().unwrap_or_default().to_string()))
+            })
+            .collect()
+    }
+
+    fn nodes_from_selection(selection: &gtk::TreeSelection) -> Vec<(NodeId, String)> {
+        let (paths, model) = selection.get_selected_rows();
+        paths
+            .iter()
+            .filter_map(|path| {
+                let iter = model.get_iter(path)?;
+                let node_id = match model
+                    .get_value(&iter, Self::COL_NODE_ID as i32)
+                    .get::<String>()
+                {
+                    Ok(Some(node_id)) if !node_id.is_empty() => NodeId::from_str(&node_id).ok()?,
+                    _ => return None,
+                };
+                let display_name = model
+                    .get_value(&iter, Self::COL_DISPLAY_NAME as i32)
+                    .get::<String>()
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
+                Some((node_id, display_name))
+            })
+            .collect()
+    }
+
+    /// Returns the node id of the row selected in the address space tree, if just one is
+    /// selected. This is for panes that track the selection themselves.
+    pub fn node_id_from_selection(selection: &gtk::TreeSelection) -> Option<NodeId> {
+        let (model, iter) = Self::single_selected(selection)?;
+        let v = model.get_value(&iter, Self::COL_NODE_ID as i32);
+        match v.get::<String>() {
+            Ok(Some(node_id)) if !node_id.is_empty() => NodeId::from_str(&node_id).ok(),
+            _ => None,
+        }
+    }
+
+    /// The tree allows multiple selection, so this gets the row when only one is selected
+    fn single_selected(selection: &gtk::TreeSelection) -> Option<(gtk::TreeModel, TreeIter)> {
+        let (paths, model) = selection.get_selected_rows();
+        if paths.len() != 1 {
+            return None;
+        }
+        let iter = model.get_iter(&paths[0])?;
+        Some((model, iter))
+    }
+
     fn insert_reference(&mut self, r: &ReferenceDescription, parent: Option<TreeIter>) {
         println!("Result = {:?}", r);
         let dummy_node = false;
@@ -112,6 +241,7 @@ impl AddressSpaceTreeView {
         let browse_name = format!("{}", r.browse_name.name);
         let display_name = format!("{}", r.display_name);
         let reference_type_id = format!("{}", r.reference_type_id);
+        let node_class = r.node_class as i32;
 
         let columns = &[
             Self::COL_DUMMY,
@@ -119,6 +249,7 @@ impl AddressSpaceTreeView {
             Self::COL_BROWSE_NAME,
             Self::COL_DISPLAY_NAME,
             Self::COL_REFERENCE_TYPE_ID,
+            Self::COL_NODE_CLASS,
         ];
         let values: Vec<&dyn ToValue> = vec![
             &dummy_node,
@@ -126,6 +257,7 @@ impl AddressSpaceTreeView {
             &browse_name,
             &display_name,
             &reference_type_id,
+            &node_class,
         ];
 
         // Insert element into tree
@@ -151,12 +283,14 @@ impl AddressSpaceTreeView {
         let browse_name = "";
         let display_name = "";
         let reference_type_id = "";
+        let node_class = NodeClass::Unspecified as i32;
         let columns = &[
             Self::COL_DUMMY,
             Self::COL_NODE_ID,
             Self::COL_BROWSE_NAME,
             Self::COL_DISPLAY_NAME,
             Self::COL_REFERENCE_TYPE_ID,
+            Self::COL_NODE_CLASS,
         ];
         let values: Vec<&dyn ToValue> = vec![
             &dummy_node,
@@ -164,6 +298,7 @@ impl AddressSpaceTreeView {
             &browse_name,
             &display_name,
             &reference_type_id,
+            &node_class,
         ];
 
         self.insert_with_values(Some(&parent), None, columns, &values)
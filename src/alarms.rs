@@ -0,0 +1,258 @@
+use opcua_client::prelude::*;
+
+use crate::{
+    events::{EventField, EventMonitor, WhereClause},
+    value_format,
+};
+
+/// The id of the event monitor that receives the events of conditions. The monitors of the
+/// events pane are numbered from 1.
+pub const CONDITIONS_MONITOR_ID: u32 = 0;
+
+/// The fields selected from condition events after the ConditionId, by the type that declares
+/// each and its browse path
+const CONDITION_FIELDS: [(ObjectTypeId, &str); 15] = [
+    (ObjectTypeId::BaseEventType, "EventId"),
+    (ObjectTypeId::BaseEventType, "EventType"),
+    (ObjectTypeId::BaseEventType, "SourceName"),
+    (ObjectTypeId::BaseEventType, "Time"),
+    (ObjectTypeId::BaseEventType, "Message"),
+    (ObjectTypeId::BaseEventType, "Severity"),
+    (ObjectTypeId::ConditionType, "ConditionName"),
+    (ObjectTypeId::ConditionType, "BranchId"),
+    (ObjectTypeId::ConditionType, "Retain"),
+    (ObjectTypeId::ConditionType, "EnabledState/Id"),
+    (ObjectTypeId::ConditionType, "Comment"),
+    (ObjectTypeId::AcknowledgeableConditionType, "AckedState/Id"),
+    (
+        ObjectTypeId::AcknowledgeableConditionType,
+        "ConfirmedState/Id",
+    ),
+    (ObjectTypeId::AlarmConditionType, "ActiveState/Id"),
+    (
+        ObjectTypeId::AlarmConditionType,
+        "ShelvingState/CurrentState",
+    ),
+];
+
+/// The monitor of the Server object's events that receives the state of every condition, and
+/// the events that start and end a ConditionRefresh
+pub fn conditions_monitor() -> EventMonitor {
+    let mut monitor = EventMonitor::new(CONDITIONS_MONITOR_ID, ObjectId::Server.into(), "Server");
+    monitor.select = std::iter::once(EventField::condition_id())
+        .chain(CONDITION_FIELDS.iter().map(|(event_type_id, path)| {
+            EventField::new(
+                (*event_type_id).into(),
+                path.split('/')
+                    .map(|name| QualifiedName::new(0, name))
+                    .collect(),
+            )
+        }))
+        .collect();
+    let of_type = |event_type_id: ObjectTypeId, name: &str| {
+        Box::new(WhereClause::OfType(event_type_id.into(), name.to_string()))
+    };
+    monitor.where_clause = Some(WhereClause::Or(
+        of_type(ObjectTypeId::ConditionType, "ConditionType"),
+        Box::new(WhereClause::Or(
+            of_type(ObjectTypeId::RefreshStartEventType, "RefreshStartEventType"),
+            of_type(ObjectTypeId::RefreshEndEventType, "RefreshEndEventType"),
+        )),
+    ));
+    monitor
+}
+
+/// The state of a condition, or of one of its branches, from its last event
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub condition_id: NodeId,
+    /// Null for the current state of the condition, otherwise the branch holding a previous
+    /// state that still needs to be acknowledged or confirmed
+    pub branch_id: NodeId,
+    /// Identifies the state that Acknowledge, Confirm and AddComment refer to
+    pub event_id: ByteString,
+    pub name: String,
+    pub source: String,
+    pub time: Option<DateTime>,
+    pub message: String,
+    pub severity: u16,
+    /// False when the condition no longer needs to be shown
+    pub retain: bool,
+    pub enabled: bool,
+    /// None for conditions without the state, e.g. Active for conditions that aren't alarms
+    pub active: Option<bool>,
+    pub acked: Option<bool>,
+    pub confirmed: Option<bool>,
+    /// The current state of the shelving state machine, e.g. Unshelved or TimedShelved
+    pub shelving: Option<String>,
+    pub comment: String,
+}
+
+impl Condition {
+    /// True if the condition and branch are the same as the other's, whatever their state
+    pub fn is_same(&self, other: &Condition) -> bool {
+        self.condition_id == other.condition_id && self.branch_id == other.branch_id
+    }
+
+    pub fn is_shelved(&self) -> bool {
+        self.shelving.as_ref().map_or(false, |s| s != "Unshelved")
+    }
+}
+
+/// What a condition event tells
+#[derive(Debug, Clone)]
+pub enum ConditionEvent {
+    /// The server is about to send the state of every condition again
+    RefreshStart,
+    /// Every condition has been sent
+    RefreshEnd,
+    Condition(Condition),
+}
+
+impl ConditionEvent {
+    /// Reads the fields of an event received by the conditions monitor
+    pub fn from_fields(fields: &[Variant]) -> Option<Self> {
+        let field = |path: &str| {
+            CONDITION_FIELDS
+                .iter()
+                .position(|(_, p)| *p == path)
+                .and_then(|i| fields.get(i + 1))
+        };
+        let boolean = |path: &str| match field(path) {
+            Some(Variant::Boolean(v)) => Some(*v),
+            _ => None,
+        };
+        let text = |path: &str| match field(path) {
+            Some(Variant::String(v)) => v.as_ref().to_string(),
+            Some(Variant::LocalizedText(v)) => v.text.as_ref().to_string(),
+            Some(Variant::Empty) | None => String::new(),
+            Some(v) => value_format::variant_to_string(v, Default::default()),
+        };
+
+        let event_type_id = match field("EventType") {
+            Some(Variant::NodeId(node_id)) => (**node_id).clone(),
+            _ => return None,
+        };
+        if event_type_id == ObjectTypeId::RefreshStartEventType.into() {
+            return Some(ConditionEvent::RefreshStart);
+        } else if event_type_id == ObjectTypeId::RefreshEndEventType.into() {
+            return Some(ConditionEvent::RefreshEnd);
+        }
+        let condition_id = match fields.first() {
+            Some(Variant::NodeId(node_id)) => (**node_id).clone(),
+            _ => return None,
+        };
+        let event_id = match field("EventId") {
+            Some(Variant::ByteString(event_id)) => event_id.clone(),
+            _ => ByteString::null(),
+        };
+        Some(ConditionEvent::Condition(Condition {
+            condition_id,
+            branch_id: match field("BranchId") {
+                Some(Variant::NodeId(node_id)) => (**node_id).clone(),
+                _ => NodeId::null(),
+            },
+            event_id,
+            name: text("ConditionName"),
+            source: text("SourceName"),
+            time: match field("Time") {
+                Some(Variant::DateTime(time)) => Some((**time).clone()),
+                _ => None,
+            },
+            message: text("Message"),
+            severity: match field("Severity") {
+                Some(Variant::UInt16(severity)) => *severity,
+                _ => 0,
+            },
+            retain: boolean("Retain").unwrap_or(true),
+            enabled: boolean("EnabledState/Id").unwrap_or(true),
+            active: boolean("ActiveState/Id"),
+            acked: boolean("AckedState/Id"),
+            confirmed: boolean("ConfirmedState/Id"),
+            shelving: match field("ShelvingState/CurrentState") {
+                Some(Variant::Empty) | None => None,
+                _ => Some(text("ShelvingState/CurrentState")),
+            },
+            comment: text("Comment"),
+        }))
+    }
+}
+
+/// The methods of a condition an operator can call
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionAction {
+    Acknowledge(LocalizedText),
+    Confirm(LocalizedText),
+    AddComment(LocalizedText),
+    Enable,
+    Disable,
+    /// Shelves the alarm for a time in milliseconds
+    TimedShelve(f64),
+    OneShotShelve,
+    Unshelve,
+}
+
+impl ConditionAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConditionAction::Acknowledge(_) => "Acknowledge",
+            ConditionAction::Confirm(_) => "Confirm",
+            ConditionAction::AddComment(_) => "AddComment",
+            ConditionAction::Enable => "Enable",
+            ConditionAction::Disable => "Disable",
+            ConditionAction::TimedShelve(_) => "TimedShelve",
+            ConditionAction::OneShotShelve => "OneShotShelve",
+            ConditionAction::Unshelve => "Unshelve",
+        }
+    }
+
+    pub fn method_id(&self) -> NodeId {
+        match self {
+            ConditionAction::Acknowledge(_) => MethodId::AcknowledgeableConditionType_Acknowledge,
+            ConditionAction::Confirm(_) => MethodId::AcknowledgeableConditionType_Confirm,
+            ConditionAction::AddComment(_) => MethodId::ConditionType_AddComment,
+            ConditionAction::Enable => MethodId::ConditionType_Enable,
+            ConditionAction::Disable => MethodId::ConditionType_Disable,
+            ConditionAction::TimedShelve(_) => MethodId::ShelvedStateMachineType_TimedShelve,
+            ConditionAction::OneShotShelve => MethodId::ShelvedStateMachineType_OneShotShelve,
+            ConditionAction::Unshelve => MethodId::ShelvedStateMachineType_Unshelve,
+        }
+        .into()
+    }
+
+    /// The shelving methods are called on the ShelvingState object of the alarm, the others on
+    /// the condition itself
+    pub fn is_shelving(&self) -> bool {
+        matches!(
+            self,
+            ConditionAction::TimedShelve(_)
+                | ConditionAction::OneShotShelve
+                | ConditionAction::Unshelve
+        )
+    }
+
+    /// The input arguments of the method, which refer to the state by its EventId where they
+    /// need to
+    pub fn input_arguments(&self, event_id: &ByteString) -> Vec<Variant> {
+        match self {
+            ConditionAction::Acknowledge(comment)
+            | ConditionAction::Confirm(comment)
+            | ConditionAction::AddComment(comment) => vec![
+                Variant::from(event_id.clone()),
+                Variant::from(comment.clone()),
+            ],
+            ConditionAction::TimedShelve(shelving_time) => vec![Variant::Double(*shelving_time)],
+            _ => vec![],
+        }
+    }
+}
+
+/// A method to call on a condition, with the state it refers to
+#[derive(Debug, Clone)]
+pub struct ConditionCall {
+    pub condition_id: NodeId,
+    /// The name of the condition, for messages
+    pub name: String,
+    pub event_id: ByteString,
+    pub action: ConditionAction,
+}
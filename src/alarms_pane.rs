@@ -0,0 +1,340 @@
+use std::{cell::RefCell, rc::Rc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    alarms::{self, Condition, ConditionAction, ConditionCall, ConditionEvent},
+    condition_action_dlg::ConditionActionDlg,
+    events::{self, Event},
+    model::ModelMessage,
+    value_format::{self, TimeDisplay},
+};
+
+const COL_CONDITION_ID: u32 = 0;
+const COL_BRANCH_ID: u32 = 1;
+const COL_TIME: u32 = 2;
+const COL_SEVERITY: u32 = 3;
+const COL_SOURCE: u32 = 4;
+const COL_NAME: u32 = 5;
+const COL_MESSAGE: u32 = 6;
+const COL_ENABLED: u32 = 7;
+const COL_ACTIVE: u32 = 8;
+const COL_ACKED: u32 = 9;
+const COL_CONFIRMED: u32 = 10;
+const COL_SHELVING: u32 = 11;
+const COL_COMMENT: u32 = 12;
+const COL_BACKGROUND: u32 = 13;
+
+struct AlarmsPaneImpl {
+    model: ActorRef<ModelMessage>,
+    alarms_tree: gtk::TreeView,
+    alarms_model: gtk::ListStore,
+    status_label: gtk::Label,
+    /// The conditions and branches the server retains, in the order they were first seen
+    conditions: RefCell<Vec<Condition>>,
+    /// The subscription of the conditions monitor, which ConditionRefresh is called for
+    subscription_id: RefCell<Option<u32>>,
+}
+
+/// Lists the conditions of the server with the state of their state machines, and calls their
+/// methods to acknowledge, confirm, comment, enable, disable and shelve them
+#[derive(Clone)]
+pub struct AlarmsPane {
+    data: Rc<AlarmsPaneImpl>,
+}
+
+impl AlarmsPane {
+    pub fn new(builder: Rc<gtk::Builder>, model: ActorRef<ModelMessage>) -> Self {
+        let refresh_btn: gtk::Button = builder.get_object("alarms_refresh_btn").unwrap();
+
+        let data = Rc::new(AlarmsPaneImpl {
+            model,
+            alarms_tree: builder.get_object("alarms_tree").unwrap(),
+            alarms_model: builder.get_object("alarms_model").unwrap(),
+            status_label: builder.get_object("alarms_status_label").unwrap(),
+            conditions: RefCell::new(Vec::new()),
+            subscription_id: RefCell::new(None),
+        });
+
+        refresh_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.condition_refresh();
+        }));
+
+        data.alarms_tree.connect_button_press_event(
+            clone!(@weak data => @default-return Inhibit(false), move |tree, event| {
+                if event.get_button() == 3 {
+                    let (x, y) = event.get_position();
+                    if let Some((Some(path), _, _, _)) = tree.get_path_at_pos(x as i32, y as i32) {
+                        tree.get_selection().select_path(&path);
+                    }
+                    AlarmsPaneImpl::popup_menu(&data);
+                    Inhibit(true)
+                } else {
+                    Inhibit(false)
+                }
+            }),
+        );
+
+        // The monitor is kept by the model, which subscribes when connected
+        data.model.tell(
+            ModelMessage::SetEventMonitor(alarms::conditions_monitor()),
+            None,
+        );
+        data.refresh();
+
+        Self { data }
+    }
+
+    /// The subscription of the conditions monitor has been created, so the state of the
+    /// conditions the server already has is asked for
+    pub fn on_subscription_created(&self, result: Result<u32, StatusCode>) {
+        match result {
+            Ok(subscription_id) => {
+                *self.data.subscription_id.borrow_mut() = Some(subscription_id);
+                self.data.condition_refresh();
+            }
+            Err(status_code) => {
+                *self.data.subscription_id.borrow_mut() = None;
+                self.data.status_label.set_text(&format!(
+                    "Cannot subscribe to conditions, {}",
+                    value_format::status_code_to_string(status_code)
+                ));
+            }
+        }
+    }
+
+    pub fn on_disconnected(&self) {
+        *self.data.subscription_id.borrow_mut() = None;
+        self.data.conditions.borrow_mut().clear();
+        self.data.refresh();
+    }
+
+    pub fn on_events(&self, events: Vec<Event>) {
+        events
+            .iter()
+            .filter_map(|event| ConditionEvent::from_fields(&event.fields))
+            .for_each(|event| self.data.on_condition_event(event));
+        self.data.refresh();
+    }
+
+    pub fn on_call_result(&self, call: ConditionCall, status_code: StatusCode) {
+        let text = format!(
+            "{} of {}: {}",
+            call.action.name(),
+            call.name,
+            value_format::status_code_to_string(status_code)
+        );
+        if status_code.is_good() {
+            self.data.status_label.set_text(&text);
+        } else {
+            self.data.status_label.set_markup(&format!(
+                "<span foreground=\"red\">{}</span>",
+                glib::markup_escape_text(&text)
+            ));
+        }
+    }
+}
+
+impl AlarmsPaneImpl {
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.alarms_tree
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok())
+    }
+
+    fn condition_refresh(&self) {
+        if let Some(subscription_id) = *self.subscription_id.borrow() {
+            self.model
+                .tell(ModelMessage::ConditionRefresh(subscription_id), None);
+        }
+    }
+
+    fn on_condition_event(&self, event: ConditionEvent) {
+        let mut conditions = self.conditions.borrow_mut();
+        match event {
+            // Conditions that are no longer retained won't be sent again, so the list starts over
+            ConditionEvent::RefreshStart => {
+                conditions.clear();
+                self.status_label.set_text("Refreshing conditions...");
+            }
+            ConditionEvent::RefreshEnd => self.status_label.set_text("Conditions refreshed"),
+            ConditionEvent::Condition(condition) => {
+                let index = conditions.iter().position(|c| c.is_same(&condition));
+                match (index, condition.retain) {
+                    (Some(index), true) => conditions[index] = condition,
+                    (Some(index), false) => {
+                        conditions.remove(index);
+                    }
+                    (None, true) => conditions.push(condition),
+                    (None, false) => {}
+                }
+            }
+        }
+    }
+
+    fn selected_condition(&self) -> Option<Condition> {
+        let (model, iter) = self.alarms_tree.get_selection().get_selected()?;
+        let text = |col: u32| {
+            model
+                .get_value(&iter, col as i32)
+                .get::<String>()
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        };
+        let (condition_id, branch_id) = (text(COL_CONDITION_ID), text(COL_BRANCH_ID));
+        self.conditions
+            .borrow()
+            .iter()
+            .find(|c| {
+                c.condition_id.to_string() == condition_id && c.branch_id.to_string() == branch_id
+            })
+            .cloned()
+    }
+
+    fn popup_menu(data: &Rc<Self>) {
+        let condition = match data.selected_condition() {
+            Some(condition) => condition,
+            None => return,
+        };
+        let menu = gtk::Menu::new();
+        let add_item = |label: &str, action: ConditionAction, sensitive: bool| {
+            let item = gtk::MenuItem::with_label(label);
+            item.set_sensitive(sensitive);
+            item.connect_activate(clone!(@weak data, @strong condition => move |_| {
+                data.call(&condition, action.clone());
+            }));
+            menu.append(&item);
+        };
+        let no_comment = LocalizedText::null();
+        add_item(
+            "Acknowledge...",
+            ConditionAction::Acknowledge(no_comment.clone()),
+            condition.acked == Some(false),
+        );
+        add_item(
+            "Confirm...",
+            ConditionAction::Confirm(no_comment.clone()),
+            condition.acked == Some(true) && condition.confirmed == Some(false),
+        );
+        add_item(
+            "Add Comment...",
+            ConditionAction::AddComment(no_comment),
+            true,
+        );
+        menu.append(&gtk::SeparatorMenuItem::new());
+        add_item("Enable", ConditionAction::Enable, !condition.enabled);
+        add_item("Disable", ConditionAction::Disable, condition.enabled);
+        // Only alarms have a shelving state machine
+        if condition.shelving.is_some() {
+            menu.append(&gtk::SeparatorMenuItem::new());
+            let shelved = condition.is_shelved();
+            add_item(
+                "Timed Shelve...",
+                ConditionAction::TimedShelve(60.0 * 60.0 * 1000.0),
+                !shelved,
+            );
+            add_item("One Shot Shelve", ConditionAction::OneShotShelve, !shelved);
+            add_item("Unshelve", ConditionAction::Unshelve, shelved);
+        }
+        menu.show_all();
+        menu.popup_at_pointer(None);
+    }
+
+    /// Calls a method of the condition, first asking for a comment or shelving time if the
+    /// method has one
+    fn call(&self, condition: &Condition, action: ConditionAction) {
+        let action = match action {
+            ConditionAction::Acknowledge(_)
+            | ConditionAction::Confirm(_)
+            | ConditionAction::AddComment(_)
+            | ConditionAction::TimedShelve(_) => {
+                let dlg =
+                    ConditionActionDlg::new(self.parent_window().as_ref(), &condition.name, action);
+                match dlg.run() {
+                    Some(action) => action,
+                    None => return,
+                }
+            }
+            action => action,
+        };
+        self.model.tell(
+            ModelMessage::CallConditionMethod(ConditionCall {
+                condition_id: condition.condition_id.clone(),
+                name: condition.name.clone(),
+                event_id: condition.event_id.clone(),
+                action,
+            }),
+            None,
+        );
+    }
+
+    /// Shows a row for each condition, keeping the selection
+    fn refresh(&self) {
+        let selected = self
+            .selected_condition()
+            .map(|c| (c.condition_id, c.branch_id));
+        self.alarms_model.clear();
+        let state = |state: Option<bool>, yes: &str, no: &str| match state {
+            Some(true) => yes.to_string(),
+            Some(false) => no.to_string(),
+            None => String::new(),
+        };
+        self.conditions.borrow().iter().for_each(|c| {
+            let name = if c.branch_id.is_null() {
+                c.name.clone()
+            } else {
+                format!("{} (branch)", c.name)
+            };
+            let background = if c.active == Some(false) && c.acked != Some(false) {
+                None
+            } else {
+                events::severity_colour(c.severity)
+            };
+            let iter = self.alarms_model.insert_with_values(
+                None,
+                &[
+                    COL_CONDITION_ID,
+                    COL_BRANCH_ID,
+                    COL_TIME,
+                    COL_SEVERITY,
+                    COL_SOURCE,
+                    COL_NAME,
+                    COL_MESSAGE,
+                    COL_ENABLED,
+                    COL_ACTIVE,
+                    COL_ACKED,
+                    COL_CONFIRMED,
+                    COL_SHELVING,
+                    COL_COMMENT,
+                    COL_BACKGROUND,
+                ],
+                &[
+                    &c.condition_id.to_string(),
+                    &c.branch_id.to_string(),
+                    &c.time.as_ref().map_or_else(String::new, |t| {
+                        value_format::date_time_to_string(t, TimeDisplay::Utc)
+                    }),
+                    &(c.severity as u32),
+                    &c.source,
+                    &name,
+                    &c.message,
+                    &state(Some(c.enabled), "Enabled", "Disabled"),
+                    &state(c.active, "Active", "Inactive"),
+                    &state(c.acked, "Acknowledged", "Unacknowledged"),
+                    &state(c.confirmed, "Confirmed", "Unconfirmed"),
+                    &c.shelving.clone().unwrap_or_default(),
+                    &c.comment,
+                    &background,
+                ],
+            );
+            if selected.as_ref() == Some(&(c.condition_id.clone(), c.branch_id.clone())) {
+                self.alarms_tree.get_selection().select_iter(&iter);
+            }
+        });
+    }
+}
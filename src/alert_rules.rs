@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use chrono::{SecondsFormat, Utc};
+
+use opcua_client::prelude::*;
+
+use crate::{engineering_units::variant_to_f64, watch_list::WatchItem};
+
+/// What makes an alert rule fire
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertCondition {
+    /// Fires when the value rises above the limit and clears when it falls below the limit
+    /// less the hysteresis
+    High { limit: f64, hysteresis: f64 },
+    /// Fires when the value falls below the limit and clears when it rises above the limit
+    /// plus the hysteresis
+    Low { limit: f64, hysteresis: f64 },
+    /// Fires when the value changes faster than this many units per second either way
+    RateOfChange(f64),
+    /// Fires when no value has arrived for this many seconds
+    Stale(f64),
+    /// Fires when the status of the value is bad
+    StatusBad,
+}
+
+impl AlertCondition {
+    /// The name of the kind of condition, as saved in watch list files
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AlertCondition::High { .. } => "High",
+            AlertCondition::Low { .. } => "Low",
+            AlertCondition::RateOfChange(_) => "RateOfChange",
+            AlertCondition::Stale(_) => "Stale",
+            AlertCondition::StatusBad => "StatusBad",
+        }
+    }
+
+    /// Makes a condition from its kind and values, e.g. when read from a file
+    pub fn from_kind(kind: &str, limit: f64, hysteresis: f64) -> Option<Self> {
+        match kind {
+            "High" => Some(AlertCondition::High { limit, hysteresis }),
+            "Low" => Some(AlertCondition::Low { limit, hysteresis }),
+            "RateOfChange" => Some(AlertCondition::RateOfChange(limit)),
+            "Stale" => Some(AlertCondition::Stale(limit)),
+            "StatusBad" => Some(AlertCondition::StatusBad),
+            _ => None,
+        }
+    }
+
+    /// The limit of the condition, 0 for conditions without one
+    pub fn limit(&self) -> f64 {
+        match *self {
+            AlertCondition::High { limit, .. } | AlertCondition::Low { limit, .. } => limit,
+            AlertCondition::RateOfChange(limit) | AlertCondition::Stale(limit) => limit,
+            AlertCondition::StatusBad => 0.0,
+        }
+    }
+
+    pub fn hysteresis(&self) -> f64 {
+        match *self {
+            AlertCondition::High { hysteresis, .. } | AlertCondition::Low { hysteresis, .. } => {
+                hysteresis
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            AlertCondition::High { hysteresis, .. } | AlertCondition::Low { hysteresis, .. }
+                if hysteresis < 0.0 =>
+            {
+                Err("The hysteresis cannot be negative".to_string())
+            }
+            AlertCondition::RateOfChange(rate) if rate <= 0.0 => {
+                Err("The rate of change must be more than 0".to_string())
+            }
+            AlertCondition::Stale(seconds) if seconds <= 0.0 => {
+                Err("The stale time must be more than 0 seconds".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertCondition::High { limit, hysteresis } => {
+                write!(f, "Above {} (hysteresis {})", limit, hysteresis)
+            }
+            AlertCondition::Low { limit, hysteresis } => {
+                write!(f, "Below {} (hysteresis {})", limit, hysteresis)
+            }
+            AlertCondition::RateOfChange(rate) => write!(f, "Changing faster than {}/s", rate),
+            AlertCondition::Stale(seconds) => write!(f, "No update for {} s", seconds),
+            AlertCondition::StatusBad => write!(f, "Status bad"),
+        }
+    }
+}
+
+/// A rule on the values of a watch item, saved with its watch list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertRule {
+    pub condition: AlertCondition,
+    /// Shows a desktop notification when the rule fires, as well as highlighting the item and
+    /// logging
+    pub notify: bool,
+}
+
+/// A rule that has fired or cleared
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub item_id: u32,
+    pub display_name: String,
+    pub rule: AlertRule,
+    /// True when the rule fired, false when it cleared
+    pub raised: bool,
+    /// What the value was, or how long since it arrived
+    pub detail: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}, {}",
+            if self.raised { "ALERT" } else { "CLEARED" },
+            self.display_name,
+            self.rule.condition,
+            self.detail
+        )
+    }
+}
+
+/// The state of a watch item's rules
+#[derive(Debug, Clone)]
+struct ItemState {
+    /// Whether each rule has fired, in the order of the item's rules
+    active: Vec<bool>,
+    /// The last numeric value and when it arrived, for the rate of change
+    last_value: Option<(f64, Instant)>,
+    last_update: Instant,
+}
+
+/// Evaluates the rules of watch items as their values arrive and as time passes. A rule is
+/// reported once when it fires and once when it clears.
+#[derive(Debug, Default)]
+pub struct AlertMonitor {
+    items: HashMap<u32, ItemState>,
+}
+
+impl AlertMonitor {
+    /// Evaluates the rules of an item against a new value
+    pub fn on_value(&mut self, item: &WatchItem, data_value: &DataValue) -> Vec<Alert> {
+        let now = Instant::now();
+        let state = self.state(item, now);
+        let value = data_value.value.as_ref().and_then(|v| match v {
+            Variant::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            v => variant_to_f64(v),
+        });
+        let status = data_value.status.unwrap_or(StatusCode::Good);
+        let rate = match (value, state.last_value) {
+            (Some(value), Some((last, at))) if now > at => {
+                Some((value - last) / (now - at).as_secs_f64())
+            }
+            _ => None,
+        };
+        if let Some(value) = value {
+            state.last_value = Some((value, now));
+        }
+        state.last_update = now;
+
+        let detail = match value {
+            Some(value) => format!("value {}, status {}", value, status),
+            None => format!("status {}", status),
+        };
+        let mut alerts = Vec::new();
+        item.alert_rules.iter().enumerate().for_each(|(i, rule)| {
+            let active = state.active[i];
+            let now_active = match (rule.condition, value) {
+                (AlertCondition::High { limit, hysteresis }, Some(value)) => {
+                    if active {
+                        value >= limit - hysteresis
+                    } else {
+                        value > limit
+                    }
+                }
+                (AlertCondition::Low { limit, hysteresis }, Some(value)) => {
+                    if active {
+                        value <= limit + hysteresis
+                    } else {
+                        value < limit
+                    }
+                }
+                (AlertCondition::RateOfChange(limit), _) => match rate {
+                    Some(rate) => rate.abs() > limit,
+                    None => active,
+                },
+                // A value has arrived, so it isn't stale
+                (AlertCondition::Stale(_), _) => false,
+                (AlertCondition::StatusBad, _) => status.is_bad(),
+                // Values that aren't numbers leave thresholds as they were
+                (_, None) => active,
+            };
+            if now_active != active {
+                state.active[i] = now_active;
+                let detail = match (rule.condition, rate) {
+                    (AlertCondition::RateOfChange(_), Some(rate)) => {
+                        format!("{}, rate {:.3}/s", detail, rate)
+                    }
+                    _ => detail.clone(),
+                };
+                alerts.push(Alert {
+                    item_id: item.item_id,
+                    display_name: item.display_name.clone(),
+                    rule: *rule,
+                    raised: now_active,
+                    detail,
+                });
+            }
+        });
+        alerts
+    }
+
+    /// Fires the stale rules of items whose values haven't arrived for long enough. Items that
+    /// are disabled, or only sampled because they are linked to a trigger, aren't expected to
+    /// report values so they are skipped.
+    pub fn check_stale(&mut self, items: &[WatchItem]) -> Vec<Alert> {
+        let now = Instant::now();
+        let mut alerts = Vec::new();
+        items
+            .iter()
+            .filter(|item| {
+                item.monitoring_mode == MonitoringMode::Reporting && item.triggered_by.is_none()
+            })
+            .for_each(|item| {
+                let state = self.state(item, now);
+                let elapsed = now - state.last_update;
+                item.alert_rules.iter().enumerate().for_each(|(i, rule)| {
+                    if let AlertCondition::Stale(seconds) = rule.condition {
+                        if !state.active[i] && elapsed > Duration::from_secs_f64(seconds) {
+                            state.active[i] = true;
+                            alerts.push(Alert {
+                                item_id: item.item_id,
+                                display_name: item.display_name.clone(),
+                                rule: *rule,
+                                raised: true,
+                                detail: format!("last update {:.0} s ago", elapsed.as_secs_f64()),
+                            });
+                        }
+                    }
+                });
+            });
+        alerts
+    }
+
+    /// True if any rule of the item has fired and not cleared
+    pub fn is_active(&self, item_id: u32) -> bool {
+        self.items
+            .get(&item_id)
+            .map_or(false, |state| state.active.iter().any(|a| *a))
+    }
+
+    /// Forgets the state of an item, e.g. when it is removed or its rules change
+    pub fn reset(&mut self, item_id: u32) {
+        self.items.remove(&item_id);
+    }
+
+    /// The state of an item, which starts with no rules fired as if a value just arrived
+    fn state(&mut self, item: &WatchItem, now: Instant) -> &mut ItemState {
+        let state = self.items.entry(item.item_id).or_insert_with(|| ItemState {
+            active: Vec::new(),
+            last_value: None,
+            last_update: now,
+        });
+        state.active.resize(item.alert_rules.len(), false);
+        state
+    }
+}
+
+/// Appends an alert to the alert log with the time it happened
+pub fn append_to_log(path: &Path, alert: &Alert) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}, {}", dir.display(), e))?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Cannot open {}, {}", path.display(), e))?;
+    writeln!(
+        file,
+        "{} {}",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        alert
+    )
+    .map_err(|e| format!("Cannot write {}, {}", path.display(), e))
+}
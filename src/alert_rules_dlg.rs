@@ -0,0 +1,181 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use crate::alert_rules::{AlertCondition, AlertRule};
+
+/// The kinds of rule offered, by id and label
+const KINDS: [(&str, &str); 5] = [
+    ("High", "Above Limit"),
+    ("Low", "Below Limit"),
+    ("RateOfChange", "Rate of Change (units/s)"),
+    ("Stale", "No Update For (s)"),
+    ("StatusBad", "Status Bad"),
+];
+
+/// Edits the alert rules of a watch item
+pub(crate) struct AlertRulesDlg {
+    dlg: gtk::Dialog,
+    rules: Rc<RefCell<Vec<AlertRule>>>,
+}
+
+impl AlertRulesDlg {
+    pub fn new(
+        parent: Option<&gtk::Window>,
+        display_name: &str,
+        rules: &[AlertRule],
+        log_path: Option<&Path>,
+    ) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&format!("Alert Rules - {}", display_name)),
+            parent,
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Apply", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+        dlg.set_default_size(480, 320);
+
+        let rules = Rc::new(RefCell::new(rules.to_vec()));
+        let rules_model = gtk::ListStore::new(&[glib::Type::String]);
+        let rules_tree = gtk::TreeView::with_model(&rules_model);
+        let column = gtk::TreeViewColumn::new();
+        column.set_title("Rule");
+        let renderer = gtk::CellRendererText::new();
+        column.pack_start(&renderer, true);
+        column.add_attribute(&renderer, "text", 0);
+        rules_tree.append_column(&column);
+        let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        scrolled.set_shadow_type(gtk::ShadowType::In);
+        scrolled.set_vexpand(true);
+        scrolled.add(&rules_tree);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+        grid.attach(&scrolled, 0, 0, 4, 1);
+        let remove_btn = gtk::Button::with_label("Remove");
+        remove_btn.set_halign(gtk::Align::Start);
+        grid.attach(&remove_btn, 0, 1, 1, 1);
+
+        let label = |row: i32, text: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_halign(gtk::Align::End);
+            grid.attach(&label, 0, row, 1, 1);
+        };
+        label(2, "Rule:");
+        let kind_combo = gtk::ComboBoxText::new();
+        KINDS
+            .iter()
+            .for_each(|(id, text)| kind_combo.append(Some(id), text));
+        kind_combo.set_active_id(Some("High"));
+        grid.attach(&kind_combo, 1, 2, 3, 1);
+        label(3, "Limit:");
+        let limit_spin = gtk::SpinButton::with_range(f64::MIN, f64::MAX, 1.0);
+        limit_spin.set_digits(3);
+        grid.attach(&limit_spin, 1, 3, 1, 1);
+        label(4, "Hysteresis:");
+        let hysteresis_spin = gtk::SpinButton::with_range(0.0, f64::MAX, 1.0);
+        hysteresis_spin.set_digits(3);
+        hysteresis_spin.set_tooltip_text(Some(
+            "How far the value must come back past the limit before the alert clears",
+        ));
+        grid.attach(&hysteresis_spin, 1, 4, 1, 1);
+        let notify_check = gtk::CheckButton::with_label("Desktop notification");
+        grid.attach(&notify_check, 1, 5, 1, 1);
+        let add_btn = gtk::Button::with_label("Add");
+        add_btn.set_halign(gtk::Align::Start);
+        grid.attach(&add_btn, 1, 6, 1, 1);
+
+        let status_label = gtk::Label::new(None);
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        if let Some(log_path) = log_path {
+            status_label.set_text(&format!("Alerts are logged to {}", log_path.display()));
+        }
+        grid.attach(&status_label, 0, 7, 4, 1);
+
+        dlg.get_content_area().add(&grid);
+
+        let fill = clone!(@weak rules_model, @strong rules => move || {
+            rules_model.clear();
+            rules.borrow().iter().for_each(|rule| {
+                let text = if rule.notify {
+                    format!("{}, notify", rule.condition)
+                } else {
+                    rule.condition.to_string()
+                };
+                rules_model.insert_with_values(None, &[0], &[&text]);
+            });
+        });
+        fill();
+
+        kind_combo.connect_changed(
+            clone!(@weak limit_spin, @weak hysteresis_spin => move |combo| {
+                let kind = combo.get_active_id();
+                let kind = kind.as_ref().map(|id| id.as_str());
+                limit_spin.set_sensitive(kind != Some("StatusBad"));
+                hysteresis_spin.set_sensitive(kind == Some("High") || kind == Some("Low"));
+            }),
+        );
+
+        add_btn.connect_clicked(clone!(@strong rules, @strong fill, @weak kind_combo,
+            @weak limit_spin, @weak hysteresis_spin, @weak notify_check,
+            @weak status_label => move |_| {
+                let condition = kind_combo.get_active_id().and_then(|kind| {
+                    AlertCondition::from_kind(
+                        kind.as_str(),
+                        limit_spin.get_value(),
+                        hysteresis_spin.get_value(),
+                    )
+                });
+                let condition = match condition {
+                    Some(condition) => condition,
+                    None => return,
+                };
+                match condition.validate() {
+                    Ok(_) => {
+                        rules.borrow_mut().push(AlertRule {
+                            condition,
+                            notify: notify_check.get_active(),
+                        });
+                        status_label.set_text("");
+                        fill();
+                    }
+                    Err(message) => status_label.set_markup(&format!(
+                        "<span foreground=\"red\">{}</span>",
+                        glib::markup_escape_text(&message)
+                    )),
+                }
+            }
+        ));
+
+        remove_btn.connect_clicked(
+            clone!(@strong rules, @strong fill, @weak rules_tree => move |_| {
+                let (paths, _) = rules_tree.get_selection().get_selected_rows();
+                if let Some(i) = paths.first().and_then(|p| p.get_indices().first().cloned()) {
+                    rules.borrow_mut().remove(i as usize);
+                    fill();
+                }
+            }),
+        );
+
+        Self { dlg, rules }
+    }
+
+    /// Shows the dialog and returns the rules if they were applied
+    pub fn run(&self) -> Option<Vec<AlertRule>> {
+        self.dlg.show_all();
+        let result = if self.dlg.run() == gtk::ResponseType::Apply {
+            Some(self.rules.borrow().clone())
+        } else {
+            None
+        };
+        self.dlg.close();
+        result
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    path::PathBuf,
     rc::Rc,
     sync::{mpsc, Arc, Mutex, RwLock},
 };
@@ -12,17 +13,80 @@ use opcua_client::prelude::*;
 
 use crate::{
     address_space_tree_view::*,
-    model::{Model, ModelMessage},
+    alarms::{ConditionCall, CONDITIONS_MONITOR_ID},
+    alarms_pane::AlarmsPane,
+    attributes_pane::AttributesPane,
+    bulk_read_dlg::BulkReadDlg,
+    call_method_dlg::CallMethodDlg,
+    connection_profile::ConnectionProfile,
+    diagnostics::SubscriptionDiagnostics,
+    diagnostics_pane::DiagnosticsPane,
+    engineering_units::AnalogInfo,
+    events::{Event, EventType},
+    events_pane::EventsPane,
+    graph_view::GraphView,
+    locales_dlg::LocalesDlg,
+    method_call::{MethodCall, MethodCallResult, MethodInfo},
+    method_history_pane::MethodHistoryPane,
+    model::{BulkReadResult, Model, ModelMessage, Translation},
+    monitored_items_pane::MonitoredItemsPane,
     new_connection_dlg::NewConnectionDlg,
+    recorder_pane::RecorderPane,
+    reference_graph::ReferenceGraph,
+    subscriptions_pane::SubscriptionsPane,
+    trend_view::TrendView,
+    type_dictionary::TypeDictionary,
+    value_edit::WriteInfo,
+    value_viewer::ValueViewer,
+    watch_list::{PollStatistics, RevisedMonitoring, RevisedSubscription, WatchList},
+    write_value_dlg::WriteValueDlg,
 };
 
 #[derive(Debug, Clone)]
 pub enum AppMessage {
     Console(String),
     Quit,
-    Connected,
+    Connected(ConnectionProfile),
     Disconnected,
+    TypeDictionary(Arc<TypeDictionary>),
     BrowseNodeResult(NodeId, BrowseResult),
+    ReferenceGraph(ReferenceGraph),
+    /// The attributes of a node and, for an analog item, its units and ranges
+    NodeAttributes(NodeId, Vec<(AttributeId, DataValue)>, Option<AnalogInfo>),
+    Translations(NodeId, Vec<Translation>),
+    WriteInfo(WriteInfo),
+    WriteResult(NodeId, AttributeId, StatusCode),
+    BulkReadResult(BulkReadResult),
+    /// Values of watch items by item id, from data change notifications or polls
+    ItemValues(Vec<(u32, DataValue)>),
+    /// The units and ranges of watch items that are analog items
+    ItemAnalogInfo(Vec<(u32, AnalogInfo)>),
+    /// The DataType of watch items, used to name the members of enumerations
+    ItemDataTypes(Vec<(u32, NodeId)>),
+    /// The timing of the polls of a watch list
+    PollStatistics(u32, PollStatistics),
+    /// The monitoring parameters the server granted to watch items, or why it refused them
+    MonitoringRevised(Vec<(u32, Result<RevisedMonitoring, StatusCode>)>),
+    /// The monitoring modes of watch items' monitored items, or why they couldn't be changed
+    MonitoringModes(Vec<(u32, Result<MonitoringMode, StatusCode>)>),
+    /// The publishing parameters the server granted to the subscription of a watch list, or why
+    /// it couldn't be created or changed
+    SubscriptionRevised(u32, Result<RevisedSubscription, StatusCode>),
+    /// The event types of the server with the fields each declares
+    EventTypes(Vec<EventType>),
+    /// Events received by event monitors
+    Events(Vec<Event>),
+    /// The subscription created for an event monitor, or why it couldn't be
+    EventMonitorCreated(u32, Result<u32, StatusCode>),
+    /// The result of calling a method of a condition
+    ConditionCallResult(ConditionCall, StatusCode),
+    /// A watch list opened from a file, with the items whose nodes were found
+    WatchListLoaded(PathBuf, WatchList),
+    /// How the subscriptions of the watch lists are doing
+    SubscriptionDiagnostics(Vec<SubscriptionDiagnostics>),
+    /// The arguments of a method, read to show a form to call it with
+    MethodInfo(MethodInfo),
+    MethodCallResult(MethodCallResult),
 }
 
 #[derive(Debug, Clone)]
@@ -47,10 +111,35 @@ impl ActorFactoryArgs<Arc<Mutex<mpsc::Sender<AppMessage>>>> for AppActor {
 
 pub struct App {
     rx: mpsc::Receiver<AppMessage>,
+    model: ActorRef<ModelMessage>,
+    main_window: Rc<gtk::ApplicationWindow>,
     toolbar_connect_btn: Rc<gtk::ToolButton>,
     toolbar_disconnect_btn: Rc<gtk::ToolButton>,
+    toolbar_locales_btn: Rc<gtk::ToolButton>,
     address_space_tree: AddressSpaceTreeView,
+    details_notebook: Rc<gtk::Notebook>,
+    attributes_pane: AttributesPane,
+    value_viewer: ValueViewer,
+    graph_view: GraphView,
+    monitored_items_pane: MonitoredItemsPane,
+    subscriptions_pane: SubscriptionsPane,
+    trend_view: TrendView,
+    events_pane: EventsPane,
+    alarms_pane: AlarmsPane,
+    recorder_pane: RecorderPane,
+    diagnostics_pane: DiagnosticsPane,
+    method_history_pane: MethodHistoryPane,
     console_text_view: Rc<gtk::TextView>,
+    write_value_dlg: Option<WriteValueDlg>,
+    call_method_dlg: Option<CallMethodDlg>,
+    /// A call from the history whose inputs fill in the Call dialog once the method's
+    /// arguments have been read
+    pending_method_call: Option<MethodCall>,
+    bulk_read_dlg: Option<BulkReadDlg>,
+    /// The profile of the current connection
+    profile: Option<ConnectionProfile>,
+    /// The layouts of the structures on the server
+    type_dictionary: Arc<TypeDictionary>,
 }
 
 impl App {
@@ -86,20 +175,79 @@ impl App {
         let toolbar_disconnect_btn: Rc<gtk::ToolButton> =
             Rc::new(builder.get_object("toolbar_disconnect_btn").unwrap());
 
+        let toolbar_locales_btn: Rc<gtk::ToolButton> =
+            Rc::new(builder.get_object("toolbar_locales_btn").unwrap());
+
         // Address space explorer pane
         let address_space_tree: Rc<gtk::TreeView> =
             Rc::new(builder.get_object("address_space_tree").unwrap());
 
+        // Notebook holding the properties, graph etc.
+        let details_notebook: Rc<gtk::Notebook> =
+            Rc::new(builder.get_object("details_notebook").unwrap());
+
         // Log / console window
         let console_text_view: Rc<gtk::TextView> =
             Rc::new(builder.get_object("console_text_view").unwrap());
 
+        // Watch lists and the subscriptions that acquire them
+        let monitored_items_pane = MonitoredItemsPane::new(builder.clone(), model.clone());
+        let subscriptions_pane =
+            SubscriptionsPane::new(builder.clone(), monitored_items_pane.clone());
+        let trend_view = TrendView::new(builder.clone(), monitored_items_pane.clone());
+        let events_pane = EventsPane::new(builder.clone(), model.clone());
+        let recorder_pane = RecorderPane::new(
+            builder.clone(),
+            monitored_items_pane.clone(),
+            events_pane.clone(),
+        );
+        let diagnostics_pane =
+            DiagnosticsPane::new(builder.clone(), model.clone(), monitored_items_pane.clone());
+        let method_history_pane = MethodHistoryPane::new(builder.clone(), model.clone());
+        monitored_items_pane.connect_alert(clone!(@weak console_text_view => move |line| {
+            let buffer = console_text_view.get_buffer().unwrap();
+            let mut end_iter = buffer.get_end_iter();
+            buffer.insert(&mut end_iter, line);
+            buffer.insert(&mut end_iter, "\n");
+        }));
+
         let app = Arc::new(RwLock::new(App {
             rx,
+            model: model.clone(),
+            main_window: main_window.clone(),
             console_text_view: console_text_view.clone(),
             toolbar_connect_btn: toolbar_connect_btn.clone(),
             toolbar_disconnect_btn: toolbar_disconnect_btn.clone(),
+            toolbar_locales_btn: toolbar_locales_btn.clone(),
             address_space_tree: AddressSpaceTreeView::new(builder.clone(), model.clone()),
+            details_notebook,
+            attributes_pane: AttributesPane::new(builder.clone(), model.clone()),
+            value_viewer: ValueViewer::new(builder.clone()),
+            graph_view: GraphView::new(builder.clone(), model.clone()),
+            monitored_items_pane,
+            subscriptions_pane,
+            trend_view,
+            events_pane,
+            alarms_pane: AlarmsPane::new(builder.clone(), model.clone()),
+            recorder_pane,
+            diagnostics_pane,
+            method_history_pane: method_history_pane.clone(),
+            write_value_dlg: None,
+            call_method_dlg: None,
+            pending_method_call: None,
+            bulk_read_dlg: None,
+            profile: None,
+            type_dictionary: Arc::new(TypeDictionary::default()),
+        }));
+
+        // Calls from the history are edited in the Call dialog of their method
+        method_history_pane.connect_edit(clone!(@weak app => move |call| {
+            let mut app = app.write().unwrap();
+            app.model.tell(
+                ModelMessage::ReadMethodInfo(call.object_id.clone(), call.method_id.clone()),
+                None,
+            );
+            app.pending_method_call = Some(call);
         }));
 
         // Hook up the toolbar buttons
@@ -117,6 +265,18 @@ impl App {
             model.tell(ModelMessage::Disconnect, None);
         }));
 
+        let _id = toolbar_locales_btn.connect_clicked(
+            clone!(@strong model, @weak app, @weak main_window => move |_| {
+                println!("toolbar_locales_btn click");
+                // The lock must not be held while the dialog runs
+                let profile = app.read().unwrap().profile.clone();
+                if let Some(profile) = profile {
+                    let dlg = LocalesDlg::new(model.clone(), &main_window, &profile);
+                    dlg.show();
+                }
+            }),
+        );
+
         // Address space
         let _id =
             address_space_tree.connect_row_expanded(clone!(@weak app => move |_, iter, path| {
@@ -124,11 +284,29 @@ impl App {
                 app.address_space_tree.row_expanded(iter, path);
             }));
 
-        // Monitored item pane
-        // TODO
+        let _id = address_space_tree.connect_button_press_event(
+            clone!(@weak app => @default-return Inhibit(false), move |_, event| {
+                if event.get_button() == 3 {
+                    let (x, y) = event.get_position();
+                    let (selected, selected_count) = {
+                        let app = app.read().unwrap();
+                        app.address_space_tree.select_at_pos(x, y);
+                        let selected = app
+                            .address_space_tree
+                            .selected_node_id()
+                            .zip(app.address_space_tree.selected_node_class());
+                        (selected, app.address_space_tree.selected_nodes().len())
+                    };
+                    if selected_count > 0 {
+                        App::popup_address_space_menu(&app, selected, selected_count);
+                    }
+                    Inhibit(true)
+                } else {
+                    Inhibit(false)
+                }
+            }),
+        );
 
-        // Monitored item properties pane
-        // TODO
         main_window.connect_delete_event(|_, _| {
             println!("Application is closing");
             gtk::main_quit();
@@ -160,11 +338,66 @@ impl App {
             println!("try_recv msg = #{:?}", msg);
             match msg {
                 AppMessage::Console(message) => self.console_write(&message),
-                AppMessage::Connected => self.on_connected(),
+                AppMessage::Connected(profile) => self.on_connected(profile),
                 AppMessage::Disconnected => self.on_disconnected(),
+                AppMessage::TypeDictionary(type_dictionary) => {
+                    self.on_type_dictionary(type_dictionary)
+                }
                 AppMessage::BrowseNodeResult(parent_node_id, browse_result) => {
                     self.on_browse_node_result(parent_node_id, browse_result)
                 }
+                AppMessage::ReferenceGraph(graph) => self.graph_view.on_reference_graph(graph),
+                AppMessage::NodeAttributes(node_id, attributes, analog_info) => {
+                    self.on_node_attributes(node_id, attributes, analog_info)
+                }
+                AppMessage::Translations(node_id, translations) => {
+                    self.attributes_pane.on_translations(node_id, translations)
+                }
+                AppMessage::WriteInfo(write_info) => self.on_write_info(write_info),
+                AppMessage::MethodInfo(method_info) => self.on_method_info(method_info),
+                AppMessage::MethodCallResult(result) => self.on_method_call_result(result),
+                AppMessage::WriteResult(node_id, attribute_id, status_code) => {
+                    self.on_write_result(node_id, attribute_id, status_code)
+                }
+                AppMessage::BulkReadResult(result) => self.on_bulk_read_result(result),
+                AppMessage::ItemValues(values) => self.on_item_values(values),
+                AppMessage::ItemAnalogInfo(analog_info) => {
+                    self.monitored_items_pane.on_item_analog_info(analog_info)
+                }
+                AppMessage::ItemDataTypes(data_types) => {
+                    self.monitored_items_pane.on_item_data_types(data_types)
+                }
+                AppMessage::PollStatistics(watch_list_id, statistics) => self
+                    .monitored_items_pane
+                    .on_poll_statistics(watch_list_id, statistics),
+                AppMessage::MonitoringRevised(revised) => {
+                    self.monitored_items_pane.on_monitoring_revised(revised)
+                }
+                AppMessage::MonitoringModes(modes) => {
+                    self.monitored_items_pane.on_monitoring_modes(modes)
+                }
+                AppMessage::SubscriptionRevised(watch_list_id, result) => self
+                    .subscriptions_pane
+                    .on_subscription_revised(watch_list_id, result),
+                AppMessage::EventTypes(event_types) => self.events_pane.on_event_types(event_types),
+                AppMessage::Events(events) => self.on_events(events),
+                AppMessage::EventMonitorCreated(monitor_id, result) => {
+                    if monitor_id == CONDITIONS_MONITOR_ID {
+                        self.alarms_pane.on_subscription_created(result)
+                    } else {
+                        self.events_pane
+                            .on_event_monitor_created(monitor_id, result)
+                    }
+                }
+                AppMessage::ConditionCallResult(call, status_code) => {
+                    self.alarms_pane.on_call_result(call, status_code)
+                }
+                AppMessage::WatchListLoaded(path, watch_list) => self
+                    .monitored_items_pane
+                    .on_watch_list_loaded(path, watch_list),
+                AppMessage::SubscriptionDiagnostics(diagnostics) => {
+                    self.diagnostics_pane.on_diagnostics(diagnostics)
+                }
                 AppMessage::Quit => {
                     println!("Application was told to quit");
                     return false;
@@ -181,13 +414,306 @@ impl App {
         buffer.insert(&mut end_iter, "\n");
     }
 
-    pub fn on_connected(&self) {
+    pub fn on_connected(&mut self, profile: ConnectionProfile) {
+        self.recorder_pane.on_connected(profile.clone());
+        self.monitored_items_pane.on_connected(&profile);
+        self.method_history_pane.on_connected(&profile);
+        self.profile = Some(profile);
         self.update_connection_state(true);
         self.address_space_tree.populate();
+        self.events_pane.on_connected();
+        self.diagnostics_pane.on_connected();
     }
 
-    pub fn on_disconnected(&self) {
+    pub fn on_type_dictionary(&mut self, type_dictionary: Arc<TypeDictionary>) {
+        self.value_viewer
+            .set_type_dictionary(type_dictionary.clone());
+        self.attributes_pane
+            .set_type_dictionary(type_dictionary.clone());
+        self.monitored_items_pane
+            .set_type_dictionary(type_dictionary.clone());
+        self.type_dictionary = type_dictionary;
+    }
+
+    pub fn on_disconnected(&mut self) {
+        self.profile = None;
+        self.on_type_dictionary(Arc::new(TypeDictionary::default()));
         self.update_connection_state(false);
+        self.attributes_pane.clear();
+        self.value_viewer.clear();
+        self.graph_view.clear();
+        self.subscriptions_pane.on_disconnected();
+        self.events_pane.on_disconnected();
+        self.recorder_pane.on_disconnected();
+        self.monitored_items_pane.on_disconnected();
+        self.diagnostics_pane.on_disconnected();
+        self.method_history_pane.on_disconnected();
+        self.alarms_pane.on_disconnected();
+        if let Some(dlg) = self.write_value_dlg.take() {
+            dlg.close();
+        }
+        if let Some(dlg) = self.call_method_dlg.take() {
+            dlg.close();
+        }
+        if let Some(dlg) = self.bulk_read_dlg.take() {
+            dlg.close();
+        }
+    }
+
+    pub fn on_node_attributes(
+        &mut self,
+        node_id: NodeId,
+        attributes: Vec<(AttributeId, DataValue)>,
+        analog_info: Option<AnalogInfo>,
+    ) {
+        if self.address_space_tree.selected_node_id().as_ref() != Some(&node_id) {
+            return;
+        }
+
+        let attribute = |attribute_id| {
+            attributes
+                .iter()
+                .find(|(a, _)| *a == attribute_id)
+                .map(|(_, v)| v.clone())
+        };
+        let array_dimensions = match attribute(AttributeId::ArrayDimensions) {
+            Some(DataValue {
+                value: Some(Variant::Array(array)),
+                ..
+            }) => Some(
+                array
+                    .values
+                    .iter()
+                    .filter_map(|v| match v {
+                        Variant::UInt32(d) => Some(*d),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let data_type = match attribute(AttributeId::DataType) {
+            Some(DataValue {
+                value: Some(Variant::NodeId(data_type)),
+                ..
+            }) => Some(*data_type),
+            _ => None,
+        };
+        self.value_viewer.show_value(
+            attribute(AttributeId::Value),
+            array_dimensions,
+            data_type,
+            analog_info.clone(),
+        );
+
+        self.attributes_pane
+            .on_node_attributes(node_id, attributes, analog_info);
+    }
+
+    /// Shows the context menu of actions on the nodes selected in the address space. Actions
+    /// on a single node are only offered when just one is selected.
+    fn popup_address_space_menu(
+        app: &Arc<RwLock<App>>,
+        selected: Option<(NodeId, NodeClass)>,
+        selected_count: usize,
+    ) {
+        let menu = gtk::Menu::new();
+
+        if let Some((node_id, node_class)) = selected {
+            if node_class == NodeClass::Variable {
+                let write_value_item = gtk::MenuItem::with_label("Write Value...");
+                write_value_item.connect_activate(clone!(@weak app, @strong node_id => move |_| {
+                    let app = app.read().unwrap();
+                    app.model
+                        .tell(ModelMessage::ReadWriteInfo(node_id.clone()), None);
+                }));
+                menu.append(&write_value_item);
+            }
+
+            if node_class == NodeClass::Method {
+                let call_item = gtk::MenuItem::with_label("Call...");
+                // The method is called on the object it was browsed from
+                let object_id = app
+                    .read()
+                    .unwrap()
+                    .address_space_tree
+                    .selected_parent_node_id();
+                call_item.set_sensitive(object_id.is_some());
+                call_item.connect_activate(clone!(@weak app, @strong node_id => move |_| {
+                    if let Some(ref object_id) = object_id {
+                        let app = app.read().unwrap();
+                        app.model.tell(
+                            ModelMessage::ReadMethodInfo(object_id.clone(), node_id.clone()),
+                            None,
+                        );
+                    }
+                }));
+                menu.append(&call_item);
+            }
+
+            let show_graph_item = gtk::MenuItem::with_label("Show Reference Graph");
+            show_graph_item.connect_activate(clone!(@weak app, @strong node_id => move |_| {
+                let app = app.read().unwrap();
+                app.show_reference_graph(node_id.clone());
+            }));
+            menu.append(&show_graph_item);
+
+            if node_class == NodeClass::Object || node_class == NodeClass::View {
+                let events_item = gtk::MenuItem::with_label("Subscribe to Events...");
+                events_item.connect_activate(clone!(@weak app, @strong node_id => move |_| {
+                    // The lock must not be held while the filter dialog runs
+                    let (events_pane, name) = {
+                        let app = app.read().unwrap();
+                        let name = app
+                            .address_space_tree
+                            .selected_nodes()
+                            .into_iter()
+                            .find(|(id, _)| *id == node_id)
+                            .map_or_else(|| node_id.to_string(), |(_, name)| name);
+                        (app.events_pane.clone(), name)
+                    };
+                    events_pane.new_monitor(node_id.clone(), &name);
+                }));
+                menu.append(&events_item);
+            }
+        }
+
+        let monitor_item = gtk::MenuItem::with_label("Monitor");
+        monitor_item.connect_activate(clone!(@weak app => move |_| {
+            let app = app.read().unwrap();
+            let nodes = app.address_space_tree.selected_nodes();
+            app.monitored_items_pane.add_nodes(nodes);
+        }));
+        menu.append(&monitor_item);
+
+        let read_selected_item =
+            gtk::MenuItem::with_label(&format!("Read Selected ({})...", selected_count));
+        read_selected_item.connect_activate(clone!(@weak app => move |_| {
+            let mut app = app.write().unwrap();
+            app.show_bulk_read_dlg();
+        }));
+        menu.append(&read_selected_item);
+
+        menu.show_all();
+        menu.popup_at_pointer(None);
+    }
+
+    pub fn on_write_info(&mut self, write_info: WriteInfo) {
+        if let Some(dlg) = self.write_value_dlg.take() {
+            dlg.close();
+        }
+        let dlg = WriteValueDlg::new(
+            self.model.clone(),
+            &self.main_window,
+            write_info,
+            self.type_dictionary.clone(),
+        );
+        dlg.show();
+        self.write_value_dlg = Some(dlg);
+    }
+
+    pub fn on_method_info(&mut self, method_info: MethodInfo) {
+        if let Some(dlg) = self.call_method_dlg.take() {
+            dlg.close();
+        }
+        let inputs = self
+            .pending_method_call
+            .take()
+            .filter(|call| {
+                call.object_id == method_info.object_id && call.method_id == method_info.method_id
+            })
+            .map(|call| call.inputs);
+        let dlg = CallMethodDlg::new(
+            self.model.clone(),
+            &self.main_window,
+            method_info,
+            inputs,
+            self.type_dictionary.clone(),
+        );
+        dlg.show();
+        self.call_method_dlg = Some(dlg);
+    }
+
+    pub fn on_method_call_result(&mut self, result: MethodCallResult) {
+        self.method_history_pane.on_call_result(&result);
+        if let Some(ref dlg) = self.call_method_dlg {
+            if dlg.is_closed() {
+                self.call_method_dlg = None;
+            } else {
+                dlg.on_call_result(&result);
+            }
+        }
+    }
+
+    pub fn on_write_result(
+        &mut self,
+        node_id: NodeId,
+        attribute_id: AttributeId,
+        status_code: StatusCode,
+    ) {
+        if attribute_id == AttributeId::Value {
+            if let Some(ref dlg) = self.write_value_dlg {
+                if dlg.is_closed() {
+                    self.write_value_dlg = None;
+                } else {
+                    dlg.on_write_result(&node_id, status_code);
+                }
+            }
+        }
+        self.attributes_pane
+            .on_write_result(&node_id, attribute_id, status_code);
+        // Show what the node holds now
+        if self.address_space_tree.selected_node_id().as_ref() == Some(&node_id) {
+            self.model.tell(ModelMessage::ReadAttributes(node_id), None);
+        }
+    }
+
+    /// Values of watch items, from polls or subscriptions
+    pub fn on_item_values(&mut self, values: Vec<(u32, DataValue)>) {
+        self.trend_view.on_item_values(&values);
+        self.recorder_pane.on_item_values(&values);
+        self.monitored_items_pane.on_item_values(values);
+    }
+
+    /// Condition events go to the alarms pane, the events of other monitors to the events pane
+    pub fn on_events(&mut self, events: Vec<Event>) {
+        let (conditions, events): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|e| e.monitor_id == CONDITIONS_MONITOR_ID);
+        if !conditions.is_empty() {
+            self.alarms_pane.on_events(conditions);
+        }
+        if !events.is_empty() {
+            self.recorder_pane.on_events(&events);
+            self.events_pane.on_events(events);
+        }
+    }
+
+    pub fn show_bulk_read_dlg(&mut self) {
+        if let Some(dlg) = self.bulk_read_dlg.take() {
+            dlg.close();
+        }
+        let nodes = self.address_space_tree.selected_nodes();
+        let dlg = BulkReadDlg::new(self.model.clone(), &self.main_window, nodes);
+        dlg.show();
+        self.bulk_read_dlg = Some(dlg);
+    }
+
+    pub fn on_bulk_read_result(&mut self, result: BulkReadResult) {
+        if let Some(ref dlg) = self.bulk_read_dlg {
+            if dlg.is_closed() {
+                self.bulk_read_dlg = None;
+            } else {
+                dlg.on_bulk_read_result(result);
+            }
+        }
+    }
+
+    pub fn show_reference_graph(&self, node_id: NodeId) {
+        self.graph_view.show_node(node_id);
+        if let Some(page) = self.details_notebook.page_num(&self.graph_view.page()) {
+            self.details_notebook.set_current_page(Some(page));
+        }
     }
 
     pub fn on_browse_node_result(
@@ -202,5 +728,6 @@ impl App {
     pub fn update_connection_state(&self, is_connected: bool) {
         self.toolbar_connect_btn.set_sensitive(!is_connected);
         self.toolbar_disconnect_btn.set_sensitive(is_connected);
+        self.toolbar_locales_btn.set_sensitive(is_connected);
     }
 }
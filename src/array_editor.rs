@@ -0,0 +1,518 @@
+use std::{cell::RefCell, rc::Rc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    value_edit::{self, BuiltinType, WriteInfo, TEXT_TYPES},
+    value_format,
+};
+
+/// Column of the grid holding the index of the row
+const COL_INDEX: u32 = 0;
+
+struct ArrayEditorImpl {
+    container: gtk::Box,
+    grid: gtk::TreeView,
+    grid_model: RefCell<gtk::ListStore>,
+    /// The dimensions of the array being edited. One dimensional arrays are a single column,
+    /// matrices are rows of the last dimension.
+    dimensions: RefCell<Vec<u32>>,
+    /// The type of the elements, or None if the user chooses it from the type combo
+    element_type: Option<BuiltinType>,
+    /// The members of an enumeration, chosen for each element from a drop down list
+    enum_members: Vec<(i64, String)>,
+    type_combo: gtk::ComboBoxText,
+    rows_spin: gtk::SpinButton,
+    columns_spin: gtk::SpinButton,
+    range_text: gtk::Entry,
+    range_label: gtk::Label,
+}
+
+/// Edits an array or matrix in a grid. The whole array is written or only the elements in
+/// an index range.
+pub struct ArrayEditor {
+    data: Rc<ArrayEditorImpl>,
+}
+
+impl ArrayEditor {
+    pub fn new(write_info: &WriteInfo) -> Self {
+        let current = match write_info.value.as_ref().and_then(|v| v.value.as_ref()) {
+            Some(Variant::Array(array)) => Some(array),
+            _ => None,
+        };
+
+        let dimensions = match current {
+            Some(array) => {
+                value_format::array_dimensions(array, write_info.array_dimensions.as_deref())
+            }
+            None => match write_info.array_dimensions {
+                Some(ref dimensions) if !dimensions.is_empty() && !dimensions.contains(&0) => {
+                    dimensions.clone()
+                }
+                _ => vec![1; write_info.value_rank.max(1) as usize],
+            },
+        };
+        let texts = current.map_or_else(Vec::new, |array| {
+            array
+                .values
+                .iter()
+                .map(|v| match v {
+                    Variant::Int32(v) if write_info.is_enum() => {
+                        value_edit::enum_value_to_string(&write_info.enum_members, *v as i64)
+                    }
+                    v => value_edit::scalar_to_edit_text(v),
+                })
+                .collect()
+        });
+
+        // Abstract types such as Number need the user to say what the elements are
+        let element_type = write_info
+            .builtin_type
+            .filter(|t| TEXT_TYPES.contains(t))
+            .or_else(|| {
+                if write_info.is_enum() {
+                    Some(BuiltinType::Int32)
+                } else {
+                    None
+                }
+            });
+        let type_combo = gtk::ComboBoxText::new();
+        TEXT_TYPES.iter().for_each(|t| {
+            let name = format!("{:?}", t);
+            type_combo.append(Some(&name), &name);
+        });
+        let current_type = current
+            .map(|array| format!("{:?}", array.value_type))
+            .filter(|t| BuiltinType::from_name(t).is_some())
+            .unwrap_or_else(|| "Double".to_string());
+        type_combo.set_active_id(Some(&current_type));
+
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 4);
+
+        let size_box = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        let rows_spin = gtk::SpinButton::with_range(0.0, 1_000_000.0, 1.0);
+        let columns_spin = gtk::SpinButton::with_range(1.0, 1_000_000.0, 1.0);
+        let resize_btn = gtk::Button::with_label("Resize");
+        let paste_btn = gtk::Button::with_label("Paste CSV");
+        let is_matrix = dimensions.len() > 1;
+        size_box.pack_start(
+            &gtk::Label::new(Some(if is_matrix { "Rows:" } else { "Length:" })),
+            false,
+            true,
+            0,
+        );
+        size_box.pack_start(&rows_spin, false, true, 0);
+        if is_matrix {
+            size_box.pack_start(&gtk::Label::new(Some("Columns:")), false, true, 0);
+            size_box.pack_start(&columns_spin, false, true, 0);
+        }
+        size_box.pack_start(&resize_btn, false, true, 0);
+        size_box.pack_end(&paste_btn, false, true, 0);
+        if element_type.is_none() {
+            size_box.pack_end(&type_combo, false, true, 0);
+        }
+        container.pack_start(&size_box, false, true, 0);
+
+        // Higher dimensions are shown flattened into rows, which can't be resized meaningfully
+        if dimensions.len() > 2 {
+            rows_spin.set_sensitive(false);
+            columns_spin.set_sensitive(false);
+            resize_btn.set_sensitive(false);
+        }
+
+        let grid = gtk::TreeView::new();
+        grid.set_enable_search(false);
+        let scrolled_window =
+            gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        scrolled_window.set_min_content_height(240);
+        scrolled_window.set_min_content_width(360);
+        scrolled_window.add(&grid);
+        container.pack_start(&scrolled_window, true, true, 0);
+
+        let range_box = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        let range_text = gtk::Entry::new();
+        range_text.set_placeholder_text(Some("Whole array, or e.g. 10:19 or 0:1,2:3"));
+        range_text.set_hexpand(true);
+        range_box.pack_start(&gtk::Label::new(Some("Index Range:")), false, true, 0);
+        range_box.pack_start(&range_text, true, true, 0);
+        container.pack_start(&range_box, false, true, 0);
+
+        let range_label = gtk::Label::new(None);
+        range_label.set_halign(gtk::Align::Start);
+        container.pack_start(&range_label, false, true, 0);
+
+        let data = Rc::new(ArrayEditorImpl {
+            container,
+            grid,
+            grid_model: RefCell::new(gtk::ListStore::new(&[glib::Type::String])),
+            dimensions: RefCell::new(Vec::new()),
+            element_type,
+            enum_members: write_info.enum_members.clone(),
+            type_combo,
+            rows_spin,
+            columns_spin,
+            range_text,
+            range_label,
+        });
+        data.populate(&texts, dimensions);
+
+        resize_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.rows_spin.update();
+            data.columns_spin.update();
+            let rows = data.rows_spin.get_value_as_int().max(0) as u32;
+            let columns = data.columns_spin.get_value_as_int().max(1) as u32;
+            data.resize(rows, columns);
+        }));
+
+        paste_btn.connect_clicked(clone!(@weak data => move |_| {
+            let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+            if let Some(text) = clipboard.wait_for_text() {
+                data.paste_csv(text.as_str());
+            }
+        }));
+
+        data.range_text
+            .connect_changed(clone!(@weak data => move |_| {
+                data.update_range_label();
+            }));
+
+        Self { data }
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        &self.data.container
+    }
+
+    /// Validates the grid and returns the value to write with the index range, if any
+    pub fn value(&self) -> Result<(Variant, Option<String>), String> {
+        self.data.value()
+    }
+}
+
+impl ArrayEditorImpl {
+    fn is_matrix(&self) -> bool {
+        self.dimensions.borrow().len() > 1
+    }
+
+    fn columns(&self) -> usize {
+        let dimensions = self.dimensions.borrow();
+        if dimensions.len() > 1 {
+            (*dimensions.last().unwrap()).max(1) as usize
+        } else {
+            1
+        }
+    }
+
+    /// Rebuilds the grid to hold the values, padding with defaults to fill the dimensions
+    fn populate(&self, texts: &[String], dimensions: Vec<u32>) {
+        self.grid.get_columns().iter().for_each(|c| {
+            self.grid.remove_column(c);
+        });
+
+        let is_matrix = dimensions.len() > 1;
+        let columns = if is_matrix {
+            (*dimensions.last().unwrap()).max(1) as usize
+        } else {
+            1
+        };
+        let row_dimensions = if is_matrix {
+            dimensions[..dimensions.len() - 1].to_vec()
+        } else {
+            dimensions.clone()
+        };
+        let len = dimensions.iter().product::<u32>() as usize;
+        let rows = len / columns;
+
+        let column_types = vec![glib::Type::String; columns + 1];
+        let grid_model = gtk::ListStore::new(&column_types);
+
+        let index_renderer = gtk::CellRendererText::new();
+        let index_column = gtk::TreeViewColumn::new();
+        index_column.set_title("Index");
+        index_column.pack_start(&index_renderer, true);
+        index_column.add_attribute(&index_renderer, "text", COL_INDEX as i32);
+        self.grid.append_column(&index_column);
+
+        // Enumerations are edited with a drop down list of their members
+        let enum_model = if self.enum_members.is_empty() {
+            None
+        } else {
+            let enum_model = gtk::ListStore::new(&[glib::Type::String]);
+            self.enum_members.iter().for_each(|(value, name)| {
+                let text = format!("{} ({})", name, value);
+                enum_model.insert_with_values(None, &[0], &[&text]);
+            });
+            Some(enum_model)
+        };
+
+        (0..columns).for_each(|c| {
+            let col = c as u32 + 1;
+            let renderer = match enum_model {
+                Some(ref enum_model) => {
+                    let renderer = gtk::CellRendererCombo::new();
+                    renderer.set_property_model(Some(enum_model));
+                    renderer.set_property_text_column(0);
+                    renderer.set_property_has_entry(false);
+                    renderer.upcast::<gtk::CellRendererText>()
+                }
+                None => gtk::CellRendererText::new(),
+            };
+            renderer.set_property_editable(true);
+            let grid_model = grid_model.clone();
+            renderer.connect_edited(move |_, path, new_text| {
+                if let Some(iter) = grid_model.get_iter(&path) {
+                    grid_model.set_value(&iter, col, &new_text.to_value());
+                }
+            });
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(&if is_matrix {
+                c.to_string()
+            } else {
+                "Value".to_string()
+            });
+            column.set_resizable(true);
+            column.set_min_width(60);
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", col as i32);
+            self.grid.append_column(&column);
+        });
+
+        let default_text = self.default_text();
+        let column_ids = (0..=columns as u32).collect::<Vec<_>>();
+        (0..rows).for_each(|row| {
+            let mut row_texts = vec![value_format::array_index_to_string(row, &row_dimensions)];
+            row_texts.extend((0..columns).map(|c| {
+                texts
+                    .get(row * columns + c)
+                    .map_or(default_text.as_str(), |t| t.as_str())
+                    .to_string()
+            }));
+            let values = row_texts
+                .iter()
+                .map(|t| t as &dyn ToValue)
+                .collect::<Vec<_>>();
+            grid_model.insert_with_values(None, &column_ids, &values);
+        });
+
+        self.grid.set_model(Some(&grid_model));
+        *self.grid_model.borrow_mut() = grid_model;
+
+        self.rows_spin.set_value(rows as f64);
+        self.columns_spin.set_value(columns as f64);
+        *self.dimensions.borrow_mut() = dimensions;
+        self.update_range_label();
+    }
+
+    /// The text of every cell in row-major order
+    fn texts(&self) -> Vec<String> {
+        let grid_model = self.grid_model.borrow();
+        let columns = self.columns();
+        let mut texts = Vec::new();
+        if let Some(iter) = grid_model.get_iter_first() {
+            loop {
+                (1..=columns as i32).for_each(|c| {
+                    let text = grid_model
+                        .get_value(&iter, c)
+                        .get::<String>()
+                        .ok()
+                        .flatten()
+                        .unwrap_or_default();
+                    texts.push(text);
+                });
+                if !grid_model.iter_next(&iter) {
+                    break;
+                }
+            }
+        }
+        texts
+    }
+
+    /// Changes the size of the array, keeping the values that still fit
+    fn resize(&self, rows: u32, columns: u32) {
+        let old_columns = self.columns();
+        let old_texts = self.texts();
+        let texts = if self.is_matrix() {
+            let default_text = self.default_text();
+            (0..rows as usize)
+                .flat_map(|r| {
+                    let old_texts = &old_texts;
+                    let default_text = &default_text;
+                    (0..columns as usize).map(move |c| {
+                        if c < old_columns {
+                            old_texts
+                                .get(r * old_columns + c)
+                                .cloned()
+                                .unwrap_or_else(|| default_text.to_string())
+                        } else {
+                            default_text.to_string()
+                        }
+                    })
+                })
+                .collect::<Vec<_>>()
+        } else {
+            old_texts.into_iter().take(rows as usize).collect()
+        };
+        let dimensions = if self.is_matrix() {
+            vec![rows, columns]
+        } else {
+            vec![rows]
+        };
+        self.populate(&texts, dimensions);
+    }
+
+    /// Replaces the contents of the grid with CSV text, resizing to fit. Each line of the CSV is
+    /// a row of a matrix. For a one dimensional array the fields are taken in order.
+    fn paste_csv(&self, text: &str) {
+        let rows = value_edit::parse_csv(text);
+        if rows.is_empty() {
+            return;
+        }
+        if self.is_matrix() && self.dimensions.borrow().len() == 2 {
+            let columns = rows.iter().map(|r| r.len()).max().unwrap_or(1);
+            let default_text = self.default_text();
+            let default_text = default_text.as_str();
+            let texts = rows
+                .iter()
+                .flat_map(|r| {
+                    (0..columns).map(move |c| r.get(c).map_or(default_text, |t| t.as_str()))
+                })
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>();
+            self.populate(&texts, vec![rows.len() as u32, columns as u32]);
+        } else {
+            let texts = rows.into_iter().flatten().collect::<Vec<_>>();
+            let dimensions = self.dimensions.borrow().clone();
+            if dimensions.len() > 2 {
+                // The shape is fixed so the values fill it in order
+                self.populate(&texts, dimensions);
+            } else {
+                let len = texts.len() as u32;
+                self.populate(&texts, vec![len]);
+            }
+        }
+    }
+
+    /// The text of a new element - the first member of an enumeration or the element type's
+    /// default
+    fn default_text(&self) -> String {
+        match self.enum_members.first() {
+            Some((value, _)) => value_edit::enum_value_to_string(&self.enum_members, *value),
+            None => self
+                .element_type()
+                .map_or("", |t| t.default_text())
+                .to_string(),
+        }
+    }
+
+    fn element_type(&self) -> Option<BuiltinType> {
+        self.element_type.or_else(|| {
+            self.type_combo
+                .get_active_id()
+                .and_then(|id| BuiltinType::from_name(id.as_str()))
+        })
+    }
+
+    /// Parses the index range, which must give a range for every dimension of the array
+    fn index_range(&self) -> Result<Option<Vec<(u32, u32)>>, String> {
+        let text = self.range_text.get_text();
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        let dimensions = self.dimensions.borrow();
+        let ranges = value_edit::parse_index_range(text.as_str(), Some(&dimensions))?;
+        if ranges.len() != dimensions.len() {
+            Err(format!(
+                "The range must have a range for each of the {} dimensions",
+                dimensions.len()
+            ))
+        } else {
+            Ok(Some(ranges))
+        }
+    }
+
+    fn update_range_label(&self) {
+        let message = match self.index_range() {
+            Ok(None) => format!(
+                "Writes the whole array of {} elements",
+                self.dimensions.borrow().iter().product::<u32>()
+            ),
+            Ok(Some(ranges)) => format!(
+                "Writes {} elements",
+                ranges
+                    .iter()
+                    .map(|(min, max)| max - min + 1)
+                    .product::<u32>()
+            ),
+            Err(err) => err,
+        };
+        self.range_label.set_text(&message);
+    }
+
+    fn value(&self) -> Result<(Variant, Option<String>), String> {
+        let element_type = self
+            .element_type()
+            .ok_or_else(|| "Choose a type for the elements".to_string())?;
+        let dimensions = self.dimensions.borrow().clone();
+        let texts = self.texts();
+
+        // Select the offsets of the elements to write, all of them or those in the range
+        let (offsets, write_dimensions, index_range) = match self.index_range()? {
+            None => (
+                (0..texts.len()).collect::<Vec<_>>(),
+                dimensions.clone(),
+                None,
+            ),
+            Some(ranges) => {
+                let offsets = range_offsets(&ranges, &dimensions);
+                let write_dimensions = ranges
+                    .iter()
+                    .map(|(min, max)| max - min + 1)
+                    .collect::<Vec<_>>();
+                let index_range = value_edit::index_range_to_string(&ranges);
+                (offsets, write_dimensions, Some(index_range))
+            }
+        };
+
+        let values = offsets
+            .iter()
+            .map(|offset| {
+                let text = texts.get(*offset).map_or("", |t| t.as_str());
+                let value = if self.enum_members.is_empty() {
+                    value_edit::parse_scalar(element_type, text)
+                } else {
+                    value_edit::parse_enum(&self.enum_members, text)
+                };
+                value.map_err(|err| {
+                    format!(
+                        "Element {}: {}",
+                        value_format::array_index_to_string(*offset, &dimensions),
+                        err
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((
+            value_edit::make_array(element_type, values, &write_dimensions),
+            index_range,
+        ))
+    }
+}
+
+/// The row-major offsets of the elements that fall within the ranges
+fn range_offsets(ranges: &[(u32, u32)], dimensions: &[u32]) -> Vec<usize> {
+    ranges
+        .iter()
+        .zip(dimensions.iter())
+        .fold(vec![0usize], |offsets, ((min, max), dimension)| {
+            offsets
+                .iter()
+                .flat_map(|offset| {
+                    (*min..=*max).map(move |i| offset * *dimension as usize + i as usize)
+                })
+                .collect()
+        })
+}
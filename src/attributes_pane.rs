@@ -0,0 +1,382 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    address_space_tree_view::AddressSpaceTreeView,
+    engineering_units::AnalogInfo,
+    model::{node_class_from_i32, ModelMessage, Translation},
+    type_dictionary::TypeDictionary,
+    value_edit::{self, BuiltinType, TEXT_TYPES},
+    value_format::{self, TimeDisplay},
+};
+
+const COL_NAME: u32 = 0;
+const COL_VALUE: u32 = 1;
+const COL_EDITABLE: u32 = 2;
+/// The AttributeId of the row, or 0 for rows that aren't attributes
+const COL_ATTRIBUTE_ID: u32 = 3;
+
+/// The bit of the WriteMask that permits each attribute to be written. The Value attribute is
+/// governed by the AccessLevel instead and is written through the Write Value dialog.
+const WRITE_MASK_BITS: &[(AttributeId, u32)] = &[
+    (AttributeId::AccessLevel, 0),
+    (AttributeId::ArrayDimensions, 1),
+    (AttributeId::BrowseName, 2),
+    (AttributeId::ContainsNoLoops, 3),
+    (AttributeId::DataType, 4),
+    (AttributeId::Description, 5),
+    (AttributeId::DisplayName, 6),
+    (AttributeId::EventNotifier, 7),
+    (AttributeId::Executable, 8),
+    (AttributeId::Historizing, 9),
+    (AttributeId::InverseName, 10),
+    (AttributeId::IsAbstract, 11),
+    (AttributeId::MinimumSamplingInterval, 12),
+    (AttributeId::NodeClass, 13),
+    (AttributeId::NodeId, 14),
+    (AttributeId::Symmetric, 15),
+    (AttributeId::UserAccessLevel, 16),
+    (AttributeId::UserExecutable, 17),
+    (AttributeId::UserWriteMask, 18),
+    (AttributeId::ValueRank, 19),
+    (AttributeId::WriteMask, 20),
+    (AttributeId::DataTypeDefinition, 22),
+    (AttributeId::RolePermissions, 23),
+    (AttributeId::AccessRestrictions, 24),
+    (AttributeId::AccessLevelEx, 25),
+];
+
+struct AttributesPaneImpl {
+    model: ActorRef<ModelMessage>,
+    properties_model: Rc<gtk::ListStore>,
+    /// The node whose attributes are being shown
+    node_id: RefCell<Option<NodeId>>,
+    /// Used to show the names of enumeration values
+    type_dictionary: RefCell<Arc<TypeDictionary>>,
+    /// The attributes being shown, which give the type to parse an edited attribute as
+    attributes: RefCell<Vec<(AttributeId, DataValue)>>,
+    /// The result of the last attribute written to the node, shown until another node is selected
+    write_result: RefCell<Option<(AttributeId, StatusCode)>>,
+}
+
+/// Shows the attributes of the node selected in the address space
+pub struct AttributesPane {
+    data: Rc<AttributesPaneImpl>,
+}
+
+impl AttributesPane {
+    pub fn new(builder: Rc<gtk::Builder>, model: ActorRef<ModelMessage>) -> Self {
+        let address_space_tree: Rc<gtk::TreeView> =
+            Rc::new(builder.get_object("address_space_tree").unwrap());
+        let value_renderer: gtk::CellRendererText =
+            builder.get_object("properties_value_renderer").unwrap();
+
+        let data = Rc::new(AttributesPaneImpl {
+            model,
+            properties_model: Rc::new(builder.get_object("properties_model").unwrap()),
+            node_id: RefCell::new(None),
+            type_dictionary: RefCell::new(Arc::new(TypeDictionary::default())),
+            attributes: RefCell::new(Vec::new()),
+            write_result: RefCell::new(None),
+        });
+
+        // The selection is tracked here rather than through the app because the selection
+        // changes while the app is busy, e.g. when the tree is cleared
+        address_space_tree
+            .get_selection()
+            .connect_changed(clone!(@weak data => move |selection| {
+                let node_id = AddressSpaceTreeView::node_id_from_selection(selection);
+                data.show_node(node_id);
+            }));
+
+        value_renderer.connect_edited(clone!(@weak data => move |_, tree_path, text| {
+            data.on_value_edited(&tree_path, text);
+        }));
+
+        Self { data }
+    }
+
+    pub fn on_node_attributes(
+        &self,
+        node_id: NodeId,
+        attributes: Vec<(AttributeId, DataValue)>,
+        analog_info: Option<AnalogInfo>,
+    ) {
+        self.data
+            .on_node_attributes(node_id, attributes, analog_info);
+    }
+
+    pub fn on_translations(&self, node_id: NodeId, translations: Vec<Translation>) {
+        self.data.on_translations(node_id, translations);
+    }
+
+    pub fn on_write_result(&self, node_id: &NodeId, attribute_id: AttributeId, status: StatusCode) {
+        if self.data.is_current(node_id) {
+            *self.data.write_result.borrow_mut() = Some((attribute_id, status));
+        }
+    }
+
+    pub fn clear(&self) {
+        self.data.show_node(None);
+    }
+
+    pub fn set_type_dictionary(&self, type_dictionary: Arc<TypeDictionary>) {
+        *self.data.type_dictionary.borrow_mut() = type_dictionary;
+    }
+}
+
+impl AttributesPaneImpl {
+    fn show_node(&self, node_id: Option<NodeId>) {
+        self.properties_model.clear();
+        self.attributes.borrow_mut().clear();
+        *self.write_result.borrow_mut() = None;
+        if let Some(ref node_id) = node_id {
+            self.model
+                .tell(ModelMessage::ReadAttributes(node_id.clone()), None);
+        }
+        *self.node_id.borrow_mut() = node_id;
+    }
+
+    fn is_current(&self, node_id: &NodeId) -> bool {
+        self.node_id.borrow().as_ref() == Some(node_id)
+    }
+
+    fn on_node_attributes(
+        &self,
+        node_id: NodeId,
+        attributes: Vec<(AttributeId, DataValue)>,
+        analog_info: Option<AnalogInfo>,
+    ) {
+        if !self.is_current(&node_id) {
+            return;
+        }
+        self.properties_model.clear();
+        let data_type = attributes.iter().find_map(|(attribute_id, value)| {
+            match (attribute_id, &value.value) {
+                (AttributeId::DataType, Some(Variant::NodeId(data_type))) => {
+                    Some((**data_type).clone())
+                }
+                _ => None,
+            }
+        });
+        let write_mask = write_mask(&attributes);
+        let type_dictionary = self.type_dictionary.borrow();
+        attributes.iter().for_each(|(attribute_id, value)| {
+            let text = match (attribute_id, &value.value) {
+                (AttributeId::Value, Some(ref v)) => {
+                    let text =
+                        type_dictionary.format_value(data_type.as_ref(), v, TimeDisplay::Utc);
+                    match analog_info {
+                        Some(ref analog_info) => analog_info.format_value(&text, v),
+                        None => text,
+                    }
+                }
+                _ => attribute_value_text(*attribute_id, value),
+            };
+            let editable = is_writable(*attribute_id, write_mask)
+                && value.value.as_ref().map_or(false, is_text_editable);
+            self.insert_attribute_row(*attribute_id, &text, editable);
+        });
+
+        if let Some((attribute_id, status)) = *self.write_result.borrow() {
+            self.insert_row(
+                &format!("Write {:?}", attribute_id),
+                &value_edit::describe_write_status(status),
+            );
+        }
+
+        // The properties of an analog item describe what the value means
+        if let Some(ref analog_info) = analog_info {
+            if let Some(units) = analog_info.units_description() {
+                self.insert_row("EngineeringUnits", &units);
+            }
+            if let Some(ref range) = analog_info.eu_range {
+                self.insert_row("EURange", &analog_info.with_units(&range.to_string()));
+            }
+            if let Some(ref range) = analog_info.instrument_range {
+                self.insert_row(
+                    "InstrumentRange",
+                    &analog_info.with_units(&range.to_string()),
+                );
+            }
+        }
+
+        *self.attributes.borrow_mut() = attributes;
+
+        // Translations are slower to fetch so they follow on afterwards
+        self.model
+            .tell(ModelMessage::ReadTranslations(node_id), None);
+    }
+
+    /// Writes an attribute edited in place
+    fn on_value_edited(&self, tree_path: &gtk::TreePath, text: &str) {
+        let node_id = match *self.node_id.borrow() {
+            Some(ref node_id) => node_id.clone(),
+            None => return,
+        };
+        let iter = match self.properties_model.get_iter(tree_path) {
+            Some(iter) => iter,
+            None => return,
+        };
+        let attribute_id = match self
+            .properties_model
+            .get_value(&iter, COL_ATTRIBUTE_ID as i32)
+            .get::<u32>()
+        {
+            Ok(Some(id)) => match AttributeId::from_u32(id) {
+                Ok(attribute_id) => attribute_id,
+                Err(_) => return,
+            },
+            _ => return,
+        };
+        let current = self
+            .attributes
+            .borrow()
+            .iter()
+            .find(|(a, _)| *a == attribute_id)
+            .and_then(|(_, v)| v.value.clone());
+        let result = match current {
+            Some(ref current) => parse_attribute_value(current, text),
+            None => return,
+        };
+        match result {
+            Ok(value) => {
+                self.model.tell(
+                    ModelMessage::WriteAttribute(node_id, attribute_id, value),
+                    None,
+                );
+            }
+            Err(err) => {
+                self.properties_model.set_value(
+                    &iter,
+                    COL_VALUE,
+                    &format!("{} ({})", text, err).to_value(),
+                );
+            }
+        }
+    }
+
+    fn on_translations(&self, node_id: NodeId, translations: Vec<Translation>) {
+        if !self.is_current(&node_id) {
+            return;
+        }
+        translations.iter().for_each(|t| {
+            if let Some(ref display_name) = t.display_name {
+                self.insert_row(
+                    &format!("DisplayName [{}]", t.locale),
+                    display_name.text.as_ref(),
+                );
+            }
+        });
+        translations.iter().for_each(|t| {
+            if let Some(ref description) = t.description {
+                self.insert_row(
+                    &format!("Description [{}]", t.locale),
+                    description.text.as_ref(),
+                );
+            }
+        });
+    }
+
+    fn insert_row(&self, name: &str, value: &str) {
+        let editable = false;
+        let attribute_id = 0u32;
+        self.properties_model.insert_with_values(
+            None,
+            &[COL_NAME, COL_VALUE, COL_EDITABLE, COL_ATTRIBUTE_ID],
+            &[&name, &value, &editable, &attribute_id],
+        );
+    }
+
+    fn insert_attribute_row(&self, attribute_id: AttributeId, value: &str, editable: bool) {
+        let name = format!("{:?}", attribute_id);
+        let id = attribute_id as u32;
+        self.properties_model.insert_with_values(
+            None,
+            &[COL_NAME, COL_VALUE, COL_EDITABLE, COL_ATTRIBUTE_ID],
+            &[&name, &value, &editable, &id],
+        );
+    }
+}
+
+/// The attributes the current user may write, the WriteMask restricted by the UserWriteMask
+fn write_mask(attributes: &[(AttributeId, DataValue)]) -> u32 {
+    let mask = |attribute_id| {
+        attributes.iter().find_map(|(a, v)| match v.value {
+            Some(Variant::UInt32(mask)) if *a == attribute_id => Some(mask),
+            _ => None,
+        })
+    };
+    match (
+        mask(AttributeId::WriteMask),
+        mask(AttributeId::UserWriteMask),
+    ) {
+        (Some(write_mask), Some(user_write_mask)) => write_mask & user_write_mask,
+        (Some(write_mask), None) => write_mask,
+        _ => 0,
+    }
+}
+
+fn is_writable(attribute_id: AttributeId, write_mask: u32) -> bool {
+    WRITE_MASK_BITS
+        .iter()
+        .find(|(a, _)| *a == attribute_id)
+        .map_or(false, |(_, bit)| write_mask & (1 << bit) != 0)
+}
+
+fn builtin_type_of(type_id: VariantTypeId) -> Option<BuiltinType> {
+    TEXT_TYPES
+        .iter()
+        .find(|t| t.variant_type_id() == type_id)
+        .cloned()
+}
+
+/// Tests if an attribute value can be edited as text. Structures such as RolePermissions can't.
+fn is_text_editable(value: &Variant) -> bool {
+    match value {
+        Variant::Array(array) => builtin_type_of(array.value_type).is_some(),
+        v => builtin_type_of(v.type_id()).is_some(),
+    }
+}
+
+/// Parses edited text into a value of the same type as the attribute currently holds. Arrays,
+/// such as ArrayDimensions, are entered as comma separated values.
+fn parse_attribute_value(current: &Variant, text: &str) -> Result<Variant, String> {
+    match current {
+        Variant::Array(array) => {
+            let element_type = builtin_type_of(array.value_type)
+                .ok_or_else(|| "The attribute cannot be edited".to_string())?;
+            let text = text.trim();
+            let text = text
+                .strip_prefix('[')
+                .and_then(|t| t.strip_suffix(']'))
+                .unwrap_or(text);
+            let values = value_edit::parse_csv(text)
+                .into_iter()
+                .flatten()
+                .filter(|t| !t.is_empty())
+                .map(|t| value_edit::parse_scalar(element_type, &t))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(value_edit::make_array(element_type, values, &[]))
+        }
+        v => match builtin_type_of(v.type_id()) {
+            Some(builtin_type) => value_edit::parse_scalar(builtin_type, text),
+            None => Err("The attribute cannot be edited".to_string()),
+        },
+    }
+}
+
+fn attribute_value_text(attribute_id: AttributeId, value: &DataValue) -> String {
+    match (attribute_id, &value.value) {
+        (AttributeId::NodeClass, Some(Variant::Int32(v))) => {
+            format!("{:?}", node_class_from_i32(*v))
+        }
+        (_, Some(ref v)) => value_format::variant_to_string(v, TimeDisplay::Utc),
+        (_, None) => String::new(),
+    }
+}
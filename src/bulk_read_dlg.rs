@@ -0,0 +1,319 @@
+use std::{cell::Cell, rc::Rc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    model::{BulkRead, BulkReadResult, ModelMessage},
+    value_format::{self, TimeDisplay},
+};
+
+const COL_NAME: u32 = 0;
+const COL_NODE_ID: u32 = 1;
+const COL_ATTRIBUTE: u32 = 2;
+const COL_VALUE: u32 = 3;
+const COL_STATUS: u32 = 4;
+const COL_SOURCE_TIMESTAMP: u32 = 5;
+const COL_SERVER_TIMESTAMP: u32 = 6;
+const COL_STATUS_COLOUR: u32 = 7;
+
+/// Attributes that can be read alongside the Value
+const OPTIONAL_ATTRIBUTES: &[AttributeId] = &[
+    AttributeId::DisplayName,
+    AttributeId::DataType,
+    AttributeId::ValueRank,
+    AttributeId::AccessLevel,
+    AttributeId::MinimumSamplingInterval,
+    AttributeId::Historizing,
+];
+
+struct BulkReadDlgImpl {
+    model: ActorRef<ModelMessage>,
+    dlg: gtk::Dialog,
+    /// The nodes to read and their display names
+    nodes: Vec<(NodeId, String)>,
+    value_check: gtk::CheckButton,
+    attribute_checks: Vec<(AttributeId, gtk::CheckButton)>,
+    max_age_spin: gtk::SpinButton,
+    timestamps_combo: gtk::ComboBoxText,
+    results_model: gtk::ListStore,
+    status_label: gtk::Label,
+    closed: Cell<bool>,
+}
+
+/// A dialog which reads attributes of the nodes selected in the address space and shows the
+/// results in a table, one row per node and attribute.
+pub(crate) struct BulkReadDlg {
+    data: Rc<BulkReadDlgImpl>,
+}
+
+impl BulkReadDlg {
+    pub fn new(
+        model: ActorRef<ModelMessage>,
+        parent: &gtk::ApplicationWindow,
+        nodes: Vec<(NodeId, String)>,
+    ) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&format!("Read Selected - {} nodes", nodes.len())),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Close", gtk::ResponseType::Close),
+                ("Read", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+        dlg.set_default_size(800, 500);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+
+        let attributes_label = gtk::Label::new(Some("Attributes:"));
+        attributes_label.set_halign(gtk::Align::End);
+        let attributes_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let value_check = gtk::CheckButton::with_label("Value");
+        value_check.set_active(true);
+        attributes_box.pack_start(&value_check, false, false, 0);
+        let attribute_checks = OPTIONAL_ATTRIBUTES
+            .iter()
+            .map(|attribute_id| {
+                let check = gtk::CheckButton::with_label(&format!("{:?}", attribute_id));
+                attributes_box.pack_start(&check, false, false, 0);
+                (*attribute_id, check)
+            })
+            .collect::<Vec<_>>();
+        grid.attach(&attributes_label, 0, 0, 1, 1);
+        grid.attach(&attributes_box, 1, 0, 1, 1);
+
+        let max_age_label = gtk::Label::new(Some("Max Age (ms):"));
+        max_age_label.set_halign(gtk::Align::End);
+        let max_age_spin = gtk::SpinButton::with_range(0.0, i32::MAX as f64, 100.0);
+        max_age_spin.set_digits(0);
+        max_age_spin.set_halign(gtk::Align::Start);
+        max_age_spin.set_tooltip_text(Some(
+            "0 reads the current value from the source, larger values allow a cached value",
+        ));
+        grid.attach(&max_age_label, 0, 1, 1, 1);
+        grid.attach(&max_age_spin, 1, 1, 1, 1);
+
+        let timestamps_label = gtk::Label::new(Some("Timestamps:"));
+        timestamps_label.set_halign(gtk::Align::End);
+        let timestamps_combo = gtk::ComboBoxText::new();
+        ["Source", "Server", "Both", "Neither"]
+            .iter()
+            .for_each(|t| timestamps_combo.append(Some(*t), t));
+        timestamps_combo.set_active_id(Some("Both"));
+        timestamps_combo.set_halign(gtk::Align::Start);
+        grid.attach(&timestamps_label, 0, 2, 1, 1);
+        grid.attach(&timestamps_combo, 1, 2, 1, 1);
+
+        let results_model = gtk::ListStore::new(&[glib::Type::String; 8]);
+        let results_tree = gtk::TreeView::with_model(&results_model);
+        [
+            ("Node", COL_NAME),
+            ("NodeId", COL_NODE_ID),
+            ("Attribute", COL_ATTRIBUTE),
+            ("Value", COL_VALUE),
+            ("Status", COL_STATUS),
+            ("SourceTimestamp", COL_SOURCE_TIMESTAMP),
+            ("ServerTimestamp", COL_SERVER_TIMESTAMP),
+        ]
+        .iter()
+        .for_each(|(title, i)| {
+            let renderer = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.set_sort_column_id(*i as i32);
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", *i as i32);
+            if *i == COL_STATUS {
+                column.add_attribute(&renderer, "foreground", COL_STATUS_COLOUR as i32);
+            }
+            results_tree.append_column(&column);
+        });
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.set_vexpand(true);
+        scrolled_window.set_hexpand(true);
+        scrolled_window.add(&results_tree);
+        grid.attach(&scrolled_window, 0, 3, 2, 1);
+
+        let status_label = gtk::Label::new(None);
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        grid.attach(&status_label, 0, 4, 2, 1);
+
+        dlg.get_content_area().pack_start(&grid, true, true, 0);
+
+        let data = Rc::new(BulkReadDlgImpl {
+            model,
+            dlg,
+            nodes,
+            value_check,
+            attribute_checks,
+            max_age_spin,
+            timestamps_combo,
+            results_model,
+            status_label,
+            closed: Cell::new(false),
+        });
+
+        data.dlg
+            .connect_response(clone!(@weak data => move |dlg, response| {
+                if response == gtk::ResponseType::Apply {
+                    data.on_read_clicked();
+                } else {
+                    data.closed.set(true);
+                    dlg.close();
+                }
+            }));
+
+        Self { data }
+    }
+
+    pub fn show(&self) {
+        self.data.dlg.show_all();
+    }
+
+    pub fn close(&self) {
+        self.data.closed.set(true);
+        self.data.dlg.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.data.closed.get()
+    }
+
+    pub fn on_bulk_read_result(&self, result: BulkReadResult) {
+        self.data.on_bulk_read_result(result);
+    }
+}
+
+impl BulkReadDlgImpl {
+    fn attribute_ids(&self) -> Vec<AttributeId> {
+        let mut attribute_ids = Vec::new();
+        if self.value_check.get_active() {
+            attribute_ids.push(AttributeId::Value);
+        }
+        attribute_ids.extend(
+            self.attribute_checks
+                .iter()
+                .filter(|(_, check)| check.get_active())
+                .map(|(attribute_id, _)| *attribute_id),
+        );
+        attribute_ids
+    }
+
+    fn timestamps_to_return(&self) -> TimestampsToReturn {
+        match self.timestamps_combo.get_active_id() {
+            Some(ref id) if id.as_str() == "Source" => TimestampsToReturn::Source,
+            Some(ref id) if id.as_str() == "Server" => TimestampsToReturn::Server,
+            Some(ref id) if id.as_str() == "Neither" => TimestampsToReturn::Neither,
+            _ => TimestampsToReturn::Both,
+        }
+    }
+
+    fn on_read_clicked(&self) {
+        let attribute_ids = self.attribute_ids();
+        if attribute_ids.is_empty() {
+            self.set_status(false, "Choose at least one attribute to read");
+            return;
+        }
+        self.set_status(
+            true,
+            &format!(
+                "Reading {} values...",
+                self.nodes.len() * attribute_ids.len()
+            ),
+        );
+        let bulk_read = BulkRead {
+            node_ids: self
+                .nodes
+                .iter()
+                .map(|(node_id, _)| node_id.clone())
+                .collect(),
+            attribute_ids,
+            max_age: self.max_age_spin.get_value(),
+            timestamps_to_return: self.timestamps_to_return(),
+        };
+        self.model.tell(ModelMessage::BulkRead(bulk_read), None);
+    }
+
+    fn on_bulk_read_result(&self, result: BulkReadResult) {
+        self.results_model.clear();
+        let columns = [
+            COL_NAME,
+            COL_NODE_ID,
+            COL_ATTRIBUTE,
+            COL_VALUE,
+            COL_STATUS,
+            COL_SOURCE_TIMESTAMP,
+            COL_SERVER_TIMESTAMP,
+            COL_STATUS_COLOUR,
+        ];
+        let timestamp = |t: &Option<DateTime>| {
+            t.as_ref().map_or_else(String::new, |t| {
+                value_format::date_time_to_string(t, TimeDisplay::Utc)
+            })
+        };
+        let mut failed = 0;
+        result
+            .values
+            .iter()
+            .for_each(|(node_id, attribute_id, data_value)| {
+                let name = self
+                    .nodes
+                    .iter()
+                    .find(|(n, _)| n == node_id)
+                    .map_or("", |(_, name)| name.as_str());
+                let status = data_value.status.unwrap_or(StatusCode::Good);
+                let colour = if status.is_bad() {
+                    failed += 1;
+                    "red"
+                } else if status.is_uncertain() {
+                    "darkorange"
+                } else {
+                    "darkgreen"
+                };
+                let value = data_value.value.as_ref().map_or_else(String::new, |v| {
+                    value_format::variant_to_string(v, TimeDisplay::Utc)
+                });
+                let texts = [
+                    name.to_string(),
+                    node_id.to_string(),
+                    format!("{:?}", attribute_id),
+                    value,
+                    value_format::status_code_to_string(status),
+                    timestamp(&data_value.source_timestamp),
+                    timestamp(&data_value.server_timestamp),
+                    colour.to_string(),
+                ];
+                let values = texts.iter().map(|t| t as &dyn ToValue).collect::<Vec<_>>();
+                self.results_model
+                    .insert_with_values(None, &columns, &values);
+            });
+
+        let message = format!(
+            "Read {} values in {} requests of at most {} nodes, {} failed",
+            result.values.len(),
+            result.requests,
+            result.nodes_per_request,
+            failed
+        );
+        self.set_status(failed == 0, &message);
+    }
+
+    fn set_status(&self, good: bool, message: &str) {
+        let colour = if good { "darkgreen" } else { "red" };
+        self.status_label.set_markup(&format!(
+            "<span foreground=\"{}\">{}</span>",
+            colour,
+            glib::markup_escape_text(message)
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+use std::{cell::Cell, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    method_call::{MethodArgument, MethodCall, MethodCallResult, MethodInfo},
+    model::ModelMessage,
+    type_dictionary::TypeDictionary,
+    value_edit::{self, WriteInfo},
+    value_editor::ValueEditor,
+    value_format::{self, TimeDisplay},
+};
+
+struct CallMethodDlgImpl {
+    model: ActorRef<ModelMessage>,
+    method_info: MethodInfo,
+    dlg: gtk::Dialog,
+    /// An editor for each input argument
+    editors: Vec<ValueEditor>,
+    /// The result the server gave for each input argument
+    input_status_labels: Vec<gtk::Label>,
+    output_labels: Vec<gtk::Label>,
+    status_label: gtk::Label,
+    closed: Cell<bool>,
+}
+
+/// A dialog which calls a method on an object with a form generated from the method's input
+/// arguments. The dialog stays open after calling so the outputs can be read and the method
+/// called again. A call from the history starts the form off with the inputs it was made with.
+pub(crate) struct CallMethodDlg {
+    data: Rc<CallMethodDlgImpl>,
+}
+
+impl CallMethodDlg {
+    pub fn new(
+        model: ActorRef<ModelMessage>,
+        parent: &gtk::ApplicationWindow,
+        mut method_info: MethodInfo,
+        inputs: Option<Vec<Variant>>,
+        type_dictionary: Arc<TypeDictionary>,
+    ) -> Self {
+        if let Some(inputs) = inputs {
+            method_info
+                .inputs
+                .iter_mut()
+                .zip(inputs.into_iter())
+                .for_each(|(argument, value)| {
+                    argument.info.value = Some(DataValue {
+                        value: Some(value),
+                        ..Default::default()
+                    });
+                });
+        }
+
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&format!("Call - {}", method_info.method_name)),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Close", gtk::ResponseType::Close),
+                ("Call", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+        dlg.set_default_size(560, 480);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+
+        let add_row = |top: i32, name: &str, value: &str| {
+            let name_label = gtk::Label::new(Some(name));
+            name_label.set_halign(gtk::Align::End);
+            let value_label = gtk::Label::new(Some(value));
+            value_label.set_halign(gtk::Align::Start);
+            value_label.set_selectable(true);
+            grid.attach(&name_label, 0, top, 1, 1);
+            grid.attach(&value_label, 1, top, 2, 1);
+        };
+        let add_heading = |top: i32, text: &str| {
+            let label = gtk::Label::new(None);
+            label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(text)));
+            label.set_halign(gtk::Align::Start);
+            label.set_margin_top(8);
+            grid.attach(&label, 0, top, 3, 1);
+        };
+        let argument_label = |top: i32, argument: &MethodArgument| {
+            let label = gtk::Label::new(Some(&format!("{}:", argument.name)));
+            label.set_halign(gtk::Align::End);
+            label.set_valign(gtk::Align::Start);
+            if !argument.description.is_empty() {
+                label.set_tooltip_text(Some(&argument.description));
+            }
+            grid.attach(&label, 0, top, 1, 1);
+            let type_label = gtk::Label::new(Some(&argument_type_text(&argument.info)));
+            type_label.set_halign(gtk::Align::Start);
+            type_label.set_valign(gtk::Align::Start);
+            grid.attach(&type_label, 2, top, 1, 1);
+        };
+
+        add_row(
+            0,
+            "Object:",
+            &format!("{} ({})", method_info.object_name, method_info.object_id),
+        );
+        add_row(
+            1,
+            "Method:",
+            &format!("{} ({})", method_info.method_name, method_info.method_id),
+        );
+
+        let mut top = 2;
+        add_heading(top, "Input Arguments");
+        top += 1;
+        if method_info.inputs.is_empty() {
+            add_row(top, "", "None");
+            top += 1;
+        }
+        let mut editors = Vec::with_capacity(method_info.inputs.len());
+        let mut input_status_labels = Vec::with_capacity(method_info.inputs.len());
+        method_info.inputs.iter().for_each(|argument| {
+            argument_label(top, argument);
+            let editor = ValueEditor::new(&argument.info, &type_dictionary);
+            if !argument.description.is_empty() {
+                editor.set_tooltip_text(Some(&argument.description));
+            }
+            editor.attach(&grid, top);
+            let status_label = gtk::Label::new(None);
+            status_label.set_halign(gtk::Align::Start);
+            status_label.set_valign(gtk::Align::Start);
+            grid.attach(&status_label, 2, top + 1, 1, 1);
+            top += editor.rows().max(2);
+            editors.push(editor);
+            input_status_labels.push(status_label);
+        });
+
+        add_heading(top, "Output Arguments");
+        top += 1;
+        if method_info.outputs.is_empty() {
+            add_row(top, "", "None");
+            top += 1;
+        }
+        let output_labels = method_info
+            .outputs
+            .iter()
+            .map(|argument| {
+                argument_label(top, argument);
+                let value_label = gtk::Label::new(None);
+                value_label.set_halign(gtk::Align::Start);
+                value_label.set_selectable(true);
+                value_label.set_line_wrap(true);
+                grid.attach(&value_label, 1, top, 1, 1);
+                top += 1;
+                value_label
+            })
+            .collect::<Vec<_>>();
+
+        let status_label = gtk::Label::new(None);
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        status_label.set_margin_top(8);
+        grid.attach(&status_label, 0, top, 3, 1);
+
+        // Methods can have many arguments, and arrays take up a lot of room
+        let scrolled_window =
+            gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        scrolled_window.add(&grid);
+        dlg.get_content_area()
+            .pack_start(&scrolled_window, true, true, 0);
+
+        let data = Rc::new(CallMethodDlgImpl {
+            model,
+            method_info,
+            dlg,
+            editors,
+            input_status_labels,
+            output_labels,
+            status_label,
+            closed: Cell::new(false),
+        });
+
+        if !data.method_info.executable {
+            data.set_status(
+                false,
+                "The method's Executable / UserExecutable attributes do not permit calling it",
+            );
+        }
+
+        data.dlg
+            .connect_response(clone!(@weak data => move |dlg, response| {
+                if response == gtk::ResponseType::Apply {
+                    data.on_call_clicked();
+                } else {
+                    data.closed.set(true);
+                    dlg.close();
+                }
+            }));
+
+        Self { data }
+    }
+
+    pub fn show(&self) {
+        self.data.dlg.show_all();
+    }
+
+    pub fn close(&self) {
+        self.data.closed.set(true);
+        self.data.dlg.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.data.closed.get()
+    }
+
+    pub fn on_call_result(&self, result: &MethodCallResult) {
+        let info = &self.data.method_info;
+        if result.call.object_id == info.object_id && result.call.method_id == info.method_id {
+            self.data.show_result(result);
+        }
+    }
+}
+
+impl CallMethodDlgImpl {
+    fn set_status(&self, good: bool, message: &str) {
+        let colour = if good { "darkgreen" } else { "red" };
+        self.status_label.set_markup(&format!(
+            "<span foreground=\"{}\">{}</span>",
+            colour,
+            glib::markup_escape_text(message)
+        ));
+    }
+
+    /// Validates every input and returns their values in order
+    fn inputs(&self) -> Result<Vec<Variant>, String> {
+        self.editors
+            .iter()
+            .zip(self.method_info.inputs.iter())
+            .map(|(editor, argument)| match editor.value() {
+                Ok((value, None)) => Ok(value),
+                Ok((_, Some(_))) => Err(format!(
+                    "{}: an index range cannot be used for an argument",
+                    argument.name
+                )),
+                Err(err) => Err(format!("{}: {}", argument.name, err)),
+            })
+            .collect()
+    }
+
+    fn on_call_clicked(&self) {
+        match self.inputs() {
+            Ok(inputs) => {
+                self.set_status(true, "Calling...");
+                self.input_status_labels
+                    .iter()
+                    .chain(self.output_labels.iter())
+                    .for_each(|label| label.set_text(""));
+                let info = &self.method_info;
+                let call = MethodCall {
+                    object_id: info.object_id.clone(),
+                    object_name: info.object_name.clone(),
+                    method_id: info.method_id.clone(),
+                    method_name: info.method_name.clone(),
+                    inputs,
+                };
+                self.model.tell(ModelMessage::CallMethod(call), None);
+            }
+            Err(err) => self.set_status(false, &err),
+        }
+    }
+
+    /// Shows the outputs, the result of each input and the overall result of a call
+    fn show_result(&self, result: &MethodCallResult) {
+        self.input_status_labels
+            .iter()
+            .zip(result.input_results.iter())
+            .for_each(|(label, status_code)| {
+                let colour = if status_code.is_good() {
+                    "darkgreen"
+                } else {
+                    "red"
+                };
+                label.set_markup(&format!(
+                    "<span foreground=\"{}\">{}</span>",
+                    colour,
+                    glib::markup_escape_text(&value_format::status_code_to_string(*status_code))
+                ));
+            });
+        self.output_labels
+            .iter()
+            .zip(self.method_info.outputs.iter())
+            .enumerate()
+            .for_each(|(i, (label, argument))| {
+                let text = result
+                    .outputs
+                    .get(i)
+                    .map(|v| argument_value_text(&argument.info, v))
+                    .unwrap_or_default();
+                label.set_text(&text);
+            });
+        let message = if result.status_code.is_good() {
+            format!("Called at {}", chrono::Local::now().format("%H:%M:%S"))
+        } else {
+            format!(
+                "The call failed, {}",
+                value_format::status_code_to_string(result.status_code)
+            )
+        };
+        self.set_status(result.status_code.is_good(), &message);
+    }
+}
+
+/// Describes the DataType and ValueRank of an argument, e.g. "Double[]"
+fn argument_type_text(info: &WriteInfo) -> String {
+    match info.value_rank {
+        -3 => format!("{} (scalar or one dimension)", info.data_type_name),
+        -2 => format!("{} (any rank)", info.data_type_name),
+        0 => format!("{}[] (one or more dimensions)", info.data_type_name),
+        rank if rank > 0 => format!("{}{}", info.data_type_name, "[]".repeat(rank as usize)),
+        _ => info.data_type_name.clone(),
+    }
+}
+
+/// Formats the value of an argument, naming the members of enumerations
+fn argument_value_text(info: &WriteInfo, value: &Variant) -> String {
+    match value {
+        Variant::Int32(v) if info.is_enum() => {
+            value_edit::enum_value_to_string(&info.enum_members, *v as i64)
+        }
+        v => value_format::variant_to_string(v, TimeDisplay::Utc),
+    }
+}
@@ -0,0 +1,95 @@
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::alarms::ConditionAction;
+
+/// Asks for what a condition method needs before it is called, i.e. the comment of Acknowledge,
+/// Confirm and AddComment or how long TimedShelve shelves the alarm for
+pub(crate) struct ConditionActionDlg {
+    dlg: gtk::Dialog,
+    action: ConditionAction,
+    comment_entry: gtk::Entry,
+    shelving_time_spin: gtk::SpinButton,
+}
+
+impl ConditionActionDlg {
+    pub fn new(
+        parent: Option<&gtk::Window>,
+        condition_name: &str,
+        action: ConditionAction,
+    ) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&format!("{} - {}", action.name(), condition_name)),
+            parent,
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                (action.name(), gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+
+        let comment_entry = gtk::Entry::new();
+        comment_entry.set_activates_default(true);
+        comment_entry.set_hexpand(true);
+        comment_entry.set_width_chars(40);
+        let shelving_time_spin = gtk::SpinButton::with_range(1.0, i32::MAX as f64, 1.0);
+        shelving_time_spin.set_digits(0);
+        shelving_time_spin.set_activates_default(true);
+        shelving_time_spin.set_halign(gtk::Align::Start);
+
+        let (text, widget): (&str, gtk::Widget) = match action {
+            ConditionAction::TimedShelve(shelving_time) => {
+                shelving_time_spin.set_value((shelving_time / 1000.0).max(1.0));
+                shelving_time_spin.set_tooltip_text(Some(
+                    "The alarm is unshelved again after this time, if the server allows it",
+                ));
+                ("Shelve For (s):", shelving_time_spin.clone().upcast())
+            }
+            _ => {
+                comment_entry.set_tooltip_text(Some("Stored with the condition's Comment"));
+                ("Comment:", comment_entry.clone().upcast())
+            }
+        };
+        let label = gtk::Label::new(Some(text));
+        label.set_halign(gtk::Align::End);
+        grid.attach(&label, 0, 0, 1, 1);
+        grid.attach(&widget, 1, 0, 1, 1);
+
+        dlg.get_content_area().add(&grid);
+
+        Self {
+            dlg,
+            action,
+            comment_entry,
+            shelving_time_spin,
+        }
+    }
+
+    /// Runs the dialog and returns the action with what was entered, or none if cancelled
+    pub fn run(&self) -> Option<ConditionAction> {
+        self.dlg.show_all();
+        let action = if self.dlg.run() == gtk::ResponseType::Apply {
+            let comment = LocalizedText::new("", self.comment_entry.get_text().as_str());
+            Some(match self.action {
+                ConditionAction::Acknowledge(_) => ConditionAction::Acknowledge(comment),
+                ConditionAction::Confirm(_) => ConditionAction::Confirm(comment),
+                ConditionAction::AddComment(_) => ConditionAction::AddComment(comment),
+                ConditionAction::TimedShelve(_) => {
+                    ConditionAction::TimedShelve(self.shelving_time_spin.get_value() * 1000.0)
+                }
+                ref action => action.clone(),
+            })
+        } else {
+            None
+        };
+        self.dlg.close();
+        action
+    }
+}
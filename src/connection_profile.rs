@@ -0,0 +1,25 @@
+use opcua_client::prelude::*;
+
+/// The settings used to connect to a server
+#[derive(Debug, Clone)]
+pub struct ConnectionProfile {
+    pub endpoint_url: String,
+    pub security_policy: SecurityPolicy,
+    pub message_security_mode: MessageSecurityMode,
+    /// LocaleIds in order of preference, e.g. "de-DE", "en"
+    pub preferred_locales: Vec<String>,
+}
+
+impl ConnectionProfile {
+    /// Parses a comma or whitespace separated list of LocaleIds as typed by the user
+    pub fn parse_locales(text: &str) -> Vec<String> {
+        text.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    pub fn locales_text(&self) -> String {
+        self.preferred_locales.join(", ")
+    }
+}
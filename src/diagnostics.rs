@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use opcua_client::prelude::*;
+
+use crate::engineering_units::variant_to_f64;
+
+/// What the client has seen of a subscription's data change notifications. The client library
+/// handles publish responses itself and only passes on those with data changes, so sequence
+/// numbers, keep-alives, missing messages and Republish calls are not seen by the client at
+/// all. They can only be shown from the server's diagnostics.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionHealth {
+    /// Publish responses that carried data changes
+    pub notifications: u64,
+    /// The values in those notifications
+    pub values: u64,
+    pub first: Option<Instant>,
+    pub last: Option<Instant>,
+    pub min_interval: Option<Duration>,
+    pub max_interval: Option<Duration>,
+    total_interval: Duration,
+    /// The time after which the server sends a keep-alive if it has nothing to publish
+    pub keep_alive_time: Option<Duration>,
+    /// The times no data change arrived for longer than the keep-alive time. These are normal
+    /// while values don't change, so they are not gaps in the sequence of messages.
+    pub silences: u64,
+}
+
+impl SubscriptionHealth {
+    /// Records a notification with some values
+    pub fn record(&mut self, values: usize) {
+        let now = Instant::now();
+        if let Some(last) = self.last {
+            let interval = now - last;
+            self.min_interval = Some(self.min_interval.map_or(interval, |min| min.min(interval)));
+            self.max_interval = Some(self.max_interval.map_or(interval, |max| max.max(interval)));
+            self.total_interval += interval;
+            if self.keep_alive_time.map_or(false, |t| interval > t) {
+                self.silences += 1;
+            }
+        } else {
+            self.first = Some(now);
+        }
+        self.last = Some(now);
+        self.notifications += 1;
+        self.values += values as u64;
+    }
+
+    /// Sets the keep-alive time from the publishing parameters the server granted
+    pub fn set_keep_alive_time(&mut self, publishing_interval: f64, max_keep_alive_count: u32) {
+        self.keep_alive_time = Some(Duration::from_secs_f64(
+            publishing_interval.max(0.0) * max_keep_alive_count as f64 / 1000.0,
+        ));
+    }
+
+    pub fn mean_interval(&self) -> Option<Duration> {
+        if self.notifications > 1 {
+            Some(self.total_interval / (self.notifications - 1) as u32)
+        } else {
+            None
+        }
+    }
+
+    /// True if nothing has arrived for longer than the keep-alive time
+    pub fn is_silent(&self) -> bool {
+        match (self.last, self.keep_alive_time) {
+            (Some(last), Some(keep_alive_time)) => last.elapsed() > keep_alive_time,
+            _ => false,
+        }
+    }
+}
+
+/// The health of a watch list's subscription as seen by the client and by the server
+#[derive(Debug, Clone)]
+pub struct SubscriptionDiagnostics {
+    pub watch_list_id: u32,
+    pub subscription_id: u32,
+    pub client: SubscriptionHealth,
+    /// The subscription's entry in the server's SubscriptionDiagnosticsArray, or why there
+    /// isn't one
+    pub server: Result<SubscriptionDiagnosticsDataType, String>,
+}
+
+/// How often a watch item's value arrives and the range of its numeric values
+#[derive(Debug, Clone, Default)]
+pub struct ItemStatistics {
+    pub count: u64,
+    first: Option<Instant>,
+    last: Option<Instant>,
+    numeric_count: u64,
+    sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl ItemStatistics {
+    pub fn record(&mut self, data_value: &DataValue) {
+        let now = Instant::now();
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+        self.count += 1;
+        let value = data_value.value.as_ref().and_then(|v| match v {
+            Variant::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            v => variant_to_f64(v),
+        });
+        if let Some(value) = value.filter(|v| v.is_finite()) {
+            self.numeric_count += 1;
+            self.sum += value;
+            self.min = Some(self.min.map_or(value, |min| min.min(value)));
+            self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        }
+    }
+
+    /// The values per second between the first and the last
+    pub fn rate(&self) -> Option<f64> {
+        let seconds = (self.last? - self.first?).as_secs_f64();
+        if seconds > 0.0 {
+            Some((self.count - 1) as f64 / seconds)
+        } else {
+            None
+        }
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.numeric_count > 0 {
+            Some(self.sum / self.numeric_count as f64)
+        } else {
+            None
+        }
+    }
+
+    pub fn rate_text(&self) -> String {
+        self.rate()
+            .map_or_else(String::new, |rate| format!("{:.2}/s", rate))
+    }
+
+    /// The minimum, mean and maximum of the numeric values
+    pub fn range_text(&self) -> String {
+        match (self.min, self.mean(), self.max) {
+            (Some(min), Some(mean), Some(max)) => format!("{} / {:.3} / {}", min, mean, max),
+            _ => String::new(),
+        }
+    }
+}
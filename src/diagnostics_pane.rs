@@ -0,0 +1,356 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use crate::{
+    diagnostics::SubscriptionDiagnostics, model::ModelMessage,
+    monitored_items_pane::MonitoredItemsPane,
+};
+
+const COL_METRIC: u32 = 0;
+const COL_CLIENT: u32 = 1;
+const COL_SERVER: u32 = 2;
+const COL_COLOUR: u32 = 3;
+
+/// How often the diagnostics are read while Auto Refresh is on, in milliseconds
+const AUTO_REFRESH_INTERVAL: u32 = 2000;
+
+/// Metrics the client would track itself if the client library passed on every publish
+/// response
+const SERVER_ONLY_METRICS: [&str; 5] = [
+    "Last Sequence Number",
+    "Current Keep-alive Count",
+    "Republish Requests",
+    "Republished Messages",
+    "Unacknowledged Messages",
+];
+
+/// What the client column says of metrics the client library doesn't let the client see
+const SERVER_ONLY: &str = "Server only";
+
+struct DiagnosticsPaneImpl {
+    model: ActorRef<ModelMessage>,
+    monitored_items_pane: MonitoredItemsPane,
+    subscription_combo: gtk::ComboBoxText,
+    auto_check: gtk::CheckButton,
+    status_label: gtk::Label,
+    diagnostics_model: gtk::ListStore,
+    /// The diagnostics last read, one per watch list with a subscription
+    diagnostics: RefCell<Vec<SubscriptionDiagnostics>>,
+    connected: Cell<bool>,
+}
+
+/// Shows how the subscription of a watch list is doing as the client sees it, next to what the
+/// server's SubscriptionDiagnosticsArray says about it
+pub struct DiagnosticsPane {
+    data: Rc<DiagnosticsPaneImpl>,
+}
+
+impl DiagnosticsPane {
+    pub fn new(
+        builder: Rc<gtk::Builder>,
+        model: ActorRef<ModelMessage>,
+        monitored_items_pane: MonitoredItemsPane,
+    ) -> Self {
+        let refresh_btn: gtk::Button = builder.get_object("diagnostics_refresh_btn").unwrap();
+
+        let data = Rc::new(DiagnosticsPaneImpl {
+            model,
+            monitored_items_pane,
+            subscription_combo: builder
+                .get_object("diagnostics_subscription_combo")
+                .unwrap(),
+            auto_check: builder.get_object("diagnostics_auto_check").unwrap(),
+            status_label: builder.get_object("diagnostics_status_label").unwrap(),
+            diagnostics_model: builder.get_object("diagnostics_model").unwrap(),
+            diagnostics: RefCell::new(Vec::new()),
+            connected: Cell::new(false),
+        });
+
+        refresh_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.read();
+        }));
+        data.subscription_combo
+            .connect_changed(clone!(@weak data => move |_| {
+                data.update_rows();
+            }));
+
+        glib::timeout_add_local(
+            AUTO_REFRESH_INTERVAL,
+            clone!(@weak data => @default-return glib::Continue(false), move || {
+                if data.auto_check.get_active() {
+                    data.read();
+                }
+                glib::Continue(true)
+            }),
+        );
+
+        Self { data }
+    }
+
+    pub fn on_connected(&self) {
+        self.data.connected.set(true);
+        self.data.read();
+    }
+
+    pub fn on_disconnected(&self) {
+        self.data.connected.set(false);
+        self.data.diagnostics.borrow_mut().clear();
+        self.data.update_combo();
+        self.data.status_label.set_text("Not connected");
+    }
+
+    pub fn on_diagnostics(&self, diagnostics: Vec<SubscriptionDiagnostics>) {
+        self.data.status_label.set_text(&format!(
+            "{} subscriptions, read at {}",
+            diagnostics.len(),
+            chrono::Local::now().format("%H:%M:%S")
+        ));
+        *self.data.diagnostics.borrow_mut() = diagnostics;
+        self.data.update_combo();
+    }
+}
+
+impl DiagnosticsPaneImpl {
+    fn read(&self) {
+        if self.connected.get() {
+            self.model
+                .tell(ModelMessage::ReadSubscriptionDiagnostics, None);
+        }
+    }
+
+    /// Lists the subscriptions by the name of their watch list, keeping the selected one
+    fn update_combo(&self) {
+        let selected = self.subscription_combo.get_active_id();
+        let watch_lists = self.monitored_items_pane.watch_lists();
+        let entries = {
+            let mut diagnostics = self.diagnostics.borrow_mut();
+            diagnostics.sort_by_key(|d| d.watch_list_id);
+            diagnostics
+                .iter()
+                .map(|d| {
+                    let name = watch_lists
+                        .iter()
+                        .find(|w| w.id == d.watch_list_id)
+                        .map_or("", |w| w.name.as_str());
+                    (
+                        d.watch_list_id.to_string(),
+                        format!("{} ({})", name, d.subscription_id),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        self.subscription_combo.remove_all();
+        entries
+            .iter()
+            .for_each(|(id, text)| self.subscription_combo.append(Some(id), text));
+        if selected.is_none() || !self.subscription_combo.set_active_id(selected.as_deref()) {
+            self.subscription_combo
+                .set_active_id(entries.first().map(|(id, _)| id.as_str()));
+        }
+        self.update_rows();
+    }
+
+    /// Shows the diagnostics of the selected subscription, one metric per row
+    fn update_rows(&self) {
+        self.diagnostics_model.clear();
+        let watch_list_id = self
+            .subscription_combo
+            .get_active_id()
+            .and_then(|id| id.as_str().parse::<u32>().ok());
+        let diagnostics = self.diagnostics.borrow();
+        let d = match diagnostics
+            .iter()
+            .find(|d| Some(d.watch_list_id) == watch_list_id)
+        {
+            Some(d) => d,
+            None => return,
+        };
+        let row = |metric: &str, client: String, server: String, colour: Option<&str>| {
+            self.diagnostics_model.insert_with_values(
+                None,
+                &[COL_METRIC, COL_CLIENT, COL_SERVER, COL_COLOUR],
+                &[&metric, &client, &server, &colour],
+            );
+        };
+        let seconds = |d: Option<Duration>| {
+            d.map_or_else(String::new, |d| format!("{:.3} s", d.as_secs_f64()))
+        };
+
+        let client = &d.client;
+        row(
+            "Subscription Id",
+            d.subscription_id.to_string(),
+            String::new(),
+            None,
+        );
+        row(
+            "Notifications",
+            client.notifications.to_string(),
+            String::new(),
+            None,
+        );
+        row(
+            "Values Received",
+            client.values.to_string(),
+            String::new(),
+            None,
+        );
+        row(
+            "Last Notification",
+            client.last.map_or_else(
+                || "None yet".to_string(),
+                |last| format!("{:.1} s ago", last.elapsed().as_secs_f64()),
+            ),
+            String::new(),
+            if client.is_silent() {
+                Some("darkorange")
+            } else {
+                None
+            },
+        );
+        row(
+            "Interval Min / Avg / Max",
+            format!(
+                "{} / {} / {}",
+                seconds(client.min_interval),
+                seconds(client.mean_interval()),
+                seconds(client.max_interval)
+            ),
+            String::new(),
+            None,
+        );
+        row(
+            "Keep-alive Time",
+            seconds(client.keep_alive_time),
+            String::new(),
+            None,
+        );
+        row(
+            "Silences Longer Than Keep-alive",
+            client.silences.to_string(),
+            String::new(),
+            None,
+        );
+
+        let server = match d.server {
+            Ok(ref server) => server,
+            Err(ref err) => {
+                row(
+                    "Server Diagnostics",
+                    String::new(),
+                    err.clone(),
+                    Some("grey"),
+                );
+                return;
+            }
+        };
+        let missing = server.data_change_notifications_count as i64 - client.values as i64;
+        row(
+            "Data Change Notifications",
+            client.values.to_string(),
+            server.data_change_notifications_count.to_string(),
+            None,
+        );
+        row(
+            "Missing Values",
+            String::new(),
+            missing.max(0).to_string(),
+            if missing > 0 { Some("red") } else { None },
+        );
+        // Sequence numbers, keep-alives and republishing are handled inside the client library,
+        // so only the server's side of them can be shown
+        let server_rows: [(&str, String, bool); 13] = [
+            (
+                "Publishing Interval",
+                format!("{} ms", server.publishing_interval),
+                false,
+            ),
+            (
+                "Max Keep-alive Count",
+                server.max_keep_alive_count.to_string(),
+                false,
+            ),
+            (
+                "Last Sequence Number",
+                server.next_sequence_number.saturating_sub(1).to_string(),
+                false,
+            ),
+            (
+                "Current Keep-alive Count",
+                server.current_keep_alive_count.to_string(),
+                false,
+            ),
+            (
+                "Notifications Sent",
+                server.notifications_count.to_string(),
+                false,
+            ),
+            (
+                "Publish Requests",
+                server.publish_request_count.to_string(),
+                false,
+            ),
+            (
+                "Late Publish Requests",
+                server.late_publish_request_count.to_string(),
+                server.late_publish_request_count > 0,
+            ),
+            (
+                "Republish Requests",
+                server.republish_request_count.to_string(),
+                server.republish_request_count > 0,
+            ),
+            (
+                "Republished Messages",
+                format!(
+                    "{} of {} asked",
+                    server.republish_message_count, server.republish_message_request_count
+                ),
+                false,
+            ),
+            (
+                "Unacknowledged Messages",
+                server.unacknowledged_message_count.to_string(),
+                server.unacknowledged_message_count > 0,
+            ),
+            (
+                "Discarded Messages",
+                server.discarded_message_count.to_string(),
+                server.discarded_message_count > 0,
+            ),
+            (
+                "Queue Overflows",
+                server.monitoring_queue_overflow_count.to_string(),
+                server.monitoring_queue_overflow_count > 0,
+            ),
+            (
+                "Monitored Items",
+                format!(
+                    "{} ({} disabled)",
+                    server.monitored_item_count, server.disabled_monitored_item_count
+                ),
+                false,
+            ),
+        ];
+        server_rows.iter().for_each(|(metric, value, is_warning)| {
+            let client = if SERVER_ONLY_METRICS.contains(metric) {
+                SERVER_ONLY.to_string()
+            } else {
+                String::new()
+            };
+            row(
+                metric,
+                client,
+                value.clone(),
+                if *is_warning { Some("red") } else { None },
+            );
+        });
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use opcua_client::prelude::*;
+
+/// The units a value is measured in, from the EngineeringUnits (EUInformation) property
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineeringUnits {
+    pub namespace_uri: String,
+    /// The UNECE common code packed into an integer, -1 if there is none
+    pub unit_id: i32,
+    pub display_name: String,
+    pub description: String,
+}
+
+impl EngineeringUnits {
+    fn from_eu_information(eu: &EUInformation) -> Self {
+        Self {
+            namespace_uri: eu.namespace_uri.as_ref().to_string(),
+            unit_id: eu.unit_id,
+            display_name: eu.display_name.text.as_ref().to_string(),
+            description: eu.description.text.as_ref().to_string(),
+        }
+    }
+
+    /// The UNECE common code, e.g. "BAR", if the units use the UNECE namespace
+    pub fn unece_code(&self) -> Option<String> {
+        unece_code(self.unit_id)
+    }
+
+    /// The symbol shown next to values, e.g. "bar"
+    pub fn symbol(&self) -> &str {
+        &self.display_name
+    }
+}
+
+/// A low and high limit, from the EURange or InstrumentRange property
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueRange {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl ValueRange {
+    pub fn contains(&self, v: f64) -> bool {
+        v >= self.low && v <= self.high
+    }
+}
+
+impl fmt::Display for ValueRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", self.low, self.high)
+    }
+}
+
+/// The properties of an AnalogItemType variable that describe what its value means
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnalogInfo {
+    pub engineering_units: Option<EngineeringUnits>,
+    /// The range the value is expected to be in during normal operation
+    pub eu_range: Option<ValueRange>,
+    /// The range the instrument can measure
+    pub instrument_range: Option<ValueRange>,
+}
+
+impl AnalogInfo {
+    /// The browse names of the properties that make up the info
+    pub const PROPERTY_NAMES: [&'static str; 3] =
+        ["EngineeringUnits", "EURange", "InstrumentRange"];
+
+    /// Makes the info from the values of the properties, in the order of `PROPERTY_NAMES`.
+    /// Returns `None` if none of the properties hold anything useful.
+    pub fn from_property_values(values: &[Option<Variant>]) -> Option<Self> {
+        let extension_object = |i: usize| match values.get(i) {
+            Some(Some(Variant::ExtensionObject(v))) => Some(v.as_ref()),
+            _ => None,
+        };
+        let decoding_options = DecodingOptions::default();
+        let range = |i| {
+            extension_object(i)
+                .and_then(|v| v.decode_inner::<Range>(&decoding_options).ok())
+                .map(|r| ValueRange {
+                    low: r.low,
+                    high: r.high,
+                })
+        };
+        let info = Self {
+            engineering_units: extension_object(0)
+                .and_then(|v| v.decode_inner::<EUInformation>(&decoding_options).ok())
+                .map(|eu| EngineeringUnits::from_eu_information(&eu)),
+            eu_range: range(1),
+            instrument_range: range(2),
+        };
+        if info == Self::default() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        self.engineering_units
+            .as_ref()
+            .map(|eu| eu.symbol())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Appends the unit symbol to the text of a value
+    pub fn with_units(&self, text: &str) -> String {
+        match self.symbol() {
+            Some(symbol) if !text.is_empty() => format!("{} {}", text, symbol),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Tests if a numeric value, or any element of an array of them, is outside the EURange
+    pub fn is_out_of_range(&self, value: &Variant) -> bool {
+        match self.eu_range {
+            Some(ref range) => match value {
+                Variant::Array(array) => array.values.iter().any(|v| self.is_out_of_range(v)),
+                v => variant_to_f64(v).map_or(false, |v| !range.contains(v)),
+            },
+            None => false,
+        }
+    }
+
+    /// The text of a value with its units, flagged if it is outside the EURange
+    pub fn format_value(&self, text: &str, value: &Variant) -> String {
+        let text = self.with_units(text);
+        if self.is_out_of_range(value) {
+            format!("{} \u{26a0} outside EURange", text)
+        } else {
+            text
+        }
+    }
+
+    /// Describes the units, e.g. "bar - bar [unit of pressure] (UNECE BAR)"
+    pub fn units_description(&self) -> Option<String> {
+        self.engineering_units.as_ref().map(|eu| {
+            let mut text = eu.display_name.clone();
+            if !eu.description.is_empty() && eu.description != eu.display_name {
+                text.push_str(&format!(" - {}", eu.description));
+            }
+            if let Some(code) = eu.unece_code() {
+                text.push_str(&format!(" (UNECE {})", code));
+            }
+            text
+        })
+    }
+}
+
+/// Converts a numeric value to a float so it can be compared to a range
+pub fn variant_to_f64(v: &Variant) -> Option<f64> {
+    match *v {
+        Variant::SByte(v) => Some(v as f64),
+        Variant::Byte(v) => Some(v as f64),
+        Variant::Int16(v) => Some(v as f64),
+        Variant::UInt16(v) => Some(v as f64),
+        Variant::Int32(v) => Some(v as f64),
+        Variant::UInt32(v) => Some(v as f64),
+        Variant::Int64(v) => Some(v as f64),
+        Variant::UInt64(v) => Some(v as f64),
+        Variant::Float(v) => Some(v as f64),
+        Variant::Double(v) => Some(v),
+        _ => None,
+    }
+}
+
+/// Unpacks the UNECE common code from a unitId. Each character of the code is a byte of the id,
+/// the first character in the most significant byte used.
+pub fn unece_code(unit_id: i32) -> Option<String> {
+    if unit_id <= 0 {
+        return None;
+    }
+    let code = unit_id
+        .to_be_bytes()
+        .iter()
+        .skip_while(|b| **b == 0)
+        .map(|b| *b as char)
+        .collect::<String>();
+    if code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Some(code)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,422 @@
+use std::{cell::RefCell, rc::Rc, str::FromStr};
+
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::events::{self, Comparison, EventField, EventMonitor, EventType, WhereClause};
+
+/// The fields and where clause being built
+struct FilterState {
+    select: Vec<EventField>,
+    where_clause: Option<WhereClause>,
+}
+
+/// Builds the event filter of an event monitor. Fields are selected from the event type
+/// hierarchy, and the where clause is built a condition at a time, each combined with the
+/// clause so far by And or Or.
+pub(crate) struct EventFilterDlg {
+    dlg: gtk::Dialog,
+    node_entry: gtk::Entry,
+    status_label: gtk::Label,
+    monitor: EventMonitor,
+    state: Rc<RefCell<FilterState>>,
+}
+
+impl EventFilterDlg {
+    pub fn new(
+        parent: Option<&gtk::Window>,
+        monitor: &EventMonitor,
+        event_types: &[EventType],
+    ) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&format!("Event Filter - {}", monitor.node_name)),
+            parent,
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Apply", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+        dlg.set_default_size(800, 500);
+
+        // Without the types of the server, the fields of BaseEventType can still be chosen
+        let event_types = if event_types.is_empty() {
+            vec![EventType {
+                node_id: ObjectTypeId::BaseEventType.into(),
+                name: "BaseEventType".to_string(),
+                parent: None,
+                fields: events::BASE_EVENT_FIELDS
+                    .iter()
+                    .map(|name| vec![QualifiedName::new(0, *name)])
+                    .collect(),
+            }]
+        } else {
+            event_types.to_vec()
+        };
+        let event_types = Rc::new(event_types);
+        let state = Rc::new(RefCell::new(FilterState {
+            select: monitor.select.clone(),
+            where_clause: monitor.where_clause.clone(),
+        }));
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        content.set_border_width(8);
+
+        let node_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        node_box.pack_start(&gtk::Label::new(Some("Notifier Node:")), false, false, 0);
+        let node_entry = gtk::Entry::new();
+        node_entry.set_text(&format!("{}", monitor.node_id));
+        node_entry.set_tooltip_text(Some(
+            "A node whose EventNotifier attribute allows subscribing to events, e.g. Server (i=2253)",
+        ));
+        node_box.pack_start(&node_entry, true, true, 0);
+        content.pack_start(&node_box, false, false, 0);
+
+        // Event types, the fields of the selected type and the fields selected
+        let types_model = gtk::TreeStore::new(&[String::static_type(), u32::static_type()]);
+        Self::populate_types(&types_model, &event_types, None, None);
+        let types_tree = gtk::TreeView::with_model(&types_model);
+        Self::add_text_column(&types_tree, "Event Type", 0);
+        types_tree.expand_row(&gtk::TreePath::new_first(), false);
+
+        let fields_model = gtk::ListStore::new(&[String::static_type(), u32::static_type()]);
+        let fields_tree = gtk::TreeView::with_model(&fields_model);
+        Self::add_text_column(&fields_tree, "Field", 0);
+        let fields: Rc<RefCell<Vec<EventField>>> = Rc::new(RefCell::new(Vec::new()));
+
+        let selected_model = gtk::ListStore::new(&[String::static_type()]);
+        let selected_tree = gtk::TreeView::with_model(&selected_model);
+        Self::add_text_column(&selected_tree, "Selected Fields", 0);
+
+        let lists_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let scrolled = |widget: &gtk::TreeView| {
+            let window =
+                gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+            window.set_shadow_type(gtk::ShadowType::In);
+            window.add(widget);
+            window
+        };
+        lists_box.pack_start(&scrolled(&types_tree), true, true, 0);
+        let fields_box = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        fields_box.pack_start(&scrolled(&fields_tree), true, true, 0);
+        let select_btn = gtk::Button::with_label("Select Field");
+        fields_box.pack_start(&select_btn, false, false, 0);
+        lists_box.pack_start(&fields_box, true, true, 0);
+        let selected_box = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        selected_box.pack_start(&scrolled(&selected_tree), true, true, 0);
+        let remove_btn = gtk::Button::with_label("Remove Field");
+        selected_box.pack_start(&remove_btn, false, false, 0);
+        lists_box.pack_start(&selected_box, true, true, 0);
+        content.pack_start(&lists_box, true, true, 0);
+
+        // Where clause
+        let where_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        where_box.pack_start(&gtk::Label::new(Some("Condition:")), false, false, 0);
+        let not_check = gtk::CheckButton::with_label("Not");
+        where_box.pack_start(&not_check, false, false, 0);
+        let operator_combo = gtk::ComboBoxText::new();
+        operator_combo.append(Some("OfType"), "OfType selected type");
+        Comparison::ALL.iter().enumerate().for_each(|(i, c)| {
+            operator_combo.append(
+                Some(&i.to_string()),
+                &format!("Selected field {}", c.symbol()),
+            )
+        });
+        operator_combo.set_active(Some(0));
+        where_box.pack_start(&operator_combo, false, false, 0);
+        let literal_entry = gtk::Entry::new();
+        literal_entry.set_placeholder_text(Some("Value, e.g. 500, true or \"text\""));
+        where_box.pack_start(&literal_entry, true, true, 0);
+        let set_btn = gtk::Button::with_label("Set");
+        let and_btn = gtk::Button::with_label("And");
+        let or_btn = gtk::Button::with_label("Or");
+        let clear_btn = gtk::Button::with_label("Clear");
+        where_box.pack_start(&set_btn, false, false, 0);
+        where_box.pack_start(&and_btn, false, false, 0);
+        where_box.pack_start(&or_btn, false, false, 0);
+        where_box.pack_start(&clear_btn, false, false, 0);
+        content.pack_start(&where_box, false, false, 0);
+
+        let where_label = gtk::Label::new(None);
+        where_label.set_halign(gtk::Align::Start);
+        where_label.set_line_wrap(true);
+        where_label.set_selectable(true);
+        content.pack_start(&where_label, false, false, 0);
+
+        let status_label = gtk::Label::new(None);
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        content.pack_start(&status_label, false, false, 0);
+
+        dlg.get_content_area().pack_start(&content, true, true, 0);
+
+        let update_selected = {
+            let state = state.clone();
+            let event_types = event_types.clone();
+            let selected_model = selected_model.clone();
+            let where_label = where_label.clone();
+            Rc::new(move || {
+                let state = state.borrow();
+                selected_model.clear();
+                state.select.iter().for_each(|field| {
+                    selected_model.insert_with_values(
+                        None,
+                        &[0],
+                        &[&Self::field_text(&event_types, field)],
+                    );
+                });
+                where_label.set_text(&match state.where_clause {
+                    Some(ref clause) => format!("Where {}", clause),
+                    None => "No where clause, every event is received".to_string(),
+                });
+            })
+        };
+        update_selected();
+
+        // The type selected in the hierarchy
+        let selected_type = {
+            let types_tree = types_tree.clone();
+            let event_types = event_types.clone();
+            move || -> Option<EventType> {
+                let (model, iter) = types_tree.get_selection().get_selected()?;
+                let index = model.get_value(&iter, 1).get::<u32>().ok().flatten()?;
+                event_types.get(index as usize).cloned()
+            }
+        };
+        // The field selected in the fields of the type
+        let selected_field = {
+            let fields_tree = fields_tree.clone();
+            let fields = fields.clone();
+            move || -> Option<EventField> {
+                let (model, iter) = fields_tree.get_selection().get_selected()?;
+                let index = model.get_value(&iter, 1).get::<u32>().ok().flatten()?;
+                fields.borrow().get(index as usize).cloned()
+            }
+        };
+
+        {
+            let event_types = event_types.clone();
+            let fields = fields.clone();
+            let fields_model = fields_model.clone();
+            let selected_type = selected_type.clone();
+            types_tree.get_selection().connect_changed(move |_| {
+                fields_model.clear();
+                let type_fields = match selected_type() {
+                    Some(event_type) => events::inherited_fields(&event_types, &event_type.node_id),
+                    None => Vec::new(),
+                };
+                type_fields.iter().enumerate().for_each(|(i, field)| {
+                    fields_model.insert_with_values(
+                        None,
+                        &[0, 1],
+                        &[&Self::field_text(&event_types, field), &(i as u32)],
+                    );
+                });
+                *fields.borrow_mut() = type_fields;
+            });
+        }
+        types_tree
+            .get_selection()
+            .select_path(&gtk::TreePath::new_first());
+
+        {
+            let state = state.clone();
+            let update_selected = update_selected.clone();
+            let selected_field = selected_field.clone();
+            select_btn.connect_clicked(move |_| {
+                if let Some(field) = selected_field() {
+                    let added = {
+                        let mut state = state.borrow_mut();
+                        if state.select.contains(&field) {
+                            false
+                        } else {
+                            state.select.push(field);
+                            true
+                        }
+                    };
+                    if added {
+                        update_selected();
+                    }
+                }
+            });
+        }
+        {
+            let state = state.clone();
+            let update_selected = update_selected.clone();
+            let selected_tree = selected_tree.clone();
+            remove_btn.connect_clicked(move |_| {
+                let (model, iter) = match selected_tree.get_selection().get_selected() {
+                    Some(selected) => selected,
+                    None => return,
+                };
+                if let Some(index) = model
+                    .get_path(&iter)
+                    .and_then(|p| p.get_indices().first().copied())
+                {
+                    state.borrow_mut().select.remove(index as usize);
+                    update_selected();
+                }
+            });
+        }
+
+        // Makes a condition from the controls and combines it with the where clause
+        let combine = {
+            let state = state.clone();
+            let update_selected = update_selected.clone();
+            let status_label = status_label.clone();
+            move |combine: fn(WhereClause, WhereClause) -> WhereClause| {
+                let condition = match operator_combo
+                    .get_active_id()
+                    .as_ref()
+                    .map(|id| id.as_str())
+                {
+                    Some("OfType") => selected_type()
+                        .map(|t| WhereClause::OfType(t.node_id, t.name))
+                        .ok_or("Select an event type for OfType"),
+                    Some(index) => {
+                        let comparison = index
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|i| Comparison::ALL.get(i).copied());
+                        match (comparison, selected_field()) {
+                            (Some(comparison), Some(field)) => Ok(WhereClause::Compare(
+                                comparison,
+                                field,
+                                events::parse_literal(&literal_entry.get_text()),
+                            )),
+                            _ => Err("Select a field to compare"),
+                        }
+                    }
+                    None => Err("Choose an operator"),
+                };
+                match condition {
+                    Ok(condition) => {
+                        let condition = if not_check.get_active() {
+                            WhereClause::Not(Box::new(condition))
+                        } else {
+                            condition
+                        };
+                        {
+                            let mut state = state.borrow_mut();
+                            state.where_clause = Some(match state.where_clause.take() {
+                                Some(clause) => combine(clause, condition),
+                                None => condition,
+                            });
+                        }
+                        status_label.set_text("");
+                        update_selected();
+                    }
+                    Err(message) => status_label.set_text(message),
+                }
+            }
+        };
+        let combine = Rc::new(combine);
+        {
+            let combine = combine.clone();
+            set_btn.connect_clicked(move |_| combine(|_, condition| condition));
+        }
+        {
+            let combine = combine.clone();
+            and_btn.connect_clicked(move |_| {
+                combine(|a, b| WhereClause::And(Box::new(a), Box::new(b)))
+            });
+        }
+        or_btn.connect_clicked(move |_| combine(|a, b| WhereClause::Or(Box::new(a), Box::new(b))));
+        {
+            let state = state.clone();
+            clear_btn.connect_clicked(move |_| {
+                state.borrow_mut().where_clause = None;
+                update_selected();
+            });
+        }
+
+        Self {
+            dlg,
+            node_entry,
+            status_label,
+            monitor: monitor.clone(),
+            state,
+        }
+    }
+
+    /// Shows the dialog until it is cancelled or the filter is valid, and returns the monitor
+    /// with the filter
+    pub fn run(&self) -> Option<EventMonitor> {
+        self.dlg.show_all();
+        let mut result = None;
+        while self.dlg.run() == gtk::ResponseType::Apply {
+            match self.monitor() {
+                Ok(monitor) => {
+                    result = Some(monitor);
+                    break;
+                }
+                Err(message) => self.status_label.set_markup(&format!(
+                    "<span foreground=\"red\">{}</span>",
+                    glib::markup_escape_text(&message)
+                )),
+            }
+        }
+        self.dlg.close();
+        result
+    }
+
+    fn monitor(&self) -> Result<EventMonitor, String> {
+        let text = self.node_entry.get_text();
+        let node_id =
+            NodeId::from_str(text.trim()).map_err(|_| format!("\"{}\" is not a node id", text))?;
+        let state = self.state.borrow();
+        if state.select.is_empty() {
+            return Err("Select at least one field".to_string());
+        }
+        let node_name = if node_id == self.monitor.node_id {
+            self.monitor.node_name.clone()
+        } else {
+            format!("{}", node_id)
+        };
+        Ok(EventMonitor {
+            node_id,
+            node_name,
+            select: state.select.clone(),
+            where_clause: state.where_clause.clone(),
+            ..self.monitor.clone()
+        })
+    }
+
+    fn add_text_column(tree: &gtk::TreeView, title: &str, column: i32) {
+        let renderer = gtk::CellRendererText::new();
+        let tree_column = gtk::TreeViewColumn::new();
+        tree_column.set_title(title);
+        tree_column.set_resizable(true);
+        tree_column.pack_start(&renderer, true);
+        tree_column.add_attribute(&renderer, "text", column);
+        tree.append_column(&tree_column);
+    }
+
+    /// Adds the subtypes of a type to the tree, or the types without a parent
+    fn populate_types(
+        model: &gtk::TreeStore,
+        event_types: &[EventType],
+        parent_id: Option<&NodeId>,
+        parent: Option<&gtk::TreeIter>,
+    ) {
+        event_types
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.parent.as_ref() == parent_id)
+            .for_each(|(i, t)| {
+                let iter = model.insert_with_values(parent, None, &[0, 1], &[&t.name, &(i as u32)]);
+                Self::populate_types(model, event_types, Some(&t.node_id), Some(&iter));
+            });
+    }
+
+    /// A field with the name of the type that declares it
+    fn field_text(event_types: &[EventType], field: &EventField) -> String {
+        let type_name = event_types
+            .iter()
+            .find(|t| t.node_id == field.event_type_id)
+            .map_or_else(|| format!("{}", field.event_type_id), |t| t.name.clone());
+        format!("{} ({})", field.path(), type_name)
+    }
+}
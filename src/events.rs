@@ -0,0 +1,372 @@
+use std::fmt;
+
+use opcua_client::prelude::*;
+
+use crate::value_format::{self, TimeDisplay};
+
+/// The fields of BaseEventType selected when a monitor is created, which the event list shows
+/// in columns of their own
+pub const BASE_EVENT_FIELDS: [&str; 6] = [
+    "EventId",
+    "EventType",
+    "SourceName",
+    "Time",
+    "Message",
+    "Severity",
+];
+
+/// A field of an event, found by following browse names from an event type
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventField {
+    pub event_type_id: NodeId,
+    pub browse_path: Vec<QualifiedName>,
+    /// Value for the fields of the type, NodeId with an empty path for the node of a condition
+    pub attribute_id: AttributeId,
+}
+
+impl EventField {
+    pub fn new(event_type_id: NodeId, browse_path: Vec<QualifiedName>) -> Self {
+        Self {
+            event_type_id,
+            browse_path,
+            attribute_id: AttributeId::Value,
+        }
+    }
+
+    /// A field of BaseEventType in namespace 0
+    pub fn base(name: &str) -> Self {
+        Self::new(
+            ObjectTypeId::BaseEventType.into(),
+            vec![QualifiedName::new(0, name)],
+        )
+    }
+
+    /// The ConditionId of condition events, which is the node id of the condition itself
+    pub fn condition_id() -> Self {
+        Self {
+            event_type_id: ObjectTypeId::ConditionType.into(),
+            browse_path: Vec::new(),
+            attribute_id: AttributeId::NodeId,
+        }
+    }
+
+    /// The browse names of the path separated by '/'
+    pub fn path(&self) -> String {
+        if self.browse_path.is_empty() && self.attribute_id == AttributeId::NodeId {
+            return "ConditionId".to_string();
+        }
+        self.browse_path
+            .iter()
+            .map(|n| n.name.as_ref())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// True if this is the field of BaseEventType with the name, which every event has
+    pub fn is_base(&self, name: &str) -> bool {
+        self.browse_path.len() == 1
+            && self.browse_path[0].namespace_index == 0
+            && self.browse_path[0].name.as_ref() == name
+    }
+
+    pub fn simple_attribute_operand(&self) -> SimpleAttributeOperand {
+        SimpleAttributeOperand {
+            type_definition_id: self.event_type_id.clone(),
+            browse_path: Some(self.browse_path.clone()),
+            attribute_id: self.attribute_id as u32,
+            index_range: UAString::null(),
+        }
+    }
+}
+
+impl fmt::Display for EventField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path())
+    }
+}
+
+/// The operators a field can be compared with a value by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equals,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+    Like,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 6] = [
+        Comparison::Equals,
+        Comparison::GreaterThan,
+        Comparison::LessThan,
+        Comparison::GreaterThanOrEqual,
+        Comparison::LessThanOrEqual,
+        Comparison::Like,
+    ];
+
+    fn filter_operator(self) -> FilterOperator {
+        match self {
+            Comparison::Equals => FilterOperator::Equals,
+            Comparison::GreaterThan => FilterOperator::GreaterThan,
+            Comparison::LessThan => FilterOperator::LessThan,
+            Comparison::GreaterThanOrEqual => FilterOperator::GreaterThanOrEqual,
+            Comparison::LessThanOrEqual => FilterOperator::LessThanOrEqual,
+            Comparison::Like => FilterOperator::Like,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Equals => "=",
+            Comparison::GreaterThan => ">",
+            Comparison::LessThan => "<",
+            Comparison::GreaterThanOrEqual => ">=",
+            Comparison::LessThanOrEqual => "<=",
+            Comparison::Like => "Like",
+        }
+    }
+}
+
+/// The where clause of an event filter as a tree of conditions
+#[derive(Debug, Clone, PartialEq)]
+pub enum WhereClause {
+    /// The event is of the type or a subtype of it. The name is only for display.
+    OfType(NodeId, String),
+    Compare(Comparison, EventField, Variant),
+    Not(Box<WhereClause>),
+    And(Box<WhereClause>, Box<WhereClause>),
+    Or(Box<WhereClause>, Box<WhereClause>),
+}
+
+impl WhereClause {
+    /// The clause as the elements of a ContentFilter, where the first element is the root and
+    /// the operands of And, Or and Not refer to the elements after it
+    pub fn content_filter(&self) -> ContentFilter {
+        let mut elements = Vec::new();
+        self.push_elements(&mut elements);
+        ContentFilter {
+            elements: Some(elements),
+        }
+    }
+
+    fn push_elements(&self, elements: &mut Vec<ContentFilterElement>) -> u32 {
+        let index = elements.len();
+        elements.push(ContentFilterElement {
+            filter_operator: FilterOperator::Equals,
+            filter_operands: None,
+        });
+        let (filter_operator, operands) = match self {
+            WhereClause::OfType(event_type_id, _) => (
+                FilterOperator::OfType,
+                vec![literal_operand(Variant::from(event_type_id.clone()))],
+            ),
+            WhereClause::Compare(comparison, field, value) => (
+                comparison.filter_operator(),
+                vec![
+                    ExtensionObject::from_encodable(
+                        ObjectId::SimpleAttributeOperand_Encoding_DefaultBinary,
+                        &field.simple_attribute_operand(),
+                    ),
+                    literal_operand(value.clone()),
+                ],
+            ),
+            WhereClause::Not(clause) => (
+                FilterOperator::Not,
+                vec![element_operand(clause.push_elements(elements))],
+            ),
+            WhereClause::And(a, b) | WhereClause::Or(a, b) => {
+                let a = element_operand(a.push_elements(elements));
+                let b = element_operand(b.push_elements(elements));
+                let filter_operator = if let WhereClause::And(_, _) = self {
+                    FilterOperator::And
+                } else {
+                    FilterOperator::Or
+                };
+                (filter_operator, vec![a, b])
+            }
+        };
+        elements[index] = ContentFilterElement {
+            filter_operator,
+            filter_operands: Some(operands),
+        };
+        index as u32
+    }
+}
+
+impl fmt::Display for WhereClause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WhereClause::OfType(_, name) => write!(f, "OfType {}", name),
+            WhereClause::Compare(comparison, field, value) => {
+                write!(
+                    f,
+                    "{} {} {}",
+                    field,
+                    comparison.symbol(),
+                    literal_text(value)
+                )
+            }
+            WhereClause::Not(clause) => write!(f, "Not ({})", clause),
+            WhereClause::And(a, b) => write!(f, "({}) And ({})", a, b),
+            WhereClause::Or(a, b) => write!(f, "({}) Or ({})", a, b),
+        }
+    }
+}
+
+fn literal_operand(value: Variant) -> ExtensionObject {
+    ExtensionObject::from_encodable(
+        ObjectId::LiteralOperand_Encoding_DefaultBinary,
+        &LiteralOperand { value },
+    )
+}
+
+fn element_operand(index: u32) -> ExtensionObject {
+    ExtensionObject::from_encodable(
+        ObjectId::ElementOperand_Encoding_DefaultBinary,
+        &ElementOperand { index },
+    )
+}
+
+fn literal_text(value: &Variant) -> String {
+    match value {
+        Variant::String(s) => format!("\"{}\"", s.as_ref()),
+        v => value_format::variant_to_string(v, TimeDisplay::Utc),
+    }
+}
+
+/// Turns the text of a literal into a value. Quoted text is a string, otherwise true and false
+/// are booleans and numbers are integers or doubles.
+pub fn parse_literal(text: &str) -> Variant {
+    let text = text.trim();
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        Variant::from(&text[1..text.len() - 1])
+    } else if text == "true" || text == "false" {
+        Variant::Boolean(text == "true")
+    } else if let Ok(v) = text.parse::<i32>() {
+        Variant::Int32(v)
+    } else if let Ok(v) = text.parse::<f64>() {
+        Variant::Double(v)
+    } else {
+        Variant::from(text)
+    }
+}
+
+/// An event type and the fields its events have besides those of its supertypes
+#[derive(Debug, Clone)]
+pub struct EventType {
+    pub node_id: NodeId,
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub fields: Vec<Vec<QualifiedName>>,
+}
+
+/// The fields of an event type, including those it inherits, with the type that declares each
+pub fn inherited_fields(event_types: &[EventType], event_type_id: &NodeId) -> Vec<EventField> {
+    let mut chain = Vec::new();
+    let mut next = Some(event_type_id.clone());
+    while let Some(node_id) = next {
+        match event_types.iter().find(|t| t.node_id == node_id) {
+            Some(event_type) if !chain.iter().any(|t: &&EventType| t.node_id == node_id) => {
+                chain.push(event_type);
+                next = event_type.parent.clone();
+            }
+            _ => break,
+        }
+    }
+    chain
+        .iter()
+        .rev()
+        .flat_map(|t| {
+            t.fields
+                .iter()
+                .map(move |path| EventField::new(t.node_id.clone(), path.clone()))
+        })
+        .collect()
+}
+
+/// Subscribes to the events a node notifies, selecting fields and filtering with a where clause
+#[derive(Debug, Clone)]
+pub struct EventMonitor {
+    pub id: u32,
+    pub node_id: NodeId,
+    pub node_name: String,
+    pub select: Vec<EventField>,
+    pub where_clause: Option<WhereClause>,
+}
+
+impl EventMonitor {
+    /// A monitor of the node that selects the common fields of BaseEventType and has no where
+    /// clause
+    pub fn new(id: u32, node_id: NodeId, node_name: &str) -> Self {
+        Self {
+            id,
+            node_id,
+            node_name: node_name.to_string(),
+            select: BASE_EVENT_FIELDS
+                .iter()
+                .map(|name| EventField::base(name))
+                .collect(),
+            where_clause: None,
+        }
+    }
+
+    pub fn event_filter(&self) -> EventFilter {
+        EventFilter {
+            select_clauses: Some(
+                self.select
+                    .iter()
+                    .map(|f| f.simple_attribute_operand())
+                    .collect(),
+            ),
+            where_clause: self
+                .where_clause
+                .as_ref()
+                .map_or(ContentFilter { elements: None }, |w| w.content_filter()),
+        }
+    }
+
+    pub fn monitoring_parameters(
+        &self,
+        client_handle: u32,
+        queue_size: u32,
+    ) -> MonitoringParameters {
+        MonitoringParameters {
+            client_handle,
+            sampling_interval: 0.0,
+            filter: ExtensionObject::from_encodable(
+                ObjectId::EventFilter_Encoding_DefaultBinary,
+                &self.event_filter(),
+            ),
+            queue_size,
+            discard_oldest: true,
+        }
+    }
+
+    /// The value of a selected field in the fields of an event
+    pub fn field_value<'a>(&self, fields: &'a [Variant], name: &str) -> Option<&'a Variant> {
+        self.select
+            .iter()
+            .position(|f| f.is_base(name))
+            .and_then(|i| fields.get(i))
+    }
+}
+
+/// An event received by a monitor, with the values of its selected fields in order
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub monitor_id: u32,
+    pub fields: Vec<Variant>,
+}
+
+/// The band of a severity that picks the colour of an event, from the ranges in Part 9
+pub fn severity_colour(severity: u16) -> Option<&'static str> {
+    match severity {
+        801..=1000 => Some("#f4b6b6"),
+        601..=800 => Some("#f8d2a8"),
+        401..=600 => Some("#fbeeaa"),
+        201..=400 => Some("#dde7f6"),
+        _ => None,
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    event_filter_dlg::EventFilterDlg,
+    events::{self, Event, EventMonitor, EventType},
+    model::ModelMessage,
+    value_format::{self, TimeDisplay},
+};
+
+const COL_MONITOR_ID: u32 = 0;
+const COL_MONITOR: u32 = 1;
+const COL_TIME: u32 = 2;
+const COL_SEVERITY: u32 = 3;
+const COL_SOURCE: u32 = 4;
+const COL_EVENT_TYPE: u32 = 5;
+const COL_MESSAGE: u32 = 6;
+const COL_FIELDS: u32 = 7;
+const COL_BACKGROUND: u32 = 8;
+const COL_SEARCH_TEXT: u32 = 9;
+
+/// The most events kept in the list, the oldest are dropped first
+const MAX_EVENTS: i32 = 10_000;
+
+struct EventsPaneImpl {
+    model: ActorRef<ModelMessage>,
+    monitor_combo: gtk::ComboBoxText,
+    events_tree: gtk::TreeView,
+    events_model: gtk::ListStore,
+    events_filter: gtk::TreeModelFilter,
+    search_entry: gtk::SearchEntry,
+    severity_combo: gtk::ComboBoxText,
+    follow_check: gtk::CheckButton,
+    status_label: gtk::Label,
+    monitors: RefCell<Vec<EventMonitor>>,
+    last_monitor_id: Cell<u32>,
+    /// The event types of the server, which the filter dialog picks fields from
+    event_types: RefCell<Vec<EventType>>,
+    /// The subscription of each monitor, or why it couldn't be created
+    subscriptions: RefCell<HashMap<u32, Result<u32, StatusCode>>>,
+}
+
+/// Subscribes to the events of nodes through monitors with an event filter, and lists the
+/// events they receive
+#[derive(Clone)]
+pub struct EventsPane {
+    data: Rc<EventsPaneImpl>,
+}
+
+impl EventsPane {
+    pub fn new(builder: Rc<gtk::Builder>, model: ActorRef<ModelMessage>) -> Self {
+        let new_btn: gtk::Button = builder.get_object("event_monitor_new_btn").unwrap();
+        let edit_btn: gtk::Button = builder.get_object("event_monitor_edit_btn").unwrap();
+        let delete_btn: gtk::Button = builder.get_object("event_monitor_delete_btn").unwrap();
+        let clear_btn: gtk::Button = builder.get_object("events_clear_btn").unwrap();
+
+        let data = Rc::new(EventsPaneImpl {
+            model,
+            monitor_combo: builder.get_object("event_monitor_combo").unwrap(),
+            events_tree: builder.get_object("events_tree").unwrap(),
+            events_model: builder.get_object("events_model").unwrap(),
+            events_filter: builder.get_object("events_filter").unwrap(),
+            search_entry: builder.get_object("events_search_entry").unwrap(),
+            severity_combo: builder.get_object("events_severity_combo").unwrap(),
+            follow_check: builder.get_object("events_follow_check").unwrap(),
+            status_label: builder.get_object("events_status_label").unwrap(),
+            monitors: RefCell::new(Vec::new()),
+            last_monitor_id: Cell::new(0),
+            event_types: RefCell::new(Vec::new()),
+            subscriptions: RefCell::new(HashMap::new()),
+        });
+
+        data.events_filter.set_visible_func(
+            clone!(@weak data => @default-return true, move |model, iter| {
+                data.is_visible(model, iter)
+            }),
+        );
+        data.search_entry
+            .connect_search_changed(clone!(@weak data => move |_| {
+                data.events_filter.refilter();
+                data.update_status();
+            }));
+        data.severity_combo
+            .connect_changed(clone!(@weak data => move |_| {
+                data.events_filter.refilter();
+                data.update_status();
+            }));
+        data.monitor_combo
+            .connect_changed(clone!(@weak data => move |_| {
+                data.update_status();
+            }));
+
+        new_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.new_monitor(ObjectId::Server.into(), "Server");
+        }));
+        edit_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(id) = data.selected_monitor_id() {
+                data.edit_monitor(id);
+            }
+        }));
+        delete_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(id) = data.selected_monitor_id() {
+                data.delete_monitor(id);
+            }
+        }));
+        clear_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.events_model.clear();
+            data.update_status();
+        }));
+
+        data.update_status();
+
+        Self { data }
+    }
+
+    /// Opens the filter of a new monitor of the node's events and subscribes to them if the
+    /// filter is accepted
+    pub fn new_monitor(&self, node_id: NodeId, node_name: &str) {
+        self.data.new_monitor(node_id, node_name);
+    }
+
+    /// Event types are browsed for each server that is connected to
+    pub fn on_connected(&self) {
+        self.data.model.tell(ModelMessage::BrowseEventTypes, None);
+    }
+
+    /// The event monitors stay in the list, but their subscriptions went with the session
+    pub fn on_disconnected(&self) {
+        self.data.subscriptions.borrow_mut().clear();
+        self.data.update_status();
+    }
+
+    pub fn on_event_types(&self, event_types: Vec<EventType>) {
+        *self.data.event_types.borrow_mut() = event_types;
+    }
+
+    pub fn on_event_monitor_created(&self, monitor_id: u32, result: Result<u32, StatusCode>) {
+        self.data
+            .subscriptions
+            .borrow_mut()
+            .insert(monitor_id, result);
+        self.data.update_status();
+    }
+
+    pub fn on_events(&self, events: Vec<Event>) {
+        self.data.add_events(events);
+    }
+
+    pub fn monitors(&self) -> Vec<EventMonitor> {
+        self.data.monitors.borrow().clone()
+    }
+}
+
+impl EventsPaneImpl {
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.events_tree
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok())
+    }
+
+    fn selected_monitor_id(&self) -> Option<u32> {
+        self.monitor_combo
+            .get_active_id()
+            .and_then(|id| id.as_str().parse::<u32>().ok())
+    }
+
+    fn new_monitor(&self, node_id: NodeId, node_name: &str) {
+        let id = self.last_monitor_id.get() + 1;
+        let monitor = EventMonitor::new(id, node_id, node_name);
+        let dlg = EventFilterDlg::new(
+            self.parent_window().as_ref(),
+            &monitor,
+            &self.event_types.borrow(),
+        );
+        if let Some(monitor) = dlg.run() {
+            self.last_monitor_id.set(id);
+            self.monitors.borrow_mut().push(monitor.clone());
+            self.model
+                .tell(ModelMessage::SetEventMonitor(monitor), None);
+            self.refresh_monitors(Some(id));
+        }
+    }
+
+    fn edit_monitor(&self, monitor_id: u32) {
+        let monitor = match self.monitors.borrow().iter().find(|m| m.id == monitor_id) {
+            Some(monitor) => monitor.clone(),
+            None => return,
+        };
+        let dlg = EventFilterDlg::new(
+            self.parent_window().as_ref(),
+            &monitor,
+            &self.event_types.borrow(),
+        );
+        if let Some(monitor) = dlg.run() {
+            if let Some(m) = self
+                .monitors
+                .borrow_mut()
+                .iter_mut()
+                .find(|m| m.id == monitor_id)
+            {
+                *m = monitor.clone();
+            }
+            self.subscriptions.borrow_mut().remove(&monitor_id);
+            self.model
+                .tell(ModelMessage::SetEventMonitor(monitor), None);
+            self.refresh_monitors(Some(monitor_id));
+        }
+    }
+
+    /// Stops the monitor's subscription. The events it received stay in the list.
+    fn delete_monitor(&self, monitor_id: u32) {
+        self.monitors.borrow_mut().retain(|m| m.id != monitor_id);
+        self.subscriptions.borrow_mut().remove(&monitor_id);
+        self.model
+            .tell(ModelMessage::RemoveEventMonitor(monitor_id), None);
+        self.refresh_monitors(None);
+    }
+
+    fn refresh_monitors(&self, active_id: Option<u32>) {
+        self.monitor_combo.remove_all();
+        self.monitors.borrow().iter().for_each(|m| {
+            let text = match m.where_clause {
+                Some(ref where_clause) => format!("{} - {}", m.node_name, where_clause),
+                None => m.node_name.clone(),
+            };
+            self.monitor_combo.append(Some(&m.id.to_string()), &text);
+        });
+        match active_id {
+            Some(id) => {
+                self.monitor_combo.set_active_id(Some(&id.to_string()));
+            }
+            None => self.monitor_combo.set_active(Some(0)),
+        }
+        self.update_status();
+    }
+
+    fn is_visible(&self, model: &gtk::TreeModel, iter: &gtk::TreeIter) -> bool {
+        let min_severity = self
+            .severity_combo
+            .get_active_id()
+            .and_then(|id| id.as_str().parse::<u32>().ok())
+            .unwrap_or(0);
+        let severity = model
+            .get_value(iter, COL_SEVERITY as i32)
+            .get::<u32>()
+            .ok()
+            .flatten()
+            .unwrap_or(0);
+        if severity < min_severity {
+            return false;
+        }
+        let search = self.search_entry.get_text().as_str().trim().to_lowercase();
+        search.is_empty()
+            || model
+                .get_value(iter, COL_SEARCH_TEXT as i32)
+                .get::<String>()
+                .ok()
+                .flatten()
+                .map_or(false, |text| text.contains(&search))
+    }
+
+    fn add_events(&self, events: Vec<Event>) {
+        let mut last = None;
+        {
+            let monitors = self.monitors.borrow();
+            let event_types = self.event_types.borrow();
+            events.iter().for_each(|event| {
+                // Events still queued for a monitor that was deleted are dropped
+                if let Some(monitor) = monitors.iter().find(|m| m.id == event.monitor_id) {
+                    last = Some(self.add_event(monitor, &event_types, &event.fields));
+                }
+            });
+        }
+        while self.events_model.iter_n_children(None) > MAX_EVENTS {
+            match self.events_model.get_iter_first() {
+                Some(iter) => {
+                    self.events_model.remove(&iter);
+                }
+                None => break,
+            }
+        }
+        if self.follow_check.get_active() {
+            let path = last
+                .and_then(|iter| self.events_filter.convert_child_iter_to_iter(&iter))
+                .and_then(|iter| self.events_filter.get_path(&iter));
+            if let Some(path) = path {
+                self.events_tree.scroll_to_cell(
+                    Some(&path),
+                    None::<&gtk::TreeViewColumn>,
+                    false,
+                    0.0,
+                    0.0,
+                );
+            }
+        }
+        self.update_status();
+    }
+
+    fn add_event(
+        &self,
+        monitor: &EventMonitor,
+        event_types: &[EventType],
+        fields: &[Variant],
+    ) -> gtk::TreeIter {
+        let text = |name: &str| {
+            monitor
+                .field_value(fields, name)
+                .map_or_else(String::new, variant_text)
+        };
+        let severity = match monitor.field_value(fields, "Severity") {
+            Some(Variant::UInt16(severity)) => *severity,
+            _ => 0,
+        };
+        let event_type = match monitor.field_value(fields, "EventType") {
+            Some(Variant::NodeId(node_id)) => event_types
+                .iter()
+                .find(|t| t.node_id == **node_id)
+                .map_or_else(|| node_id.to_string(), |t| t.name.clone()),
+            Some(v) => variant_text(v),
+            None => String::new(),
+        };
+        let time = text("Time");
+        let source = text("SourceName");
+        let message = text("Message");
+        // Fields without a column of their own are listed together
+        let other_fields = monitor
+            .select
+            .iter()
+            .zip(fields.iter())
+            .filter(|(f, _)| !events::BASE_EVENT_FIELDS.iter().any(|name| f.is_base(name)))
+            .map(|(f, v)| format!("{}={}", f, variant_text(v)))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let search_text = [
+            &monitor.node_name,
+            &time,
+            &source,
+            &event_type,
+            &message,
+            &other_fields,
+        ]
+        .iter()
+        .map(|s| s.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        self.events_model.insert_with_values(
+            None,
+            &[
+                COL_MONITOR_ID,
+                COL_MONITOR,
+                COL_TIME,
+                COL_SEVERITY,
+                COL_SOURCE,
+                COL_EVENT_TYPE,
+                COL_MESSAGE,
+                COL_FIELDS,
+                COL_BACKGROUND,
+                COL_SEARCH_TEXT,
+            ],
+            &[
+                &monitor.id,
+                &monitor.node_name,
+                &time,
+                &(severity as u32),
+                &source,
+                &event_type,
+                &message,
+                &other_fields,
+                &events::severity_colour(severity),
+                &search_text,
+            ],
+        )
+    }
+
+    /// Shows the subscription of the selected monitor and how many events are listed
+    fn update_status(&self) {
+        let subscription = match self.selected_monitor_id() {
+            Some(id) => match self.subscriptions.borrow().get(&id) {
+                Some(Ok(subscription_id)) => format!("Subscription {}", subscription_id),
+                Some(Err(status_code)) => format!(
+                    "Rejected, {}",
+                    value_format::status_code_to_string(*status_code)
+                ),
+                None => "Not subscribed".to_string(),
+            },
+            None => "No monitors".to_string(),
+        };
+        let total = self.events_model.iter_n_children(None);
+        let shown = self.events_filter.iter_n_children(None);
+        let count = if shown == total {
+            format!("{} events", total)
+        } else {
+            format!("{} of {} events", shown, total)
+        };
+        self.status_label
+            .set_text(&format!("{}, {}", subscription, count));
+    }
+}
+
+fn variant_text(v: &Variant) -> String {
+    match v {
+        Variant::LocalizedText(text) => text.text.to_string(),
+        Variant::String(s) => s.to_string(),
+        v => value_format::variant_to_string(v, TimeDisplay::Utc),
+    }
+}
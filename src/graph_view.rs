@@ -0,0 +1,551 @@
+use std::{
+    cell::RefCell, collections::HashMap, f64::consts::PI, fs, path::Path, rc::Rc, str::FromStr,
+};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    model::ModelMessage,
+    reference_graph::{NodeShape, NodeStyle, ReferenceGraph},
+};
+
+const RING_SPACING: f64 = 180.0;
+const NODE_HEIGHT: f64 = 24.0;
+const NODE_PADDING: f64 = 8.0;
+const FONT_SIZE: f64 = 10.0;
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 5.0;
+
+#[derive(Default)]
+struct GraphViewState {
+    root_node_id: Option<NodeId>,
+    graph: Option<ReferenceGraph>,
+    positions: HashMap<NodeId, (f64, f64)>,
+    zoom: f64,
+    offset: (f64, f64),
+    drag_start: Option<(f64, f64)>,
+    drag_moved: bool,
+}
+
+struct GraphViewImpl {
+    model: ActorRef<ModelMessage>,
+    main_window: Rc<gtk::ApplicationWindow>,
+    drawing_area: Rc<gtk::DrawingArea>,
+    depth_spin: Rc<gtk::SpinButton>,
+    reference_type_combo: Rc<gtk::ComboBoxText>,
+    status_label: Rc<gtk::Label>,
+    state: RefCell<GraphViewState>,
+}
+
+/// Draws the references around a node as a graph which can be zoomed, panned and recentred by
+/// clicking on another node.
+pub struct GraphView {
+    data: Rc<GraphViewImpl>,
+}
+
+impl GraphView {
+    pub fn new(builder: Rc<gtk::Builder>, model: ActorRef<ModelMessage>) -> Self {
+        let export_dot_btn: Rc<gtk::Button> =
+            Rc::new(builder.get_object("graph_export_dot_btn").unwrap());
+        let export_svg_btn: Rc<gtk::Button> =
+            Rc::new(builder.get_object("graph_export_svg_btn").unwrap());
+        let zoom_reset_btn: Rc<gtk::Button> =
+            Rc::new(builder.get_object("graph_zoom_reset_btn").unwrap());
+
+        let data = Rc::new(GraphViewImpl {
+            model,
+            main_window: Rc::new(builder.get_object("main_window").unwrap()),
+            drawing_area: Rc::new(builder.get_object("graph_drawing_area").unwrap()),
+            depth_spin: Rc::new(builder.get_object("graph_depth_spin").unwrap()),
+            reference_type_combo: Rc::new(
+                builder.get_object("graph_reference_type_combo").unwrap(),
+            ),
+            status_label: Rc::new(builder.get_object("graph_status_label").unwrap()),
+            state: RefCell::new(GraphViewState {
+                zoom: 1.0,
+                ..Default::default()
+            }),
+        });
+
+        data.drawing_area.add_events(
+            gdk::EventMask::BUTTON_PRESS_MASK
+                | gdk::EventMask::BUTTON_RELEASE_MASK
+                | gdk::EventMask::POINTER_MOTION_MASK
+                | gdk::EventMask::SCROLL_MASK,
+        );
+
+        data.drawing_area.connect_draw(
+            clone!(@weak data => @default-return Inhibit(false), move |area, cr| {
+                data.draw(area, cr);
+                Inhibit(false)
+            }),
+        );
+
+        data.drawing_area.connect_button_press_event(
+            clone!(@weak data => @default-return Inhibit(false), move |_, event| {
+                if event.get_button() == 1 {
+                    let mut state = data.state.borrow_mut();
+                    state.drag_start = Some(event.get_position());
+                    state.drag_moved = false;
+                }
+                Inhibit(true)
+            }),
+        );
+
+        data.drawing_area.connect_motion_notify_event(
+            clone!(@weak data => @default-return Inhibit(false), move |area, event| {
+                let mut state = data.state.borrow_mut();
+                if let Some((start_x, start_y)) = state.drag_start {
+                    let (x, y) = event.get_position();
+                    state.offset.0 += x - start_x;
+                    state.offset.1 += y - start_y;
+                    state.drag_start = Some((x, y));
+                    state.drag_moved = true;
+                    area.queue_draw();
+                }
+                Inhibit(true)
+            }),
+        );
+
+        data.drawing_area.connect_button_release_event(
+            clone!(@weak data => @default-return Inhibit(false), move |area, event| {
+                let clicked_node_id = {
+                    let mut state = data.state.borrow_mut();
+                    let clicked = state.drag_start.is_some() && !state.drag_moved;
+                    state.drag_start = None;
+                    if clicked {
+                        data.node_at(area, &state, event.get_position())
+                    } else {
+                        None
+                    }
+                };
+                if let Some(node_id) = clicked_node_id {
+                    data.show_node(node_id);
+                }
+                Inhibit(true)
+            }),
+        );
+
+        data.drawing_area.connect_scroll_event(
+            clone!(@weak data => @default-return Inhibit(false), move |area, event| {
+                let factor = match event.get_direction() {
+                    gdk::ScrollDirection::Up => 1.1,
+                    gdk::ScrollDirection::Down => 1.0 / 1.1,
+                    gdk::ScrollDirection::Smooth => {
+                        let (_, dy) = event.get_delta();
+                        if dy < 0.0 { 1.1 } else if dy > 0.0 { 1.0 / 1.1 } else { 1.0 }
+                    }
+                    _ => 1.0,
+                };
+                data.zoom_about(area, event.get_position(), factor);
+                Inhibit(true)
+            }),
+        );
+
+        data.depth_spin
+            .connect_value_changed(clone!(@weak data => move |_| {
+                data.refresh();
+            }));
+
+        data.reference_type_combo
+            .connect_changed(clone!(@weak data => move |_| {
+                data.refresh();
+            }));
+
+        zoom_reset_btn.connect_clicked(clone!(@weak data => move |_| {
+            {
+                let mut state = data.state.borrow_mut();
+                state.zoom = 1.0;
+                state.offset = (0.0, 0.0);
+            }
+            data.drawing_area.queue_draw();
+        }));
+
+        export_dot_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.on_export_dot_clicked();
+        }));
+
+        export_svg_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.on_export_svg_clicked();
+        }));
+
+        Self { data }
+    }
+
+    /// Requests the graph around the node
+    pub fn show_node(&self, node_id: NodeId) {
+        self.data.show_node(node_id);
+    }
+
+    pub fn on_reference_graph(&self, graph: ReferenceGraph) {
+        self.data.on_reference_graph(graph);
+    }
+
+    /// The notebook page hosting the graph
+    pub fn page(&self) -> gtk::Widget {
+        self.data.drawing_area.get_parent().unwrap()
+    }
+
+    pub fn clear(&self) {
+        {
+            let mut state = self.data.state.borrow_mut();
+            state.root_node_id = None;
+            state.graph = None;
+            state.positions.clear();
+        }
+        self.data.drawing_area.queue_draw();
+    }
+}
+
+impl GraphViewImpl {
+    fn show_node(&self, node_id: NodeId) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.root_node_id = Some(node_id);
+            state.offset = (0.0, 0.0);
+        }
+        self.refresh();
+    }
+
+    fn refresh(&self) {
+        let root_node_id = self.state.borrow().root_node_id.clone();
+        if let Some(root_node_id) = root_node_id {
+            let depth = self.depth_spin.get_value_as_int().max(1) as u32;
+            let reference_type_id = self
+                .reference_type_combo
+                .get_active_id()
+                .and_then(|id| NodeId::from_str(id.as_str()).ok())
+                .unwrap_or_else(|| ReferenceTypeId::References.into());
+            self.model.tell(
+                ModelMessage::BrowseGraph(root_node_id, depth, reference_type_id),
+                None,
+            );
+        }
+    }
+
+    fn on_reference_graph(&self, graph: ReferenceGraph) {
+        {
+            let mut state = self.state.borrow_mut();
+            // Ignore stale results for a node that is no longer the centre
+            if state.root_node_id.as_ref() != Some(&graph.root) {
+                return;
+            }
+            state.positions = graph.layout(RING_SPACING);
+            state.graph = Some(graph);
+        }
+        self.drawing_area.queue_draw();
+    }
+
+    fn to_graph_coords(
+        area: &gtk::DrawingArea,
+        state: &GraphViewState,
+        (x, y): (f64, f64),
+    ) -> (f64, f64) {
+        let cx = area.get_allocated_width() as f64 / 2.0 + state.offset.0;
+        let cy = area.get_allocated_height() as f64 / 2.0 + state.offset.1;
+        ((x - cx) / state.zoom, (y - cy) / state.zoom)
+    }
+
+    fn zoom_about(&self, area: &gtk::DrawingArea, position: (f64, f64), factor: f64) {
+        {
+            let mut state = self.state.borrow_mut();
+            let (gx, gy) = Self::to_graph_coords(area, &state, position);
+            state.zoom = (state.zoom * factor).max(MIN_ZOOM).min(MAX_ZOOM);
+            // Keep the point under the cursor where it was
+            let (nx, ny) = Self::to_graph_coords(area, &state, position);
+            state.offset.0 += (nx - gx) * state.zoom;
+            state.offset.1 += (ny - gy) * state.zoom;
+        }
+        area.queue_draw();
+    }
+
+    fn node_at(
+        &self,
+        area: &gtk::DrawingArea,
+        state: &GraphViewState,
+        position: (f64, f64),
+    ) -> Option<NodeId> {
+        let graph = state.graph.as_ref()?;
+        let (gx, gy) = Self::to_graph_coords(area, state, position);
+        // Hit testing needs text extents which only a context can supply
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).ok()?;
+        let cr = cairo::Context::new(&surface);
+        cr.set_font_size(FONT_SIZE);
+        graph.nodes.iter().find_map(|n| {
+            let (x, y) = state.positions.get(&n.node_id)?;
+            let (w, h) = node_size(&cr, &n.display_name);
+            if (gx - x).abs() <= w / 2.0 && (gy - y).abs() <= h / 2.0 {
+                Some(n.node_id.clone())
+            } else {
+                None
+            }
+        })
+    }
+
+    fn draw(&self, area: &gtk::DrawingArea, cr: &cairo::Context) {
+        let state = self.state.borrow();
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.paint();
+        if let Some(ref graph) = state.graph {
+            cr.translate(
+                area.get_allocated_width() as f64 / 2.0 + state.offset.0,
+                area.get_allocated_height() as f64 / 2.0 + state.offset.1,
+            );
+            cr.scale(state.zoom, state.zoom);
+            render(cr, graph, &state.positions);
+        } else {
+            cr.set_source_rgb(0.5, 0.5, 0.5);
+            cr.set_font_size(FONT_SIZE + 2.0);
+            cr.move_to(10.0, 20.0);
+            cr.show_text("Choose \"Show Reference Graph\" on a node in the address space");
+        }
+    }
+
+    fn choose_export_file(&self, title: &str, extension: &str) -> Option<std::path::PathBuf> {
+        let dlg = gtk::FileChooserDialog::with_buttons(
+            Some(title),
+            Some(&*self.main_window),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("_Save", gtk::ResponseType::Accept),
+            ],
+        );
+        dlg.set_do_overwrite_confirmation(true);
+        dlg.set_current_name(&format!("graph.{}", extension));
+        let filter = gtk::FileFilter::new();
+        filter.add_pattern(&format!("*.{}", extension));
+        dlg.set_filter(&filter);
+        let filename = if dlg.run() == gtk::ResponseType::Accept {
+            dlg.get_filename()
+        } else {
+            None
+        };
+        dlg.close();
+        filename
+    }
+
+    fn on_export_dot_clicked(&self) {
+        let dot = match self.state.borrow().graph {
+            Some(ref graph) => graph.to_dot(),
+            None => return,
+        };
+        if let Some(path) = self.choose_export_file("Export Graphviz DOT", "dot") {
+            match fs::write(&path, dot) {
+                Ok(_) => self
+                    .status_label
+                    .set_text(&format!("Exported the graph to {}", path.display())),
+                Err(err) => self.status_label.set_text(&format!(
+                    "Cannot write DOT file {}, {}",
+                    path.display(),
+                    err
+                )),
+            }
+        }
+    }
+
+    fn on_export_svg_clicked(&self) {
+        if self.state.borrow().graph.is_none() {
+            return;
+        }
+        if let Some(path) = self.choose_export_file("Export SVG", "svg") {
+            let state = self.state.borrow();
+            if let Some(ref graph) = state.graph {
+                match export_svg(&path, graph, &state.positions) {
+                    Ok(_) => self
+                        .status_label
+                        .set_text(&format!("Exported the graph to {}", path.display())),
+                    Err(err) => self.status_label.set_text(&format!(
+                        "Cannot write SVG file {}, {:?}",
+                        path.display(),
+                        err
+                    )),
+                }
+            }
+        }
+    }
+}
+
+fn node_size(cr: &cairo::Context, label: &str) -> (f64, f64) {
+    let extents = cr.text_extents(label);
+    (extents.width + NODE_PADDING * 2.0, NODE_HEIGHT)
+}
+
+/// Finds where the line from the centre of a node towards a point leaves the node's bounds
+fn boundary_point((cx, cy): (f64, f64), (w, h): (f64, f64), (tx, ty): (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (tx - cx, ty - cy);
+    if dx == 0.0 && dy == 0.0 {
+        return (cx, cy);
+    }
+    let sx = if dx != 0.0 {
+        (w / 2.0) / dx.abs()
+    } else {
+        f64::MAX
+    };
+    let sy = if dy != 0.0 {
+        (h / 2.0) / dy.abs()
+    } else {
+        f64::MAX
+    };
+    let s = sx.min(sy);
+    (cx + dx * s, cy + dy * s)
+}
+
+fn draw_node_shape(cr: &cairo::Context, shape: NodeShape, (x, y): (f64, f64), (w, h): (f64, f64)) {
+    let (left, top) = (x - w / 2.0, y - h / 2.0);
+    match shape {
+        NodeShape::Box => cr.rectangle(left, top, w, h),
+        NodeShape::RoundedBox => {
+            let r = h / 4.0;
+            cr.new_sub_path();
+            cr.arc(left + w - r, top + r, r, -PI / 2.0, 0.0);
+            cr.arc(left + w - r, top + h - r, r, 0.0, PI / 2.0);
+            cr.arc(left + r, top + h - r, r, PI / 2.0, PI);
+            cr.arc(left + r, top + r, r, PI, 3.0 * PI / 2.0);
+            cr.close_path();
+        }
+        NodeShape::Ellipse => {
+            cr.save();
+            cr.translate(x, y);
+            cr.scale(w / 2.0, h / 2.0);
+            cr.arc(0.0, 0.0, 1.0, 0.0, 2.0 * PI);
+            cr.restore();
+        }
+        NodeShape::Hexagon => {
+            let inset = h / 2.0;
+            cr.move_to(left, y);
+            cr.line_to(left + inset, top);
+            cr.line_to(left + w - inset, top);
+            cr.line_to(left + w, y);
+            cr.line_to(left + w - inset, top + h);
+            cr.line_to(left + inset, top + h);
+            cr.close_path();
+        }
+        NodeShape::Diamond => {
+            cr.move_to(left, y);
+            cr.line_to(x, top);
+            cr.line_to(left + w, y);
+            cr.line_to(x, top + h);
+            cr.close_path();
+        }
+    }
+}
+
+/// Renders the graph in graph coordinates onto the context. The same code is used for the screen
+/// and for SVG export.
+fn render(cr: &cairo::Context, graph: &ReferenceGraph, positions: &HashMap<NodeId, (f64, f64)>) {
+    cr.set_font_size(FONT_SIZE);
+
+    let sizes = graph
+        .nodes
+        .iter()
+        .map(|n| (n.node_id.clone(), node_size(cr, &n.display_name)))
+        .collect::<HashMap<_, _>>();
+
+    // Edges go underneath the nodes
+    cr.set_line_width(1.0);
+    graph.edges.iter().for_each(|e| {
+        if let (Some(&from), Some(&to)) = (positions.get(&e.source), positions.get(&e.target)) {
+            let start = boundary_point(from, sizes[&e.source], to);
+            let end = boundary_point(to, sizes[&e.target], from);
+            cr.set_source_rgb(0.4, 0.4, 0.4);
+            cr.move_to(start.0, start.1);
+            cr.line_to(end.0, end.1);
+            cr.stroke();
+
+            // Arrow head
+            let angle = (end.1 - start.1).atan2(end.0 - start.0);
+            let arrow_length = 8.0;
+            cr.move_to(end.0, end.1);
+            cr.line_to(
+                end.0 - arrow_length * (angle - PI / 8.0).cos(),
+                end.1 - arrow_length * (angle - PI / 8.0).sin(),
+            );
+            cr.line_to(
+                end.0 - arrow_length * (angle + PI / 8.0).cos(),
+                end.1 - arrow_length * (angle + PI / 8.0).sin(),
+            );
+            cr.close_path();
+            cr.fill();
+
+            // Label
+            let extents = cr.text_extents(&e.reference_type_name);
+            cr.set_source_rgb(0.2, 0.2, 0.6);
+            cr.move_to(
+                (start.0 + end.0 - extents.width) / 2.0,
+                (start.1 + end.1) / 2.0 - 2.0,
+            );
+            cr.show_text(&e.reference_type_name);
+        }
+    });
+
+    graph.nodes.iter().for_each(|n| {
+        if let Some(&position) = positions.get(&n.node_id) {
+            let size = sizes[&n.node_id];
+            let style = NodeStyle::for_node_class(n.node_class);
+
+            draw_node_shape(cr, style.shape, position, size);
+            let (r, g, b) = style.fill;
+            cr.set_source_rgb(r, g, b);
+            cr.fill_preserve();
+            cr.set_source_rgb(0.0, 0.0, 0.0);
+            cr.set_line_width(if n.node_id == graph.root { 3.0 } else { 1.0 });
+            if style.dashed {
+                cr.set_dash(&[4.0, 2.0], 0.0);
+            }
+            cr.stroke();
+            cr.set_dash(&[], 0.0);
+
+            let extents = cr.text_extents(&n.display_name);
+            cr.move_to(
+                position.0 - extents.width / 2.0 - extents.x_bearing,
+                position.1 - extents.height / 2.0 - extents.y_bearing,
+            );
+            cr.show_text(&n.display_name);
+        }
+    });
+}
+
+fn export_svg(
+    path: &Path,
+    graph: &ReferenceGraph,
+    positions: &HashMap<NodeId, (f64, f64)>,
+) -> Result<(), cairo::Status> {
+    const MARGIN: f64 = 20.0;
+
+    // Work out the bounds of the drawing to size the document
+    let measure = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)?;
+    let cr = cairo::Context::new(&measure);
+    cr.set_font_size(FONT_SIZE);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (0f64, 0f64, 0f64, 0f64);
+    graph.nodes.iter().for_each(|n| {
+        if let Some(&(x, y)) = positions.get(&n.node_id) {
+            let (w, h) = node_size(&cr, &n.display_name);
+            min_x = min_x.min(x - w / 2.0);
+            min_y = min_y.min(y - h / 2.0);
+            max_x = max_x.max(x + w / 2.0);
+            max_y = max_y.max(y + h / 2.0);
+        }
+    });
+
+    let surface = cairo::SvgSurface::new(
+        max_x - min_x + MARGIN * 2.0,
+        max_y - min_y + MARGIN * 2.0,
+        Some(path),
+    )?;
+    let cr = cairo::Context::new(&surface);
+    cr.set_source_rgb(1.0, 1.0, 1.0);
+    cr.paint();
+    cr.translate(MARGIN - min_x, MARGIN - min_y);
+    render(&cr, graph, positions);
+    surface.finish();
+    // Errors writing the file only show in the status of the finished surface
+    match surface.status() {
+        cairo::Status::Success => Ok(()),
+        status => Err(status),
+    }
+}
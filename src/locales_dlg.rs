@@ -0,0 +1,60 @@
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use crate::{connection_profile::ConnectionProfile, model::ModelMessage};
+
+/// Asks the user for the preferred locales of the current connection. Changing them causes the
+/// model to create a new session with the new locales.
+pub(crate) struct LocalesDlg {
+    model: ActorRef<ModelMessage>,
+    dlg: gtk::Dialog,
+    locales_text: gtk::Entry,
+}
+
+impl LocalesDlg {
+    pub fn new(
+        model: ActorRef<ModelMessage>,
+        parent: &gtk::ApplicationWindow,
+        profile: &ConnectionProfile,
+    ) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some("Preferred Locales"),
+            Some(parent),
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Apply", gtk::ResponseType::Apply),
+            ],
+        );
+
+        let label = gtk::Label::new(Some(
+            "LocaleIds in order of preference, separated by commas, e.g. de-DE, en",
+        ));
+        let locales_text = gtk::Entry::new();
+        locales_text.set_text(&profile.locales_text());
+        locales_text.set_activates_default(true);
+        dlg.set_default_response(gtk::ResponseType::Apply);
+
+        let content_area = dlg.get_content_area();
+        content_area.set_spacing(4);
+        content_area.pack_start(&label, false, true, 0);
+        content_area.pack_start(&locales_text, false, true, 0);
+
+        Self {
+            model,
+            dlg,
+            locales_text,
+        }
+    }
+
+    pub fn show(&self) {
+        self.dlg.show_all();
+        if self.dlg.run() == gtk::ResponseType::Apply {
+            let preferred_locales =
+                ConnectionProfile::parse_locales(self.locales_text.get_text().as_str());
+            self.model
+                .tell(ModelMessage::SetPreferredLocales(preferred_locales), None);
+        }
+        self.dlg.close();
+    }
+}
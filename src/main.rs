@@ -1,7 +1,47 @@
 mod address_space_tree_view;
+mod alarms;
+mod alarms_pane;
+mod alert_rules;
+mod alert_rules_dlg;
 mod app;
+mod array_editor;
+mod attributes_pane;
+mod bulk_read_dlg;
+mod call_method_dlg;
+mod condition_action_dlg;
+mod connection_profile;
+mod diagnostics;
+mod diagnostics_pane;
+mod engineering_units;
+mod event_filter_dlg;
+mod events;
+mod events_pane;
+mod graph_view;
+mod locales_dlg;
+mod method_call;
+mod method_history;
+mod method_history_pane;
 mod model;
+mod monitored_items_pane;
+mod monitoring_parameters_dlg;
 mod new_connection_dlg;
+mod recorder;
+mod recorder_dlg;
+mod recorder_pane;
+mod reference_graph;
+mod structure_editor;
+mod subscription_dlg;
+mod subscriptions_pane;
+mod trend;
+mod trend_view;
+mod type_dictionary;
+mod value_edit;
+mod value_editor;
+mod value_format;
+mod value_viewer;
+mod watch_list;
+mod watch_list_file;
+mod write_value_dlg;
 
 fn main() {
     app::App::run();
@@ -0,0 +1,62 @@
+use opcua_client::prelude::*;
+
+use crate::value_edit::WriteInfo;
+
+/// An input or output argument of a method, from its InputArguments or OutputArguments
+/// property
+#[derive(Debug, Clone)]
+pub struct MethodArgument {
+    pub name: String,
+    pub description: String,
+    /// The DataType, ValueRank and ArrayDimensions of the argument, in the form the value
+    /// editors take
+    pub info: WriteInfo,
+}
+
+/// What is needed to call a method on an object
+#[derive(Debug, Clone)]
+pub struct MethodInfo {
+    /// The object the method is called on, which is the node it was browsed from
+    pub object_id: NodeId,
+    pub object_name: String,
+    pub method_id: NodeId,
+    pub method_name: String,
+    /// False if the Executable or UserExecutable attribute of the method says it can't be called
+    pub executable: bool,
+    pub inputs: Vec<MethodArgument>,
+    pub outputs: Vec<MethodArgument>,
+}
+
+/// A call of a method on an object with the values of its input arguments
+#[derive(Debug, Clone)]
+pub struct MethodCall {
+    pub object_id: NodeId,
+    /// The names of the object and method, for messages
+    pub object_name: String,
+    pub method_id: NodeId,
+    pub method_name: String,
+    pub inputs: Vec<Variant>,
+}
+
+/// What the server returned from calling a method
+#[derive(Debug, Clone)]
+pub struct MethodCallResult {
+    pub call: MethodCall,
+    pub status_code: StatusCode,
+    /// The status of each input argument. Servers may leave these out when every input is
+    /// good.
+    pub input_results: Vec<StatusCode>,
+    pub outputs: Vec<Variant>,
+}
+
+impl MethodCallResult {
+    /// A result for a call that didn't get a response, e.g. because the service failed
+    pub fn failed(call: MethodCall, status_code: StatusCode) -> Self {
+        Self {
+            call,
+            status_code,
+            input_results: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::{fs, io::Cursor, path::Path, str::FromStr};
+
+use chrono::{DateTime as ChronoDateTime, SecondsFormat, Utc};
+use serde_json::{json, Value as JsonValue};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    method_call::{MethodCall, MethodCallResult},
+    trend::csv_field,
+    value_format::{self, TimeDisplay},
+    watch_list_file::config_dir,
+};
+
+const HISTORY_FILE: &str = "method_history.json";
+
+/// The most calls kept for each server, the oldest are dropped first
+const MAX_RECORDS_PER_ENDPOINT: usize = 500;
+
+/// A method call made on a server, kept so it can be run again and reported
+#[derive(Debug, Clone)]
+pub struct MethodCallRecord {
+    pub endpoint_url: String,
+    pub time: ChronoDateTime<Utc>,
+    pub result: MethodCallResult,
+}
+
+impl MethodCallRecord {
+    pub fn inputs_text(&self) -> String {
+        values_text(&self.result.call.inputs)
+    }
+
+    pub fn outputs_text(&self) -> String {
+        values_text(&self.result.outputs)
+    }
+
+    /// The overall result, followed by the results of any inputs that were bad
+    pub fn status_text(&self) -> String {
+        let mut text = value_format::status_code_to_string(self.result.status_code);
+        let bad_inputs = self
+            .result
+            .input_results
+            .iter()
+            .enumerate()
+            .filter(|(_, status_code)| status_code.is_bad())
+            .map(|(i, status_code)| {
+                format!(
+                    "input {}: {}",
+                    i + 1,
+                    value_format::status_code_to_string(*status_code)
+                )
+            })
+            .collect::<Vec<_>>();
+        if !bad_inputs.is_empty() {
+            text.push_str(&format!(" ({})", bad_inputs.join(", ")));
+        }
+        text
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let call = &self.result.call;
+        json!({
+            "endpoint_url": self.endpoint_url,
+            "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "object_id": call.object_id.to_string(),
+            "object_name": call.object_name,
+            "method_id": call.method_id.to_string(),
+            "method_name": call.method_name,
+            "inputs": values_to_json(&call.inputs),
+            "status_code": self.result.status_code.bits(),
+            "input_results": self
+                .result
+                .input_results
+                .iter()
+                .map(|s| s.bits())
+                .collect::<Vec<_>>(),
+            "outputs": values_to_json(&self.result.outputs),
+        })
+    }
+
+    fn from_json(json: &JsonValue) -> Option<Self> {
+        let call = MethodCall {
+            object_id: NodeId::from_str(json["object_id"].as_str()?).ok()?,
+            object_name: json["object_name"].as_str().unwrap_or_default().to_string(),
+            method_id: NodeId::from_str(json["method_id"].as_str()?).ok()?,
+            method_name: json["method_name"].as_str().unwrap_or_default().to_string(),
+            inputs: values_from_json(&json["inputs"])?,
+        };
+        let status_code = |v: &JsonValue| StatusCode::from_bits_truncate(v.as_u64()? as u32);
+        Some(Self {
+            endpoint_url: json["endpoint_url"].as_str()?.to_string(),
+            time: ChronoDateTime::parse_from_rfc3339(json["time"].as_str()?)
+                .ok()?
+                .with_timezone(&Utc),
+            result: MethodCallResult {
+                call,
+                status_code: status_code(&json["status_code"])?,
+                input_results: json["input_results"]
+                    .as_array()
+                    .map_or_else(Vec::new, |a| a.iter().filter_map(status_code).collect()),
+                outputs: values_from_json(&json["outputs"]).unwrap_or_default(),
+            },
+        })
+    }
+}
+
+/// The method calls made on every server, oldest first
+#[derive(Debug, Default)]
+pub struct MethodHistory {
+    pub records: Vec<MethodCallRecord>,
+}
+
+impl MethodHistory {
+    /// Reads the history. A missing or unreadable history is an empty one.
+    pub fn load() -> Self {
+        let text =
+            match config_dir().and_then(|dir| fs::read_to_string(dir.join(HISTORY_FILE)).ok()) {
+                Some(text) => text,
+                None => return Self::default(),
+            };
+        let records = serde_json::from_str::<JsonValue>(&text)
+            .ok()
+            .and_then(|json| json.as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(MethodCallRecord::from_json)
+            .collect();
+        Self { records }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = config_dir().ok_or("There is no directory for settings")?;
+        fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}, {}", dir.display(), e))?;
+        let json = self
+            .records
+            .iter()
+            .map(|record| record.to_json())
+            .collect::<Vec<_>>();
+        let path = dir.join(HISTORY_FILE);
+        fs::write(&path, JsonValue::Array(json).to_string())
+            .map_err(|e| format!("Cannot write {}, {}", path.display(), e))
+    }
+
+    /// Adds a call, dropping the oldest call to the same server if there are too many
+    pub fn add(&mut self, record: MethodCallRecord) {
+        let endpoint_records = self
+            .records
+            .iter()
+            .filter(|r| r.endpoint_url == record.endpoint_url)
+            .count();
+        if endpoint_records >= MAX_RECORDS_PER_ENDPOINT {
+            if let Some(i) = self
+                .records
+                .iter()
+                .position(|r| r.endpoint_url == record.endpoint_url)
+            {
+                self.records.remove(i);
+            }
+        }
+        self.records.push(record);
+    }
+
+    /// Forgets the calls made on a server
+    pub fn clear(&mut self, endpoint_url: &str) {
+        self.records.retain(|r| r.endpoint_url != endpoint_url);
+    }
+}
+
+/// Writes calls as CSV with a row per call, for reports
+pub fn export_csv(path: &Path, records: &[&MethodCallRecord]) -> Result<(), String> {
+    let mut text =
+        "Time,Endpoint,Object,Object Id,Method,Method Id,Inputs,Outputs,Status\n".to_string();
+    records.iter().for_each(|record| {
+        let call = &record.result.call;
+        let fields = [
+            record.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            record.endpoint_url.clone(),
+            call.object_name.clone(),
+            call.object_id.to_string(),
+            call.method_name.clone(),
+            call.method_id.to_string(),
+            record.inputs_text(),
+            record.outputs_text(),
+            record.status_text(),
+        ];
+        let fields = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    });
+    fs::write(path, text).map_err(|e| format!("Cannot write {}, {}", path.display(), e))
+}
+
+fn values_text(values: &[Variant]) -> String {
+    values
+        .iter()
+        .map(|v| value_format::variant_to_string(v, TimeDisplay::Utc))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Values are saved in the binary encoding so calls can be run again with exactly the same
+/// inputs, next to text for people reading the file
+fn values_to_json(values: &[Variant]) -> JsonValue {
+    values
+        .iter()
+        .map(|v| {
+            let mut stream = Cursor::new(Vec::new());
+            let binary = v
+                .encode(&mut stream)
+                .map(|_| ByteString::from(stream.into_inner()).as_base64())
+                .ok();
+            json!({
+                "text": value_format::variant_to_string(v, TimeDisplay::Utc),
+                "binary": binary,
+            })
+        })
+        .collect()
+}
+
+fn values_from_json(json: &JsonValue) -> Option<Vec<Variant>> {
+    let decoding_options = DecodingOptions::default();
+    json.as_array()?
+        .iter()
+        .map(|v| {
+            let bytes = ByteString::from_base64(v["binary"].as_str()?)?;
+            let mut stream = Cursor::new(bytes.value.unwrap_or_default());
+            Variant::decode(&mut stream, &decoding_options).ok()
+        })
+        .collect()
+}
@@ -0,0 +1,294 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use chrono::{Local, Utc};
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use crate::{
+    connection_profile::ConnectionProfile,
+    method_call::{MethodCall, MethodCallResult},
+    method_history::{self, MethodCallRecord, MethodHistory},
+    model::ModelMessage,
+};
+
+const COL_INDEX: u32 = 0;
+const COL_TIME: u32 = 1;
+const COL_OBJECT: u32 = 2;
+const COL_METHOD: u32 = 3;
+const COL_INPUTS: u32 = 4;
+const COL_OUTPUTS: u32 = 5;
+const COL_STATUS: u32 = 6;
+const COL_STATUS_COLOUR: u32 = 7;
+
+struct MethodHistoryPaneImpl {
+    model: ActorRef<ModelMessage>,
+    history_tree: gtk::TreeView,
+    history_model: gtk::ListStore,
+    run_btn: gtk::Button,
+    edit_btn: gtk::Button,
+    status_label: gtk::Label,
+    history: RefCell<MethodHistory>,
+    /// The endpoint of the connection, or the last connection, whose calls are shown
+    endpoint_url: RefCell<Option<String>>,
+    connected: Cell<bool>,
+    /// Called with a call to show in the Call dialog, so its inputs can be changed first
+    edit_handlers: RefCell<Vec<Box<dyn Fn(MethodCall)>>>,
+}
+
+/// The methods called on the server, newest first, which can be called again as they were or
+/// after changing their inputs, and exported for commissioning reports
+#[derive(Clone)]
+pub struct MethodHistoryPane {
+    data: Rc<MethodHistoryPaneImpl>,
+}
+
+impl MethodHistoryPane {
+    pub fn new(builder: Rc<gtk::Builder>, model: ActorRef<ModelMessage>) -> Self {
+        let export_btn: gtk::Button = builder.get_object("method_history_export_btn").unwrap();
+        let clear_btn: gtk::Button = builder.get_object("method_history_clear_btn").unwrap();
+
+        let data = Rc::new(MethodHistoryPaneImpl {
+            model,
+            history_tree: builder.get_object("method_history_tree").unwrap(),
+            history_model: builder.get_object("method_history_model").unwrap(),
+            run_btn: builder.get_object("method_history_run_btn").unwrap(),
+            edit_btn: builder.get_object("method_history_edit_btn").unwrap(),
+            status_label: builder.get_object("method_history_status_label").unwrap(),
+            history: RefCell::new(MethodHistory::load()),
+            endpoint_url: RefCell::new(None),
+            connected: Cell::new(false),
+            edit_handlers: RefCell::new(Vec::new()),
+        });
+
+        data.run_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(record) = data.selected_record() {
+                data.model
+                    .tell(ModelMessage::CallMethod(record.result.call), None);
+                data.status_label.set_text("Calling...");
+            }
+        }));
+        data.edit_btn
+            .connect_clicked(clone!(@weak data => move |_| {
+                data.edit_selected();
+            }));
+        data.history_tree
+            .connect_row_activated(clone!(@weak data => move |_, _, _| {
+                data.edit_selected();
+            }));
+        data.history_tree
+            .get_selection()
+            .connect_changed(clone!(@weak data => move |_| {
+                data.update_buttons();
+            }));
+        export_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.export();
+        }));
+        clear_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.clear();
+        }));
+
+        data.update_buttons();
+        Self { data }
+    }
+
+    /// Adds a function called with a call from the history whose inputs are to be changed
+    /// before calling it again
+    pub fn connect_edit<F>(&self, f: F)
+    where
+        F: Fn(MethodCall) + 'static,
+    {
+        self.data.edit_handlers.borrow_mut().push(Box::new(f));
+    }
+
+    /// Shows the calls made on the server connected to
+    pub fn on_connected(&self, profile: &ConnectionProfile) {
+        *self.data.endpoint_url.borrow_mut() = Some(profile.endpoint_url.clone());
+        self.data.connected.set(true);
+        self.data.populate();
+    }
+
+    /// Keeps showing the calls so they can still be exported, but they can't be run
+    pub fn on_disconnected(&self) {
+        self.data.connected.set(false);
+        self.data.update_buttons();
+    }
+
+    /// Adds a call to the history of the server it was made on
+    pub fn on_call_result(&self, result: &MethodCallResult) {
+        let endpoint_url = match *self.data.endpoint_url.borrow() {
+            Some(ref endpoint_url) => endpoint_url.clone(),
+            None => return,
+        };
+        let record = MethodCallRecord {
+            endpoint_url,
+            time: Utc::now(),
+            result: result.clone(),
+        };
+        let save_result = {
+            let mut history = self.data.history.borrow_mut();
+            history.add(record);
+            history.save()
+        };
+        self.data.populate();
+        match save_result {
+            Ok(_) => self.data.status_label.set_text(""),
+            Err(err) => self.data.status_label.set_text(&err),
+        }
+    }
+}
+
+impl MethodHistoryPaneImpl {
+    /// Lists the calls of the server connected to, newest first
+    fn populate(&self) {
+        self.history_model.clear();
+        let endpoint_url = self.endpoint_url.borrow();
+        let history = self.history.borrow();
+        history
+            .records
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, r)| Some(&r.endpoint_url) == endpoint_url.as_ref())
+            .for_each(|(i, record)| {
+                let call = &record.result.call;
+                let time = record
+                    .time
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string();
+                let colour = if record.result.status_code.is_good() {
+                    None
+                } else {
+                    Some("red")
+                };
+                self.history_model.insert_with_values(
+                    None,
+                    &[
+                        COL_INDEX,
+                        COL_TIME,
+                        COL_OBJECT,
+                        COL_METHOD,
+                        COL_INPUTS,
+                        COL_OUTPUTS,
+                        COL_STATUS,
+                        COL_STATUS_COLOUR,
+                    ],
+                    &[
+                        &(i as u32),
+                        &time,
+                        &call.object_name,
+                        &call.method_name,
+                        &record.inputs_text(),
+                        &record.outputs_text(),
+                        &record.status_text(),
+                        &colour,
+                    ],
+                );
+            });
+        self.update_buttons();
+    }
+
+    fn selected_record(&self) -> Option<MethodCallRecord> {
+        let (model, iter) = self.history_tree.get_selection().get_selected()?;
+        let i = model
+            .get_value(&iter, COL_INDEX as i32)
+            .get::<u32>()
+            .ok()
+            .flatten()?;
+        self.history.borrow().records.get(i as usize).cloned()
+    }
+
+    /// Calls can only be made again while connected to the server they were made on
+    fn update_buttons(&self) {
+        let sensitive = self.connected.get() && self.selected_record().is_some();
+        self.run_btn.set_sensitive(sensitive);
+        self.edit_btn.set_sensitive(sensitive);
+    }
+
+    fn edit_selected(&self) {
+        if !self.connected.get() {
+            return;
+        }
+        if let Some(record) = self.selected_record() {
+            self.edit_handlers
+                .borrow()
+                .iter()
+                .for_each(|f| f(record.result.call.clone()));
+        }
+    }
+
+    fn export(&self) {
+        // The calls shown, oldest first as a report would list them. They are copied so calls
+        // can still be added while the file chooser runs.
+        let records = {
+            let endpoint_url = self.endpoint_url.borrow();
+            self.history
+                .borrow()
+                .records
+                .iter()
+                .filter(|r| Some(&r.endpoint_url) == endpoint_url.as_ref())
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        if records.is_empty() {
+            self.status_label.set_text("There are no calls to export");
+            return;
+        }
+        let parent = self
+            .history_tree
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok());
+        let dlg = gtk::FileChooserDialog::with_buttons(
+            Some("Export Method Calls"),
+            parent.as_ref(),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("_Save", gtk::ResponseType::Accept),
+            ],
+        );
+        dlg.set_do_overwrite_confirmation(true);
+        dlg.set_current_name("method-calls.csv");
+        let filter = gtk::FileFilter::new();
+        filter.add_pattern("*.csv");
+        dlg.set_filter(&filter);
+        let filename = if dlg.run() == gtk::ResponseType::Accept {
+            dlg.get_filename()
+        } else {
+            None
+        };
+        dlg.close();
+        if let Some(path) = filename {
+            let records = records.iter().collect::<Vec<_>>();
+            match method_history::export_csv(&path, &records) {
+                Ok(_) => self.status_label.set_text(&format!(
+                    "Exported {} calls to {}",
+                    records.len(),
+                    path.display()
+                )),
+                Err(err) => self.status_label.set_text(&err),
+            }
+        }
+    }
+
+    /// Forgets the calls made on the server connected to
+    fn clear(&self) {
+        let endpoint_url = match *self.endpoint_url.borrow() {
+            Some(ref endpoint_url) => endpoint_url.clone(),
+            None => return,
+        };
+        let save_result = {
+            let mut history = self.history.borrow_mut();
+            history.clear(&endpoint_url);
+            history.save()
+        };
+        self.populate();
+        if let Err(err) = save_result {
+            self.status_label.set_text(&err);
+        }
+    }
+}
@@ -1,46 +1,645 @@
-use std::str::FromStr;
-use std::sync::{Arc, Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, RwLock,
+};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use riker::actors::*;
 
 pub use opcua_client::prelude::*;
 
+use crate::alarms::ConditionCall;
 pub use crate::app::AppMessage;
+use crate::connection_profile::ConnectionProfile;
+use crate::diagnostics::{SubscriptionDiagnostics, SubscriptionHealth};
+use crate::engineering_units::AnalogInfo;
+use crate::events::{Event, EventMonitor, EventType};
+use crate::method_call::{MethodArgument, MethodCall, MethodCallResult, MethodInfo};
+use crate::reference_graph::{GraphEdge, GraphNode, ReferenceGraph};
+use crate::type_dictionary::{self, BinarySchema, EnumLayout, FieldType, TypeDictionary};
+use crate::value_edit::{BuiltinType, WriteInfo, ACCESS_LEVEL_CURRENT_WRITE};
+use crate::watch_list::{
+    Acquisition, MonitoringSettings, PollStatistics, RevisedMonitoring, RevisedSubscription,
+    SubscriptionSettings, WatchItem, WatchList,
+};
+use crate::watch_list_file::{self, WatchListFile};
+
+/// The most nodes read or browsed in one request when loading types, which keeps requests
+/// within the limits of most servers
+const NODES_PER_REQUEST: usize = 100;
+
+/// The events a server may hold for an event monitor between publishes
+const EVENT_QUEUE_SIZE: u32 = 1000;
 
 struct Connection {
     client: Client,
     session: Option<Arc<RwLock<Session>>>,
+    profile: Option<ConnectionProfile>,
+    /// The structured and enumerated DataTypes of the server
+    type_dictionary: Arc<TypeDictionary>,
+    /// The MaxNodesPerRead operation limit of the server, 0 if it has none
+    max_nodes_per_read: usize,
 }
 
 impl Default for Connection {
     fn default() -> Self {
-        let client = ClientBuilder::default()
+        Self {
+            client: Self::create_client(&[]),
+            session: None,
+            profile: None,
+            type_dictionary: Arc::new(TypeDictionary::default()),
+            max_nodes_per_read: 0,
+        }
+    }
+}
+
+impl Connection {
+    /// Creates a client. The preferred locales are passed by the client to the server when it
+    /// creates and activates a session so they are fixed for the lifetime of the client.
+    fn create_client(preferred_locales: &[String]) -> Client {
+        ClientBuilder::default()
             .application_name("OPCUA Explorer")
             .application_uri("urn:OPCUAExplorer")
             .product_uri("urn:OPCUAExplorer")
             .trust_server_certs(true)
             .create_sample_keypair(true)
             .session_retry_limit(3)
+            .preferred_locales(preferred_locales.to_vec())
             .client()
-            .unwrap();
+            .unwrap()
+    }
 
-        Self {
-            client,
-            session: None,
+    fn connect_session(
+        client: &mut Client,
+        profile: &ConnectionProfile,
+    ) -> Result<Arc<RwLock<Session>>, StatusCode> {
+        let user_token_policy = UserTokenPolicy::anonymous();
+        let identity_token = IdentityToken::Anonymous;
+        client.connect_to_endpoint(
+            (
+                profile.endpoint_url.as_ref(),
+                profile.security_policy.to_str(),
+                profile.message_security_mode,
+                user_token_policy,
+            ),
+            identity_token,
+        )
+    }
+
+    /// Creates a session whose only preferred locale is the one supplied. The caller
+    /// disconnects it when done so servers aren't left holding a session per locale.
+    fn connect_locale_session(&self, locale: &str) -> Result<Arc<RwLock<Session>>, StatusCode> {
+        let profile = self.profile.as_ref().ok_or(StatusCode::BadNotConnected)?;
+        let mut client = Self::create_client(&[locale.to_string()]);
+        Self::connect_session(&mut client, profile)
+    }
+
+    /// The most nodes to put in one Read request. Servers without a limit still get requests of
+    /// a moderate size.
+    fn nodes_per_read(&self) -> usize {
+        if self.max_nodes_per_read > 0 {
+            self.max_nodes_per_read
+        } else {
+            NODES_PER_REQUEST
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum ModelMessage {
-    Connect(String, SecurityPolicy, MessageSecurityMode),
+    Connect(ConnectionProfile),
+    SetPreferredLocales(Vec<String>),
     Disconnect,
     BrowseNode(NodeId),
+    BrowseGraph(NodeId, u32, NodeId),
+    ReadAttributes(NodeId),
+    ReadTranslations(NodeId),
+    ReadWriteInfo(NodeId),
+    /// Writes the value of a variable, optionally only the elements in a NumericRange
+    WriteValue(NodeId, Variant, Option<String>),
+    /// Writes an attribute other than Value, e.g. DisplayName, where the WriteMask allows it
+    WriteAttribute(NodeId, AttributeId, Variant),
+    /// Reads attributes of many nodes, split into as many requests as the server needs
+    BulkRead(BulkRead),
+    /// Creates or updates a watch list and starts acquiring the values of its items
+    SetWatchList(WatchList),
+    /// Stops acquiring the values of a watch list's items
+    RemoveWatchList(u32),
+    /// Finds the event types of the server and the fields each declares
+    BrowseEventTypes,
+    /// Creates or replaces the subscription to a node's events for an event monitor
+    SetEventMonitor(EventMonitor),
+    /// Stops receiving the events of an event monitor
+    RemoveEventMonitor(u32),
+    /// Asks the server to send the state of every condition to a subscription again
+    ConditionRefresh(u32),
+    /// Calls a method of a condition, e.g. Acknowledge
+    CallConditionMethod(ConditionCall),
+    /// Reads how the subscriptions of the watch lists are doing, from the client and the server
+    ReadSubscriptionDiagnostics,
+    /// Saves a watch list to a file, optionally with the endpoint it belongs to
+    SaveWatchList(WatchList, Option<String>, PathBuf),
+    /// Opens a watch list file and finds which of its nodes the server has
+    LoadWatchList(PathBuf),
+    /// Reads the arguments of a method of an object, by object id and method id
+    ReadMethodInfo(NodeId, NodeId),
+    /// Calls a method of an object with the values of its input arguments
+    CallMethod(MethodCall),
+}
+
+/// Attributes to read from a set of nodes in one go
+#[derive(Debug, Clone)]
+pub struct BulkRead {
+    pub node_ids: Vec<NodeId>,
+    pub attribute_ids: Vec<AttributeId>,
+    /// The maximum age in milliseconds of a cached value the server may return, 0 to read
+    /// the current value from the source
+    pub max_age: f64,
+    pub timestamps_to_return: TimestampsToReturn,
+}
+
+/// The values read by a bulk read. Each node and attribute has a value, whose status says if
+/// the read of that node failed.
+#[derive(Debug, Clone)]
+pub struct BulkReadResult {
+    pub values: Vec<(NodeId, AttributeId, DataValue)>,
+    /// The number of Read requests that were made
+    pub requests: usize,
+    /// The most nodes that were put in a request
+    pub nodes_per_request: usize,
+}
+
+/// A translation of a node's DisplayName and Description into one locale
+#[derive(Debug, Clone)]
+pub struct Translation {
+    pub locale: String,
+    pub display_name: Option<LocalizedText>,
+    pub description: Option<LocalizedText>,
+}
+
+/// Converts the value of a NodeClass attribute into the enum
+pub fn node_class_from_i32(v: i32) -> NodeClass {
+    match v {
+        1 => NodeClass::Object,
+        2 => NodeClass::Variable,
+        4 => NodeClass::Method,
+        8 => NodeClass::ObjectType,
+        16 => NodeClass::VariableType,
+        32 => NodeClass::ReferenceType,
+        64 => NodeClass::DataType,
+        128 => NodeClass::View,
+        _ => NodeClass::Unspecified,
+    }
+}
+
+/// Polls the items of a watch list with cyclic Read on a thread of its own. The thread stops
+/// when the poller is dropped.
+struct Poller {
+    /// The items to read, which can change without restarting the thread
+    watch_list: Arc<Mutex<WatchList>>,
+    interval: u32,
+    stop: Arc<AtomicBool>,
+}
+
+impl Poller {
+    fn start(
+        watch_list: WatchList,
+        interval: u32,
+        connection: Arc<Mutex<Connection>>,
+        app: ActorRef<AppMessage>,
+    ) -> Self {
+        let watch_list = Arc::new(Mutex::new(watch_list));
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let watch_list = watch_list.clone();
+            let stop = stop.clone();
+            let interval = Duration::from_millis(interval as u64);
+            thread::spawn(move || Model::poll(watch_list, interval, stop, connection, app));
+        }
+        Self {
+            watch_list,
+            interval,
+            stop,
+        }
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The subscription of a watch list whose values are acquired by monitored items. The server
+/// assigns the monitored item ids, which are mapped back to the watch items they belong to.
+struct WatchSubscription {
+    subscription_id: u32,
+    /// The publishing parameters last requested
+    settings: SubscriptionSettings,
+    /// The watch item of each monitored item, shared with the data change callback
+    item_ids: Arc<Mutex<HashMap<u32, u32>>>,
+    /// The monitored item id of each watch item and the settings last requested for it
+    monitored_items: HashMap<u32, (u32, MonitoringSettings)>,
+    /// The trigger item each linked watch item has on the server, by watch item id
+    links: HashMap<u32, u32>,
+    /// The monitoring mode last requested for each watch item's monitored item
+    modes: HashMap<u32, MonitoringMode>,
+    /// The notifications received, counted by the data change callback
+    health: Arc<Mutex<SubscriptionHealth>>,
+}
+
+impl WatchSubscription {
+    /// Creates a subscription whose data changes are sent to the app as item values
+    fn create(
+        session: &mut Session,
+        settings: SubscriptionSettings,
+        app: ActorRef<AppMessage>,
+    ) -> Result<Self, StatusCode> {
+        let item_ids = Arc::new(Mutex::new(HashMap::<u32, u32>::new()));
+        let health = Arc::new(Mutex::new(SubscriptionHealth::default()));
+        let callback = {
+            let item_ids = item_ids.clone();
+            let health = health.clone();
+            DataChangeCallback::new(move |changed_monitored_items| {
+                health.lock().unwrap().record(changed_monitored_items.len());
+                let item_ids = item_ids.lock().unwrap();
+                let values = changed_monitored_items
+                    .iter()
+                    .filter_map(|monitored_item| {
+                        item_ids
+                            .get(&monitored_item.id())
+                            .map(|item_id| (*item_id, monitored_item.last_value().clone()))
+                    })
+                    .collect::<Vec<_>>();
+                if !values.is_empty() {
+                    app.tell(AppMessage::ItemValues(values), None);
+                }
+            })
+        };
+        let subscription_id = session.create_subscription(
+            settings.publishing_interval,
+            settings.lifetime_count,
+            settings.max_keep_alive_count,
+            settings.max_notifications_per_publish,
+            settings.priority,
+            settings.publishing_enabled,
+            callback,
+        )?;
+        Ok(Self {
+            subscription_id,
+            settings,
+            item_ids,
+            monitored_items: HashMap::new(),
+            links: HashMap::new(),
+            modes: HashMap::new(),
+            health,
+        })
+    }
+
+    /// Changes the publishing parameters with ModifySubscription and the publishing mode with
+    /// SetPublishingMode, as far as they differ from those last requested
+    fn modify(
+        &mut self,
+        session: &mut Session,
+        settings: SubscriptionSettings,
+    ) -> Result<(), StatusCode> {
+        // The settings are kept even if the server refuses them, otherwise every update of the
+        // watch list would ask again
+        let old_settings = self.settings;
+        self.settings = settings;
+        if settings.publishing_parameters_differ(&old_settings) {
+            session.modify_subscription(
+                self.subscription_id,
+                settings.publishing_interval,
+                settings.lifetime_count,
+                settings.max_keep_alive_count,
+                settings.max_notifications_per_publish,
+                settings.priority,
+            )?;
+        }
+        if settings.publishing_enabled != old_settings.publishing_enabled {
+            let results = session
+                .set_publishing_mode(&[self.subscription_id], settings.publishing_enabled)?;
+            match results.first() {
+                Some(status_code) if status_code.is_bad() => return Err(*status_code),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The publishing parameters the server granted, which the client keeps from the responses
+    /// to CreateSubscription and ModifySubscription
+    fn revised(&self, session: &Session) -> Option<RevisedSubscription> {
+        let subscription_state = session.subscription_state();
+        let subscription_state = subscription_state.read().unwrap();
+        let subscription = subscription_state.get(self.subscription_id)?;
+        Some(RevisedSubscription {
+            subscription_id: self.subscription_id,
+            publishing_interval: subscription.publishing_interval(),
+            lifetime_count: subscription.lifetime_count(),
+            max_keep_alive_count: subscription.max_keep_alive_count(),
+        })
+    }
+
+    /// Creates monitored items for watch items with the parameters in their settings and in
+    /// their monitoring mode, and returns what the server granted for each item or why it
+    /// couldn't be monitored
+    fn add_items(
+        &mut self,
+        session: &mut Session,
+        items: &[&WatchItem],
+    ) -> Vec<(u32, Result<RevisedMonitoring, StatusCode>)> {
+        let items_to_create = items
+            .iter()
+            .map(|item| MonitoredItemCreateRequest {
+                item_to_monitor: item.node_id.clone().into(),
+                monitoring_mode: item.monitoring_mode,
+                requested_parameters: item.monitoring.monitoring_parameters(item.item_id),
+            })
+            .collect::<Vec<_>>();
+        let results = match session.create_monitored_items(
+            self.subscription_id,
+            TimestampsToReturn::Both,
+            &items_to_create,
+        ) {
+            Ok(results) => results,
+            Err(err) => return items.iter().map(|item| (item.item_id, Err(err))).collect(),
+        };
+        let mut item_ids = self.item_ids.lock().unwrap();
+        items
+            .iter()
+            .zip(results.iter())
+            .map(|(item, result)| {
+                if result.status_code.is_good() {
+                    item_ids.insert(result.monitored_item_id, item.item_id);
+                    self.monitored_items
+                        .insert(item.item_id, (result.monitored_item_id, item.monitoring));
+                    self.modes.insert(item.item_id, item.monitoring_mode);
+                    let revised = RevisedMonitoring {
+                        sampling_interval: result.revised_sampling_interval,
+                        queue_size: result.revised_queue_size,
+                    };
+                    (item.item_id, Ok(revised))
+                } else {
+                    (item.item_id, Err(result.status_code))
+                }
+            })
+            .collect()
+    }
+
+    /// Changes the parameters of the monitored items of watch items to those in their settings
+    /// with ModifyMonitoredItems. A server that rejects new parameters keeps the old ones.
+    fn modify_items(
+        &mut self,
+        session: &mut Session,
+        items: &[&WatchItem],
+    ) -> Vec<(u32, Result<RevisedMonitoring, StatusCode>)> {
+        let items = items
+            .iter()
+            .filter_map(|item| {
+                self.monitored_items
+                    .get_mut(&item.item_id)
+                    .map(|(monitored_item_id, settings)| {
+                        // A rejected item keeps its old parameters on the server, and isn't
+                        // asked again until its settings are edited
+                        *settings = item.monitoring;
+                        (item.item_id, *monitored_item_id, item.monitoring)
+                    })
+            })
+            .collect::<Vec<_>>();
+        let items_to_modify = items
+            .iter()
+            .map(
+                |(item_id, monitored_item_id, settings)| MonitoredItemModifyRequest {
+                    monitored_item_id: *monitored_item_id,
+                    requested_parameters: settings.monitoring_parameters(*item_id),
+                },
+            )
+            .collect::<Vec<_>>();
+        match session.modify_monitored_items(
+            self.subscription_id,
+            TimestampsToReturn::Both,
+            &items_to_modify,
+        ) {
+            Ok(results) => items
+                .iter()
+                .zip(results.iter())
+                .map(|((item_id, _, _), result)| {
+                    if result.status_code.is_good() {
+                        let revised = RevisedMonitoring {
+                            sampling_interval: result.revised_sampling_interval,
+                            queue_size: result.revised_queue_size,
+                        };
+                        (*item_id, Ok(revised))
+                    } else {
+                        (*item_id, Err(result.status_code))
+                    }
+                })
+                .collect(),
+            Err(err) => items
+                .iter()
+                .map(|(item_id, _, _)| (*item_id, Err(err)))
+                .collect(),
+        }
+    }
+
+    /// Makes the trigger links on the server match those of the watch items with SetTriggering.
+    /// Returns the number of links added and removed, and the items that couldn't be linked.
+    /// The monitoring modes of linked and unlinked items are changed by `update_modes`.
+    fn update_links(
+        &mut self,
+        session: &mut Session,
+        items: &[WatchItem],
+    ) -> (usize, usize, Vec<(u32, StatusCode)>) {
+        let subscription_id = self.subscription_id;
+        let monitored_items = &self.monitored_items;
+        let monitored_item_id = |item_id: &u32| monitored_items.get(item_id).map(|(id, _)| *id);
+        let wanted = items
+            .iter()
+            .filter_map(|item| {
+                item.triggered_by
+                    .map(|trigger_id| (item.item_id, trigger_id))
+            })
+            .filter(|(item_id, trigger_id)| {
+                monitored_item_id(item_id).is_some() && monitored_item_id(trigger_id).is_some()
+            })
+            .collect::<HashMap<_, _>>();
+        let to_remove = self
+            .links
+            .iter()
+            .filter(|(item_id, trigger_id)| wanted.get(item_id) != Some(trigger_id))
+            .map(|(item_id, trigger_id)| (*item_id, *trigger_id))
+            .collect::<Vec<_>>();
+        let to_add = wanted
+            .iter()
+            .filter(|(item_id, trigger_id)| self.links.get(item_id) != Some(trigger_id))
+            .map(|(item_id, trigger_id)| (*item_id, *trigger_id))
+            .collect::<Vec<_>>();
+        if to_remove.is_empty() && to_add.is_empty() {
+            return (0, 0, Vec::new());
+        }
+
+        // Links of deleted monitored items have already gone from the server
+        let group_by_trigger = |links: &[(u32, u32)]| {
+            let mut groups = HashMap::<u32, Vec<(u32, u32)>>::new();
+            links
+                .iter()
+                .filter_map(|(item_id, trigger_id)| {
+                    Some((
+                        *item_id,
+                        monitored_item_id(item_id)?,
+                        monitored_item_id(trigger_id)?,
+                    ))
+                })
+                .for_each(|(item_id, id, trigger_id)| {
+                    groups.entry(trigger_id).or_default().push((item_id, id))
+                });
+            groups
+        };
+        let remove_groups = group_by_trigger(&to_remove);
+        let add_groups = group_by_trigger(&to_add);
+
+        for (item_id, _) in to_remove.iter() {
+            self.links.remove(item_id);
+        }
+        remove_groups.iter().for_each(|(trigger_id, items)| {
+            let ids = items.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+            let _ = session.set_triggering(subscription_id, *trigger_id, &[], &ids);
+        });
+
+        let mut failed = Vec::new();
+        add_groups.iter().for_each(|(trigger_id, items)| {
+            let ids = items.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+            let results = session
+                .set_triggering(subscription_id, *trigger_id, &ids, &[])
+                .map(|(add_results, _)| add_results.unwrap_or_default());
+            items.iter().enumerate().for_each(|(i, (item_id, _))| {
+                let status_code = match results {
+                    Ok(ref results) => results.get(i).cloned().unwrap_or(StatusCode::Good),
+                    Err(err) => err,
+                };
+                if status_code.is_good() {
+                    self.links.insert(*item_id, wanted[item_id]);
+                } else {
+                    failed.push((*item_id, status_code));
+                }
+            });
+        });
+        (to_add.len() - failed.len(), to_remove.len(), failed)
+    }
+
+    /// The mode a watch item's monitored item should be in. Items linked to a trigger are
+    /// sampled so they are only reported along with their trigger, unless they are disabled.
+    fn wanted_mode(&self, item: &WatchItem) -> MonitoringMode {
+        match item.monitoring_mode {
+            MonitoringMode::Disabled => MonitoringMode::Disabled,
+            _ if self.links.contains_key(&item.item_id) => MonitoringMode::Sampling,
+            mode => mode,
+        }
+    }
+
+    /// Makes the monitoring modes on the server match those wanted for the watch items with
+    /// SetMonitoringMode, and returns the mode each changed item is now in or why it couldn't
+    /// be changed
+    fn update_modes(
+        &mut self,
+        session: &mut Session,
+        items: &[WatchItem],
+    ) -> Vec<(u32, Result<MonitoringMode, StatusCode>)> {
+        let changes = items
+            .iter()
+            .filter_map(|item| {
+                let (monitored_item_id, _) = self.monitored_items.get(&item.item_id)?;
+                let mode = self.wanted_mode(item);
+                if self.modes.get(&item.item_id) != Some(&mode) {
+                    Some((item.item_id, *monitored_item_id, mode))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut results = Vec::new();
+        for mode in &[
+            MonitoringMode::Disabled,
+            MonitoringMode::Sampling,
+            MonitoringMode::Reporting,
+        ] {
+            let (item_ids, ids): (Vec<u32>, Vec<u32>) = changes
+                .iter()
+                .filter(|(_, _, m)| m == mode)
+                .map(|(item_id, id, _)| (*item_id, *id))
+                .unzip();
+            if ids.is_empty() {
+                continue;
+            }
+            // Modes are recorded before the call, so a server that refuses one isn't asked
+            // again on every update
+            for item_id in item_ids.iter() {
+                self.modes.insert(*item_id, *mode);
+            }
+            let status_codes = session.set_monitoring_mode(self.subscription_id, *mode, &ids);
+            item_ids.iter().enumerate().for_each(|(i, item_id)| {
+                let status_code = match status_codes {
+                    Ok(ref status_codes) => {
+                        status_codes.get(i).cloned().unwrap_or(StatusCode::Good)
+                    }
+                    Err(err) => err,
+                };
+                if status_code.is_good() {
+                    results.push((*item_id, Ok(*mode)));
+                } else {
+                    results.push((*item_id, Err(status_code)));
+                }
+            });
+        }
+        results
+    }
+
+    /// Deletes the monitored items of watch items
+    fn remove_items(&mut self, session: &mut Session, item_ids: &[u32]) -> Result<(), StatusCode> {
+        let monitored_item_ids = item_ids
+            .iter()
+            .filter_map(|item_id| {
+                self.modes.remove(item_id);
+                self.monitored_items.remove(item_id)
+            })
+            .map(|(monitored_item_id, _)| monitored_item_id)
+            .collect::<Vec<_>>();
+        if monitored_item_ids.is_empty() {
+            return Ok(());
+        }
+        {
+            let mut item_ids = self.item_ids.lock().unwrap();
+            monitored_item_ids.iter().for_each(|id| {
+                item_ids.remove(id);
+            });
+        }
+        session
+            .delete_monitored_items(self.subscription_id, &monitored_item_ids)
+            .map(|_| ())
+    }
 }
 
 pub struct Model {
     connection: Arc<Mutex<Connection>>,
     app: ActorRef<AppMessage>,
+    /// Every watch list by id, so their subscriptions can be created again after reconnecting
+    watch_lists: HashMap<u32, WatchList>,
+    /// The pollers of the watch lists that use polling, by watch list id
+    pollers: HashMap<u32, Poller>,
+    /// The subscriptions of the watch lists that use one, by watch list id
+    subscriptions: HashMap<u32, WatchSubscription>,
+    /// The watch items whose units and ranges have been read
+    known_items: HashSet<u32>,
+    /// Every event monitor by id, so their subscriptions can be created again after
+    /// reconnecting
+    event_monitors: HashMap<u32, EventMonitor>,
+    /// The subscription id of each event monitor that has one
+    event_subscriptions: HashMap<u32, u32>,
 }
 
 impl ActorFactoryArgs<ActorRef<AppMessage>> for Model {
@@ -48,6 +647,12 @@ impl ActorFactoryArgs<ActorRef<AppMessage>> for Model {
         Self {
             connection: Arc::new(Mutex::new(Connection::default())),
             app,
+            watch_lists: HashMap::new(),
+            pollers: HashMap::new(),
+            subscriptions: HashMap::new(),
+            known_items: HashSet::new(),
+            event_monitors: HashMap::new(),
+            event_subscriptions: HashMap::new(),
         }
     }
 }
@@ -57,11 +662,43 @@ impl Actor for Model {
 
     fn recv(&mut self, _ctx: &Context<ModelMessage>, msg: ModelMessage, _sender: Sender) {
         match msg {
-            ModelMessage::Connect(endpoint_url, security_policy, message_security_mode) => {
-                self.connect(&endpoint_url, security_policy, message_security_mode)
+            ModelMessage::Connect(profile) => self.connect(profile),
+            ModelMessage::SetPreferredLocales(preferred_locales) => {
+                self.set_preferred_locales(preferred_locales)
             }
             ModelMessage::Disconnect => self.disconnect(),
             ModelMessage::BrowseNode(parent_node_id) => self.browse_node(parent_node_id),
+            ModelMessage::BrowseGraph(root_node_id, depth, reference_type_id) => {
+                self.browse_graph(root_node_id, depth, reference_type_id)
+            }
+            ModelMessage::ReadAttributes(node_id) => self.read_attributes(node_id),
+            ModelMessage::ReadTranslations(node_id) => self.read_translations(node_id),
+            ModelMessage::ReadWriteInfo(node_id) => self.read_write_info(node_id),
+            ModelMessage::WriteValue(node_id, value, index_range) => {
+                self.write_value(node_id, value, index_range)
+            }
+            ModelMessage::WriteAttribute(node_id, attribute_id, value) => {
+                self.write_attribute(node_id, attribute_id, value, None)
+            }
+            ModelMessage::BulkRead(bulk_read) => self.bulk_read(bulk_read),
+            ModelMessage::SetWatchList(watch_list) => self.set_watch_list(watch_list),
+            ModelMessage::RemoveWatchList(id) => self.remove_watch_list(id),
+            ModelMessage::BrowseEventTypes => self.browse_event_types(),
+            ModelMessage::SetEventMonitor(event_monitor) => self.set_event_monitor(event_monitor),
+            ModelMessage::RemoveEventMonitor(id) => self.remove_event_monitor(id),
+            ModelMessage::ConditionRefresh(subscription_id) => {
+                self.condition_refresh(subscription_id)
+            }
+            ModelMessage::CallConditionMethod(call) => self.call_condition_method(call),
+            ModelMessage::SaveWatchList(watch_list, endpoint_url, path) => {
+                self.save_watch_list(watch_list, endpoint_url, path)
+            }
+            ModelMessage::LoadWatchList(path) => self.load_watch_list(path),
+            ModelMessage::ReadSubscriptionDiagnostics => self.read_subscription_diagnostics(),
+            ModelMessage::ReadMethodInfo(object_id, method_id) => {
+                self.read_method_info(object_id, method_id)
+            }
+            ModelMessage::CallMethod(call) => self.call_method(call),
         }
     }
 }
@@ -78,30 +715,21 @@ impl Model {
         self.app.tell(message, None);
     }
 
-    pub fn connect(
-        &self,
-        endpoint_url: &str,
-        security_policy: SecurityPolicy,
-        message_security_mode: MessageSecurityMode,
-    ) {
-        let user_token_policy = UserTokenPolicy::anonymous();
-        let identity_token = IdentityToken::Anonymous;
-
+    pub fn connect(&mut self, profile: ConnectionProfile) {
         self.log(format!(
             "Attempting to connection to endpoint \"{}\"",
-            endpoint_url
+            profile.endpoint_url
         ));
+        if !profile.preferred_locales.is_empty() {
+            self.log(format!(
+                "Preferred locales are {}",
+                profile.preferred_locales.join(", ")
+            ));
+        }
 
         let mut connection = self.connection.lock().unwrap();
-        match connection.client.connect_to_endpoint(
-            (
-                endpoint_url,
-                security_policy.to_str(),
-                message_security_mode,
-                user_token_policy,
-            ),
-            identity_token,
-        ) {
+        connection.client = Connection::create_client(&profile.preferred_locales);
+        let connected = match Connection::connect_session(&mut connection.client, &profile) {
             Ok(session) => {
                 {
                     let mut session = session.write().unwrap();
@@ -113,18 +741,36 @@ impl Model {
                 }
 
                 self.log("Connection succeeded");
-                self.send_app_msg(AppMessage::Connected);
+                self.send_app_msg(AppMessage::Connected(profile.clone()));
+
+                let type_dictionary = {
+                    let mut session = session.write().unwrap();
+                    connection.max_nodes_per_read = self.read_max_nodes_per_read(&mut session);
+                    Arc::new(self.load_type_dictionary(&mut session, connection.nodes_per_read()))
+                };
+                self.send_app_msg(AppMessage::TypeDictionary(type_dictionary.clone()));
+                connection.type_dictionary = type_dictionary;
+
                 connection.session = Some(session);
+                connection.profile = Some(profile);
+                true
             }
             Err(err) => {
                 self.log(format!("Connection failed, status code = {}", err));
                 connection.session = None;
+                connection.profile = None;
                 self.send_app_msg(AppMessage::Disconnected);
+                false
             }
+        };
+        drop(connection);
+
+        if connected {
+            self.restore_subscriptions();
         }
     }
 
-    pub fn disconnect(&self) {
+    pub fn disconnect(&mut self) {
         let mut connection = self.connection.lock().unwrap();
         if let Some(ref session) = connection.session {
             let mut session = session.write().unwrap();
@@ -132,9 +778,1165 @@ impl Model {
             self.log("Disconnecting from session");
         }
         connection.session = None;
+        connection.profile = None;
+        connection.type_dictionary = Arc::new(TypeDictionary::default());
+        connection.max_nodes_per_read = 0;
+        // Another server may have different items behind the same node ids
+        self.known_items.clear();
+        // Subscriptions belong to the session
+        self.subscriptions.clear();
+        self.event_subscriptions.clear();
         self.send_app_msg(AppMessage::Disconnected);
     }
 
+    /// Reads the MaxNodesPerRead operation limit of the server. Servers that don't expose
+    /// their operation limits are treated as having none.
+    fn read_max_nodes_per_read(&self, session: &mut Session) -> usize {
+        let max_nodes_per_read = Self::read_value_id(
+            VariableId::Server_ServerCapabilities_OperationLimits_MaxNodesPerRead.into(),
+            AttributeId::Value,
+        );
+        let limit = match session.read(&[max_nodes_per_read], TimestampsToReturn::Neither, 0.0) {
+            Ok(mut values) => match values.pop().and_then(|v| v.value) {
+                Some(Variant::UInt32(limit)) => limit as usize,
+                _ => 0,
+            },
+            Err(_) => 0,
+        };
+        if limit > 0 {
+            self.log(format!("Server reads at most {} nodes per request", limit));
+        }
+        limit
+    }
+
+    /// Reads attributes of many nodes. The reads are split into requests that fit the server's
+    /// MaxNodesPerRead, and a request that fails gives its status to each of its nodes.
+    pub fn bulk_read(&self, bulk_read: BulkRead) {
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            let items = bulk_read
+                .node_ids
+                .iter()
+                .flat_map(|node_id| {
+                    bulk_read
+                        .attribute_ids
+                        .iter()
+                        .map(move |attribute_id| (node_id.clone(), *attribute_id))
+                })
+                .collect::<Vec<_>>();
+            let nodes_to_read = items
+                .iter()
+                .map(|(node_id, attribute_id)| Self::read_value_id(node_id.clone(), *attribute_id))
+                .collect::<Vec<_>>();
+            let nodes_per_request = connection.nodes_per_read();
+            let requests = (nodes_to_read.len() + nodes_per_request - 1) / nodes_per_request;
+            let values = {
+                let mut session = session.write().unwrap();
+                Self::read_chunked(
+                    &mut session,
+                    &nodes_to_read,
+                    nodes_per_request,
+                    bulk_read.timestamps_to_return,
+                    bulk_read.max_age,
+                )
+            };
+
+            let failed = values
+                .iter()
+                .filter(|v| v.status.map_or(false, |s| s.is_bad()))
+                .count();
+            self.log(format!(
+                "Read {} values in {} requests, {} failed",
+                values.len(),
+                requests,
+                failed
+            ));
+            let values = items
+                .into_iter()
+                .zip(values.into_iter())
+                .map(|((node_id, attribute_id), value)| (node_id, attribute_id, value))
+                .collect();
+            self.send_app_msg(AppMessage::BulkReadResult(BulkReadResult {
+                values,
+                requests,
+                nodes_per_request,
+            }));
+        }
+    }
+
+    /// Reads the NamespaceArray of the server, whose index of each URI is the namespace index
+    /// used in node ids
+    fn read_namespace_array(session: &mut Session) -> Result<Vec<String>, StatusCode> {
+        let namespace_array =
+            Self::read_value_id(VariableId::Server_NamespaceArray.into(), AttributeId::Value);
+        let mut values = session.read(&[namespace_array], TimestampsToReturn::Neither, 0.0)?;
+        match values.pop().and_then(|v| v.value) {
+            Some(Variant::Array(array)) => Ok(array
+                .values
+                .iter()
+                .map(|v| match v {
+                    Variant::String(uri) => uri.as_ref().to_string(),
+                    _ => String::new(),
+                })
+                .collect()),
+            _ => Err(StatusCode::BadTypeMismatch),
+        }
+    }
+
+    /// Saves a watch list with the URIs of its namespaces, which need the session to be known
+    pub fn save_watch_list(
+        &self,
+        watch_list: WatchList,
+        endpoint_url: Option<String>,
+        path: PathBuf,
+    ) {
+        let connection = self.connection.lock().unwrap();
+        let namespaces = match connection.session {
+            Some(ref session) => {
+                let mut session = session.write().unwrap();
+                Self::read_namespace_array(&mut session)
+                    .map_err(|err| format!("Cannot read the namespaces of the server, {}", err))
+            }
+            None => Err("Connect to the server to save watch lists".to_string()),
+        };
+        let result = namespaces
+            .and_then(|namespaces| {
+                WatchListFile::from_watch_list(&watch_list, endpoint_url, &namespaces)
+            })
+            .and_then(|file| file.save(&path));
+        match result {
+            Ok(_) => self.log(format!(
+                "Saved watch list \"{}\" with {} items to {}",
+                watch_list.name,
+                watch_list.items.len(),
+                path.display()
+            )),
+            Err(err) => self.log(format!(
+                "Cannot save watch list \"{}\", {}",
+                watch_list.name, err
+            )),
+        }
+    }
+
+    /// Opens a watch list file and checks that each of its nodes exists by reading its
+    /// NodeClass. Nodes that can't be found are logged and left out, and the rest are sent to
+    /// the app numbered in the order of the file, for it to number again.
+    pub fn load_watch_list(&self, path: PathBuf) {
+        let file = match WatchListFile::load(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                self.log(format!("Cannot open watch list, {}", err));
+                return;
+            }
+        };
+        let connection = self.connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            None => {
+                self.log("Connect to the server to open watch lists");
+                return;
+            }
+        };
+        let mut session = session.write().unwrap();
+        let namespaces = match Self::read_namespace_array(&mut session) {
+            Ok(namespaces) => namespaces,
+            Err(err) => {
+                self.log(format!(
+                    "Cannot open watch list {}, the namespaces of the server can't be read, {}",
+                    path.display(),
+                    err
+                ));
+                return;
+            }
+        };
+
+        let mut missing = Vec::new();
+        let items = file
+            .items
+            .into_iter()
+            .filter_map(|(text, item)| {
+                match watch_list_file::node_id_from_text(&text, &namespaces) {
+                    Ok(node_id) => Some(WatchItem { node_id, ..item }),
+                    Err(err) => {
+                        missing.push(format!("{} ({}), {}", item.display_name, text, err));
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        let nodes_to_read = items
+            .iter()
+            .map(|item| Self::read_value_id(item.node_id.clone(), AttributeId::NodeClass))
+            .collect::<Vec<_>>();
+        let values = Self::read_chunked(
+            &mut session,
+            &nodes_to_read,
+            connection.nodes_per_read(),
+            TimestampsToReturn::Neither,
+            0.0,
+        );
+        let items = items
+            .into_iter()
+            .zip(values.into_iter())
+            .filter_map(|(item, value)| match value.status {
+                Some(status_code) if status_code.is_bad() => {
+                    missing.push(format!(
+                        "{} ({}), {}",
+                        item.display_name, item.node_id, status_code
+                    ));
+                    None
+                }
+                _ => Some(item),
+            })
+            .collect::<Vec<_>>();
+
+        self.log(format!(
+            "Opened watch list \"{}\" from {} with {} items, {} nodes not found",
+            file.name,
+            path.display(),
+            items.len(),
+            missing.len()
+        ));
+        missing
+            .iter()
+            .for_each(|m| self.log(format!("  Not found: {}", m)));
+        let mut watch_list = WatchList::new(0, &file.name, file.acquisition);
+        watch_list.subscription = file.subscription;
+        watch_list.items = items;
+        watch_list.unlink_missing_triggers();
+        self.send_app_msg(AppMessage::WatchListLoaded(path, watch_list));
+    }
+
+    /// Starts acquiring the values of a watch list's items in the way it says, or changes what
+    /// is acquired if the watch list is already known.
+    pub fn set_watch_list(&mut self, watch_list: WatchList) {
+        self.watch_lists.insert(watch_list.id, watch_list.clone());
+        self.read_item_analog_info(&watch_list);
+        match watch_list.acquisition {
+            Acquisition::Polling(interval) => {
+                self.delete_subscription(watch_list.id);
+                if let Some(poller) = self.pollers.get(&watch_list.id) {
+                    if poller.interval == interval {
+                        *poller.watch_list.lock().unwrap() = watch_list;
+                        return;
+                    }
+                }
+                self.log(format!(
+                    "Polling watch list \"{}\" every {} ms",
+                    watch_list.name, interval
+                ));
+                let poller = Poller::start(
+                    watch_list.clone(),
+                    interval,
+                    self.connection.clone(),
+                    self.app.clone(),
+                );
+                // Replacing a poller stops the old one
+                self.pollers.insert(watch_list.id, poller);
+            }
+            Acquisition::Subscription => {
+                self.pollers.remove(&watch_list.id);
+                self.update_subscription(&watch_list);
+            }
+        }
+    }
+
+    /// Stops acquiring the values of a watch list's items and forgets it
+    pub fn remove_watch_list(&mut self, watch_list_id: u32) {
+        self.watch_lists.remove(&watch_list_id);
+        self.pollers.remove(&watch_list_id);
+        self.delete_subscription(watch_list_id);
+    }
+
+    /// Makes the subscription of a watch list match its settings and items. The subscription is
+    /// created if the watch list doesn't have one yet, or has its publishing parameters changed,
+    /// then monitored items are created for new items and deleted for items that have been
+    /// removed.
+    fn update_subscription(&mut self, watch_list: &WatchList) {
+        let connection = self.connection.clone();
+        let connection = connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            // The subscription is created when connected
+            None => return,
+        };
+        let mut session = session.write().unwrap();
+
+        let publishing_result = if !self.subscriptions.contains_key(&watch_list.id) {
+            match WatchSubscription::create(&mut session, watch_list.subscription, self.app.clone())
+            {
+                Ok(subscription) => {
+                    self.log(format!(
+                        "Created subscription {} for watch list \"{}\"",
+                        subscription.subscription_id, watch_list.name
+                    ));
+                    self.subscriptions.insert(watch_list.id, subscription);
+                    Some(Ok(()))
+                }
+                Err(err) => {
+                    self.log(format!(
+                        "Cannot create a subscription for watch list \"{}\", status code = {}",
+                        watch_list.name, err
+                    ));
+                    self.send_app_msg(AppMessage::SubscriptionRevised(watch_list.id, Err(err)));
+                    return;
+                }
+            }
+        } else {
+            let subscription = self.subscriptions.get_mut(&watch_list.id).unwrap();
+            if subscription.settings != watch_list.subscription {
+                Some(subscription.modify(&mut session, watch_list.subscription))
+            } else {
+                None
+            }
+        };
+
+        let subscription = self.subscriptions.get_mut(&watch_list.id).unwrap();
+        let subscription_id = subscription.subscription_id;
+        let publishing_result = publishing_result.map(|result| {
+            result.and_then(|_| {
+                subscription
+                    .revised(&session)
+                    .ok_or(StatusCode::BadSubscriptionIdInvalid)
+            })
+        });
+        if let Some(Ok(ref revised)) = publishing_result {
+            subscription
+                .health
+                .lock()
+                .unwrap()
+                .set_keep_alive_time(revised.publishing_interval, revised.max_keep_alive_count);
+        }
+        let removed = subscription
+            .monitored_items
+            .keys()
+            .filter(|item_id| !watch_list.items.iter().any(|i| i.item_id == **item_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        let added = watch_list
+            .items
+            .iter()
+            .filter(|i| !subscription.monitored_items.contains_key(&i.item_id))
+            .collect::<Vec<_>>();
+        let modified = watch_list
+            .items
+            .iter()
+            .filter(|i| {
+                subscription
+                    .monitored_items
+                    .get(&i.item_id)
+                    .map_or(false, |(_, settings)| *settings != i.monitoring)
+            })
+            .collect::<Vec<_>>();
+        let remove_result = subscription.remove_items(&mut session, &removed);
+        let created = if added.is_empty() {
+            Vec::new()
+        } else {
+            subscription.add_items(&mut session, &added)
+        };
+        let changed = if modified.is_empty() {
+            Vec::new()
+        } else {
+            subscription.modify_items(&mut session, &modified)
+        };
+        let (linked, unlinked, not_linked) =
+            subscription.update_links(&mut session, &watch_list.items);
+        let mut modes = subscription.update_modes(&mut session, &watch_list.items);
+        // New items show the mode they were created in unless it has just changed
+        created
+            .iter()
+            .filter(|(item_id, result)| {
+                result.is_ok() && !modes.iter().any(|(id, _)| id == item_id)
+            })
+            .for_each(|(item_id, _)| {
+                if let Some(mode) = subscription.modes.get(item_id) {
+                    modes.push((*item_id, Ok(*mode)));
+                }
+            });
+
+        match publishing_result {
+            Some(Ok(revised)) => {
+                self.send_app_msg(AppMessage::SubscriptionRevised(watch_list.id, Ok(revised)))
+            }
+            Some(Err(err)) => {
+                self.log(format!(
+                    "Cannot change the publishing parameters of subscription {}, status code = {}",
+                    subscription_id, err
+                ));
+                self.send_app_msg(AppMessage::SubscriptionRevised(watch_list.id, Err(err)));
+            }
+            None => {}
+        }
+        if let Err(err) = remove_result {
+            self.log(format!(
+                "Cannot delete {} monitored items from subscription {}, status code = {}",
+                removed.len(),
+                subscription_id,
+                err
+            ));
+        } else if !removed.is_empty() {
+            self.log(format!(
+                "Deleted {} monitored items from subscription {}",
+                removed.len(),
+                subscription_id
+            ));
+        }
+        // Items that couldn't be monitored show why in place of a value
+        let failed = created
+            .iter()
+            .filter_map(|(item_id, result)| match result {
+                Err(status_code) => Some((
+                    *item_id,
+                    DataValue {
+                        status: Some(*status_code),
+                        ..Default::default()
+                    },
+                )),
+                Ok(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if !created.is_empty() {
+            self.log(format!(
+                "Created {} monitored items on subscription {}, {} failed",
+                created.len() - failed.len(),
+                subscription_id,
+                failed.len()
+            ));
+        }
+        if !changed.is_empty() {
+            self.log(format!(
+                "Modified {} monitored items on subscription {}, {} rejected",
+                changed.len(),
+                subscription_id,
+                changed.iter().filter(|(_, r)| r.is_err()).count()
+            ));
+        }
+        if linked > 0 || unlinked > 0 {
+            self.log(format!(
+                "Linked {} and unlinked {} monitored items to triggers on subscription {}",
+                linked, unlinked, subscription_id
+            ));
+        }
+        not_linked.iter().for_each(|(item_id, status_code)| {
+            let name = watch_list
+                .items
+                .iter()
+                .find(|i| i.item_id == *item_id)
+                .map_or("", |i| i.display_name.as_str());
+            self.log(format!(
+                "Cannot link \"{}\" to its trigger, status code = {}",
+                name, status_code
+            ));
+        });
+        let rejected_modes = modes.iter().filter(|(_, r)| r.is_err()).count();
+        if rejected_modes > 0 {
+            self.log(format!(
+                "Cannot change the monitoring mode of {} monitored items on subscription {}",
+                rejected_modes, subscription_id
+            ));
+        }
+        if !failed.is_empty() {
+            self.send_app_msg(AppMessage::ItemValues(failed));
+        }
+        if !modes.is_empty() {
+            self.send_app_msg(AppMessage::MonitoringModes(modes));
+        }
+        let revised = created.into_iter().chain(changed).collect::<Vec<_>>();
+        if !revised.is_empty() {
+            self.send_app_msg(AppMessage::MonitoringRevised(revised));
+        }
+    }
+
+    /// Sends the notifications each watch list's subscription has received, alongside its
+    /// entry in the server's SubscriptionDiagnosticsArray. Servers only fill the array while
+    /// their diagnostics are enabled.
+    pub fn read_subscription_diagnostics(&self) {
+        let connection = self.connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            None => return,
+        };
+        let server = {
+            let mut session = session.write().unwrap();
+            let array = Self::read_value_id(
+                VariableId::Server_ServerDiagnostics_SubscriptionDiagnosticsArray.into(),
+                AttributeId::Value,
+            );
+            match session.read(&[array], TimestampsToReturn::Neither, 0.0) {
+                Ok(mut values) => match values.pop() {
+                    Some(DataValue {
+                        value: Some(Variant::Array(array)),
+                        ..
+                    }) => {
+                        let decoding_options = DecodingOptions::default();
+                        Ok(array
+                            .values
+                            .iter()
+                            .filter_map(|v| match v {
+                                Variant::ExtensionObject(v) => v
+                                    .decode_inner::<SubscriptionDiagnosticsDataType>(
+                                        &decoding_options,
+                                    )
+                                    .ok(),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>())
+                    }
+                    Some(DataValue {
+                        status: Some(status_code),
+                        ..
+                    }) if status_code.is_bad() => {
+                        Err(format!("Not available, status code = {}", status_code))
+                    }
+                    _ => Err("Not available".to_string()),
+                },
+                Err(err) => Err(format!("Not available, status code = {}", err)),
+            }
+        };
+        let diagnostics = self
+            .subscriptions
+            .iter()
+            .map(|(watch_list_id, subscription)| SubscriptionDiagnostics {
+                watch_list_id: *watch_list_id,
+                subscription_id: subscription.subscription_id,
+                client: subscription.health.lock().unwrap().clone(),
+                server: match server {
+                    Ok(ref all) => all
+                        .iter()
+                        .find(|d| d.subscription_id == subscription.subscription_id)
+                        .cloned()
+                        .ok_or_else(|| {
+                            "Not listed, the server's diagnostics may be disabled".to_string()
+                        }),
+                    Err(ref err) => Err(err.clone()),
+                },
+            })
+            .collect();
+        self.send_app_msg(AppMessage::SubscriptionDiagnostics(diagnostics));
+    }
+
+    /// Deletes the subscription of a watch list, if it has one
+    fn delete_subscription(&mut self, watch_list_id: u32) {
+        let subscription = match self.subscriptions.remove(&watch_list_id) {
+            Some(subscription) => subscription,
+            None => return,
+        };
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            let mut session = session.write().unwrap();
+            match session.delete_subscription(subscription.subscription_id) {
+                Ok(_) => self.log(format!(
+                    "Deleted subscription {}",
+                    subscription.subscription_id
+                )),
+                Err(err) => self.log(format!(
+                    "Cannot delete subscription {}, status code = {}",
+                    subscription.subscription_id, err
+                )),
+            }
+        }
+    }
+
+    /// Creates the subscriptions of the watch lists and event monitors again on a new session
+    fn restore_subscriptions(&mut self) {
+        self.subscriptions.clear();
+        let watch_lists = self.watch_lists.values().cloned().collect::<Vec<_>>();
+        watch_lists.iter().for_each(|watch_list| {
+            // Items added while disconnected haven't had their units read
+            self.read_item_analog_info(watch_list);
+            if watch_list.acquisition == Acquisition::Subscription {
+                self.update_subscription(watch_list);
+            }
+        });
+        self.event_subscriptions.clear();
+        let event_monitors = self.event_monitors.values().cloned().collect::<Vec<_>>();
+        event_monitors
+            .iter()
+            .for_each(|event_monitor| self.create_event_subscription(event_monitor));
+    }
+
+    /// Subscribes to the events of a monitor's node with its filter, replacing the subscription
+    /// it had before
+    pub fn set_event_monitor(&mut self, event_monitor: EventMonitor) {
+        self.event_monitors
+            .insert(event_monitor.id, event_monitor.clone());
+        self.delete_event_subscription(event_monitor.id);
+        self.create_event_subscription(&event_monitor);
+    }
+
+    /// Stops receiving the events of a monitor and forgets it
+    pub fn remove_event_monitor(&mut self, event_monitor_id: u32) {
+        self.event_monitors.remove(&event_monitor_id);
+        self.delete_event_subscription(event_monitor_id);
+    }
+
+    /// Creates a subscription with one monitored item on the EventNotifier attribute of the
+    /// monitor's node. Its events are sent to the app with the monitor's id, and the app is told
+    /// whether the subscription was created.
+    fn create_event_subscription(&mut self, event_monitor: &EventMonitor) {
+        let connection = self.connection.clone();
+        let connection = connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            // The subscription is created when connected
+            None => return,
+        };
+        let mut session = session.write().unwrap();
+
+        let result = Self::check_event_notifier(&mut session, &event_monitor.node_id)
+            .and_then(|_| {
+                let monitor_id = event_monitor.id;
+                let app = self.app.clone();
+                let settings = SubscriptionSettings::default();
+                session.create_subscription(
+                    settings.publishing_interval,
+                    settings.lifetime_count,
+                    settings.max_keep_alive_count,
+                    settings.max_notifications_per_publish,
+                    settings.priority,
+                    settings.publishing_enabled,
+                    EventCallback::new(move |events| {
+                        let events = events
+                            .events
+                            .iter()
+                            .flatten()
+                            .map(|e| Event {
+                                monitor_id,
+                                fields: e.event_fields.clone().unwrap_or_default(),
+                            })
+                            .collect::<Vec<_>>();
+                        if !events.is_empty() {
+                            app.tell(AppMessage::Events(events), None);
+                        }
+                    }),
+                )
+            })
+            .and_then(|subscription_id| {
+                let item_to_create = MonitoredItemCreateRequest {
+                    item_to_monitor: ReadValueId {
+                        node_id: event_monitor.node_id.clone(),
+                        attribute_id: AttributeId::EventNotifier as u32,
+                        index_range: UAString::null(),
+                        data_encoding: QualifiedName::null(),
+                    },
+                    monitoring_mode: MonitoringMode::Reporting,
+                    requested_parameters: event_monitor
+                        .monitoring_parameters(event_monitor.id, EVENT_QUEUE_SIZE),
+                };
+                let status_code = match session.create_monitored_items(
+                    subscription_id,
+                    TimestampsToReturn::Neither,
+                    &[item_to_create],
+                ) {
+                    Ok(results) => results
+                        .first()
+                        .map_or(StatusCode::BadUnexpectedError, |r| r.status_code),
+                    Err(err) => err,
+                };
+                if status_code.is_good() {
+                    Ok(subscription_id)
+                } else {
+                    let _ = session.delete_subscription(subscription_id);
+                    Err(status_code)
+                }
+            });
+
+        match result {
+            Ok(subscription_id) => {
+                self.log(format!(
+                    "Created subscription {} for the events of \"{}\"",
+                    subscription_id, event_monitor.node_name
+                ));
+                self.event_subscriptions
+                    .insert(event_monitor.id, subscription_id);
+            }
+            Err(err) => self.log(format!(
+                "Cannot subscribe to the events of \"{}\", status code = {}",
+                event_monitor.node_name, err
+            )),
+        }
+        self.send_app_msg(AppMessage::EventMonitorCreated(event_monitor.id, result));
+    }
+
+    /// Checks that the SubscribeToEvents bit of a node's EventNotifier attribute is set
+    fn check_event_notifier(session: &mut Session, node_id: &NodeId) -> Result<(), StatusCode> {
+        let read_value_id = Self::read_value_id(node_id.clone(), AttributeId::EventNotifier);
+        let mut values = session.read(&[read_value_id], TimestampsToReturn::Neither, 0.0)?;
+        match values.pop() {
+            Some(DataValue {
+                value: Some(Variant::Byte(event_notifier)),
+                ..
+            }) if event_notifier & 1 != 0 => Ok(()),
+            Some(DataValue {
+                status: Some(status_code),
+                ..
+            }) if status_code.is_bad() => Err(status_code),
+            _ => Err(StatusCode::BadNotSupported),
+        }
+    }
+
+    fn delete_event_subscription(&mut self, event_monitor_id: u32) {
+        let subscription_id = match self.event_subscriptions.remove(&event_monitor_id) {
+            Some(subscription_id) => subscription_id,
+            None => return,
+        };
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            let mut session = session.write().unwrap();
+            match session.delete_subscription(subscription_id) {
+                Ok(_) => self.log(format!("Deleted subscription {}", subscription_id)),
+                Err(err) => self.log(format!(
+                    "Cannot delete subscription {}, status code = {}",
+                    subscription_id, err
+                )),
+            }
+        }
+    }
+
+    /// Calls ConditionRefresh so the server sends the state of every retained condition to the
+    /// subscription, between a RefreshStartEvent and a RefreshEndEvent
+    pub fn condition_refresh(&self, subscription_id: u32) {
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            let mut session = session.write().unwrap();
+            let request = CallMethodRequest {
+                object_id: ObjectTypeId::ConditionType.into(),
+                method_id: MethodId::ConditionType_ConditionRefresh.into(),
+                input_arguments: Some(vec![Variant::UInt32(subscription_id)]),
+            };
+            let status_code = match session.call(request) {
+                Ok(result) => result.status_code,
+                Err(err) => err,
+            };
+            self.log(format!(
+                "ConditionRefresh of subscription {} finished, status code = {}",
+                subscription_id, status_code
+            ));
+        }
+    }
+
+    /// Calls a method of a condition and tells the app the result. The shelving methods are
+    /// called on the ShelvingState of the condition, which is found by its browse name.
+    pub fn call_condition_method(&self, call: ConditionCall) {
+        let connection = self.connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            None => return,
+        };
+        let mut session = session.write().unwrap();
+
+        let object_id = if call.action.is_shelving() {
+            Self::shelving_state_id(&mut session, &call.condition_id)
+        } else {
+            Ok(call.condition_id.clone())
+        };
+        let status_code = object_id
+            .and_then(|object_id| {
+                let request = CallMethodRequest {
+                    object_id,
+                    method_id: call.action.method_id(),
+                    input_arguments: Some(call.action.input_arguments(&call.event_id)),
+                };
+                session.call(request)
+            })
+            .map_or_else(|err| err, |result| result.status_code);
+        self.log(format!(
+            "{} of condition \"{}\" finished, status code = {}",
+            call.action.name(),
+            call.name,
+            status_code
+        ));
+        self.send_app_msg(AppMessage::ConditionCallResult(call, status_code));
+    }
+
+    fn shelving_state_id(
+        session: &mut Session,
+        condition_id: &NodeId,
+    ) -> Result<NodeId, StatusCode> {
+        let browse_path = BrowsePath {
+            starting_node: condition_id.clone(),
+            relative_path: RelativePath {
+                elements: Some(vec![RelativePathElement {
+                    reference_type_id: ReferenceTypeId::HasComponent.into(),
+                    is_inverse: false,
+                    include_subtypes: true,
+                    target_name: QualifiedName::new(0, "ShelvingState"),
+                }]),
+            },
+        };
+        let result = session
+            .translate_browse_paths_to_node_ids(&[browse_path])?
+            .pop()
+            .ok_or(StatusCode::BadUnexpectedError)?;
+        if result.status_code.is_bad() {
+            return Err(result.status_code);
+        }
+        result
+            .targets
+            .and_then(|targets| targets.into_iter().next())
+            .map(|target| target.target_id.node_id)
+            .ok_or(StatusCode::BadNoMatch)
+    }
+
+    /// Finds every subtype of BaseEventType and the fields it declares, i.e. the variables it
+    /// aggregates and the properties of those variables
+    pub fn browse_event_types(&self) {
+        let connection = self.connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            None => return,
+        };
+        let mut session = session.write().unwrap();
+
+        let base_event_type_id: NodeId = ObjectTypeId::BaseEventType.into();
+        let mut event_types = vec![EventType {
+            node_id: base_event_type_id.clone(),
+            name: "BaseEventType".to_string(),
+            parent: None,
+            fields: Vec::new(),
+        }];
+        let mut frontier = vec![base_event_type_id];
+        while !frontier.is_empty() {
+            let results =
+                Self::browse_many(&mut session, &frontier, ReferenceTypeId::HasSubtype, 0x0);
+            frontier = Vec::new();
+            results.into_iter().for_each(|(parent, references)| {
+                references.into_iter().for_each(|r| {
+                    let node_id = r.node_id.node_id;
+                    if !event_types.iter().any(|t| t.node_id == node_id) {
+                        event_types.push(EventType {
+                            node_id: node_id.clone(),
+                            name: r.browse_name.name.to_string(),
+                            parent: Some(parent.clone()),
+                            fields: Vec::new(),
+                        });
+                        frontier.push(node_id);
+                    }
+                });
+            });
+        }
+
+        // Variables of the types, then the properties of those variables, e.g. EnabledState/Id
+        let type_ids = event_types
+            .iter()
+            .map(|t| t.node_id.clone())
+            .collect::<Vec<_>>();
+        let mut variables = Vec::new();
+        Self::browse_many(
+            &mut session,
+            &type_ids,
+            ReferenceTypeId::Aggregates,
+            NodeClass::Variable as u32,
+        )
+        .into_iter()
+        .for_each(|(type_id, references)| {
+            let event_type = event_types
+                .iter_mut()
+                .find(|t| t.node_id == type_id)
+                .unwrap();
+            references.into_iter().for_each(|r| {
+                event_type.fields.push(vec![r.browse_name.clone()]);
+                variables.push((r.node_id.node_id, type_id.clone(), r.browse_name));
+            });
+        });
+        let variable_ids = variables
+            .iter()
+            .map(|(node_id, _, _)| node_id.clone())
+            .collect::<Vec<_>>();
+        Self::browse_many(
+            &mut session,
+            &variable_ids,
+            ReferenceTypeId::HasProperty,
+            NodeClass::Variable as u32,
+        )
+        .into_iter()
+        .for_each(|(variable_id, references)| {
+            if let Some((_, type_id, browse_name)) = variables
+                .iter()
+                .find(|(node_id, _, _)| *node_id == variable_id)
+            {
+                let event_type = event_types
+                    .iter_mut()
+                    .find(|t| t.node_id == *type_id)
+                    .unwrap();
+                references.into_iter().for_each(|r| {
+                    event_type
+                        .fields
+                        .push(vec![browse_name.clone(), r.browse_name]);
+                });
+            }
+        });
+
+        self.log(format!("Found {} event types", event_types.len()));
+        self.send_app_msg(AppMessage::EventTypes(event_types));
+    }
+
+    /// Browses forward references of a type (and its subtypes) from many nodes, a chunk at a
+    /// time, and returns the references found from each node
+    fn browse_many(
+        session: &mut Session,
+        node_ids: &[NodeId],
+        reference_type_id: ReferenceTypeId,
+        node_class_mask: u32,
+    ) -> Vec<(NodeId, Vec<ReferenceDescription>)> {
+        let browse_descriptions = node_ids
+            .iter()
+            .map(|node_id| BrowseDescription {
+                node_id: node_id.clone(),
+                browse_direction: BrowseDirection::Forward,
+                reference_type_id: reference_type_id.into(),
+                include_subtypes: true,
+                node_class_mask,
+                result_mask: 0x3f,
+            })
+            .collect::<Vec<_>>();
+        Self::browse_chunked(session, &browse_descriptions)
+    }
+
+    /// Browses in requests of at most `NODES_PER_REQUEST` nodes, following continuation points
+    /// with BrowseNext until every reference has been returned. Nodes the server could not
+    /// browse, or whose references could not all be fetched, are left out of the results.
+    fn browse_chunked(
+        session: &mut Session,
+        browse_descriptions: &[BrowseDescription],
+    ) -> Vec<(NodeId, Vec<ReferenceDescription>)> {
+        let mut found = Vec::with_capacity(browse_descriptions.len());
+        browse_descriptions
+            .chunks(NODES_PER_REQUEST)
+            .for_each(|chunk| {
+                let retry = Self::browse_chunk(session, chunk, &mut found);
+                // The server ran out of continuation points for a request this size, so the
+                // nodes it could not browse are tried again one at a time
+                retry.into_iter().for_each(|description| {
+                    let _ =
+                        Self::browse_chunk(session, std::slice::from_ref(description), &mut found);
+                });
+            });
+        found
+    }
+
+    /// Browses the nodes in one request, adding the references of each node to `found`.
+    /// Returns the nodes that failed with BadNoContinuationPoints, which may succeed when
+    /// browsed in a smaller request.
+    fn browse_chunk<'a>(
+        session: &mut Session,
+        chunk: &'a [BrowseDescription],
+        found: &mut Vec<(NodeId, Vec<ReferenceDescription>)>,
+    ) -> Vec<&'a BrowseDescription> {
+        let mut retry = Vec::new();
+        let results = match session.browse(chunk) {
+            Ok(Some(results)) => results,
+            _ => return retry,
+        };
+        chunk.iter().zip(results).for_each(|(description, result)| {
+            if result.status_code == StatusCode::BadNoContinuationPoints && chunk.len() > 1 {
+                retry.push(description);
+                return;
+            } else if result.status_code.is_bad() {
+                return;
+            }
+            let mut references = result.references.unwrap_or_default();
+            let mut continuation_point = result.continuation_point;
+            while !continuation_point.is_null() {
+                let result = match session.browse_next(false, &[continuation_point.clone()]) {
+                    Ok(Some(results)) => results.into_iter().next(),
+                    _ => None,
+                };
+                match result {
+                    Some(result) if !result.status_code.is_bad() => {
+                        references.extend(result.references.unwrap_or_default());
+                        continuation_point = result.continuation_point;
+                    }
+                    _ => {
+                        // Release the continuation point so the server can reuse it
+                        let _ = session.browse_next(true, &[continuation_point]);
+                        return;
+                    }
+                }
+            }
+            found.push((description.node_id.clone(), references));
+        });
+        retry
+    }
+
+    /// Reads the DataType of watch items and the units and ranges of those that are analog
+    /// items, so the app can show their values with enumeration names and units
+    fn read_item_analog_info(&mut self, watch_list: &WatchList) {
+        let new_items = watch_list
+            .items
+            .iter()
+            .filter(|i| !self.known_items.contains(&i.item_id))
+            .collect::<Vec<_>>();
+        if new_items.is_empty() {
+            return;
+        }
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            let mut session = session.write().unwrap();
+            let nodes_to_read = new_items
+                .iter()
+                .map(|i| Self::read_value_id(i.node_id.clone(), AttributeId::DataType))
+                .collect::<Vec<_>>();
+            let data_types = Self::read_chunked(
+                &mut session,
+                &nodes_to_read,
+                connection.nodes_per_read(),
+                TimestampsToReturn::Neither,
+                0.0,
+            )
+            .into_iter()
+            .zip(new_items.iter())
+            .filter_map(|(value, i)| match value.value {
+                Some(Variant::NodeId(data_type)) => Some((i.item_id, *data_type)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+            let mut analog_info = Vec::new();
+            let mut read_items = Vec::new();
+            new_items.iter().for_each(|i| {
+                match Self::read_analog_info(&mut session, &i.node_id) {
+                    Ok(Some(info)) => analog_info.push((i.item_id, info)),
+                    Ok(None) => {}
+                    Err(_) => return,
+                }
+                // Items whose info couldn't be read are tried again when the watch list next
+                // changes or the app reconnects
+                if data_types.iter().any(|(item_id, _)| *item_id == i.item_id) {
+                    read_items.push(i.item_id);
+                }
+            });
+            if !data_types.is_empty() {
+                self.send_app_msg(AppMessage::ItemDataTypes(data_types));
+            }
+            if !analog_info.is_empty() {
+                self.send_app_msg(AppMessage::ItemAnalogInfo(analog_info));
+            }
+            self.known_items.extend(read_items);
+        }
+    }
+
+    /// The body of a poller's thread. Polls are scheduled from when they were due rather than
+    /// when the last one finished so jitter doesn't accumulate, but polls that were missed while
+    /// a slow one was in progress are skipped rather than made up.
+    fn poll(
+        watch_list: Arc<Mutex<WatchList>>,
+        interval: Duration,
+        stop: Arc<AtomicBool>,
+        connection: Arc<Mutex<Connection>>,
+        app: ActorRef<AppMessage>,
+    ) {
+        let mut statistics = PollStatistics::default();
+        let mut last_start: Option<Instant> = None;
+        let mut next_poll = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            let start = Instant::now();
+            if start < next_poll {
+                // Sleep in short steps so a stop is noticed promptly
+                thread::sleep((next_poll - start).min(Duration::from_millis(100)));
+                continue;
+            }
+
+            let (watch_list_id, items) = {
+                let watch_list = watch_list.lock().unwrap();
+                let items = watch_list
+                    .items
+                    .iter()
+                    .map(|i| (i.item_id, i.node_id.clone()))
+                    .collect::<Vec<_>>();
+                (watch_list.id, items)
+            };
+            let values = if items.is_empty() {
+                None
+            } else {
+                let connection = connection.lock().unwrap();
+                connection.session.as_ref().map(|session| {
+                    let nodes_to_read = items
+                        .iter()
+                        .map(|(_, node_id)| {
+                            Self::read_value_id(node_id.clone(), AttributeId::Value)
+                        })
+                        .collect::<Vec<_>>();
+                    let mut session = session.write().unwrap();
+                    Self::read_chunked(
+                        &mut session,
+                        &nodes_to_read,
+                        connection.nodes_per_read(),
+                        TimestampsToReturn::Both,
+                        0.0,
+                    )
+                })
+            };
+
+            match values {
+                Some(values) => {
+                    statistics.record(start.elapsed(), last_start.map(|l| start - l), interval);
+                    last_start = Some(start);
+                    let item_values = items
+                        .into_iter()
+                        .map(|(item_id, _)| item_id)
+                        .zip(values.into_iter())
+                        .collect();
+                    app.tell(AppMessage::ItemValues(item_values), None);
+                    app.tell(
+                        AppMessage::PollStatistics(watch_list_id, statistics.clone()),
+                        None,
+                    );
+                }
+                // Nothing was read so the next poll has no period to measure
+                None => last_start = None,
+            }
+
+            next_poll += interval;
+            let now = Instant::now();
+            if next_poll < now {
+                next_poll = now;
+            }
+        }
+    }
+
+    /// Reads values in requests of at most `nodes_per_request` nodes. A request that fails gives
+    /// its status to each of its nodes, so there is always one value per node read.
+    fn read_chunked(
+        session: &mut Session,
+        nodes_to_read: &[ReadValueId],
+        nodes_per_request: usize,
+        timestamps_to_return: TimestampsToReturn,
+        max_age: f64,
+    ) -> Vec<DataValue> {
+        let mut values = Vec::with_capacity(nodes_to_read.len());
+        nodes_to_read.chunks(nodes_per_request).for_each(|chunk| {
+            let status_code = match session.read(chunk, timestamps_to_return, max_age) {
+                Ok(results) if results.len() == chunk.len() => {
+                    values.extend(results);
+                    return;
+                }
+                Ok(_) => StatusCode::BadUnexpectedError,
+                Err(err) => err,
+            };
+            values.extend(chunk.iter().map(|_| DataValue {
+                status: Some(status_code),
+                ..Default::default()
+            }));
+        });
+        values
+    }
+
+    /// Changes the preferred locales. Locales are only sent to the server when a session is
+    /// created and activated, so if there is a session it is replaced with a new one, which
+    /// causes the address space to be browsed again.
+    pub fn set_preferred_locales(&mut self, preferred_locales: Vec<String>) {
+        let profile = {
+            let connection = self.connection.lock().unwrap();
+            connection.profile.clone()
+        };
+        if let Some(mut profile) = profile {
+            self.log("Preferred locales have changed so reconnecting");
+            profile.preferred_locales = preferred_locales;
+            self.disconnect();
+            self.connect(profile);
+        }
+    }
+
     pub fn browse_node(&self, parent_node_id: NodeId) {
         let connection = self.connection.lock().unwrap();
         if let Some(ref session) = connection.session {
@@ -164,39 +1966,1341 @@ impl Model {
         }
     }
 
-    pub fn subscribe_to_items(&self, node_ids: &[String]) {
+    /// Browses outwards from the root node in both directions, following references of the
+    /// specified type (and subtypes) until the depth is reached and sends the resulting graph to
+    /// the app.
+    pub fn browse_graph(&self, root_node_id: NodeId, depth: u32, reference_type_id: NodeId) {
         let connection = self.connection.lock().unwrap();
         if let Some(ref session) = connection.session {
-            let callback = DataChangeCallback::new(|_| {
-                // TODO datachange
-                println!("datachange");
-            });
+            self.log(format!(
+                "Fetching reference graph around node {} to depth {}",
+                root_node_id, depth
+            ));
+
+            let mut session = session.write().unwrap();
+
+            let root = match Self::read_node_summary(&mut session, &root_node_id) {
+                Ok(root) => root,
+                Err(err) => {
+                    self.log(format!("Graph fetch failed, status code = {}", err));
+                    return;
+                }
+            };
+
+            let mut graph = ReferenceGraph {
+                root: root_node_id.clone(),
+                nodes: vec![root],
+                edges: Vec::new(),
+            };
+
+            let mut frontier = vec![root_node_id];
+            for current_depth in 1..=depth {
+                let browse_descriptions = frontier
+                    .iter()
+                    .map(|node_id| BrowseDescription {
+                        node_id: node_id.clone(),
+                        browse_direction: BrowseDirection::Both,
+                        reference_type_id: reference_type_id.clone(),
+                        include_subtypes: true,
+                        node_class_mask: 0x0,
+                        result_mask: 0x3f,
+                    })
+                    .collect::<Vec<_>>();
+
+                let results = Self::browse_chunked(&mut session, &browse_descriptions);
+                if results.len() < frontier.len() {
+                    self.log(format!(
+                        "Graph fetch could not browse {} of {} nodes at depth {}",
+                        frontier.len() - results.len(),
+                        frontier.len(),
+                        current_depth
+                    ));
+                }
+
+                let mut next_frontier = Vec::new();
+                results.iter().for_each(|(node_id, references)| {
+                    references.iter().for_each(|r| {
+                        let other = &r.node_id.node_id;
+                        // Only nodes on this server can be browsed further
+                        if r.node_id.server_index == 0 && graph.node(other).is_none() {
+                            graph.nodes.push(GraphNode {
+                                node_id: other.clone(),
+                                browse_name: r.browse_name.name.to_string(),
+                                display_name: r.display_name.to_string(),
+                                node_class: r.node_class,
+                                depth: current_depth,
+                            });
+                            next_frontier.push(other.clone());
+                        }
+                        let (source, target) = if r.is_forward {
+                            (node_id.clone(), other.clone())
+                        } else {
+                            (other.clone(), node_id.clone())
+                        };
+                        graph.edges.push(GraphEdge {
+                            source,
+                            target,
+                            reference_type_id: r.reference_type_id.clone(),
+                            reference_type_name: r.reference_type_id.to_string(),
+                        });
+                    });
+                });
+                frontier = next_frontier;
+                if frontier.is_empty() {
+                    break;
+                }
+            }
+
+            graph.prune_edges();
+
+            // Label the edges with the browse names of their reference types
+            let mut reference_type_ids = graph
+                .edges
+                .iter()
+                .map(|e| e.reference_type_id.clone())
+                .collect::<Vec<_>>();
+            reference_type_ids.sort_by_key(|n| n.to_string());
+            reference_type_ids.dedup();
+            let nodes_to_read = reference_type_ids
+                .iter()
+                .map(|n| Self::read_value_id(n.clone(), AttributeId::BrowseName))
+                .collect::<Vec<_>>();
+            if let Ok(values) = session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0) {
+                reference_type_ids.iter().zip(values.iter()).for_each(
+                    |(reference_type_id, value)| {
+                        if let Some(Variant::QualifiedName(ref name)) = value.value {
+                            graph
+                                .edges
+                                .iter_mut()
+                                .filter(|e| e.reference_type_id == *reference_type_id)
+                                .for_each(|e| e.reference_type_name = name.name.to_string());
+                        }
+                    },
+                );
+            }
+
+            self.send_app_msg(AppMessage::ReferenceGraph(graph));
+        }
+    }
+
+    /// Reads every attribute of a node and sends the ones the node actually has to the app
+    pub fn read_attributes(&self, node_id: NodeId) {
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            let attribute_ids = (AttributeId::NodeId as u32..=AttributeId::AccessLevelEx as u32)
+                .filter_map(|a| AttributeId::from_u32(a).ok())
+                .collect::<Vec<_>>();
+            let nodes_to_read = attribute_ids
+                .iter()
+                .map(|a| Self::read_value_id(node_id.clone(), *a))
+                .collect::<Vec<_>>();
+
+            let mut session = session.write().unwrap();
+            match session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0) {
+                Ok(values) => {
+                    let attributes = attribute_ids
+                        .into_iter()
+                        .zip(values.into_iter())
+                        .filter(|(_, v)| v.status.map_or(true, |s| s.is_good()))
+                        .collect::<Vec<_>>();
+                    let is_variable = attributes.iter().any(|(a, v)| {
+                        *a == AttributeId::NodeClass
+                            && v.value == Some(Variant::Int32(NodeClass::Variable as i32))
+                    });
+                    let analog_info = if is_variable {
+                        Self::read_analog_info(&mut session, &node_id)
+                            .ok()
+                            .flatten()
+                    } else {
+                        None
+                    };
+                    self.send_app_msg(AppMessage::NodeAttributes(node_id, attributes, analog_info));
+                }
+                Err(err) => {
+                    self.log(format!(
+                        "Cannot read attributes of node {}, status code = {}",
+                        node_id, err
+                    ));
+                }
+            }
+        }
+    }
 
-            // Create a subscription
-            let mut session = session.try_write().unwrap();
-            match session.create_subscription(1.0, 100, 100, 100, 0, true, callback) {
-                Ok(subscription_id) => {
-                    let items_to_create = node_ids
+    /// Reads the DisplayName and Description of the node in every locale the server says it
+    /// supports. Translations that the server falls back to another language for are omitted.
+    pub fn read_translations(&self, node_id: NodeId) {
+        let connection = self.connection.lock().unwrap();
+        let locales = if let Some(ref session) = connection.session {
+            let mut session = session.write().unwrap();
+            let locale_id_array = Self::read_value_id(
+                VariableId::Server_ServerCapabilities_LocaleIdArray.into(),
+                AttributeId::Value,
+            );
+            match session.read(&[locale_id_array], TimestampsToReturn::Neither, 0.0) {
+                Ok(mut values) => match values.pop().and_then(|v| v.value) {
+                    Some(Variant::Array(array)) => array
+                        .values
                         .iter()
-                        .map(|n| NodeId::from_str(n.as_ref()).unwrap())
-                        .map(|n| MonitoredItemCreateRequest {
-                            item_to_monitor: n.into(),
-                            monitoring_mode: MonitoringMode::Reporting,
-                            requested_parameters: MonitoringParameters::default(),
+                        .filter_map(|v| match v {
+                            Variant::String(locale) if !locale.is_null() => {
+                                Some(locale.as_ref().to_string())
+                            }
+                            _ => None,
                         })
-                        .collect::<Vec<MonitoredItemCreateRequest>>();
+                        .collect::<Vec<_>>(),
+                    _ => Vec::new(),
+                },
+                Err(_) => Vec::new(),
+            }
+        } else {
+            return;
+        };
 
-                    // Create monitored items on the subscription
-                    let _ = session.create_monitored_items(
-                        subscription_id,
-                        TimestampsToReturn::Both,
-                        &items_to_create,
-                    );
+        // A single locale has nothing to translate into
+        if locales.len() < 2 {
+            return;
+        }
+
+        let nodes_to_read = [
+            Self::read_value_id(node_id.clone(), AttributeId::DisplayName),
+            Self::read_value_id(node_id.clone(), AttributeId::Description),
+        ];
+        // A server may answer "de" with "de-DE" text or the reverse, so only the language has
+        // to match
+        let language = |locale: &str| {
+            locale
+                .split('-')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        };
+        let localized_text = |value: &DataValue, locale: &str| match value.value {
+            Some(Variant::LocalizedText(ref text))
+                if language(text.locale.as_ref()) == language(locale) =>
+            {
+                Some((**text).clone())
+            }
+            _ => None,
+        };
+
+        let translations = locales
+            .iter()
+            .filter_map(|locale| {
+                let session = match connection.connect_locale_session(locale) {
+                    Ok(session) => session,
+                    Err(err) => {
+                        self.log(format!(
+                            "Cannot create session for locale {}, status code = {}",
+                            locale, err
+                        ));
+                        return None;
+                    }
+                };
+                let mut session = session.write().unwrap();
+                let values = session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0);
+                session.disconnect();
+                let values = values
+                    .ok()
+                    .filter(|values| values.len() == nodes_to_read.len())?;
+                Some(Translation {
+                    locale: locale.clone(),
+                    display_name: localized_text(&values[0], locale),
+                    description: localized_text(&values[1], locale),
+                })
+            })
+            .filter(|t| t.display_name.is_some() || t.description.is_some())
+            .collect::<Vec<_>>();
+
+        self.send_app_msg(AppMessage::Translations(node_id, translations));
+    }
+
+    /// Reads what is needed to edit the value of a variable - its DataType, ValueRank, access
+    /// levels and so on, and sends it to the app so it can show a suitable editor.
+    pub fn read_write_info(&self, node_id: NodeId) {
+        let mut connection = self.connection.lock().unwrap();
+        if let Some(session) = connection.session.clone() {
+            let mut session = session.write().unwrap();
+            match Self::fetch_write_info(&mut session, &node_id, &connection.type_dictionary) {
+                Ok((write_info, enumeration)) => {
+                    // Cache an enumeration that wasn't loaded when connecting
+                    if let Some(enumeration) = enumeration {
+                        Arc::make_mut(&mut connection.type_dictionary)
+                            .insert_enumeration(enumeration);
+                        self.send_app_msg(AppMessage::TypeDictionary(
+                            connection.type_dictionary.clone(),
+                        ));
+                    }
+                    self.send_app_msg(AppMessage::WriteInfo(write_info))
                 }
-                Err(err) => {
-                    println!("Cannot create subscription, error = {}", err);
+                Err(err) => self.log(format!(
+                    "Cannot read the data type of node {}, status code = {}",
+                    node_id, err
+                )),
+            }
+        }
+    }
+
+    /// Reads the write info of a variable. If the variable is of an enumerated type that isn't
+    /// in the type dictionary then its members are read and returned so they can be cached.
+    fn fetch_write_info(
+        session: &mut Session,
+        node_id: &NodeId,
+        type_dictionary: &TypeDictionary,
+    ) -> Result<(WriteInfo, Option<EnumLayout>), StatusCode> {
+        let nodes_to_read = [
+            AttributeId::DisplayName,
+            AttributeId::DataType,
+            AttributeId::ValueRank,
+            AttributeId::ArrayDimensions,
+            AttributeId::AccessLevel,
+            AttributeId::UserAccessLevel,
+            AttributeId::Value,
+        ]
+        .iter()
+        .map(|a| Self::read_value_id(node_id.clone(), *a))
+        .collect::<Vec<_>>();
+        let mut values = session.read(&nodes_to_read, TimestampsToReturn::Both, 0.0)?;
+        if values.len() < nodes_to_read.len() {
+            return Err(StatusCode::BadUnexpectedError);
+        }
+
+        let display_name = match values[0].value {
+            Some(Variant::LocalizedText(ref v)) => v.text.to_string(),
+            _ => node_id.to_string(),
+        };
+        let data_type = match values[1].value {
+            Some(Variant::NodeId(ref v)) => (**v).clone(),
+            _ => return Err(StatusCode::BadNodeClassInvalid),
+        };
+        let value_rank = match values[2].value {
+            Some(Variant::Int32(v)) => v,
+            _ => -1,
+        };
+        let array_dimensions = match values[3].value {
+            Some(Variant::Array(ref array)) => Some(
+                array
+                    .values
+                    .iter()
+                    .filter_map(|v| match v {
+                        Variant::UInt32(d) => Some(*d),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let access_level = match values[4].value {
+            Some(Variant::Byte(v)) => v,
+            _ => 0,
+        };
+        let user_access_level = match values[5].value {
+            Some(Variant::Byte(v)) => v,
+            _ => access_level,
+        };
+        let value = Some(values.remove(6));
+
+        let mut new_enumerations = Vec::new();
+        let (data_type_name, builtin_type, enum_members) =
+            Self::describe_data_type(session, &data_type, type_dictionary, &mut new_enumerations);
+
+        let write_info = WriteInfo {
+            node_id: node_id.clone(),
+            display_name,
+            data_type,
+            data_type_name,
+            builtin_type,
+            enum_members,
+            value_rank,
+            array_dimensions,
+            access_level,
+            user_access_level,
+            value,
+        };
+        Ok((write_info, new_enumerations.pop()))
+    }
+
+    /// Finds the name of a DataType and how its values are edited - as the built-in type it
+    /// derives from, or as an enumeration. The members of an enumeration that isn't in the type
+    /// dictionary are read and added to the new enumerations so they can be cached.
+    fn describe_data_type(
+        session: &mut Session,
+        data_type: &NodeId,
+        type_dictionary: &TypeDictionary,
+        new_enumerations: &mut Vec<EnumLayout>,
+    ) -> (String, Option<BuiltinType>, Vec<(i64, String)>) {
+        let data_type_name =
+            Self::read_browse_name(session, data_type).unwrap_or_else(|| data_type.to_string());
+        let base_type = Self::resolve_base_data_type(session, data_type);
+        if base_type != DataTypeId::Enumeration.into() {
+            let builtin_type = BuiltinType::from_data_type(&base_type);
+            return (data_type_name, builtin_type, Vec::new());
+        }
+        let known = type_dictionary.enumeration(data_type).cloned().or_else(|| {
+            new_enumerations
+                .iter()
+                .find(|e| e.data_type_id == *data_type)
+                .cloned()
+        });
+        let enumeration = match known {
+            Some(enumeration) => Some(enumeration),
+            None => {
+                let enumeration = Self::read_enumerations(
+                    session,
+                    &[(data_type.clone(), data_type_name.clone())],
+                )
+                .pop();
+                new_enumerations.extend(enumeration.clone());
+                enumeration
+            }
+        };
+        (
+            data_type_name,
+            Some(BuiltinType::Int32),
+            enumeration.map_or_else(Vec::new, |e| e.members),
+        )
+    }
+
+    /// Reads the arguments of a method and sends them to the app so it can show a form to
+    /// call the method with
+    pub fn read_method_info(&self, object_id: NodeId, method_id: NodeId) {
+        let mut connection = self.connection.lock().unwrap();
+        if let Some(session) = connection.session.clone() {
+            let mut session = session.write().unwrap();
+            match Self::fetch_method_info(
+                &mut session,
+                &object_id,
+                &method_id,
+                &connection.type_dictionary,
+            ) {
+                Ok((method_info, enumerations)) => {
+                    // Cache enumerations that weren't loaded when connecting
+                    if !enumerations.is_empty() {
+                        let type_dictionary = Arc::make_mut(&mut connection.type_dictionary);
+                        enumerations
+                            .into_iter()
+                            .for_each(|e| type_dictionary.insert_enumeration(e));
+                        self.send_app_msg(AppMessage::TypeDictionary(
+                            connection.type_dictionary.clone(),
+                        ));
+                    }
+                    self.send_app_msg(AppMessage::MethodInfo(method_info))
+                }
+                Err(err) => self.log(format!(
+                    "Cannot read the arguments of method {}, status code = {}",
+                    method_id, err
+                )),
+            }
+        }
+    }
+
+    fn fetch_method_info(
+        session: &mut Session,
+        object_id: &NodeId,
+        method_id: &NodeId,
+        type_dictionary: &TypeDictionary,
+    ) -> Result<(MethodInfo, Vec<EnumLayout>), StatusCode> {
+        let nodes_to_read = [
+            Self::read_value_id(object_id.clone(), AttributeId::DisplayName),
+            Self::read_value_id(method_id.clone(), AttributeId::DisplayName),
+            Self::read_value_id(method_id.clone(), AttributeId::Executable),
+            Self::read_value_id(method_id.clone(), AttributeId::UserExecutable),
+        ];
+        let values = session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)?;
+        let value = |i: usize| values.get(i).and_then(|v| v.value.as_ref());
+        let name = |i: usize, node_id: &NodeId| match value(i) {
+            Some(Variant::LocalizedText(v)) => v.text.to_string(),
+            _ => node_id.to_string(),
+        };
+        // Servers that don't say are assumed to allow the call
+        let executable = |i: usize| match value(i) {
+            Some(Variant::Boolean(v)) => *v,
+            _ => true,
+        };
+
+        let mut new_enumerations = Vec::new();
+        let inputs = Self::read_method_arguments(
+            session,
+            method_id,
+            "InputArguments",
+            type_dictionary,
+            &mut new_enumerations,
+        )?;
+        let outputs = Self::read_method_arguments(
+            session,
+            method_id,
+            "OutputArguments",
+            type_dictionary,
+            &mut new_enumerations,
+        )?;
+        let method_info = MethodInfo {
+            object_id: object_id.clone(),
+            object_name: name(0, object_id),
+            method_id: method_id.clone(),
+            method_name: name(1, method_id),
+            executable: executable(2) && executable(3),
+            inputs,
+            outputs,
+        };
+        Ok((method_info, new_enumerations))
+    }
+
+    /// Reads the Argument structures in the InputArguments or OutputArguments property of a
+    /// method. Methods without arguments may not have the property at all.
+    fn read_method_arguments(
+        session: &mut Session,
+        method_id: &NodeId,
+        property_name: &str,
+        type_dictionary: &TypeDictionary,
+        new_enumerations: &mut Vec<EnumLayout>,
+    ) -> Result<Vec<MethodArgument>, StatusCode> {
+        let property_id = match Self::find_property(session, method_id, property_name) {
+            Some(property_id) => property_id,
+            None => return Ok(Vec::new()),
+        };
+        let read_value_id = Self::read_value_id(property_id, AttributeId::Value);
+        let mut values = session.read(&[read_value_id], TimestampsToReturn::Neither, 0.0)?;
+        let arguments = match values.pop().and_then(|v| v.value) {
+            Some(Variant::Array(array)) => array.values,
+            Some(Variant::Empty) | None => Vec::new(),
+            _ => return Err(StatusCode::BadTypeMismatch),
+        };
+        let decoding_options = DecodingOptions::default();
+        arguments
+            .iter()
+            .map(|v| {
+                let argument = match v {
+                    Variant::ExtensionObject(v) => v.decode_inner::<Argument>(&decoding_options)?,
+                    _ => return Err(StatusCode::BadTypeMismatch),
+                };
+                let (data_type_name, builtin_type, enum_members) = Self::describe_data_type(
+                    session,
+                    &argument.data_type,
+                    type_dictionary,
+                    new_enumerations,
+                );
+                let name = argument.name.as_ref().to_string();
+                Ok(MethodArgument {
+                    description: argument.description.text.to_string(),
+                    info: WriteInfo {
+                        node_id: method_id.clone(),
+                        display_name: name.clone(),
+                        data_type: argument.data_type,
+                        data_type_name,
+                        builtin_type,
+                        enum_members,
+                        value_rank: argument.value_rank,
+                        array_dimensions: argument.array_dimensions,
+                        access_level: ACCESS_LEVEL_CURRENT_WRITE,
+                        user_access_level: ACCESS_LEVEL_CURRENT_WRITE,
+                        value: None,
+                    },
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    /// Calls a method with the Call service and sends the outputs, the result of each input
+    /// and the overall result to the app
+    pub fn call_method(&self, call: MethodCall) {
+        let connection = self.connection.lock().unwrap();
+        let session = match connection.session {
+            Some(ref session) => session,
+            None => return,
+        };
+        let request = CallMethodRequest {
+            object_id: call.object_id.clone(),
+            method_id: call.method_id.clone(),
+            input_arguments: Some(call.inputs.clone()),
+        };
+        let result = session.write().unwrap().call(request);
+        let result = match result {
+            Ok(result) => MethodCallResult {
+                call,
+                status_code: result.status_code,
+                input_results: result.input_argument_results.unwrap_or_default(),
+                outputs: result.output_arguments.unwrap_or_default(),
+            },
+            Err(err) => MethodCallResult::failed(call, err),
+        };
+        self.log(format!(
+            "Call of \"{}\" on \"{}\" finished, status code = {}",
+            result.call.method_name, result.call.object_name, result.status_code
+        ));
+        self.send_app_msg(AppMessage::MethodCallResult(result));
+    }
+
+    fn read_browse_name(session: &mut Session, node_id: &NodeId) -> Option<String> {
+        let read_value_id = Self::read_value_id(node_id.clone(), AttributeId::BrowseName);
+        let value = session
+            .read(&[read_value_id], TimestampsToReturn::Neither, 0.0)
+            .ok()?
+            .into_iter()
+            .next()?;
+        match value.value {
+            Some(Variant::QualifiedName(v)) => Some(v.name.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Follows the HasSubtype references up from a DataType until a built-in type or
+    /// Enumeration is found. If neither is found then BaseDataType is returned.
+    fn resolve_base_data_type(session: &mut Session, data_type: &NodeId) -> NodeId {
+        Self::resolve_base_data_types(session, std::slice::from_ref(data_type))
+            .remove(data_type)
+            .unwrap_or_else(|| DataTypeId::BaseDataType.into())
+    }
+
+    /// Resolves the base types of many DataTypes, browsing the supertypes of all of them a
+    /// level at a time. Supertypes are only browsed once however many DataTypes share them.
+    fn resolve_base_data_types(
+        session: &mut Session,
+        data_types: &[NodeId],
+    ) -> HashMap<NodeId, NodeId> {
+        let enumeration: NodeId = DataTypeId::Enumeration.into();
+        let is_base = |data_type: &NodeId| {
+            *data_type == enumeration || BuiltinType::from_data_type(data_type).is_some()
+        };
+        let mut supertypes: HashMap<NodeId, Option<NodeId>> = HashMap::new();
+        let mut base_types = HashMap::with_capacity(data_types.len());
+        let mut current = data_types
+            .iter()
+            .map(|data_type| (data_type.clone(), data_type.clone()))
+            .collect::<Vec<_>>();
+        // The type hierarchy should never be this deep but it protects against loops
+        for _ in 0..32 {
+            let to_browse = current
+                .iter()
+                .map(|(_, ancestor)| ancestor)
+                .filter(|ancestor| !is_base(*ancestor) && !supertypes.contains_key(*ancestor))
+                .cloned()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            if !to_browse.is_empty() {
+                let mut found = Self::browse_references_many(
+                    session,
+                    &to_browse,
+                    BrowseDirection::Inverse,
+                    ReferenceTypeId::HasSubtype,
+                );
+                to_browse.into_iter().for_each(|ancestor| {
+                    let supertype = found
+                        .remove(&ancestor)
+                        .and_then(|references| references.into_iter().next())
+                        .map(|r| r.node_id.node_id);
+                    supertypes.insert(ancestor, supertype);
+                });
+            }
+            let mut next = Vec::new();
+            current.into_iter().for_each(|(data_type, ancestor)| {
+                if is_base(&ancestor) {
+                    base_types.insert(data_type, ancestor);
+                } else if let Some(Some(supertype)) = supertypes.get(&ancestor) {
+                    next.push((data_type, supertype.clone()));
                 }
+            });
+            if next.is_empty() {
+                break;
             }
+            current = next;
+        }
+        data_types.iter().for_each(|data_type| {
+            base_types
+                .entry(data_type.clone())
+                .or_insert_with(|| DataTypeId::BaseDataType.into());
+        });
+        base_types
+    }
+
+    /// Reads the EngineeringUnits, EURange and InstrumentRange properties of a variable, which
+    /// are found on AnalogItemType variables and sometimes on other DataItems. Variables with
+    /// none of them have no info, which is told apart from failing to browse or read them.
+    pub fn read_analog_info(
+        session: &mut Session,
+        node_id: &NodeId,
+    ) -> Result<Option<AnalogInfo>, StatusCode> {
+        let browse_description = BrowseDescription {
+            node_id: node_id.clone(),
+            browse_direction: BrowseDirection::Forward,
+            reference_type_id: ReferenceTypeId::HasProperty.into(),
+            include_subtypes: true,
+            node_class_mask: 0x0,
+            result_mask: 0x3f,
+        };
+        let properties = match session.browse(&[browse_description])? {
+            Some(results) => match results.into_iter().next() {
+                Some(result) if result.status_code.is_bad() => return Err(result.status_code),
+                Some(result) => result.references.unwrap_or_default(),
+                None => return Err(StatusCode::BadUnexpectedError),
+            },
+            None => return Err(StatusCode::BadUnexpectedError),
+        };
+        let property_ids = AnalogInfo::PROPERTY_NAMES
+            .iter()
+            .map(|name| {
+                properties
+                    .iter()
+                    .find(|r| r.browse_name.name.as_ref() == *name)
+                    .map(|r| r.node_id.node_id.clone())
+            })
+            .collect::<Vec<_>>();
+        if property_ids.iter().all(|p| p.is_none()) {
+            return Ok(None);
         }
+        let nodes_to_read = property_ids
+            .iter()
+            .flatten()
+            .map(|p| Self::read_value_id(p.clone(), AttributeId::Value))
+            .collect::<Vec<_>>();
+        let values = session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)?;
+        if values.len() != nodes_to_read.len() {
+            return Err(StatusCode::BadUnexpectedError);
+        }
+        let mut values = values.into_iter();
+        let property_values = property_ids
+            .iter()
+            .map(|p| p.as_ref().and_then(|_| values.next()).and_then(|v| v.value))
+            .collect::<Vec<_>>();
+        Ok(AnalogInfo::from_property_values(&property_values))
+    }
+
+    /// Finds a property of a node by its browse name
+    fn find_property(session: &mut Session, node_id: &NodeId, name: &str) -> Option<NodeId> {
+        Self::browse_references(
+            session,
+            node_id,
+            BrowseDirection::Forward,
+            ReferenceTypeId::HasProperty,
+        )
+        .into_iter()
+        .find(|r| r.browse_name.name.as_ref() == name)
+        .map(|r| r.node_id.node_id)
+    }
+
+    /// Browses the references of a type (and its subtypes) from a node
+    fn browse_references(
+        session: &mut Session,
+        node_id: &NodeId,
+        browse_direction: BrowseDirection,
+        reference_type_id: ReferenceTypeId,
+    ) -> Vec<ReferenceDescription> {
+        let browse_description = BrowseDescription {
+            node_id: node_id.clone(),
+            browse_direction,
+            reference_type_id: reference_type_id.into(),
+            include_subtypes: true,
+            node_class_mask: 0x0,
+            result_mask: 0x3f,
+        };
+        match session.browse(&[browse_description]) {
+            Ok(Some(results)) => results
+                .into_iter()
+                .next()
+                .and_then(|result| result.references)
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Browses the references of a type (and its subtypes) from many nodes. Nodes that could
+    /// not be browsed are missing from the results.
+    fn browse_references_many(
+        session: &mut Session,
+        node_ids: &[NodeId],
+        browse_direction: BrowseDirection,
+        reference_type_id: ReferenceTypeId,
+    ) -> HashMap<NodeId, Vec<ReferenceDescription>> {
+        let browse_descriptions = node_ids
+            .iter()
+            .map(|node_id| BrowseDescription {
+                node_id: node_id.clone(),
+                browse_direction,
+                reference_type_id: reference_type_id.into(),
+                include_subtypes: true,
+                node_class_mask: 0x0,
+                result_mask: 0x3f,
+            })
+            .collect::<Vec<_>>();
+        Self::browse_chunked(session, &browse_descriptions)
+            .into_iter()
+            .collect()
+    }
+
+    /// Loads the layouts of the server's structured DataTypes so their values can be decoded.
+    /// The DataTypeDefinition attribute is used where the server supports it, otherwise the
+    /// layout comes from the legacy DataTypeDictionary that describes the binary encoding.
+    fn load_type_dictionary(&self, session: &mut Session, nodes_per_read: usize) -> TypeDictionary {
+        let mut type_dictionary = TypeDictionary::default();
+        let data_types = Self::browse_subtypes(session, &DataTypeId::Structure.into());
+
+        let mut definitions = Vec::with_capacity(data_types.len());
+        data_types.chunks(NODES_PER_REQUEST).for_each(|chunk| {
+            let nodes_to_read = chunk
+                .iter()
+                .flat_map(|(data_type_id, _)| {
+                    vec![
+                        Self::read_value_id(data_type_id.clone(), AttributeId::IsAbstract),
+                        Self::read_value_id(data_type_id.clone(), AttributeId::DataTypeDefinition),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let values = session
+                .read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)
+                .unwrap_or_default();
+            (0..chunk.len()).for_each(|i| {
+                let is_abstract = match values.get(i * 2).and_then(|v| v.value.as_ref()) {
+                    Some(Variant::Boolean(v)) => *v,
+                    _ => false,
+                };
+                let definition = match values.get(i * 2 + 1).and_then(|v| v.value.as_ref()) {
+                    Some(Variant::ExtensionObject(v)) => v
+                        .decode_inner::<StructureDefinition>(&DecodingOptions::default())
+                        .ok(),
+                    _ => None,
+                };
+                definitions.push((is_abstract, definition));
+            });
+        });
+
+        // Abstract structures have no encoding of their own so fields of those types are
+        // ExtensionObjects, as are fields of Structure itself
+        let structure_ids = data_types
+            .iter()
+            .zip(definitions.iter())
+            .filter(|(_, (is_abstract, _))| !is_abstract)
+            .map(|((data_type_id, _), _)| data_type_id.clone())
+            .collect::<HashSet<_>>();
+
+        // The base types of every field are resolved together rather than field by field
+        let field_data_types = definitions
+            .iter()
+            .filter_map(|(_, definition)| definition.as_ref())
+            .filter_map(|definition| definition.fields.as_ref())
+            .flatten()
+            .map(|field| &field.data_type)
+            .filter(|data_type| !structure_ids.contains(*data_type))
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let base_types = Self::resolve_base_data_types(session, &field_data_types);
+
+        let mut layouts = Vec::new();
+        let mut legacy_types = Vec::new();
+        data_types.iter().zip(definitions.into_iter()).for_each(
+            |((data_type_id, name), (is_abstract, definition))| {
+                if is_abstract {
+                    return;
+                }
+                match definition {
+                    Some(definition) => {
+                        layouts.push(type_dictionary::layout_from_definition(
+                            data_type_id.clone(),
+                            name,
+                            &definition,
+                            |field_data_type| {
+                                Self::field_type(field_data_type, &structure_ids, &base_types)
+                            },
+                        ));
+                    }
+                    None => legacy_types.push(data_type_id.clone()),
+                }
+            },
+        );
+        let missing_encodings = layouts
+            .iter()
+            .filter(|layout| layout.encoding_id.is_null())
+            .map(|layout| layout.data_type_id.clone())
+            .collect::<Vec<_>>();
+        let mut encodings = Self::find_binary_encodings(session, &missing_encodings);
+        layouts.into_iter().for_each(|mut layout| {
+            if let Some(encoding_id) = encodings.remove(&layout.data_type_id) {
+                layout.encoding_id = encoding_id;
+            }
+            type_dictionary.insert(layout);
+        });
+        let from_definitions = type_dictionary.structure_count();
+
+        self.load_legacy_layouts(session, &legacy_types, nodes_per_read, &mut type_dictionary);
+
+        let enumerations = Self::browse_subtypes(session, &DataTypeId::Enumeration.into());
+        Self::read_enumerations(session, &enumerations)
+            .into_iter()
+            .for_each(|e| type_dictionary.insert_enumeration(e));
+
+        self.log(format!(
+            "Loaded {} structured data types ({} from DataTypeDefinition, {} from type dictionaries) and {} enumerated data types",
+            type_dictionary.structure_count(),
+            from_definitions,
+            type_dictionary.structure_count() - from_definitions,
+            type_dictionary.enumeration_count()
+        ));
+        type_dictionary
+    }
+
+    /// Loads the layouts of structures from the legacy DataTypeDictionary. Each structure's
+    /// binary encoding has a description naming the type in the dictionary.
+    fn load_legacy_layouts(
+        &self,
+        session: &mut Session,
+        data_types: &[NodeId],
+        nodes_per_read: usize,
+        type_dictionary: &mut TypeDictionary,
+    ) {
+        let entries = Self::find_dictionary_entries(session, data_types, nodes_per_read);
+
+        let mut schemas = HashMap::new();
+        entries.iter().for_each(|(_, _, dictionary_id, _)| {
+            if !schemas.contains_key(dictionary_id) {
+                let schema = self.read_binary_schema(session, dictionary_id);
+                schemas.insert(dictionary_id.clone(), schema);
+            }
+        });
+
+        entries
+            .iter()
+            .for_each(|(data_type_id, encoding_id, dictionary_id, name)| {
+                let schema = match schemas.get(dictionary_id) {
+                    Some(Some(schema)) => schema,
+                    _ => return,
+                };
+                // Other structures are found by name, in the same dictionary or amongst the
+                // standard types
+                let resolve_structure = |namespace: &str, type_name: &str| {
+                    if namespace == schema.target_namespace && schema.has_structure(type_name) {
+                        entries
+                            .iter()
+                            .find(|(_, _, d, n)| d == dictionary_id && n == type_name)
+                            .map(|(data_type_id, _, _, _)| data_type_id.clone())
+                    } else {
+                        type_dictionary
+                            .structure_by_name(type_name)
+                            .filter(|s| s.data_type_id.namespace == 0)
+                            .map(|s| s.data_type_id.clone())
+                    }
+                };
+                match schema.layout(
+                    name,
+                    data_type_id.clone(),
+                    encoding_id.clone(),
+                    resolve_structure,
+                ) {
+                    Ok(layout) => type_dictionary.insert(layout),
+                    Err(err) => self.log(format!(
+                        "Cannot load the layout of data type {}, {}",
+                        data_type_id, err
+                    )),
+                }
+            });
+    }
+
+    /// Finds the binary encodings of structures, their dictionaries and the names they have
+    /// there. Each step is done for every structure at once, and structures missing any of
+    /// them are left out.
+    fn find_dictionary_entries(
+        session: &mut Session,
+        data_type_ids: &[NodeId],
+        nodes_per_read: usize,
+    ) -> Vec<(NodeId, NodeId, NodeId, String)> {
+        let first_target = |references: Option<&Vec<ReferenceDescription>>| {
+            references
+                .and_then(|references| references.first())
+                .map(|r| r.node_id.node_id.clone())
+        };
+        let encodings = Self::find_binary_encodings(session, data_type_ids);
+        let encoding_ids = encodings.values().cloned().collect::<Vec<_>>();
+        let descriptions = Self::browse_references_many(
+            session,
+            &encoding_ids,
+            BrowseDirection::Forward,
+            ReferenceTypeId::HasDescription,
+        );
+        let entries = data_type_ids
+            .iter()
+            .filter_map(|data_type_id| {
+                let encoding_id = encodings.get(data_type_id)?;
+                let description_id = first_target(descriptions.get(encoding_id))?;
+                Some((data_type_id.clone(), encoding_id.clone(), description_id))
+            })
+            .collect::<Vec<_>>();
+
+        let description_ids = entries
+            .iter()
+            .map(|(_, _, description_id)| description_id.clone())
+            .collect::<Vec<_>>();
+        let dictionaries = Self::browse_references_many(
+            session,
+            &description_ids,
+            BrowseDirection::Inverse,
+            ReferenceTypeId::HasComponent,
+        );
+        let nodes_to_read = description_ids
+            .into_iter()
+            .map(|description_id| Self::read_value_id(description_id, AttributeId::Value))
+            .collect::<Vec<_>>();
+        let names = Self::read_chunked(
+            session,
+            &nodes_to_read,
+            nodes_per_read,
+            TimestampsToReturn::Neither,
+            0.0,
+        );
+        entries
+            .into_iter()
+            .zip(names)
+            .filter_map(|((data_type_id, encoding_id, description_id), name)| {
+                let dictionary_id = first_target(dictionaries.get(&description_id))?;
+                match name.value {
+                    Some(Variant::String(name)) if !name.is_null() => Some((
+                        data_type_id,
+                        encoding_id,
+                        dictionary_id,
+                        name.as_ref().to_string(),
+                    )),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn read_binary_schema(
+        &self,
+        session: &mut Session,
+        dictionary_id: &NodeId,
+    ) -> Option<BinarySchema> {
+        let read_value_id = Self::read_value_id(dictionary_id.clone(), AttributeId::Value);
+        let value = session
+            .read(&[read_value_id], TimestampsToReturn::Neither, 0.0)
+            .ok()?
+            .into_iter()
+            .next()?;
+        let xml = match value.value {
+            Some(Variant::ByteString(v)) => String::from_utf8_lossy(v.value.as_ref()?).to_string(),
+            _ => return None,
+        };
+        match BinarySchema::parse(&xml) {
+            Ok(schema) => Some(schema),
+            Err(err) => {
+                self.log(format!(
+                    "Cannot parse type dictionary {}, {}",
+                    dictionary_id, err
+                ));
+                None
+            }
+        }
+    }
+
+    /// Finds the "Default Binary" DataTypeEncodings of DataTypes. DataTypes without one are
+    /// missing from the results.
+    fn find_binary_encodings(
+        session: &mut Session,
+        data_type_ids: &[NodeId],
+    ) -> HashMap<NodeId, NodeId> {
+        Self::browse_references_many(
+            session,
+            data_type_ids,
+            BrowseDirection::Forward,
+            ReferenceTypeId::HasEncoding,
+        )
+        .into_iter()
+        .filter_map(|(data_type_id, references)| {
+            references
+                .into_iter()
+                .find(|r| r.browse_name.name.as_ref() == "Default Binary")
+                .map(|r| (data_type_id, r.node_id.node_id))
+        })
+        .collect()
+    }
+
+    /// Finds every subtype of a DataType with its browse name
+    fn browse_subtypes(session: &mut Session, data_type_id: &NodeId) -> Vec<(NodeId, String)> {
+        let mut subtypes = Vec::new();
+        let mut found = HashSet::new();
+        let mut frontier = vec![data_type_id.clone()];
+        while !frontier.is_empty() {
+            let mut next_frontier = Vec::new();
+            frontier.chunks(NODES_PER_REQUEST).for_each(|chunk| {
+                let browse_descriptions = chunk
+                    .iter()
+                    .map(|node_id| BrowseDescription {
+                        node_id: node_id.clone(),
+                        browse_direction: BrowseDirection::Forward,
+                        reference_type_id: ReferenceTypeId::HasSubtype.into(),
+                        include_subtypes: false,
+                        node_class_mask: 0x0,
+                        result_mask: 0x3f,
+                    })
+                    .collect::<Vec<_>>();
+                if let Ok(Some(results)) = session.browse(&browse_descriptions) {
+                    results
+                        .into_iter()
+                        .filter_map(|r| r.references)
+                        .flatten()
+                        .for_each(|r| {
+                            let node_id = r.node_id.node_id;
+                            if found.insert(node_id.clone()) {
+                                subtypes.push((node_id.clone(), r.browse_name.name.to_string()));
+                                next_frontier.push(node_id);
+                            }
+                        });
+                }
+            });
+            frontier = next_frontier;
+        }
+        subtypes
+    }
+
+    /// Works out how a field of a structure with the DataType is encoded
+    fn field_type(
+        data_type_id: &NodeId,
+        structure_ids: &HashSet<NodeId>,
+        base_types: &HashMap<NodeId, NodeId>,
+    ) -> FieldType {
+        if structure_ids.contains(data_type_id) {
+            return FieldType::Structure(data_type_id.clone());
+        }
+        let base_type = base_types
+            .get(data_type_id)
+            .cloned()
+            .unwrap_or_else(|| DataTypeId::BaseDataType.into());
+        if base_type == DataTypeId::Enumeration.into() {
+            FieldType::Enumeration(data_type_id.clone())
+        } else {
+            // Abstract types such as Number are encoded as a Variant
+            FieldType::Builtin(
+                BuiltinType::from_data_type(&base_type).unwrap_or(BuiltinType::Variant),
+            )
+        }
+    }
+
+    /// Reads the members of enumerated DataTypes. The EnumDefinition in the DataTypeDefinition
+    /// attribute is preferred, then the EnumValues property and lastly EnumStrings, whose
+    /// members are numbered from zero.
+    fn read_enumerations(
+        session: &mut Session,
+        data_types: &[(NodeId, String)],
+    ) -> Vec<EnumLayout> {
+        let mut enumerations = Vec::with_capacity(data_types.len());
+        for chunk in data_types.chunks(NODES_PER_REQUEST) {
+            let nodes_to_read = chunk
+                .iter()
+                .map(|(data_type_id, _)| {
+                    Self::read_value_id(data_type_id.clone(), AttributeId::DataTypeDefinition)
+                })
+                .collect::<Vec<_>>();
+            let definitions = session
+                .read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)
+                .unwrap_or_default();
+            let mut members = (0..chunk.len())
+                .map(
+                    |i| match definitions.get(i).and_then(|v| v.value.as_ref()) {
+                        Some(Variant::ExtensionObject(v)) => v
+                            .decode_inner::<EnumDefinition>(&DecodingOptions::default())
+                            .ok()
+                            .map(|definition| {
+                                definition
+                                    .fields
+                                    .unwrap_or_default()
+                                    .iter()
+                                    .map(|f| {
+                                        let name = if f.display_name.text.is_empty() {
+                                            f.name.to_string()
+                                        } else {
+                                            f.display_name.text.to_string()
+                                        };
+                                        (f.value, name)
+                                    })
+                                    .collect::<Vec<_>>()
+                            }),
+                        _ => None,
+                    },
+                )
+                .collect::<Vec<_>>();
+
+            // Fall back to the properties for types without a definition
+            let missing = (0..chunk.len())
+                .filter(|i| members[*i].is_none())
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                let browse_descriptions = missing
+                    .iter()
+                    .map(|i| BrowseDescription {
+                        node_id: chunk[*i].0.clone(),
+                        browse_direction: BrowseDirection::Forward,
+                        reference_type_id: ReferenceTypeId::HasProperty.into(),
+                        include_subtypes: true,
+                        node_class_mask: 0x0,
+                        result_mask: 0x3f,
+                    })
+                    .collect::<Vec<_>>();
+                let results = match session.browse(&browse_descriptions) {
+                    Ok(Some(results)) => results,
+                    _ => Vec::new(),
+                };
+                let properties = missing
+                    .iter()
+                    .zip(results.iter())
+                    .filter_map(|(i, result)| {
+                        let references = result.references.as_ref()?;
+                        let find = |name: &str| {
+                            references
+                                .iter()
+                                .find(|r| r.browse_name.name.as_ref() == name)
+                        };
+                        let property = find("EnumValues").or_else(|| find("EnumStrings"))?;
+                        Some((*i, property.node_id.node_id.clone()))
+                    })
+                    .collect::<Vec<_>>();
+                let nodes_to_read = properties
+                    .iter()
+                    .map(|(_, property)| Self::read_value_id(property.clone(), AttributeId::Value))
+                    .collect::<Vec<_>>();
+                if !nodes_to_read.is_empty() {
+                    if let Ok(values) =
+                        session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)
+                    {
+                        properties
+                            .iter()
+                            .zip(values.iter())
+                            .for_each(|((i, _), value)| {
+                                members[*i] = Self::enum_members_from_property(value);
+                            });
+                    }
+                }
+            }
+
+            chunk
+                .iter()
+                .zip(members.into_iter())
+                .for_each(|((data_type_id, name), members)| {
+                    if let Some(members) = members {
+                        enumerations.push(EnumLayout {
+                            data_type_id: data_type_id.clone(),
+                            name: name.clone(),
+                            members,
+                        });
+                    }
+                });
+        }
+        enumerations
+    }
+
+    /// Gets the members from the value of an EnumValues or EnumStrings property
+    fn enum_members_from_property(value: &DataValue) -> Option<Vec<(i64, String)>> {
+        match value.value {
+            Some(Variant::Array(ref array)) => Some(
+                array
+                    .values
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, v)| match v {
+                        Variant::LocalizedText(text) => Some((i as i64, text.text.to_string())),
+                        Variant::ExtensionObject(v) => v
+                            .decode_inner::<EnumValueType>(&DecodingOptions::default())
+                            .ok()
+                            .map(|v| (v.value, v.display_name.text.to_string())),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Writes the value of a variable and tells the app the result. An index range writes just
+    /// those elements of an array.
+    pub fn write_value(&self, node_id: NodeId, value: Variant, index_range: Option<String>) {
+        self.write_attribute(node_id, AttributeId::Value, value, index_range);
+    }
+
+    /// Writes an attribute of a node and tells the app the result
+    pub fn write_attribute(
+        &self,
+        node_id: NodeId,
+        attribute_id: AttributeId,
+        value: Variant,
+        index_range: Option<String>,
+    ) {
+        let connection = self.connection.lock().unwrap();
+        if let Some(ref session) = connection.session {
+            match index_range {
+                Some(ref index_range) => self.log(format!(
+                    "Writing {:?} {:?} to node {} index range {}",
+                    attribute_id, value, node_id, index_range
+                )),
+                None => self.log(format!(
+                    "Writing {:?} {:?} to node {}",
+                    attribute_id, value, node_id
+                )),
+            }
+            let write_value = WriteValue {
+                node_id: node_id.clone(),
+                attribute_id: attribute_id as u32,
+                index_range: index_range.map_or_else(UAString::null, UAString::from),
+                value: DataValue::value_only(value),
+            };
+            let mut session = session.write().unwrap();
+            let status_code = match session.write(&[write_value]) {
+                Ok(results) => results
+                    .get(0)
+                    .cloned()
+                    .unwrap_or(StatusCode::BadUnexpectedError),
+                Err(err) => err,
+            };
+            self.log(format!(
+                "Write of {:?} to node {} finished, status code = {}",
+                attribute_id, node_id, status_code
+            ));
+            self.send_app_msg(AppMessage::WriteResult(node_id, attribute_id, status_code));
+        }
+    }
+
+    fn read_value_id(node_id: NodeId, attribute_id: AttributeId) -> ReadValueId {
+        ReadValueId {
+            node_id,
+            attribute_id: attribute_id as u32,
+            index_range: UAString::null(),
+            data_encoding: QualifiedName::null(),
+        }
+    }
+
+    /// Reads the attributes needed to describe a node in the reference graph
+    fn read_node_summary(session: &mut Session, node_id: &NodeId) -> Result<GraphNode, StatusCode> {
+        let nodes_to_read = [
+            AttributeId::BrowseName,
+            AttributeId::DisplayName,
+            AttributeId::NodeClass,
+        ]
+        .iter()
+        .map(|a| Self::read_value_id(node_id.clone(), *a))
+        .collect::<Vec<_>>();
+        let values = session.read(&nodes_to_read, TimestampsToReturn::Neither, 0.0)?;
+        // A server that returns fewer values than were asked for is broken
+        let value = |i: usize| {
+            values
+                .get(i)
+                .map(|v| &v.value)
+                .ok_or(StatusCode::BadUnexpectedError)
+        };
+        let browse_name = match *value(0)? {
+            Some(Variant::QualifiedName(ref v)) => v.name.to_string(),
+            _ => return Err(StatusCode::BadNodeIdUnknown),
+        };
+        let display_name = match *value(1)? {
+            Some(Variant::LocalizedText(ref v)) => v.text.to_string(),
+            _ => browse_name.clone(),
+        };
+        let node_class = match *value(2)? {
+            Some(Variant::Int32(v)) => node_class_from_i32(v),
+            _ => NodeClass::Unspecified,
+        };
+        Ok(GraphNode {
+            node_id: node_id.clone(),
+            browse_name,
+            display_name,
+            node_class,
+            depth: 0,
+        })
     }
 }
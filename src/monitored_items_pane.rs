@@ -0,0 +1,1512 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::PathBuf,
+    rc::Rc,
+    sync::Arc,
+};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    address_space_tree_view::AddressSpaceTreeView,
+    alert_rules::{self, Alert, AlertMonitor},
+    alert_rules_dlg::AlertRulesDlg,
+    connection_profile::ConnectionProfile,
+    diagnostics::ItemStatistics,
+    engineering_units::{AnalogInfo, ValueRange},
+    model::ModelMessage,
+    monitoring_parameters_dlg::MonitoringParametersDlg,
+    type_dictionary::TypeDictionary,
+    value_format::{self, TimeDisplay},
+    watch_list::{
+        Acquisition, Deadband, MonitoringSettings, PollStatistics, RevisedMonitoring,
+        SubscriptionSettings, WatchItem, WatchList,
+    },
+    watch_list_file::{self, WatchListLink},
+};
+
+const COL_ITEM_ID: u32 = 0;
+const COL_NODE_ID: u32 = 1;
+const COL_DISPLAY_NAME: u32 = 2;
+const COL_VALUE: u32 = 3;
+const COL_SERVER_TIMESTAMP: u32 = 4;
+const COL_SOURCE_TIMESTAMP: u32 = 5;
+const COL_STATUS: u32 = 6;
+const COL_WATCH_LIST: u32 = 7;
+const COL_COLOUR: u32 = 8;
+const COL_CHANGES: u32 = 9;
+const COL_MONITORING: u32 = 10;
+const COL_MONITORING_COLOUR: u32 = 11;
+const COL_TRIGGER: u32 = 12;
+const COL_RATE: u32 = 13;
+const COL_RANGE: u32 = 14;
+const COL_ALERT_BACKGROUND: u32 = 15;
+const COL_MODE: u32 = 16;
+const COL_MODE_COLOUR: u32 = 17;
+
+/// How often stale value rules are checked, in milliseconds
+const STALE_CHECK_INTERVAL: u32 = 1000;
+
+/// The acquisition of a new watch list
+const DEFAULT_ACQUISITION: Acquisition = Acquisition::Subscription;
+
+struct MonitoredItemsPaneImpl {
+    model: ActorRef<ModelMessage>,
+    items_tree: gtk::TreeView,
+    items_model: gtk::ListStore,
+    watch_list_combo: gtk::ComboBoxText,
+    acquisition_combo: gtk::ComboBoxText,
+    interval_spin: gtk::SpinButton,
+    stats_label: gtk::Label,
+    watch_lists: RefCell<Vec<WatchList>>,
+    /// The id of the watch list shown in the controls, which new items are added to
+    current: Cell<Option<u32>>,
+    /// The row of each item by item id. Rows of a list store stay valid while they exist.
+    rows: RefCell<HashMap<u32, gtk::TreeIter>>,
+    /// The units and ranges of items that are analog items
+    analog_info: RefCell<HashMap<u32, AnalogInfo>>,
+    /// The DataType of each item, to name the members of enumerations
+    data_types: RefCell<HashMap<u32, NodeId>>,
+    /// The structures and enumerations of the server
+    type_dictionary: RefCell<Arc<TypeDictionary>>,
+    /// The last value of each item and how many times its value or status has changed
+    changes: RefCell<HashMap<u32, (DataValue, u32)>>,
+    /// How often the value of each item arrives and the range of its values
+    item_statistics: RefCell<HashMap<u32, ItemStatistics>>,
+    /// The timing of polled watch lists by watch list id
+    statistics: RefCell<HashMap<u32, PollStatistics>>,
+    /// The last id given to a watch list or item
+    last_id: Cell<u32>,
+    /// Set while the controls are being changed to match a watch list, so the changes aren't
+    /// taken as edits
+    updating: Cell<bool>,
+    /// Called when watch lists are added, removed or changed
+    changed_handlers: RefCell<Vec<Box<dyn Fn()>>>,
+    /// Called with the ids and names of items to plot in the trend
+    add_to_trend_handler: RefCell<Option<Box<dyn Fn(Vec<(u32, String)>)>>>,
+    /// The file each watch list was opened from or saved to, by watch list id
+    files: RefCell<HashMap<u32, PathBuf>>,
+    /// The endpoint of the connection, which watch list files can be linked to
+    endpoint_url: RefCell<Option<String>>,
+    /// Which alert rules of the items have fired
+    alerts: RefCell<AlertMonitor>,
+    /// The file alerts are appended to
+    alert_log: Option<PathBuf>,
+    /// Called with a line describing each alert that fires or clears
+    alert_handlers: RefCell<Vec<Box<dyn Fn(&str)>>>,
+}
+
+/// Shows the items of the watch lists and their values as they are acquired. The pane owns the
+/// watch lists, so it is cloned by panes that manage them in other ways.
+#[derive(Clone)]
+pub struct MonitoredItemsPane {
+    data: Rc<MonitoredItemsPaneImpl>,
+}
+
+impl MonitoredItemsPane {
+    pub fn new(builder: Rc<gtk::Builder>, model: ActorRef<ModelMessage>) -> Self {
+        let new_btn: gtk::Button = builder.get_object("watch_list_new_btn").unwrap();
+        let open_btn: gtk::Button = builder.get_object("watch_list_open_btn").unwrap();
+        let save_btn: gtk::Button = builder.get_object("watch_list_save_btn").unwrap();
+
+        let data = Rc::new(MonitoredItemsPaneImpl {
+            model,
+            items_tree: builder.get_object("monitored_items_tree").unwrap(),
+            items_model: builder.get_object("monitored_items_model").unwrap(),
+            watch_list_combo: builder.get_object("watch_list_combo").unwrap(),
+            acquisition_combo: builder.get_object("watch_list_acquisition_combo").unwrap(),
+            interval_spin: builder.get_object("watch_list_interval_spin").unwrap(),
+            stats_label: builder.get_object("watch_list_stats_label").unwrap(),
+            watch_lists: RefCell::new(Vec::new()),
+            current: Cell::new(None),
+            rows: RefCell::new(HashMap::new()),
+            analog_info: RefCell::new(HashMap::new()),
+            data_types: RefCell::new(HashMap::new()),
+            type_dictionary: RefCell::new(Arc::new(TypeDictionary::default())),
+            changes: RefCell::new(HashMap::new()),
+            item_statistics: RefCell::new(HashMap::new()),
+            statistics: RefCell::new(HashMap::new()),
+            last_id: Cell::new(0),
+            updating: Cell::new(false),
+            changed_handlers: RefCell::new(Vec::new()),
+            add_to_trend_handler: RefCell::new(None),
+            files: RefCell::new(HashMap::new()),
+            endpoint_url: RefCell::new(None),
+            alerts: RefCell::new(AlertMonitor::default()),
+            alert_log: watch_list_file::config_dir().map(|dir| dir.join("alerts.log")),
+            alert_handlers: RefCell::new(Vec::new()),
+        });
+
+        data.add_watch_list(DEFAULT_ACQUISITION, SubscriptionSettings::default());
+
+        new_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.add_watch_list(DEFAULT_ACQUISITION, SubscriptionSettings::default());
+        }));
+        open_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.open();
+        }));
+        save_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.save();
+        }));
+
+        data.watch_list_combo
+            .connect_changed(clone!(@weak data => move |combo| {
+                if data.updating.get() {
+                    return;
+                }
+                let id = combo.get_active_id().and_then(|id| id.as_str().parse().ok());
+                data.current.set(id);
+                data.update_controls();
+            }));
+
+        data.acquisition_combo
+            .connect_changed(clone!(@weak data => move |_| {
+                data.on_acquisition_changed();
+            }));
+
+        data.interval_spin
+            .connect_value_changed(clone!(@weak data => move |_| {
+                data.on_acquisition_changed();
+            }));
+
+        // Nodes dragged from the address space tree are added to the current watch list
+        data.items_tree.drag_dest_set(
+            gtk::DestDefaults::ALL,
+            &[AddressSpaceTreeView::nodes_drag_target()],
+            gdk::DragAction::COPY,
+        );
+        data.items_tree.connect_drag_data_received(
+            clone!(@weak data => move |_, _, _, _, selection_data, _, _| {
+                let nodes = AddressSpaceTreeView::nodes_from_drag_data(&selection_data.get_data());
+                data.add_nodes(nodes);
+            }),
+        );
+
+        data.items_tree.connect_key_press_event(
+            clone!(@weak data => @default-return Inhibit(false), move |_, event| {
+                if event.get_keyval() == gdk::keys::constants::Delete {
+                    data.remove_selected();
+                    Inhibit(true)
+                } else {
+                    Inhibit(false)
+                }
+            }),
+        );
+
+        data.items_tree.connect_button_press_event(
+            clone!(@weak data => @default-return Inhibit(false), move |tree, event| {
+                if event.get_button() == 3 {
+                    let (x, y) = event.get_position();
+                    if let Some((Some(path), _, _, _)) = tree.get_path_at_pos(x as i32, y as i32) {
+                        let selection = tree.get_selection();
+                        if !selection.path_is_selected(&path) {
+                            selection.unselect_all();
+                            selection.select_path(&path);
+                        }
+                    }
+                    MonitoredItemsPaneImpl::popup_menu(&data);
+                    Inhibit(true)
+                } else {
+                    Inhibit(false)
+                }
+            }),
+        );
+
+        glib::timeout_add_local(
+            STALE_CHECK_INTERVAL,
+            clone!(@weak data => @default-return glib::Continue(false), move || {
+                let items = data
+                    .watch_lists
+                    .borrow()
+                    .iter()
+                    .flat_map(|w| w.items.iter())
+                    .filter(|i| !i.alert_rules.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let alerts = data.alerts.borrow_mut().check_stale(&items);
+                data.on_alerts(alerts);
+                glib::Continue(true)
+            }),
+        );
+
+        Self { data }
+    }
+
+    /// Adds nodes to the current watch list. Nodes that are already in it are skipped.
+    pub fn add_nodes(&self, nodes: Vec<(NodeId, String)>) {
+        self.data.add_nodes(nodes);
+    }
+
+    pub fn watch_lists(&self) -> Vec<WatchList> {
+        self.data.watch_lists.borrow().clone()
+    }
+
+    /// Adds a watch list whose values are acquired by a subscription with the settings, and
+    /// returns its id
+    pub fn add_subscription(&self, settings: SubscriptionSettings) -> u32 {
+        self.data
+            .add_watch_list(Acquisition::Subscription, settings)
+    }
+
+    /// Changes the publishing parameters of a watch list's subscription
+    pub fn set_subscription_settings(&self, watch_list_id: u32, settings: SubscriptionSettings) {
+        let watch_list = self
+            .data
+            .watch_lists
+            .borrow_mut()
+            .iter_mut()
+            .find(|w| w.id == watch_list_id)
+            .map(|w| {
+                w.subscription = settings;
+                w.clone()
+            });
+        if let Some(watch_list) = watch_list {
+            self.data
+                .model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+            self.data.notify_changed();
+        }
+    }
+
+    /// Removes a watch list and its items. The last watch list is replaced with an empty one
+    /// so there is always one to add nodes to.
+    pub fn remove_watch_list(&self, watch_list_id: u32) {
+        self.data.remove_watch_list(watch_list_id);
+    }
+
+    /// Adds a function called when watch lists are added, removed or changed
+    pub fn connect_watch_lists_changed<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.data.changed_handlers.borrow_mut().push(Box::new(f));
+    }
+
+    /// Sets the function called with the ids and names of items chosen to plot in the trend
+    pub fn connect_add_to_trend<F>(&self, f: F)
+    where
+        F: Fn(Vec<(u32, String)>) + 'static,
+    {
+        *self.data.add_to_trend_handler.borrow_mut() = Some(Box::new(f));
+    }
+
+    /// Opens the watch list files linked to the endpoint, except those already open
+    pub fn on_connected(&self, profile: &ConnectionProfile) {
+        *self.data.endpoint_url.borrow_mut() = Some(profile.endpoint_url.clone());
+        watch_list_file::load_links()
+            .into_iter()
+            .filter(|link| link.endpoint_url == profile.endpoint_url)
+            .filter(|link| !self.data.files.borrow().values().any(|p| *p == link.path))
+            .for_each(|link| {
+                self.data
+                    .model
+                    .tell(ModelMessage::LoadWatchList(link.path), None);
+            });
+    }
+
+    pub fn on_disconnected(&self) {
+        *self.data.endpoint_url.borrow_mut() = None;
+    }
+
+    /// Adds a watch list opened from a file, replacing the watch list from the same file if
+    /// there is one
+    pub fn on_watch_list_loaded(&self, path: PathBuf, watch_list: WatchList) {
+        let open_id = self
+            .data
+            .files
+            .borrow()
+            .iter()
+            .find(|(_, p)| **p == path)
+            .map(|(id, _)| *id);
+        if let Some(id) = open_id {
+            self.data.remove_watch_list(id);
+        }
+        let id = self
+            .data
+            .add_watch_list(watch_list.acquisition, watch_list.subscription);
+        self.data.files.borrow_mut().insert(id, path);
+        let item_ids = watch_list
+            .items
+            .iter()
+            .map(|item| (item.item_id, self.data.next_id()))
+            .collect::<HashMap<_, _>>();
+        let items = watch_list
+            .items
+            .into_iter()
+            .map(|item| WatchItem {
+                item_id: item_ids[&item.item_id],
+                triggered_by: item.triggered_by.and_then(|id| item_ids.get(&id).cloned()),
+                ..item
+            })
+            .collect::<Vec<_>>();
+        let watch_list = self.data.current_watch_list(|w| {
+            w.name = watch_list.name;
+            w.items = items;
+            w.clone()
+        });
+        if let Some(watch_list) = watch_list {
+            watch_list
+                .items
+                .iter()
+                .for_each(|item| self.data.insert_row(item, &watch_list.name));
+            self.data.update_trigger_text(&watch_list);
+            self.data
+                .model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+            self.data.update_watch_list_combo();
+            self.data.notify_changed();
+        }
+    }
+
+    /// Adds a function called with a line describing each alert that fires or clears
+    pub fn connect_alert<F>(&self, f: F)
+    where
+        F: Fn(&str) + 'static,
+    {
+        self.data.alert_handlers.borrow_mut().push(Box::new(f));
+    }
+
+    pub fn on_item_values(&self, values: Vec<(u32, DataValue)>) {
+        values
+            .iter()
+            .for_each(|(item_id, value)| self.data.set_item_value(*item_id, value));
+        let alerts = values
+            .iter()
+            .filter_map(|(item_id, value)| {
+                let item = self.data.find_item(*item_id)?;
+                if item.alert_rules.is_empty() {
+                    None
+                } else {
+                    Some(self.data.alerts.borrow_mut().on_value(&item, value))
+                }
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        self.data.on_alerts(alerts);
+    }
+
+    pub fn on_item_analog_info(&self, analog_info: Vec<(u32, AnalogInfo)>) {
+        self.data.analog_info.borrow_mut().extend(analog_info);
+    }
+
+    pub fn on_item_data_types(&self, data_types: Vec<(u32, NodeId)>) {
+        self.data.data_types.borrow_mut().extend(data_types);
+        self.data.refresh_values();
+    }
+
+    pub fn set_type_dictionary(&self, type_dictionary: Arc<TypeDictionary>) {
+        *self.data.type_dictionary.borrow_mut() = type_dictionary;
+        self.data.refresh_values();
+    }
+
+    /// The symbol of the engineering units of an item, if it has any
+    pub fn item_units(&self, item_id: u32) -> Option<String> {
+        self.data
+            .analog_info
+            .borrow()
+            .get(&item_id)
+            .and_then(|a| a.symbol().map(|s| s.to_string()))
+    }
+
+    /// The members of the enumeration an item's DataType is, or none if it isn't one
+    pub fn item_enum_members(&self, item_id: u32) -> Vec<(i64, String)> {
+        let data_types = self.data.data_types.borrow();
+        data_types
+            .get(&item_id)
+            .and_then(|data_type| {
+                self.data
+                    .type_dictionary
+                    .borrow()
+                    .enumeration(data_type)
+                    .map(|e| e.members.clone())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The text of a value of an item, naming enumeration members and followed by the units
+    pub fn format_item_value(&self, item_id: u32, v: &Variant) -> String {
+        let text = self.data.item_value_text(item_id, v);
+        match self.data.analog_info.borrow().get(&item_id) {
+            Some(analog_info) => analog_info.with_units(&text),
+            None => text,
+        }
+    }
+
+    pub fn on_monitoring_revised(
+        &self,
+        revised: Vec<(u32, Result<RevisedMonitoring, StatusCode>)>,
+    ) {
+        revised
+            .iter()
+            .for_each(|(item_id, result)| self.data.set_item_monitoring(*item_id, result));
+    }
+
+    pub fn on_monitoring_modes(&self, modes: Vec<(u32, Result<MonitoringMode, StatusCode>)>) {
+        modes
+            .iter()
+            .for_each(|(item_id, result)| self.data.set_item_mode(*item_id, result));
+    }
+
+    pub fn on_poll_statistics(&self, watch_list_id: u32, statistics: PollStatistics) {
+        self.data
+            .statistics
+            .borrow_mut()
+            .insert(watch_list_id, statistics);
+        if self.data.current.get() == Some(watch_list_id) {
+            self.data.update_statistics();
+        }
+    }
+}
+
+impl MonitoredItemsPaneImpl {
+    fn next_id(&self) -> u32 {
+        let id = self.last_id.get() + 1;
+        self.last_id.set(id);
+        id
+    }
+
+    fn notify_changed(&self) {
+        self.changed_handlers.borrow().iter().for_each(|f| f());
+    }
+
+    fn add_selected_to_trend(&self) {
+        let items = self
+            .selected_item_ids()
+            .into_iter()
+            .filter_map(|item_id| self.find_item(item_id))
+            .map(|item| (item.item_id, item.display_name))
+            .collect::<Vec<_>>();
+        if let Some(ref f) = *self.add_to_trend_handler.borrow() {
+            f(items);
+        }
+    }
+
+    fn add_watch_list(&self, acquisition: Acquisition, subscription: SubscriptionSettings) -> u32 {
+        let id = self.next_id();
+        let name = format!("Watch List {}", self.watch_lists.borrow().len() + 1);
+        let mut watch_list = WatchList::new(id, &name, acquisition);
+        watch_list.subscription = subscription;
+        self.watch_lists.borrow_mut().push(watch_list.clone());
+        self.model
+            .tell(ModelMessage::SetWatchList(watch_list), None);
+
+        self.current.set(Some(id));
+        self.update_watch_list_combo();
+        self.update_controls();
+        self.notify_changed();
+        id
+    }
+
+    fn remove_watch_list(&self, watch_list_id: u32) {
+        let watch_list = {
+            let mut watch_lists = self.watch_lists.borrow_mut();
+            match watch_lists.iter().position(|w| w.id == watch_list_id) {
+                Some(i) => watch_lists.remove(i),
+                None => return,
+            }
+        };
+        self.model
+            .tell(ModelMessage::RemoveWatchList(watch_list_id), None);
+        let item_ids = watch_list
+            .items
+            .iter()
+            .map(|i| i.item_id)
+            .collect::<Vec<_>>();
+        self.remove_rows(&item_ids);
+        self.statistics.borrow_mut().remove(&watch_list_id);
+        self.files.borrow_mut().remove(&watch_list_id);
+
+        if self.watch_lists.borrow().is_empty() {
+            self.add_watch_list(DEFAULT_ACQUISITION, SubscriptionSettings::default());
+            return;
+        }
+        if self.current.get() == Some(watch_list_id) {
+            self.current
+                .set(self.watch_lists.borrow().first().map(|w| w.id));
+        }
+        self.update_watch_list_combo();
+        self.update_controls();
+        self.notify_changed();
+    }
+
+    /// Fills the combo with the watch lists and selects the current one
+    fn update_watch_list_combo(&self) {
+        self.updating.set(true);
+        self.watch_list_combo.remove_all();
+        self.watch_lists.borrow().iter().for_each(|w| {
+            self.watch_list_combo
+                .append(Some(&w.id.to_string()), &w.name);
+        });
+        if let Some(id) = self.current.get() {
+            self.watch_list_combo.set_active_id(Some(&id.to_string()));
+        }
+        self.updating.set(false);
+    }
+
+    /// Makes the controls show the acquisition of the current watch list
+    fn update_controls(&self) {
+        let acquisition = match self.current_watch_list(|w| w.acquisition) {
+            Some(acquisition) => acquisition,
+            None => return,
+        };
+        self.updating.set(true);
+        match acquisition {
+            Acquisition::Subscription => {
+                self.acquisition_combo.set_active_id(Some("Subscription"));
+            }
+            Acquisition::Polling(interval) => {
+                self.acquisition_combo.set_active_id(Some("Polling"));
+                self.interval_spin.set_value(interval as f64);
+            }
+        }
+        self.interval_spin
+            .set_sensitive(acquisition != Acquisition::Subscription);
+        self.updating.set(false);
+        self.update_statistics();
+    }
+
+    fn update_statistics(&self) {
+        let text = match (
+            self.current.get(),
+            self.current_watch_list(|w| w.acquisition),
+        ) {
+            (Some(id), Some(Acquisition::Polling(_))) => match self.statistics.borrow().get(&id) {
+                Some(statistics) => statistics.to_string(),
+                None => "Not polled yet".to_string(),
+            },
+            _ => String::new(),
+        };
+        self.stats_label.set_text(&text);
+        self.stats_label.set_tooltip_text(Some(&text));
+    }
+
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.items_tree
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok())
+    }
+
+    /// A file chooser for watch list files with a check button that links the file to the
+    /// endpoint of the connection
+    fn file_chooser(
+        &self,
+        title: &str,
+        action: gtk::FileChooserAction,
+        accept: &str,
+        path: Option<&PathBuf>,
+    ) -> (gtk::FileChooserDialog, gtk::CheckButton) {
+        let chooser = gtk::FileChooserDialog::with_buttons(
+            Some(title),
+            self.parent_window().as_ref(),
+            action,
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                (accept, gtk::ResponseType::Accept),
+            ],
+        );
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Watch lists (*.json)"));
+        filter.add_pattern("*.json");
+        chooser.add_filter(&filter);
+
+        let endpoint_url = self.endpoint_url.borrow().clone();
+        let link_check = gtk::CheckButton::with_label(&match endpoint_url {
+            Some(ref endpoint_url) => format!("Restore when connecting to {}", endpoint_url),
+            None => "Restore when connecting (connect first)".to_string(),
+        });
+        link_check.set_sensitive(endpoint_url.is_some());
+        if let (Some(endpoint_url), Some(path)) = (endpoint_url, path) {
+            link_check.set_active(watch_list_file::load_links().contains(&WatchListLink {
+                endpoint_url,
+                path: path.clone(),
+            }));
+        }
+        link_check.show();
+        chooser.set_extra_widget(&link_check);
+        (chooser, link_check)
+    }
+
+    /// Links a file to the endpoint of the connection, or removes the link
+    fn set_link(&self, path: PathBuf, linked: bool) {
+        let endpoint_url = match *self.endpoint_url.borrow() {
+            Some(ref endpoint_url) => endpoint_url.clone(),
+            None => return,
+        };
+        let link = WatchListLink { endpoint_url, path };
+        let mut links = watch_list_file::load_links();
+        let exists = links.contains(&link);
+        if linked && !exists {
+            links.push(link);
+        } else if !linked && exists {
+            links.retain(|l| *l != link);
+        } else {
+            return;
+        }
+        if let Err(err) = watch_list_file::save_links(&links) {
+            self.stats_label.set_text(&err);
+        }
+    }
+
+    fn open(&self) {
+        let (chooser, link_check) = self.file_chooser(
+            "Open Watch List",
+            gtk::FileChooserAction::Open,
+            "_Open",
+            None,
+        );
+        if chooser.run() == gtk::ResponseType::Accept {
+            if let Some(path) = chooser.get_filename() {
+                self.set_link(path.clone(), link_check.get_active());
+                self.model.tell(ModelMessage::LoadWatchList(path), None);
+            }
+        }
+        chooser.close();
+    }
+
+    /// Saves the current watch list. Files are saved by the model, which knows the namespace
+    /// URIs of the server.
+    fn save(&self) {
+        let watch_list = match self.current_watch_list(|w| w.clone()) {
+            Some(watch_list) => watch_list,
+            None => return,
+        };
+        let path = self.files.borrow().get(&watch_list.id).cloned();
+        let (chooser, link_check) = self.file_chooser(
+            "Save Watch List",
+            gtk::FileChooserAction::Save,
+            "_Save",
+            path.as_ref(),
+        );
+        chooser.set_do_overwrite_confirmation(true);
+        match path {
+            Some(ref path) => {
+                chooser.set_filename(path);
+            }
+            None => chooser.set_current_name(&format!("{}.json", watch_list.name)),
+        }
+        if chooser.run() == gtk::ResponseType::Accept {
+            if let Some(path) = chooser.get_filename() {
+                self.set_link(path.clone(), link_check.get_active());
+                self.files.borrow_mut().insert(watch_list.id, path.clone());
+                let endpoint_url = self.endpoint_url.borrow().clone();
+                self.model.tell(
+                    ModelMessage::SaveWatchList(watch_list, endpoint_url, path),
+                    None,
+                );
+            }
+        }
+        chooser.close();
+    }
+
+    fn current_watch_list<T>(&self, f: impl FnOnce(&mut WatchList) -> T) -> Option<T> {
+        let id = self.current.get()?;
+        self.watch_lists
+            .borrow_mut()
+            .iter_mut()
+            .find(|w| w.id == id)
+            .map(f)
+    }
+
+    fn on_acquisition_changed(&self) {
+        if self.updating.get() {
+            return;
+        }
+        let acquisition = match self.acquisition_combo.get_active_id() {
+            Some(ref id) if id.as_str() == "Polling" => {
+                Acquisition::Polling(self.interval_spin.get_value_as_int().max(1) as u32)
+            }
+            _ => Acquisition::Subscription,
+        };
+        self.interval_spin
+            .set_sensitive(acquisition != Acquisition::Subscription);
+        let watch_list = self.current_watch_list(|w| {
+            w.acquisition = acquisition;
+            w.clone()
+        });
+        if let Some(watch_list) = watch_list {
+            // Monitoring parameters granted by a subscription don't apply to polls, and a new
+            // subscription grants them again
+            watch_list
+                .items
+                .iter()
+                .for_each(|item| self.set_monitoring_text(item.item_id, "", None));
+            // Statistics of the previous interval don't apply any more
+            self.statistics.borrow_mut().remove(&watch_list.id);
+            self.model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+            self.notify_changed();
+        }
+        self.update_statistics();
+    }
+
+    fn add_nodes(&self, nodes: Vec<(NodeId, String)>) {
+        let new_items = self.current_watch_list(|w| {
+            let new_items = nodes
+                .into_iter()
+                .filter(|(node_id, _)| !w.items.iter().any(|i| i.node_id == *node_id))
+                .map(|(node_id, display_name)| {
+                    WatchItem::new(self.next_id(), node_id, display_name)
+                })
+                .collect::<Vec<_>>();
+            w.items.extend(new_items.iter().cloned());
+            (w.clone(), new_items)
+        });
+        if let Some((watch_list, new_items)) = new_items {
+            new_items
+                .iter()
+                .for_each(|item| self.insert_row(item, &watch_list.name));
+            self.model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+            self.notify_changed();
+        }
+    }
+
+    /// The ids of the items in the selected rows
+    fn selected_item_ids(&self) -> Vec<u32> {
+        let (paths, model) = self.items_tree.get_selection().get_selected_rows();
+        paths
+            .iter()
+            .filter_map(|path| {
+                let iter = model.get_iter(path)?;
+                model
+                    .get_value(&iter, COL_ITEM_ID as i32)
+                    .get::<u32>()
+                    .ok()
+                    .flatten()
+            })
+            .collect()
+    }
+
+    fn popup_menu(data: &Rc<Self>) {
+        let item_ids = data.selected_item_ids();
+        if item_ids.is_empty() {
+            return;
+        }
+        let menu = gtk::Menu::new();
+        let remove_item = gtk::MenuItem::with_label(&format!("Remove ({})", item_ids.len()));
+        remove_item.connect_activate(clone!(@weak data => move |_| {
+            data.remove_selected();
+        }));
+        menu.append(&remove_item);
+        let monitoring_item = gtk::MenuItem::with_label("Monitoring Parameters...");
+        monitoring_item.connect_activate(clone!(@weak data => move |_| {
+            data.edit_monitoring();
+        }));
+        menu.append(&monitoring_item);
+        let alert_rules_item = gtk::MenuItem::with_label("Alert Rules...");
+        alert_rules_item.connect_activate(clone!(@weak data => move |_| {
+            data.edit_alert_rules();
+        }));
+        menu.append(&alert_rules_item);
+        let trend_item = gtk::MenuItem::with_label("Add to Trend");
+        trend_item.connect_activate(clone!(@weak data => move |_| {
+            data.add_selected_to_trend();
+        }));
+        menu.append(&trend_item);
+        Self::append_trigger_items(data, &menu, &item_ids);
+        Self::append_mode_items(data, &menu, &item_ids);
+
+        let watch_lists = data
+            .watch_lists
+            .borrow()
+            .iter()
+            .map(|w| (w.id, format!("{} ({})", w.name, w.acquisition)))
+            .collect::<Vec<_>>();
+        if watch_lists.len() > 1 {
+            let move_menu = gtk::Menu::new();
+            watch_lists.into_iter().for_each(|(id, label)| {
+                let watch_list_item = gtk::MenuItem::with_label(&label);
+                watch_list_item.connect_activate(clone!(@weak data => move |_| {
+                    let item_ids = data.selected_item_ids();
+                    data.move_items(&item_ids, id);
+                }));
+                move_menu.append(&watch_list_item);
+            });
+            let move_item = gtk::MenuItem::with_label("Move To");
+            move_item.set_submenu(Some(&move_menu));
+            menu.append(&move_item);
+        }
+
+        menu.show_all();
+        menu.popup_at_pointer(None);
+    }
+
+    /// Adds the items that mark triggers and link items to them, when the selected items are
+    /// in a watch list acquired by a subscription. Polled watch lists read every item anyway.
+    fn append_trigger_items(data: &Rc<Self>, menu: &gtk::Menu, item_ids: &[u32]) {
+        let watch_list = data
+            .watch_lists
+            .borrow()
+            .iter()
+            .find(|w| w.items.iter().any(|i| item_ids.contains(&i.item_id)))
+            .cloned();
+        let watch_list = match watch_list {
+            Some(w) if w.acquisition == Acquisition::Subscription => w,
+            _ => return,
+        };
+        let selected = watch_list
+            .items
+            .iter()
+            .filter(|i| item_ids.contains(&i.item_id))
+            .collect::<Vec<_>>();
+
+        menu.append(&gtk::SeparatorMenuItem::new());
+        let trigger_item = gtk::CheckMenuItem::with_label("Trigger");
+        trigger_item.set_active(selected.iter().all(|i| i.is_trigger));
+        trigger_item.connect_toggled(clone!(@weak data => move |item| {
+            let is_trigger = item.get_active();
+            let item_ids = data.selected_item_ids();
+            data.change_items(|i| {
+                if item_ids.contains(&i.item_id) {
+                    i.is_trigger = is_trigger;
+                    if is_trigger {
+                        i.triggered_by = None;
+                    }
+                }
+            });
+        }));
+        menu.append(&trigger_item);
+
+        let triggers = watch_list
+            .items
+            .iter()
+            .filter(|i| i.is_trigger && !item_ids.contains(&i.item_id))
+            .collect::<Vec<_>>();
+        if !triggers.is_empty() && selected.iter().any(|i| !i.is_trigger) {
+            let link_menu = gtk::Menu::new();
+            triggers.iter().for_each(|trigger| {
+                let trigger_id = trigger.item_id;
+                let link_item = gtk::MenuItem::with_label(&trigger.display_name);
+                link_item.connect_activate(clone!(@weak data => move |_| {
+                    let item_ids = data.selected_item_ids();
+                    data.change_items(|i| {
+                        if item_ids.contains(&i.item_id) && !i.is_trigger {
+                            i.triggered_by = Some(trigger_id);
+                        }
+                    });
+                }));
+                link_menu.append(&link_item);
+            });
+            let link_item = gtk::MenuItem::with_label("Link to Trigger");
+            link_item.set_submenu(Some(&link_menu));
+            menu.append(&link_item);
+        }
+
+        // Unlinking a trigger unlinks every item linked to it
+        let is_linked = |i: &WatchItem| {
+            item_ids.contains(&i.item_id)
+                || i.triggered_by.map_or(false, |id| item_ids.contains(&id))
+        };
+        if watch_list
+            .items
+            .iter()
+            .any(|i| i.triggered_by.is_some() && is_linked(i))
+        {
+            let unlink_item = gtk::MenuItem::with_label("Remove Trigger Links");
+            unlink_item.connect_activate(clone!(@weak data => move |_| {
+                let item_ids = data.selected_item_ids();
+                data.change_items(|i| {
+                    if item_ids.contains(&i.item_id)
+                        || i.triggered_by.map_or(false, |id| item_ids.contains(&id))
+                    {
+                        i.triggered_by = None;
+                    }
+                });
+            }));
+            menu.append(&unlink_item);
+        }
+    }
+
+    /// Adds the items that switch the monitoring mode of the selected items that are in watch
+    /// lists acquired by a subscription. The monitored items keep their settings and handles
+    /// while they are disabled.
+    fn append_mode_items(data: &Rc<Self>, menu: &gtk::Menu, item_ids: &[u32]) {
+        let modes = data
+            .watch_lists
+            .borrow()
+            .iter()
+            .filter(|w| w.acquisition == Acquisition::Subscription)
+            .flat_map(|w| w.items.iter())
+            .filter(|i| item_ids.contains(&i.item_id))
+            .map(|i| i.monitoring_mode)
+            .collect::<Vec<_>>();
+        if modes.is_empty() {
+            return;
+        }
+        let mode_menu = gtk::Menu::new();
+        [
+            (MonitoringMode::Disabled, "Disabled"),
+            (MonitoringMode::Sampling, "Sampling"),
+            (MonitoringMode::Reporting, "Reporting"),
+        ]
+        .iter()
+        .for_each(|(mode, label)| {
+            let mode = *mode;
+            let mode_item = gtk::CheckMenuItem::with_label(label);
+            mode_item.set_draw_as_radio(true);
+            mode_item.set_active(modes.iter().all(|m| *m == mode));
+            mode_item.connect_activate(clone!(@weak data => move |_| {
+                let item_ids = data.selected_item_ids();
+                data.change_items(|i| {
+                    if item_ids.contains(&i.item_id) {
+                        i.monitoring_mode = mode;
+                    }
+                });
+            }));
+            mode_menu.append(&mode_item);
+        });
+        let mode_item = gtk::MenuItem::with_label("Monitoring Mode");
+        mode_item.set_submenu(Some(&mode_menu));
+        menu.append(&mode_item);
+    }
+
+    /// Changes items of any watch list, then sends the watch lists whose items changed to the
+    /// model, which makes the trigger links and monitoring modes of their subscriptions match
+    fn change_items(&self, f: impl Fn(&mut WatchItem)) {
+        let is_reporting = |i: &WatchItem| {
+            i.monitoring_mode == MonitoringMode::Reporting && i.triggered_by.is_none()
+        };
+        let changed = self
+            .watch_lists
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(|w| {
+                let before = w
+                    .items
+                    .iter()
+                    .map(|i| (i.is_trigger, i.triggered_by, i.monitoring_mode))
+                    .collect::<Vec<_>>();
+                let paused = w
+                    .items
+                    .iter()
+                    .filter(|i| !is_reporting(i))
+                    .map(|i| i.item_id)
+                    .collect::<Vec<_>>();
+                w.items.iter_mut().for_each(|i| f(i));
+                w.unlink_missing_triggers();
+                // Items that report again start afresh, so their stale rules don't fire for
+                // the time they were paused
+                w.items
+                    .iter()
+                    .filter(|i| paused.contains(&i.item_id) && is_reporting(i))
+                    .for_each(|i| self.alerts.borrow_mut().reset(i.item_id));
+                let after = w
+                    .items
+                    .iter()
+                    .map(|i| (i.is_trigger, i.triggered_by, i.monitoring_mode));
+                if before.into_iter().ne(after) {
+                    Some(w.clone())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        changed.into_iter().for_each(|watch_list| {
+            self.update_trigger_text(&watch_list);
+            self.model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+        });
+        self.notify_changed();
+    }
+
+    /// Shows which items of a watch list are triggers and which trigger each item is linked to
+    fn update_trigger_text(&self, watch_list: &WatchList) {
+        let rows = self.rows.borrow();
+        watch_list.items.iter().for_each(|item| {
+            let text = if item.is_trigger {
+                let linked = watch_list
+                    .items
+                    .iter()
+                    .filter(|i| i.triggered_by == Some(item.item_id))
+                    .count();
+                format!("Trigger, {} linked", linked)
+            } else if let Some(trigger) = item
+                .triggered_by
+                .and_then(|id| watch_list.items.iter().find(|i| i.item_id == id))
+            {
+                format!("Sampled, reported with {}", trigger.display_name)
+            } else {
+                String::new()
+            };
+            if let Some(iter) = rows.get(&item.item_id) {
+                self.items_model.set(iter, &[COL_TRIGGER], &[&text]);
+            }
+        });
+    }
+
+    fn find_item(&self, item_id: u32) -> Option<WatchItem> {
+        self.watch_lists
+            .borrow()
+            .iter()
+            .flat_map(|w| w.items.iter())
+            .find(|i| i.item_id == item_id)
+            .cloned()
+    }
+
+    /// Edits the monitoring parameters of the selected items, starting from those of the first
+    fn edit_monitoring(&self) {
+        let items = self
+            .selected_item_ids()
+            .into_iter()
+            .filter_map(|item_id| self.find_item(item_id))
+            .collect::<Vec<_>>();
+        let settings = match items.first() {
+            Some(item) => item.monitoring,
+            None => return,
+        };
+        let eu_ranges = {
+            let analog_info = self.analog_info.borrow();
+            items
+                .iter()
+                .map(|item| {
+                    let eu_range = analog_info.get(&item.item_id).and_then(|a| a.eu_range);
+                    (item.display_name.clone(), eu_range)
+                })
+                .collect::<Vec<_>>()
+        };
+        let parent = self
+            .items_tree
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok());
+        let dlg = MonitoringParametersDlg::new(parent.as_ref(), &settings, eu_ranges);
+        let settings = match dlg.run() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        let item_ids = items.iter().map(|i| i.item_id).collect::<Vec<_>>();
+        let changed = self
+            .watch_lists
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(|w| {
+                let mut changed = false;
+                w.items
+                    .iter_mut()
+                    .filter(|i| item_ids.contains(&i.item_id) && i.monitoring != settings)
+                    .for_each(|i| {
+                        i.monitoring = settings;
+                        changed = true;
+                    });
+                if changed {
+                    Some(w.clone())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        changed.into_iter().for_each(|watch_list| {
+            if watch_list.acquisition == Acquisition::Subscription {
+                // Shown until the server says what it granted
+                let text = format!("Requested {}", settings);
+                item_ids
+                    .iter()
+                    .for_each(|item_id| self.set_monitoring_text(*item_id, &text, Some("grey")));
+            }
+            self.model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+        });
+    }
+
+    /// Edits the alert rules of the first selected item
+    fn edit_alert_rules(&self) {
+        let item = match self
+            .selected_item_ids()
+            .first()
+            .and_then(|item_id| self.find_item(*item_id))
+        {
+            Some(item) => item,
+            None => return,
+        };
+        let dlg = AlertRulesDlg::new(
+            self.parent_window().as_ref(),
+            &item.display_name,
+            &item.alert_rules,
+            self.alert_log.as_deref(),
+        );
+        let rules = match dlg.run() {
+            Some(rules) => rules,
+            None => return,
+        };
+        self.watch_lists
+            .borrow_mut()
+            .iter_mut()
+            .flat_map(|w| w.items.iter_mut())
+            .filter(|i| i.item_id == item.item_id)
+            .for_each(|i| i.alert_rules = rules.clone());
+        // The rules start again from the next value
+        self.alerts.borrow_mut().reset(item.item_id);
+        self.update_alert_background(item.item_id);
+        self.notify_changed();
+    }
+
+    /// Highlights the items whose rules fired, and reports each alert to the console, the alert
+    /// log and, where the rule asks for it, the desktop
+    fn on_alerts(&self, alerts: Vec<Alert>) {
+        alerts.iter().for_each(|alert| {
+            self.update_alert_background(alert.item_id);
+            let line = alert.to_string();
+            self.alert_handlers.borrow().iter().for_each(|f| f(&line));
+            if let Some(ref path) = self.alert_log {
+                if let Err(err) = alert_rules::append_to_log(path, alert) {
+                    self.alert_handlers.borrow().iter().for_each(|f| f(&err));
+                }
+            }
+            if alert.raised && alert.rule.notify {
+                self.notify_desktop(alert);
+            }
+        });
+    }
+
+    fn update_alert_background(&self, item_id: u32) {
+        let background = if self.alerts.borrow().is_active(item_id) {
+            Some("#ffc8c8")
+        } else {
+            None
+        };
+        if let Some(iter) = self.rows.borrow().get(&item_id) {
+            self.items_model
+                .set(iter, &[COL_ALERT_BACKGROUND], &[&background]);
+        }
+    }
+
+    fn notify_desktop(&self, alert: &Alert) {
+        let application = self.parent_window().and_then(|w| w.get_application());
+        if let Some(application) = application {
+            let notification = gio::Notification::new(&format!("Alert on {}", alert.display_name));
+            notification.set_body(Some(&alert.to_string()));
+            application.send_notification(Some(&format!("alert-{}", alert.item_id)), &notification);
+        }
+    }
+
+    fn set_item_monitoring(&self, item_id: u32, result: &Result<RevisedMonitoring, StatusCode>) {
+        let item = match self.find_item(item_id) {
+            Some(item) => item,
+            None => return,
+        };
+        match result {
+            Ok(revised) => {
+                let eu_range = self
+                    .analog_info
+                    .borrow()
+                    .get(&item_id)
+                    .and_then(|a| a.eu_range);
+                let text = Self::monitoring_text(&item.monitoring, revised, eu_range);
+                self.set_monitoring_text(item_id, &text, None);
+            }
+            Err(status_code) => {
+                let text = format!(
+                    "Rejected {}, {}",
+                    item.monitoring,
+                    value_format::status_code_to_string(*status_code)
+                );
+                self.set_monitoring_text(item_id, &text, Some("red"));
+            }
+        }
+    }
+
+    /// Describes what the server granted, noting where it differs from what was asked for
+    fn monitoring_text(
+        settings: &MonitoringSettings,
+        revised: &RevisedMonitoring,
+        eu_range: Option<ValueRange>,
+    ) -> String {
+        let mut text = format!(
+            "{} ms, queue {}",
+            revised.sampling_interval, revised.queue_size
+        );
+        if settings.sampling_interval >= 0.0
+            && settings.sampling_interval != revised.sampling_interval
+        {
+            text.push_str(&format!(" (asked {} ms)", settings.sampling_interval));
+        }
+        if settings.queue_size > 1 && settings.queue_size != revised.queue_size {
+            text.push_str(&format!(" (asked {})", settings.queue_size));
+        }
+        if settings.data_change_filter().is_some() {
+            text.push_str(&format!(", {:?}, {}", settings.trigger, settings.deadband));
+            if let Deadband::Percent(_) = settings.deadband {
+                if let Some(deadband) = settings.absolute_deadband(eu_range) {
+                    text.push_str(&format!(" = {}", deadband));
+                }
+            }
+        }
+        text
+    }
+
+    /// Shows the mode the item's monitored item is in on the server. Disabled items are greyed
+    /// and sampled ones, which are only reported along with a trigger, are marked.
+    fn set_item_mode(&self, item_id: u32, result: &Result<MonitoringMode, StatusCode>) {
+        let (text, colour) = match result {
+            Ok(MonitoringMode::Disabled) => ("Disabled".to_string(), Some("grey")),
+            Ok(MonitoringMode::Sampling) => ("Sampling".to_string(), Some("darkorange")),
+            Ok(MonitoringMode::Reporting) => ("Reporting".to_string(), None),
+            Err(status_code) => {
+                let mode = self
+                    .find_item(item_id)
+                    .map_or(MonitoringMode::Reporting, |i| i.monitoring_mode);
+                (
+                    format!(
+                        "Cannot set {:?}, {}",
+                        mode,
+                        value_format::status_code_to_string(*status_code)
+                    ),
+                    Some("red"),
+                )
+            }
+        };
+        if let Some(iter) = self.rows.borrow().get(&item_id) {
+            self.items_model
+                .set(iter, &[COL_MODE, COL_MODE_COLOUR], &[&text, &colour]);
+        }
+    }
+
+    fn set_monitoring_text(&self, item_id: u32, text: &str, colour: Option<&str>) {
+        if let Some(iter) = self.rows.borrow().get(&item_id) {
+            self.items_model.set(
+                iter,
+                &[COL_MONITORING, COL_MONITORING_COLOUR],
+                &[&text, &colour],
+            );
+        }
+    }
+
+    fn remove_selected(&self) {
+        let item_ids = self.selected_item_ids();
+        if !item_ids.is_empty() {
+            self.remove_items(&item_ids);
+        }
+    }
+
+    /// Removes items from their watch lists. The model deletes their monitored items or stops
+    /// polling them when it is sent the changed watch lists.
+    fn remove_items(&self, item_ids: &[u32]) {
+        let changed = self
+            .watch_lists
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(|w| {
+                let count = w.items.len();
+                w.items.retain(|i| !item_ids.contains(&i.item_id));
+                if w.items.len() != count {
+                    w.unlink_missing_triggers();
+                    Some(w.clone())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        self.remove_rows(item_ids);
+        changed.into_iter().for_each(|watch_list| {
+            self.update_trigger_text(&watch_list);
+            self.model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+        });
+        self.notify_changed();
+    }
+
+    /// Moves items to another watch list, keeping their ids so their rows and counts carry on.
+    /// Items whose node is already in the other watch list stay where they are.
+    fn move_items(&self, item_ids: &[u32], watch_list_id: u32) {
+        let changed = {
+            let mut watch_lists = self.watch_lists.borrow_mut();
+            let target_nodes = match watch_lists.iter().find(|w| w.id == watch_list_id) {
+                Some(target) => target
+                    .items
+                    .iter()
+                    .map(|i| i.node_id.clone())
+                    .collect::<Vec<_>>(),
+                None => return,
+            };
+            let mut moved = Vec::new();
+            // The sources are sent first so monitored items are deleted before being created on
+            // another subscription
+            let mut changed = Vec::new();
+            watch_lists
+                .iter_mut()
+                .filter(|w| w.id != watch_list_id)
+                .for_each(|w| {
+                    let (to_move, to_keep) = w.items.drain(..).partition::<Vec<_>, _>(|i| {
+                        item_ids.contains(&i.item_id) && !target_nodes.contains(&i.node_id)
+                    });
+                    w.items = to_keep;
+                    // Links don't reach across watch lists
+                    w.unlink_missing_triggers();
+                    if !to_move.is_empty() {
+                        moved.extend(to_move);
+                        changed.push(w.clone());
+                    }
+                });
+            if moved.is_empty() {
+                return;
+            }
+            let target = watch_lists
+                .iter_mut()
+                .find(|w| w.id == watch_list_id)
+                .unwrap();
+            moved.iter().for_each(|item| {
+                if let Some(iter) = self.rows.borrow().get(&item.item_id) {
+                    self.items_model
+                        .set(iter, &[COL_WATCH_LIST], &[&target.name]);
+                }
+                // The new subscription grants the parameters again
+                self.set_monitoring_text(item.item_id, "", None);
+            });
+            target.items.extend(moved);
+            target.unlink_missing_triggers();
+            changed.push(target.clone());
+            changed
+        };
+        changed.into_iter().for_each(|watch_list| {
+            self.update_trigger_text(&watch_list);
+            self.model
+                .tell(ModelMessage::SetWatchList(watch_list), None);
+        });
+        self.notify_changed();
+    }
+
+    fn remove_rows(&self, item_ids: &[u32]) {
+        let mut rows = self.rows.borrow_mut();
+        item_ids.iter().for_each(|item_id| {
+            if let Some(iter) = rows.remove(item_id) {
+                self.items_model.remove(&iter);
+            }
+            self.analog_info.borrow_mut().remove(item_id);
+            self.data_types.borrow_mut().remove(item_id);
+            self.changes.borrow_mut().remove(item_id);
+            self.item_statistics.borrow_mut().remove(item_id);
+            self.alerts.borrow_mut().reset(*item_id);
+        });
+    }
+
+    fn insert_row(&self, item: &WatchItem, watch_list_name: &str) {
+        let node_id = item.node_id.to_string();
+        let iter = self.items_model.insert_with_values(
+            None,
+            &[COL_ITEM_ID, COL_NODE_ID, COL_DISPLAY_NAME, COL_WATCH_LIST],
+            &[
+                &item.item_id,
+                &node_id,
+                &item.display_name,
+                &watch_list_name,
+            ],
+        );
+        self.rows.borrow_mut().insert(item.item_id, iter);
+    }
+
+    /// The text of a value, with the names of enumeration members from the type dictionary
+    fn item_value_text(&self, item_id: u32, v: &Variant) -> String {
+        let data_types = self.data_types.borrow();
+        self.type_dictionary
+            .borrow()
+            .format_value(data_types.get(&item_id), v, TimeDisplay::Utc)
+    }
+
+    /// The text of the Value column, with units and flagged if it is outside the EURange
+    fn value_column_text(&self, item_id: u32, data_value: &DataValue) -> String {
+        match data_value.value {
+            Some(ref v) => {
+                let text = self.item_value_text(item_id, v);
+                match self.analog_info.borrow().get(&item_id) {
+                    Some(analog_info) => analog_info.format_value(&text, v),
+                    None => text,
+                }
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Shows the last values again, e.g. once the enumerations they belong to are known
+    fn refresh_values(&self) {
+        let rows = self.rows.borrow();
+        self.changes
+            .borrow()
+            .iter()
+            .for_each(|(item_id, (data_value, _))| {
+                if let Some(iter) = rows.get(item_id) {
+                    let value = self.value_column_text(*item_id, data_value);
+                    self.items_model.set(iter, &[COL_VALUE], &[&value]);
+                }
+            });
+    }
+
+    fn set_item_value(&self, item_id: u32, data_value: &DataValue) {
+        let rows = self.rows.borrow();
+        let iter = match rows.get(&item_id) {
+            Some(iter) => iter,
+            // The item has been removed since the value was acquired
+            None => return,
+        };
+        let analog_info = self.analog_info.borrow();
+        let analog_info = analog_info.get(&item_id);
+        let status = data_value.status.unwrap_or(StatusCode::Good);
+        let changes = {
+            let mut changes = self.changes.borrow_mut();
+            let changed = match changes.get(&item_id) {
+                Some((last, _)) => {
+                    last.value != data_value.value
+                        || last.status != data_value.status
+                        || last.source_timestamp != data_value.source_timestamp
+                }
+                None => true,
+            };
+            let entry = changes.entry(item_id).or_insert((DataValue::default(), 0));
+            entry.0 = data_value.clone();
+            if changed {
+                entry.1 += 1;
+            }
+            entry.1
+        };
+        let (rate, range) = {
+            let mut item_statistics = self.item_statistics.borrow_mut();
+            let statistics = item_statistics.entry(item_id).or_default();
+            statistics.record(data_value);
+            (statistics.rate_text(), statistics.range_text())
+        };
+        let value = self.value_column_text(item_id, data_value);
+        let out_of_range = match (analog_info, data_value.value.as_ref()) {
+            (Some(analog_info), Some(v)) => analog_info.is_out_of_range(v),
+            _ => false,
+        };
+        let colour = if status.is_bad() {
+            Some("red")
+        } else if status.is_uncertain() || out_of_range {
+            Some("darkorange")
+        } else {
+            None
+        };
+        let timestamp = |t: &Option<DateTime>| {
+            t.as_ref().map_or_else(String::new, |t| {
+                value_format::date_time_to_string(t, TimeDisplay::Utc)
+            })
+        };
+        self.items_model.set(
+            iter,
+            &[
+                COL_VALUE,
+                COL_STATUS,
+                COL_SOURCE_TIMESTAMP,
+                COL_SERVER_TIMESTAMP,
+                COL_COLOUR,
+                COL_CHANGES,
+                COL_RATE,
+                COL_RANGE,
+            ],
+            &[
+                &value,
+                &value_format::status_code_to_string(status),
+                &timestamp(&data_value.source_timestamp),
+                &timestamp(&data_value.server_timestamp),
+                &colour,
+                &changes,
+                &rate,
+                &range,
+            ],
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    engineering_units::ValueRange,
+    watch_list::{Deadband, MonitoringSettings},
+};
+
+/// Edits the monitoring parameters of one or more watch items. The settings are checked
+/// against the EURange of every item before the dialog accepts them.
+pub(crate) struct MonitoringParametersDlg {
+    dlg: gtk::Dialog,
+    sampling_interval_spin: gtk::SpinButton,
+    queue_size_spin: gtk::SpinButton,
+    discard_oldest_check: gtk::CheckButton,
+    trigger_combo: gtk::ComboBoxText,
+    deadband_combo: gtk::ComboBoxText,
+    deadband_spin: gtk::SpinButton,
+    status_label: gtk::Label,
+    /// The display name and EURange of each item being edited
+    items: Vec<(String, Option<ValueRange>)>,
+}
+
+impl MonitoringParametersDlg {
+    pub fn new(
+        parent: Option<&gtk::Window>,
+        settings: &MonitoringSettings,
+        items: Vec<(String, Option<ValueRange>)>,
+    ) -> Self {
+        let title = match items.as_slice() {
+            [(display_name, _)] => format!("Monitoring Parameters - {}", display_name),
+            _ => format!("Monitoring Parameters - {} items", items.len()),
+        };
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&title),
+            parent,
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Apply", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+        let attach_row = |row: i32, text: &str, widget: &gtk::Widget| {
+            let label = gtk::Label::new(Some(text));
+            label.set_halign(gtk::Align::End);
+            widget.set_halign(gtk::Align::Start);
+            grid.attach(&label, 0, row, 1, 1);
+            grid.attach(widget, 1, row, 1, 1);
+        };
+
+        let sampling_interval_spin = gtk::SpinButton::with_range(-1.0, i32::MAX as f64, 100.0);
+        sampling_interval_spin.set_digits(0);
+        sampling_interval_spin.set_value(settings.sampling_interval);
+        sampling_interval_spin.set_tooltip_text(Some(
+            "-1 samples at the publishing interval, 0 as fast as the server can",
+        ));
+        attach_row(
+            0,
+            "Sampling Interval (ms):",
+            sampling_interval_spin.upcast_ref(),
+        );
+
+        let queue_size_spin = gtk::SpinButton::with_range(0.0, u16::MAX as f64, 1.0);
+        queue_size_spin.set_value(settings.queue_size as f64);
+        queue_size_spin.set_tooltip_text(Some(
+            "Values kept between publishes, 0 and 1 keep just the latest",
+        ));
+        attach_row(1, "Queue Size:", queue_size_spin.upcast_ref());
+
+        let discard_oldest_check = gtk::CheckButton::with_label("Discard oldest when full");
+        discard_oldest_check.set_active(settings.discard_oldest);
+        attach_row(2, "", discard_oldest_check.upcast_ref());
+
+        let trigger_combo = gtk::ComboBoxText::new();
+        ["Status", "StatusValue", "StatusValueTimestamp"]
+            .iter()
+            .for_each(|t| trigger_combo.append(Some(*t), t));
+        trigger_combo.set_active_id(Some(&format!("{:?}", settings.trigger)));
+        attach_row(3, "Trigger:", trigger_combo.upcast_ref());
+
+        let deadband_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let deadband_combo = gtk::ComboBoxText::new();
+        ["None", "Absolute", "Percent"]
+            .iter()
+            .for_each(|t| deadband_combo.append(Some(*t), t));
+        let deadband_spin = gtk::SpinButton::with_range(0.0, f64::MAX, 0.1);
+        deadband_spin.set_digits(3);
+        let (deadband_type, deadband_value) = match settings.deadband {
+            Deadband::None => ("None", 0.0),
+            Deadband::Absolute(v) => ("Absolute", v),
+            Deadband::Percent(v) => ("Percent", v),
+        };
+        deadband_combo.set_active_id(Some(deadband_type));
+        deadband_spin.set_value(deadband_value);
+        deadband_spin.set_sensitive(settings.deadband != Deadband::None);
+        deadband_box.pack_start(&deadband_combo, false, false, 0);
+        deadband_box.pack_start(&deadband_spin, false, false, 0);
+        attach_row(4, "Deadband:", deadband_box.upcast_ref());
+
+        let eu_range_text = match items.as_slice() {
+            [(_, Some(range))] => format!("EURange {}", range),
+            [(_, None)] => "No EURange, so only an absolute deadband can be used".to_string(),
+            _ => format!(
+                "{} of {} items have an EURange",
+                items.iter().filter(|(_, r)| r.is_some()).count(),
+                items.len()
+            ),
+        };
+        let eu_range_label = gtk::Label::new(Some(&eu_range_text));
+        eu_range_label.set_halign(gtk::Align::Start);
+        grid.attach(&eu_range_label, 0, 5, 2, 1);
+
+        let status_label = gtk::Label::new(Some(
+            "Only watch lists that use a subscription use these parameters",
+        ));
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        grid.attach(&status_label, 0, 6, 2, 1);
+
+        dlg.get_content_area().pack_start(&grid, true, true, 0);
+
+        {
+            let deadband_spin = deadband_spin.clone();
+            deadband_combo.connect_changed(move |combo| {
+                let none = combo
+                    .get_active_id()
+                    .map_or(true, |id| id.as_str() == "None");
+                deadband_spin.set_sensitive(!none);
+            });
+        }
+
+        Self {
+            dlg,
+            sampling_interval_spin,
+            queue_size_spin,
+            discard_oldest_check,
+            trigger_combo,
+            deadband_combo,
+            deadband_spin,
+            status_label,
+            items,
+        }
+    }
+
+    /// Runs the dialog and returns the monitoring parameters for every item it was opened for,
+    /// or none if it is cancelled. A deadband that doesn't suit one of the items names it.
+    pub fn run(&self) -> Option<MonitoringSettings> {
+        self.dlg.show_all();
+        let mut result = None;
+        while self.dlg.run() == gtk::ResponseType::Apply {
+            let settings = self.settings();
+            match self.validate(&settings) {
+                Ok(_) => {
+                    result = Some(settings);
+                    break;
+                }
+                Err(message) => self.status_label.set_markup(&format!(
+                    "<span foreground=\"red\">{}</span>",
+                    glib::markup_escape_text(&message)
+                )),
+            }
+        }
+        self.dlg.close();
+        result
+    }
+
+    fn settings(&self) -> MonitoringSettings {
+        let active_id = |combo: &gtk::ComboBoxText| {
+            combo
+                .get_active_id()
+                .map_or_else(String::new, |id| id.as_str().to_string())
+        };
+        let trigger = match active_id(&self.trigger_combo).as_str() {
+            "Status" => DataChangeTrigger::Status,
+            "StatusValueTimestamp" => DataChangeTrigger::StatusValueTimestamp,
+            _ => DataChangeTrigger::StatusValue,
+        };
+        let deadband_value = self.deadband_spin.get_value();
+        let deadband = match active_id(&self.deadband_combo).as_str() {
+            "Absolute" => Deadband::Absolute(deadband_value),
+            "Percent" => Deadband::Percent(deadband_value),
+            _ => Deadband::None,
+        };
+        MonitoringSettings {
+            sampling_interval: self.sampling_interval_spin.get_value(),
+            queue_size: self.queue_size_spin.get_value_as_int() as u32,
+            discard_oldest: self.discard_oldest_check.get_active(),
+            trigger,
+            deadband,
+        }
+    }
+
+    /// Checks the settings suit every item, naming the first item they don't suit
+    fn validate(&self, settings: &MonitoringSettings) -> Result<(), String> {
+        self.items.iter().try_for_each(|(display_name, eu_range)| {
+            settings
+                .validate(*eu_range)
+                .map_err(|message| format!("{}: {}", display_name, message))
+        })
+    }
+}
@@ -7,7 +7,7 @@ use riker::actors::*;
 
 use opcua_client::prelude::*;
 
-use crate::model::ModelMessage;
+use crate::{connection_profile::ConnectionProfile, model::ModelMessage};
 
 struct NewConnectionDlgImpl {
     model: ActorRef<ModelMessage>,
@@ -15,6 +15,7 @@ struct NewConnectionDlgImpl {
     security_policy_combo: Rc<gtk::ComboBoxText>,
     message_security_mode_combo: Rc<gtk::ComboBoxText>,
     endpoint_url_text: Rc<gtk::Entry>,
+    preferred_locales_text: Rc<gtk::Entry>,
 }
 
 pub(crate) struct NewConnectionDlg {
@@ -38,6 +39,7 @@ impl NewConnectionDlg {
                 builder.get_object("security_policy_combo").unwrap(),
             ),
             endpoint_url_text: Rc::new(builder.get_object("endpoint_url_text").unwrap()),
+            preferred_locales_text: Rc::new(builder.get_object("preferred_locales_text").unwrap()),
         });
 
         // Connect button
@@ -86,10 +88,16 @@ impl NewConnectionDlgImpl {
             _ => panic!("Unrecognized message security mode"),
         };
 
-        self.model.tell(
-            ModelMessage::Connect(endpoint_url, security_policy, message_security_mode),
-            None,
-        );
+        let preferred_locales =
+            ConnectionProfile::parse_locales(self.preferred_locales_text.get_text().as_str());
+
+        let profile = ConnectionProfile {
+            endpoint_url,
+            security_policy,
+            message_security_mode,
+            preferred_locales,
+        };
+        self.model.tell(ModelMessage::Connect(profile), None);
         self.dlg.response(gtk::ResponseType::Apply);
     }
 
@@ -0,0 +1,482 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Map, Value as JsonValue};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    connection_profile::ConnectionProfile,
+    events::EventMonitor,
+    trend::csv_field,
+    value_format::{self, TimeDisplay},
+    watch_list::{Acquisition, WatchList},
+};
+
+/// The columns of a CSV recording
+const CSV_COLUMNS: &str = "Kind,NodeId,DisplayName,Value,Status,SourceTimestamp,ServerTimestamp";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    /// One row per record after a commented header
+    Csv,
+    /// One JSON object per line, the first describing the recording
+    JsonLines,
+}
+
+impl RecordFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Csv => "csv",
+            RecordFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+impl fmt::Display for RecordFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordFormat::Csv => write!(f, "CSV"),
+            RecordFormat::JsonLines => write!(f, "JSON Lines"),
+        }
+    }
+}
+
+/// When a recording moves on to a new file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    /// Everything goes in one file
+    None,
+    /// A new file is started once the current one reaches this many bytes
+    Size(u64),
+    /// A new file is started once the current one has been open this long
+    Time(Duration),
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rotation::None => write!(f, "none"),
+            Rotation::Size(bytes) => write!(f, "every {} bytes", bytes),
+            Rotation::Time(duration) => write!(f, "every {} s", duration.as_secs()),
+        }
+    }
+}
+
+/// Where and how a recording is written
+#[derive(Debug, Clone)]
+pub struct RecorderSettings {
+    pub path: PathBuf,
+    pub format: RecordFormat,
+    pub rotation: Rotation,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("capture.csv"),
+            format: RecordFormat::Csv,
+            rotation: Rotation::None,
+        }
+    }
+}
+
+impl RecorderSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.path.file_stem().is_none() {
+            Err("Choose the file to record to".to_string())
+        } else if self.rotation == Rotation::Size(0)
+            || self.rotation == Rotation::Time(Duration::from_secs(0))
+        {
+            Err("The rotation size or time must be more than 0".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The path of a file of the recording. Rotated recordings number their files from 1,
+    /// e.g. capture-0001.csv, otherwise the path is used as it is.
+    pub fn file_path(&self, sequence: u32) -> PathBuf {
+        if self.rotation == Rotation::None {
+            return self.path.clone();
+        }
+        let stem = self
+            .path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().to_string());
+        let extension = self.path.extension().map_or_else(
+            || self.format.extension().to_string(),
+            |e| e.to_string_lossy().to_string(),
+        );
+        self.path
+            .with_file_name(format!("{}-{:04}.{}", stem, sequence, extension))
+    }
+
+    /// Creates a file of the recording, starting from the sequence number. The user confirmed
+    /// overwriting an unrotated file when choosing it, but a rotated file is never overwritten
+    /// since it may hold an earlier capture, so the next free sequence number is taken instead.
+    fn create_file(&self, mut sequence: u32) -> io::Result<(u32, PathBuf, File)> {
+        if self.rotation == Rotation::None {
+            return Ok((sequence, self.path.clone(), File::create(&self.path)?));
+        }
+        loop {
+            let path = self.file_path(sequence);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((sequence, path, file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    sequence = sequence.checked_add(1).ok_or(err)?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// What the header at the top of each file describes
+#[derive(Debug, Clone)]
+pub struct RecordingHeader {
+    /// The connection, or none if recording started before connecting
+    pub profile: Option<ConnectionProfile>,
+    pub watch_lists: Vec<WatchList>,
+    pub event_monitors: Vec<EventMonitor>,
+}
+
+/// Something to record
+pub enum Record<'a> {
+    /// A value of a watch item
+    Value {
+        node_id: &'a NodeId,
+        display_name: &'a str,
+        data_value: &'a DataValue,
+        /// The value as shown in the watch table, with enumeration names and units
+        text: String,
+    },
+    /// An event received by an event monitor
+    Event {
+        monitor: &'a EventMonitor,
+        fields: &'a [Variant],
+    },
+}
+
+/// Writes records to a file, moving on to a new file when the rotation says so. Each file
+/// starts with a header describing the session and the subscriptions.
+pub struct Recorder {
+    settings: RecorderSettings,
+    header: RecordingHeader,
+    /// When the recording started
+    started: String,
+    writer: BufWriter<File>,
+    path: PathBuf,
+    sequence: u32,
+    opened: Instant,
+    bytes: u64,
+    records: u64,
+}
+
+impl Recorder {
+    /// Creates the first file of the recording and writes its header
+    pub fn start(settings: RecorderSettings, header: RecordingHeader) -> io::Result<Self> {
+        let (sequence, path, file) = settings.create_file(1)?;
+        let mut recorder = Self {
+            writer: BufWriter::new(file),
+            settings,
+            header,
+            started: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            path,
+            sequence,
+            opened: Instant::now(),
+            bytes: 0,
+            records: 0,
+        };
+        recorder.write_header()?;
+        Ok(recorder)
+    }
+
+    /// The file being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How many records have been written, over all files
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Writes records, which are flushed so the file is complete if the app stops
+    pub fn write(&mut self, records: &[Record<'_>]) -> io::Result<()> {
+        for record in records {
+            if self.should_rotate() {
+                self.rotate()?;
+            }
+            let line = match self.settings.format {
+                RecordFormat::Csv => csv_line(record),
+                RecordFormat::JsonLines => json_line(record),
+            };
+            self.write_line(&line)?;
+            self.records += 1;
+        }
+        self.writer.flush()
+    }
+
+    pub fn stop(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn should_rotate(&self) -> bool {
+        match self.settings.rotation {
+            Rotation::None => false,
+            Rotation::Size(bytes) => self.bytes >= bytes,
+            Rotation::Time(duration) => self.opened.elapsed() >= duration,
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        let (sequence, path, file) = self.settings.create_file(self.sequence + 1)?;
+        self.sequence = sequence;
+        self.path = path;
+        self.writer = BufWriter::new(file);
+        self.opened = Instant::now();
+        self.bytes = 0;
+        self.write_header()
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.bytes += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let header = match self.settings.format {
+            RecordFormat::Csv => self.csv_header(),
+            RecordFormat::JsonLines => self.json_header().to_string(),
+        };
+        self.write_line(&header)
+    }
+
+    /// The header as comment lines, then the column names
+    fn csv_header(&self) -> String {
+        let mut header = String::new();
+        let _ = writeln!(
+            header,
+            "# Recording started {}, file {}",
+            self.started, self.sequence
+        );
+        match self.header.profile {
+            Some(ref profile) => {
+                let _ = writeln!(
+                    header,
+                    "# Endpoint {}, security policy {}, message security mode {:?}",
+                    profile.endpoint_url,
+                    profile.security_policy.to_str(),
+                    profile.message_security_mode
+                );
+            }
+            None => header.push_str("# Not connected\n"),
+        }
+        self.header.watch_lists.iter().for_each(|w| {
+            let _ = write!(
+                header,
+                "# Watch list \"{}\", {}, {} items",
+                w.name,
+                w.acquisition,
+                w.items.len()
+            );
+            if w.acquisition == Acquisition::Subscription {
+                let s = &w.subscription;
+                let _ = write!(
+                    header,
+                    ", publishing interval {} ms, lifetime count {}, max keep-alive count {}, \
+                     max notifications {}, priority {}, publishing {}",
+                    s.publishing_interval,
+                    s.lifetime_count,
+                    s.max_keep_alive_count,
+                    s.max_notifications_per_publish,
+                    s.priority,
+                    if s.publishing_enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                );
+            }
+            header.push('\n');
+        });
+        self.header.event_monitors.iter().for_each(|m| {
+            let _ = writeln!(
+                header,
+                "# Event monitor \"{}\" ({}), where {}",
+                m.node_name,
+                m.node_id,
+                m.where_clause
+                    .as_ref()
+                    .map_or_else(|| "none".to_string(), |w| w.to_string())
+            );
+        });
+        header.push_str(CSV_COLUMNS);
+        header
+    }
+
+    fn json_header(&self) -> JsonValue {
+        let session = match self.header.profile {
+            Some(ref profile) => json!({
+                "endpoint_url": profile.endpoint_url,
+                "security_policy": profile.security_policy.to_str(),
+                "message_security_mode": format!("{:?}", profile.message_security_mode),
+            }),
+            None => JsonValue::Null,
+        };
+        let watch_lists = self
+            .header
+            .watch_lists
+            .iter()
+            .map(|w| {
+                let mut watch_list = Map::new();
+                watch_list.insert("name".into(), json!(w.name));
+                watch_list.insert("acquisition".into(), json!(w.acquisition.to_string()));
+                watch_list.insert("items".into(), json!(w.items.len()));
+                if w.acquisition == Acquisition::Subscription {
+                    let s = &w.subscription;
+                    watch_list.insert(
+                        "subscription".into(),
+                        json!({
+                            "publishing_interval": s.publishing_interval,
+                            "lifetime_count": s.lifetime_count,
+                            "max_keep_alive_count": s.max_keep_alive_count,
+                            "max_notifications_per_publish": s.max_notifications_per_publish,
+                            "priority": s.priority,
+                            "publishing_enabled": s.publishing_enabled,
+                        }),
+                    );
+                }
+                JsonValue::Object(watch_list)
+            })
+            .collect::<Vec<_>>();
+        let event_monitors = self
+            .header
+            .event_monitors
+            .iter()
+            .map(|m| {
+                json!({
+                    "node_id": m.node_id.to_string(),
+                    "display_name": m.node_name,
+                    "where": m.where_clause.as_ref().map(|w| w.to_string()),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "kind": "header",
+            "started": self.started,
+            "file": self.sequence,
+            "session": session,
+            "watch_lists": watch_lists,
+            "event_monitors": event_monitors,
+        })
+    }
+}
+
+fn timestamp_text(timestamp: Option<&DateTime>) -> String {
+    timestamp.map_or_else(String::new, |t| {
+        value_format::date_time_to_string(t, TimeDisplay::Utc).replace(' ', "T")
+    })
+}
+
+fn event_time(monitor: &EventMonitor, fields: &[Variant]) -> Option<DateTime> {
+    match monitor.field_value(fields, "Time") {
+        Some(Variant::DateTime(time)) => Some((**time).clone()),
+        _ => None,
+    }
+}
+
+fn csv_line(record: &Record<'_>) -> String {
+    match record {
+        Record::Value {
+            node_id,
+            display_name,
+            data_value,
+            text,
+        } => format!(
+            "value,{},{},{},{},{},{}",
+            csv_field(&node_id.to_string()),
+            csv_field(display_name),
+            csv_field(text),
+            csv_field(&value_format::status_code_to_string(
+                data_value.status.unwrap_or(StatusCode::Good)
+            )),
+            timestamp_text(data_value.source_timestamp.as_ref()),
+            timestamp_text(data_value.server_timestamp.as_ref())
+        ),
+        // The fields of an event are its value, the Time field its source timestamp
+        Record::Event { monitor, fields } => {
+            let value = monitor
+                .select
+                .iter()
+                .zip(fields.iter())
+                .map(|(f, v)| {
+                    format!(
+                        "{}={}",
+                        f,
+                        value_format::variant_to_string(v, TimeDisplay::Utc)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            format!(
+                "event,{},{},{},,{},",
+                csv_field(&monitor.node_id.to_string()),
+                csv_field(&monitor.node_name),
+                csv_field(&value),
+                timestamp_text(event_time(monitor, fields).as_ref())
+            )
+        }
+    }
+}
+
+fn json_line(record: &Record<'_>) -> String {
+    match record {
+        Record::Value {
+            node_id,
+            display_name,
+            data_value,
+            text,
+        } => json!({
+            "kind": "value",
+            "node_id": node_id.to_string(),
+            "display_name": display_name,
+            "value": data_value
+                .value
+                .as_ref()
+                .map_or(JsonValue::Null, value_format::variant_to_json),
+            "text": text,
+            "status": value_format::status_code_to_string(
+                data_value.status.unwrap_or(StatusCode::Good)
+            ),
+            "source_timestamp": timestamp_text(data_value.source_timestamp.as_ref()),
+            "server_timestamp": timestamp_text(data_value.server_timestamp.as_ref()),
+        }),
+        Record::Event { monitor, fields } => {
+            let values = monitor
+                .select
+                .iter()
+                .zip(fields.iter())
+                .map(|(f, v)| (f.path(), value_format::variant_to_json(v)))
+                .collect::<Map<_, _>>();
+            json!({
+                "kind": "event",
+                "node_id": monitor.node_id.to_string(),
+                "display_name": monitor.node_name,
+                "fields": values,
+                "source_timestamp": timestamp_text(event_time(monitor, fields).as_ref()),
+            })
+        }
+    }
+    .to_string()
+}
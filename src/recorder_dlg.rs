@@ -0,0 +1,199 @@
+use std::{path::PathBuf, time::Duration};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use crate::recorder::{RecordFormat, RecorderSettings, Rotation};
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// Chooses the file, format and rotation of a recording
+pub(crate) struct RecorderDlg {
+    dlg: gtk::Dialog,
+    path_entry: gtk::Entry,
+    format_combo: gtk::ComboBoxText,
+    rotation_combo: gtk::ComboBoxText,
+    size_spin: gtk::SpinButton,
+    time_spin: gtk::SpinButton,
+    status_label: gtk::Label,
+}
+
+impl RecorderDlg {
+    pub fn new(parent: Option<&gtk::Window>, settings: &RecorderSettings) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some("Record Values and Events"),
+            parent,
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Record", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+        let label = |row: i32, text: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_halign(gtk::Align::End);
+            grid.attach(&label, 0, row, 1, 1);
+        };
+
+        label(0, "File:");
+        let path_entry = gtk::Entry::new();
+        path_entry.set_width_chars(40);
+        path_entry.set_hexpand(true);
+        path_entry.set_text(&settings.path.to_string_lossy());
+        path_entry.set_tooltip_text(Some(
+            "Rotated recordings number their files, e.g. capture-0001.csv",
+        ));
+        grid.attach(&path_entry, 1, 0, 1, 1);
+        let browse_btn = gtk::Button::with_label("Browse...");
+        grid.attach(&browse_btn, 2, 0, 1, 1);
+
+        label(1, "Format:");
+        let format_combo = gtk::ComboBoxText::new();
+        [RecordFormat::Csv, RecordFormat::JsonLines]
+            .iter()
+            .for_each(|f| format_combo.append(Some(f.extension()), &f.to_string()));
+        format_combo.set_active_id(Some(settings.format.extension()));
+        format_combo.set_halign(gtk::Align::Start);
+        grid.attach(&format_combo, 1, 1, 1, 1);
+
+        label(2, "Rotation:");
+        let rotation_combo = gtk::ComboBoxText::new();
+        rotation_combo.append(Some("None"), "None");
+        rotation_combo.append(Some("Size"), "By Size");
+        rotation_combo.append(Some("Time"), "By Time");
+        rotation_combo.set_halign(gtk::Align::Start);
+        grid.attach(&rotation_combo, 1, 2, 1, 1);
+
+        let spin_row = |row: i32, text: &str, value: f64, tooltip: &str| {
+            label(row, text);
+            let spin = gtk::SpinButton::with_range(1.0, 1_000_000.0, 1.0);
+            spin.set_digits(0);
+            spin.set_value(value);
+            spin.set_halign(gtk::Align::Start);
+            spin.set_tooltip_text(Some(tooltip));
+            grid.attach(&spin, 1, row, 1, 1);
+            spin
+        };
+        let (size, time) = match settings.rotation {
+            Rotation::None => (10.0, 60.0),
+            Rotation::Size(bytes) => ((bytes as f64 / BYTES_PER_MB).ceil(), 60.0),
+            Rotation::Time(duration) => (10.0, (duration.as_secs() as f64 / 60.0).ceil()),
+        };
+        let size_spin = spin_row(
+            3,
+            "File Size (MB):",
+            size,
+            "A new file is started when the file reaches this size",
+        );
+        let time_spin = spin_row(
+            4,
+            "File Time (min):",
+            time,
+            "A new file is started when the file has been written for this long",
+        );
+
+        let status_label = gtk::Label::new(None);
+        status_label.set_halign(gtk::Align::Start);
+        grid.attach(&status_label, 0, 5, 3, 1);
+
+        dlg.get_content_area().add(&grid);
+
+        rotation_combo.connect_changed(clone!(@weak size_spin, @weak time_spin => move |combo| {
+            let rotation = combo.get_active_id();
+            let rotation = rotation.as_ref().map(|id| id.as_str());
+            size_spin.set_sensitive(rotation == Some("Size"));
+            time_spin.set_sensitive(rotation == Some("Time"));
+        }));
+        rotation_combo.set_active_id(Some(match settings.rotation {
+            Rotation::None => "None",
+            Rotation::Size(_) => "Size",
+            Rotation::Time(_) => "Time",
+        }));
+
+        browse_btn.connect_clicked(
+            clone!(@weak dlg, @weak path_entry, @weak format_combo => move |_| {
+                let chooser = gtk::FileChooserDialog::with_buttons(
+                    Some("Record To"),
+                    Some(&dlg),
+                    gtk::FileChooserAction::Save,
+                    &[
+                        ("_Cancel", gtk::ResponseType::Cancel),
+                        ("_Choose", gtk::ResponseType::Accept),
+                    ],
+                );
+                chooser.set_do_overwrite_confirmation(true);
+                let extension = format_combo
+                    .get_active_id()
+                    .map_or_else(|| "csv".to_string(), |id| id.to_string());
+                chooser.set_current_name(&format!("capture.{}", extension));
+                if chooser.run() == gtk::ResponseType::Accept {
+                    if let Some(path) = chooser.get_filename() {
+                        path_entry.set_text(&path.to_string_lossy());
+                    }
+                }
+                chooser.close();
+            }),
+        );
+
+        Self {
+            dlg,
+            path_entry,
+            format_combo,
+            rotation_combo,
+            size_spin,
+            time_spin,
+            status_label,
+        }
+    }
+
+    /// Runs the dialog and returns the settings to record with, or none if it is cancelled. A
+    /// missing file or an empty rotation keeps the dialog open with the reason shown.
+    pub fn run(&self) -> Option<RecorderSettings> {
+        self.dlg.show_all();
+        let mut result = None;
+        while self.dlg.run() == gtk::ResponseType::Apply {
+            let settings = self.settings();
+            match settings.validate() {
+                Ok(_) => {
+                    result = Some(settings);
+                    break;
+                }
+                Err(message) => self.status_label.set_markup(&format!(
+                    "<span foreground=\"red\">{}</span>",
+                    glib::markup_escape_text(&message)
+                )),
+            }
+        }
+        self.dlg.close();
+        result
+    }
+
+    fn settings(&self) -> RecorderSettings {
+        let format = match self.format_combo.get_active_id() {
+            Some(id) if id.as_str() == RecordFormat::JsonLines.extension() => {
+                RecordFormat::JsonLines
+            }
+            _ => RecordFormat::Csv,
+        };
+        let rotation = match self.rotation_combo.get_active_id() {
+            Some(id) if id.as_str() == "Size" => {
+                Rotation::Size((self.size_spin.get_value() * BYTES_PER_MB) as u64)
+            }
+            Some(id) if id.as_str() == "Time" => {
+                Rotation::Time(Duration::from_secs(self.time_spin.get_value() as u64 * 60))
+            }
+            _ => Rotation::None,
+        };
+        RecorderSettings {
+            path: PathBuf::from(self.path_entry.get_text().as_str().trim()),
+            format,
+            rotation,
+        }
+    }
+}
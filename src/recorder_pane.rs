@@ -0,0 +1,207 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    connection_profile::ConnectionProfile,
+    events::Event,
+    events_pane::EventsPane,
+    monitored_items_pane::MonitoredItemsPane,
+    recorder::{Record, Recorder, RecorderSettings, RecordingHeader},
+    recorder_dlg::RecorderDlg,
+};
+
+struct RecorderPaneImpl {
+    monitored_items_pane: MonitoredItemsPane,
+    events_pane: EventsPane,
+    start_btn: gtk::Button,
+    stop_btn: gtk::Button,
+    status_label: gtk::Label,
+    /// The settings of the last recording, offered for the next
+    settings: RefCell<RecorderSettings>,
+    recorder: RefCell<Option<Recorder>>,
+    /// The connection, for the header of the files
+    profile: RefCell<Option<ConnectionProfile>>,
+}
+
+/// Starts and stops recording the values of the watch items and the events of the event
+/// monitors to a file
+pub struct RecorderPane {
+    data: Rc<RecorderPaneImpl>,
+}
+
+impl RecorderPane {
+    pub fn new(
+        builder: Rc<gtk::Builder>,
+        monitored_items_pane: MonitoredItemsPane,
+        events_pane: EventsPane,
+    ) -> Self {
+        let data = Rc::new(RecorderPaneImpl {
+            monitored_items_pane,
+            events_pane,
+            start_btn: builder.get_object("recorder_start_btn").unwrap(),
+            stop_btn: builder.get_object("recorder_stop_btn").unwrap(),
+            status_label: builder.get_object("recorder_status_label").unwrap(),
+            settings: RefCell::new(RecorderSettings::default()),
+            recorder: RefCell::new(None),
+            profile: RefCell::new(None),
+        });
+
+        data.start_btn
+            .connect_clicked(clone!(@weak data => move |_| {
+                data.start();
+            }));
+        data.stop_btn
+            .connect_clicked(clone!(@weak data => move |_| {
+                data.stop();
+            }));
+
+        Self { data }
+    }
+
+    pub fn on_connected(&self, profile: ConnectionProfile) {
+        *self.data.profile.borrow_mut() = Some(profile);
+    }
+
+    pub fn on_disconnected(&self) {
+        *self.data.profile.borrow_mut() = None;
+    }
+
+    pub fn on_item_values(&self, values: &[(u32, DataValue)]) {
+        if self.data.recorder.borrow().is_none() {
+            return;
+        }
+        let items = self
+            .data
+            .monitored_items_pane
+            .watch_lists()
+            .into_iter()
+            .flat_map(|w| w.items.into_iter())
+            .map(|i| (i.item_id, i))
+            .collect::<HashMap<_, _>>();
+        let records = values
+            .iter()
+            .filter_map(|(item_id, data_value)| {
+                items.get(item_id).map(|item| Record::Value {
+                    node_id: &item.node_id,
+                    display_name: &item.display_name,
+                    data_value,
+                    text: data_value.value.as_ref().map_or_else(String::new, |v| {
+                        self.data
+                            .monitored_items_pane
+                            .format_item_value(*item_id, v)
+                    }),
+                })
+            })
+            .collect::<Vec<_>>();
+        self.data.write(&records);
+    }
+
+    pub fn on_events(&self, events: &[Event]) {
+        if self.data.recorder.borrow().is_none() {
+            return;
+        }
+        let monitors = self.data.events_pane.monitors();
+        let records = events
+            .iter()
+            .filter_map(|event| {
+                monitors
+                    .iter()
+                    .find(|m| m.id == event.monitor_id)
+                    .map(|monitor| Record::Event {
+                        monitor,
+                        fields: &event.fields,
+                    })
+            })
+            .collect::<Vec<_>>();
+        self.data.write(&records);
+    }
+}
+
+impl RecorderPaneImpl {
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.start_btn
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok())
+    }
+
+    fn start(&self) {
+        let dlg = RecorderDlg::new(self.parent_window().as_ref(), &self.settings.borrow());
+        let settings = match dlg.run() {
+            Some(settings) => settings,
+            None => return,
+        };
+        *self.settings.borrow_mut() = settings.clone();
+        let header = RecordingHeader {
+            profile: self.profile.borrow().clone(),
+            watch_lists: self.monitored_items_pane.watch_lists(),
+            event_monitors: self.events_pane.monitors(),
+        };
+        match Recorder::start(settings, header) {
+            Ok(recorder) => {
+                *self.recorder.borrow_mut() = Some(recorder);
+                self.start_btn.set_sensitive(false);
+                self.stop_btn.set_sensitive(true);
+                self.update_status();
+            }
+            Err(err) => self.show_error(&format!("Cannot start recording, {}", err)),
+        }
+    }
+
+    fn stop(&self) {
+        if let Some(recorder) = self.recorder.borrow_mut().take() {
+            let text = format!(
+                "Recorded {} values and events to {}",
+                recorder.records(),
+                recorder.path().display()
+            );
+            match recorder.stop() {
+                Ok(_) => self.status_label.set_text(&text),
+                Err(err) => self.show_error(&format!("Cannot finish recording, {}", err)),
+            }
+        }
+        self.start_btn.set_sensitive(true);
+        self.stop_btn.set_sensitive(false);
+    }
+
+    /// Writes records, stopping the recording if the file can't be written
+    fn write(&self, records: &[Record<'_>]) {
+        if records.is_empty() {
+            return;
+        }
+        let result = match *self.recorder.borrow_mut() {
+            Some(ref mut recorder) => recorder.write(records),
+            None => return,
+        };
+        match result {
+            Ok(_) => self.update_status(),
+            Err(err) => {
+                self.recorder.borrow_mut().take();
+                self.start_btn.set_sensitive(true);
+                self.stop_btn.set_sensitive(false);
+                self.show_error(&format!("Recording stopped, {}", err));
+            }
+        }
+    }
+
+    fn update_status(&self) {
+        if let Some(ref recorder) = *self.recorder.borrow() {
+            self.status_label.set_text(&format!(
+                "Recording to {}, {} records, rotation {}",
+                recorder.path().display(),
+                recorder.records(),
+                self.settings.borrow().rotation
+            ));
+        }
+    }
+
+    fn show_error(&self, message: &str) {
+        self.status_label.set_markup(&format!(
+            "<span foreground=\"red\">{}</span>",
+            glib::markup_escape_text(message)
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::PI,
+    fmt::Write,
+};
+
+use opcua_client::prelude::*;
+
+/// A node discovered while browsing the neighbourhood of the graph's root node.
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub node_id: NodeId,
+    pub browse_name: String,
+    pub display_name: String,
+    pub node_class: NodeClass,
+    /// Number of references followed from the root to reach this node
+    pub depth: u32,
+}
+
+/// A reference between two nodes. Inverse references are stored the other way around so every
+/// edge points in the forward direction.
+#[derive(Debug, Clone)]
+pub struct GraphEdge {
+    pub source: NodeId,
+    pub target: NodeId,
+    pub reference_type_id: NodeId,
+    pub reference_type_name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReferenceGraph {
+    pub root: NodeId,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// The shape a node is drawn with, which depends on its node class.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeShape {
+    Box,
+    RoundedBox,
+    Ellipse,
+    Hexagon,
+    Diamond,
+}
+
+pub struct NodeStyle {
+    pub shape: NodeShape,
+    pub dashed: bool,
+    pub fill: (f64, f64, f64),
+}
+
+impl NodeStyle {
+    pub fn for_node_class(node_class: NodeClass) -> NodeStyle {
+        let (shape, dashed, fill) = match node_class {
+            NodeClass::Object => (NodeShape::RoundedBox, false, (0.75, 0.85, 1.0)),
+            NodeClass::Variable => (NodeShape::Box, false, (0.8, 0.95, 0.8)),
+            NodeClass::Method => (NodeShape::Ellipse, false, (1.0, 0.85, 0.65)),
+            NodeClass::ObjectType => (NodeShape::RoundedBox, true, (0.85, 0.8, 1.0)),
+            NodeClass::VariableType => (NodeShape::Box, true, (0.85, 1.0, 0.85)),
+            NodeClass::ReferenceType => (NodeShape::Diamond, false, (0.9, 0.9, 0.9)),
+            NodeClass::DataType => (NodeShape::Hexagon, false, (1.0, 1.0, 0.75)),
+            NodeClass::View => (NodeShape::Box, true, (1.0, 0.95, 0.6)),
+            NodeClass::Unspecified => (NodeShape::Box, false, (1.0, 1.0, 1.0)),
+        };
+        NodeStyle {
+            shape,
+            dashed,
+            fill,
+        }
+    }
+
+    fn dot_attributes(&self) -> String {
+        let (shape, mut style) = match self.shape {
+            NodeShape::Box => ("box", vec![]),
+            NodeShape::RoundedBox => ("box", vec!["rounded"]),
+            NodeShape::Ellipse => ("ellipse", vec![]),
+            NodeShape::Hexagon => ("hexagon", vec![]),
+            NodeShape::Diamond => ("diamond", vec![]),
+        };
+        if self.dashed {
+            style.push("dashed");
+        }
+        style.push("filled");
+        let (r, g, b) = self.fill;
+        format!(
+            "shape={}, style=\"{}\", fillcolor=\"#{:02x}{:02x}{:02x}\"",
+            shape,
+            style.join(","),
+            (r * 255.0) as u8,
+            (g * 255.0) as u8,
+            (b * 255.0) as u8
+        )
+    }
+}
+
+impl ReferenceGraph {
+    pub fn node(&self, node_id: &NodeId) -> Option<&GraphNode> {
+        self.nodes.iter().find(|n| n.node_id == *node_id)
+    }
+
+    /// Lays the graph out radially with the root in the middle and a ring for each depth. Nodes in
+    /// a ring are ordered by the angle of the node that discovered them to reduce edge crossings.
+    pub fn layout(&self, ring_spacing: f64) -> HashMap<NodeId, (f64, f64)> {
+        let mut positions = HashMap::new();
+        let mut angles: HashMap<NodeId, f64> = HashMap::new();
+        positions.insert(self.root.clone(), (0.0, 0.0));
+        angles.insert(self.root.clone(), 0.0);
+
+        let max_depth = self.nodes.iter().map(|n| n.depth).max().unwrap_or(0);
+        for depth in 1..=max_depth {
+            let mut ring = self
+                .nodes
+                .iter()
+                .filter(|n| n.depth == depth)
+                .map(|n| (self.parent_angle(&n.node_id, &angles), n.node_id.clone()))
+                .collect::<Vec<_>>();
+            ring.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            let radius = ring_spacing * depth as f64;
+            let step = 2.0 * PI / ring.len() as f64;
+            ring.into_iter().enumerate().for_each(|(i, (_, node_id))| {
+                let angle = step * i as f64;
+                positions.insert(
+                    node_id.clone(),
+                    (radius * angle.cos(), radius * angle.sin()),
+                );
+                angles.insert(node_id, angle);
+            });
+        }
+        positions
+    }
+
+    fn parent_angle(&self, node_id: &NodeId, angles: &HashMap<NodeId, f64>) -> f64 {
+        self.edges
+            .iter()
+            .filter_map(|e| {
+                if e.target == *node_id {
+                    angles.get(&e.source)
+                } else if e.source == *node_id {
+                    angles.get(&e.target)
+                } else {
+                    None
+                }
+            })
+            .cloned()
+            .next()
+            .unwrap_or(0.0)
+    }
+
+    /// Produces the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph \"{}\" {{", escape_dot(&self.root.to_string()));
+        let _ = writeln!(dot, "  node [fontname=\"Helvetica\", fontsize=10];");
+        let _ = writeln!(dot, "  edge [fontname=\"Helvetica\", fontsize=8];");
+
+        // Graphviz ids are generated rather than using the node id which may contain anything
+        let ids = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.node_id.clone(), format!("n{}", i)))
+            .collect::<HashMap<_, _>>();
+
+        self.nodes.iter().for_each(|n| {
+            let style = NodeStyle::for_node_class(n.node_class);
+            let pen_width = if n.node_id == self.root { 3 } else { 1 };
+            let _ = writeln!(
+                dot,
+                "  {} [label=\"{}\\n{}\", tooltip=\"{:?}\", penwidth={}, {}];",
+                ids[&n.node_id],
+                escape_dot(&n.display_name),
+                escape_dot(&n.node_id.to_string()),
+                n.node_class,
+                pen_width,
+                style.dot_attributes()
+            );
+        });
+
+        self.edges.iter().for_each(|e| {
+            if let (Some(source), Some(target)) = (ids.get(&e.source), ids.get(&e.target)) {
+                let _ = writeln!(
+                    dot,
+                    "  {} -> {} [label=\"{}\"];",
+                    source,
+                    target,
+                    escape_dot(&e.reference_type_name)
+                );
+            }
+        });
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Drops references that point at nodes which weren't visited, e.g. because the depth limit
+    /// was reached, and duplicates arising from seeing a reference from both ends.
+    pub fn prune_edges(&mut self) {
+        let node_ids = self
+            .nodes
+            .iter()
+            .map(|n| n.node_id.clone())
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        self.edges.retain(|e| {
+            node_ids.contains(&e.source)
+                && node_ids.contains(&e.target)
+                && seen.insert((
+                    e.source.clone(),
+                    e.target.clone(),
+                    e.reference_type_id.clone(),
+                ))
+        });
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,484 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    type_dictionary::{
+        DecodedStructure, FieldLayout, FieldType, FieldValue, StructureKind, TypeDictionary,
+    },
+    value_edit::{self, BuiltinType, TEXT_TYPES},
+    value_format::{self, TimeDisplay},
+};
+
+const COL_NAME: u32 = 0;
+const COL_TYPE: u32 = 1;
+const COL_VALUE: u32 = 2;
+const COL_EDITABLE: u32 = 3;
+const COL_INCLUDED: u32 = 4;
+const COL_OPTIONAL: u32 = 5;
+/// The indices of fields and array elements leading from the top of the structure to the row
+const COL_PATH: u32 = 6;
+
+struct StructureEditorImpl {
+    container: gtk::Box,
+    tree: gtk::TreeView,
+    tree_model: gtk::TreeStore,
+    error_label: gtk::Label,
+    type_dictionary: Arc<TypeDictionary>,
+    structure: RefCell<DecodedStructure>,
+}
+
+/// Edits a structure field by field so it can be encoded and written. Optional fields and
+/// union members are included with a toggle, arrays of simple values are typed in as a comma
+/// separated list and arrays of structures are resized by typing in their length.
+pub struct StructureEditor {
+    data: Rc<StructureEditorImpl>,
+}
+
+impl StructureEditor {
+    pub fn new(type_dictionary: Arc<TypeDictionary>, structure: DecodedStructure) -> Self {
+        let tree_model = gtk::TreeStore::new(&[
+            glib::Type::String,
+            glib::Type::String,
+            glib::Type::String,
+            glib::Type::Bool,
+            glib::Type::Bool,
+            glib::Type::Bool,
+            glib::Type::String,
+        ]);
+        let tree = gtk::TreeView::with_model(&tree_model);
+        tree.set_enable_search(false);
+
+        let add_text_column = |title: &str, col: u32| {
+            let renderer = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", col as i32);
+            tree.append_column(&column);
+            (column, renderer)
+        };
+
+        add_text_column("Name", COL_NAME);
+
+        let include_renderer = gtk::CellRendererToggle::new();
+        let include_column = gtk::TreeViewColumn::new();
+        include_column.set_title("Include");
+        include_column.pack_start(&include_renderer, false);
+        include_column.add_attribute(&include_renderer, "active", COL_INCLUDED as i32);
+        include_column.add_attribute(&include_renderer, "visible", COL_OPTIONAL as i32);
+        include_column.add_attribute(&include_renderer, "activatable", COL_OPTIONAL as i32);
+        tree.append_column(&include_column);
+
+        add_text_column("Type", COL_TYPE);
+        let (value_column, value_renderer) = add_text_column("Value", COL_VALUE);
+        value_column.add_attribute(&value_renderer, "editable", COL_EDITABLE as i32);
+
+        let scrolled_window =
+            gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        scrolled_window.set_min_content_height(240);
+        scrolled_window.set_min_content_width(360);
+        scrolled_window.add(&tree);
+
+        let error_label = gtk::Label::new(None);
+        error_label.set_halign(gtk::Align::Start);
+        error_label.set_line_wrap(true);
+
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        container.pack_start(&scrolled_window, true, true, 0);
+        container.pack_start(&error_label, false, true, 0);
+
+        let data = Rc::new(StructureEditorImpl {
+            container,
+            tree,
+            tree_model,
+            error_label,
+            type_dictionary,
+            structure: RefCell::new(structure),
+        });
+        data.populate();
+
+        value_renderer.connect_edited(clone!(@weak data => move |_, tree_path, text| {
+            data.on_value_edited(&tree_path, text);
+        }));
+        include_renderer.connect_toggled(clone!(@weak data => move |_, tree_path| {
+            data.on_include_toggled(&tree_path);
+        }));
+
+        Self { data }
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        &self.data.container
+    }
+
+    /// Encodes the structure as an ExtensionObject
+    pub fn value(&self) -> Result<Variant, String> {
+        let structure = self.data.structure.borrow();
+        self.data
+            .type_dictionary
+            .encode(&structure)
+            .map(Variant::from)
+    }
+}
+
+impl StructureEditorImpl {
+    fn populate(&self) {
+        self.tree_model.clear();
+        let structure = self.structure.borrow();
+        self.insert_structure(None, &structure, &[]);
+        self.tree.expand_all();
+    }
+
+    fn insert_structure(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        structure: &DecodedStructure,
+        path: &[usize],
+    ) {
+        let layout = match self.type_dictionary.structure(&structure.data_type_id) {
+            Some(layout) => layout,
+            None => return,
+        };
+        layout
+            .fields
+            .iter()
+            .zip(structure.fields.iter())
+            .enumerate()
+            .for_each(|(i, (field, (_, value)))| {
+                let mut field_path = path.to_vec();
+                field_path.push(i);
+                self.insert_field(parent, field, value, &field_path);
+            });
+    }
+
+    fn insert_field(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        field: &FieldLayout,
+        value: &FieldValue,
+        path: &[usize],
+    ) {
+        let type_name = field.type_name(&self.type_dictionary);
+        let (text, editable) = match value {
+            FieldValue::Array(values) => {
+                if let FieldType::Structure(_) = field.field_type {
+                    (format!("[{}]", values.len()), true)
+                } else {
+                    (
+                        array_to_edit_text(&self.type_dictionary, &field.field_type, values),
+                        is_editable(&field.field_type),
+                    )
+                }
+            }
+            value => element_text(&self.type_dictionary, &field.field_type, value),
+        };
+        let iter = self.insert_row(
+            parent,
+            &field.name,
+            &type_name,
+            &text,
+            editable,
+            *value != FieldValue::Absent,
+            field.switch.is_some(),
+            path,
+        );
+
+        match value {
+            FieldValue::Structure(s) => self.insert_structure(Some(&iter), s, path),
+            FieldValue::Array(values) => {
+                if let FieldType::Structure(_) = field.field_type {
+                    values.iter().enumerate().for_each(|(i, v)| {
+                        let mut element_path = path.to_vec();
+                        element_path.push(i);
+                        let element_iter = self.insert_row(
+                            Some(&iter),
+                            &format!("[{}]", i),
+                            type_name.trim_end_matches("[]"),
+                            "",
+                            false,
+                            true,
+                            false,
+                            &element_path,
+                        );
+                        if let FieldValue::Structure(s) = v {
+                            self.insert_structure(Some(&element_iter), s, &element_path);
+                        }
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn insert_row(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        name: &str,
+        type_name: &str,
+        value: &str,
+        editable: bool,
+        included: bool,
+        optional: bool,
+        path: &[usize],
+    ) -> gtk::TreeIter {
+        let path = path_to_string(path);
+        self.tree_model.insert_with_values(
+            parent,
+            None,
+            &[
+                COL_NAME,
+                COL_TYPE,
+                COL_VALUE,
+                COL_EDITABLE,
+                COL_INCLUDED,
+                COL_OPTIONAL,
+                COL_PATH,
+            ],
+            &[
+                &name, &type_name, &value, &editable, &included, &optional, &path,
+            ],
+        )
+    }
+
+    fn row_path(&self, tree_path: &gtk::TreePath) -> Option<(gtk::TreeIter, Vec<usize>)> {
+        let iter = self.tree_model.get_iter(tree_path)?;
+        let path = self
+            .tree_model
+            .get_value(&iter, COL_PATH as i32)
+            .get::<String>()
+            .ok()
+            .flatten()?;
+        Some((iter, path_from_string(&path)))
+    }
+
+    fn set_error(&self, error: Option<&str>) {
+        match error {
+            Some(error) => self.error_label.set_markup(&format!(
+                "<span foreground=\"red\">{}</span>",
+                glib::markup_escape_text(error)
+            )),
+            None => self.error_label.set_text(""),
+        }
+    }
+
+    fn on_value_edited(&self, tree_path: &gtk::TreePath, text: &str) {
+        let (iter, path) = match self.row_path(tree_path) {
+            Some(row) => row,
+            None => return,
+        };
+        let mut structure = self.structure.borrow_mut();
+        let (field, is_element, value) = match locate(&self.type_dictionary, &mut structure, &path)
+        {
+            Some(located) => located,
+            None => return,
+        };
+
+        let result = if field.is_array() && !is_element {
+            match field.field_type {
+                FieldType::Structure(_) => {
+                    // Arrays of structures are resized by their length
+                    match text.trim().parse::<usize>() {
+                        Ok(len) => {
+                            if let FieldValue::Array(values) = value {
+                                values.resize_with(len, || {
+                                    self.type_dictionary
+                                        .default_element_value(&field.field_type, 0)
+                                });
+                            }
+                            drop(structure);
+                            self.populate();
+                            self.set_error(None);
+                            return;
+                        }
+                        Err(_) => Err(format!("\"{}\" is not a valid length", text)),
+                    }
+                }
+                ref field_type => value_edit::parse_csv(text)
+                    .into_iter()
+                    .flatten()
+                    .map(|t| parse_element(&self.type_dictionary, field_type, &t))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(FieldValue::Array),
+            }
+        } else {
+            parse_element(&self.type_dictionary, &field.field_type, text)
+        };
+
+        match result {
+            Ok(new_value) => {
+                let text = match new_value {
+                    FieldValue::Array(ref values) => {
+                        array_to_edit_text(&self.type_dictionary, &field.field_type, values)
+                    }
+                    ref v => element_text(&self.type_dictionary, &field.field_type, v).0,
+                };
+                *value = new_value;
+                self.tree_model
+                    .set_value(&iter, COL_VALUE, &text.to_value());
+                self.set_error(None);
+            }
+            Err(err) => self.set_error(Some(&format!("{}: {}", field.name, err))),
+        }
+    }
+
+    /// Includes or excludes an optional field or union member. Only one member of a union can
+    /// be included.
+    fn on_include_toggled(&self, tree_path: &gtk::TreePath) {
+        let path = match self.row_path(tree_path) {
+            Some((_, path)) => path,
+            None => return,
+        };
+        {
+            let mut structure = self.structure.borrow_mut();
+            let (parent_path, index) = path.split_at(path.len() - 1);
+            let parent = if parent_path.is_empty() {
+                Some(&mut *structure)
+            } else {
+                match locate(&self.type_dictionary, &mut structure, parent_path) {
+                    Some((_, _, FieldValue::Structure(s))) => Some(s),
+                    _ => None,
+                }
+            };
+            let parent = match parent {
+                Some(parent) => parent,
+                None => return,
+            };
+            let layout = match self.type_dictionary.structure(&parent.data_type_id) {
+                Some(layout) => layout,
+                None => return,
+            };
+            let index = index[0];
+            let field = &layout.fields[index];
+            if parent.fields[index].1 == FieldValue::Absent {
+                if layout.kind == StructureKind::Union {
+                    parent
+                        .fields
+                        .iter_mut()
+                        .for_each(|(_, v)| *v = FieldValue::Absent);
+                }
+                parent.fields[index].1 = self.type_dictionary.default_field_value(field, 0);
+            } else {
+                parent.fields[index].1 = FieldValue::Absent;
+            }
+        }
+        self.populate();
+    }
+}
+
+/// Finds the layout and value of the field or array element at the path
+fn locate<'a>(
+    type_dictionary: &'a TypeDictionary,
+    structure: &'a mut DecodedStructure,
+    path: &[usize],
+) -> Option<(&'a FieldLayout, bool, &'a mut FieldValue)> {
+    let layout = type_dictionary.structure(&structure.data_type_id)?;
+    let field = layout.fields.get(*path.first()?)?;
+    let value = &mut structure.fields.get_mut(path[0])?.1;
+    let rest = &path[1..];
+    if rest.is_empty() {
+        return Some((field, false, value));
+    }
+    match value {
+        FieldValue::Array(values) if field.is_array() => {
+            let element = values.get_mut(rest[0])?;
+            if rest.len() == 1 {
+                Some((field, true, element))
+            } else if let FieldValue::Structure(s) = element {
+                locate(type_dictionary, s, &rest[1..])
+            } else {
+                None
+            }
+        }
+        FieldValue::Structure(s) => locate(type_dictionary, s, rest),
+        _ => None,
+    }
+}
+
+fn path_to_string(path: &[usize]) -> String {
+    path.iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_from_string(path: &str) -> Vec<usize> {
+    path.split('/').filter_map(|i| i.parse().ok()).collect()
+}
+
+fn is_editable(field_type: &FieldType) -> bool {
+    match field_type {
+        FieldType::Builtin(t) => TEXT_TYPES.contains(t),
+        FieldType::Enumeration(_) => true,
+        FieldType::Structure(_) => false,
+    }
+}
+
+/// The members of an enumeration field, if the dictionary knows them
+fn enum_members<'a>(td: &'a TypeDictionary, field_type: &FieldType) -> Option<&'a [(i64, String)]> {
+    match field_type {
+        FieldType::Enumeration(id) => td.enumeration(id).map(|e| e.members.as_slice()),
+        _ => None,
+    }
+}
+
+/// The edit text of a single scalar, showing enumerations as "Name (value)"
+fn scalar_text(td: &TypeDictionary, field_type: &FieldType, value: &Variant) -> String {
+    match (enum_members(td, field_type), value) {
+        (Some(members), Variant::Int32(v)) => value_edit::enum_value_to_string(members, *v as i64),
+        _ => value_edit::scalar_to_edit_text(value),
+    }
+}
+
+/// The text shown for a single value and whether it can be edited
+fn element_text(td: &TypeDictionary, field_type: &FieldType, value: &FieldValue) -> (String, bool) {
+    match value {
+        FieldValue::Scalar(v) if is_editable(field_type) => (scalar_text(td, field_type, v), true),
+        FieldValue::Scalar(v) => (value_format::variant_to_string(v, TimeDisplay::Utc), false),
+        _ => (String::new(), false),
+    }
+}
+
+fn parse_element(
+    td: &TypeDictionary,
+    field_type: &FieldType,
+    text: &str,
+) -> Result<FieldValue, String> {
+    if let Some(members) = enum_members(td, field_type) {
+        return value_edit::parse_enum(members, text).map(FieldValue::Scalar);
+    }
+    let builtin_type = match field_type {
+        FieldType::Builtin(t) if TEXT_TYPES.contains(t) => *t,
+        FieldType::Enumeration(_) => BuiltinType::Int32,
+        _ => return Err("The value cannot be edited".to_string()),
+    };
+    value_edit::parse_scalar(builtin_type, text.trim()).map(FieldValue::Scalar)
+}
+
+/// Formats an array of simple values as a comma separated list, quoting where needed
+fn array_to_edit_text(
+    td: &TypeDictionary,
+    field_type: &FieldType,
+    values: &[FieldValue],
+) -> String {
+    values
+        .iter()
+        .map(|v| match v {
+            FieldValue::Scalar(v) => {
+                let text = scalar_text(td, field_type, v);
+                if text.contains(',') || text.contains('"') {
+                    format!("\"{}\"", text.replace('"', "\"\""))
+                } else {
+                    text
+                }
+            }
+            _ => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,140 @@
+use gtk::{self, prelude::*};
+
+use crate::watch_list::SubscriptionSettings;
+
+/// Edits the publishing parameters of a subscription
+pub(crate) struct SubscriptionDlg {
+    dlg: gtk::Dialog,
+    publishing_interval_spin: gtk::SpinButton,
+    lifetime_count_spin: gtk::SpinButton,
+    keep_alive_count_spin: gtk::SpinButton,
+    max_notifications_spin: gtk::SpinButton,
+    priority_spin: gtk::SpinButton,
+    publishing_enabled_check: gtk::CheckButton,
+    status_label: gtk::Label,
+}
+
+impl SubscriptionDlg {
+    pub fn new(parent: Option<&gtk::Window>, title: &str, settings: &SubscriptionSettings) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some(title),
+            parent,
+            gtk::DialogFlags::MODAL,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Apply", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+        let spin_row = |row: i32, text: &str, max: f64, value: f64, tooltip: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_halign(gtk::Align::End);
+            let spin = gtk::SpinButton::with_range(0.0, max, 1.0);
+            spin.set_digits(0);
+            spin.set_value(value);
+            spin.set_halign(gtk::Align::Start);
+            spin.set_tooltip_text(Some(tooltip));
+            grid.attach(&label, 0, row, 1, 1);
+            grid.attach(&spin, 1, row, 1, 1);
+            spin
+        };
+
+        let publishing_interval_spin = spin_row(
+            0,
+            "Publishing Interval (ms):",
+            i32::MAX as f64,
+            settings.publishing_interval,
+            "How often the server sends the changes it has collected",
+        );
+        let lifetime_count_spin = spin_row(
+            1,
+            "Lifetime Count:",
+            u32::MAX as f64,
+            settings.lifetime_count as f64,
+            "Publishing intervals without a Publish request before the server deletes the subscription",
+        );
+        let keep_alive_count_spin = spin_row(
+            2,
+            "Max Keep-Alive Count:",
+            u32::MAX as f64,
+            settings.max_keep_alive_count as f64,
+            "Publishing intervals without a change before the server sends a keep-alive",
+        );
+        let max_notifications_spin = spin_row(
+            3,
+            "Max Notifications:",
+            u32::MAX as f64,
+            settings.max_notifications_per_publish as f64,
+            "The most notifications in one publish, 0 for no limit",
+        );
+        let priority_spin = spin_row(
+            4,
+            "Priority:",
+            u8::MAX as f64,
+            settings.priority as f64,
+            "Subscriptions with a higher priority are published first",
+        );
+
+        let publishing_enabled_check = gtk::CheckButton::with_label("Publishing enabled");
+        publishing_enabled_check.set_active(settings.publishing_enabled);
+        grid.attach(&publishing_enabled_check, 1, 5, 1, 1);
+
+        let status_label = gtk::Label::new(Some("The server may revise these parameters"));
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        grid.attach(&status_label, 0, 6, 2, 1);
+
+        dlg.get_content_area().pack_start(&grid, true, true, 0);
+
+        Self {
+            dlg,
+            publishing_interval_spin,
+            lifetime_count_spin,
+            keep_alive_count_spin,
+            max_notifications_spin,
+            priority_spin,
+            publishing_enabled_check,
+            status_label,
+        }
+    }
+
+    /// Runs the dialog and returns the publishing parameters, or none if it is cancelled.
+    /// Parameters a server would have to revise, such as a lifetime count below three
+    /// keep-alives, are refused in the dialog rather than sent.
+    pub fn run(&self) -> Option<SubscriptionSettings> {
+        self.dlg.show_all();
+        let mut result = None;
+        while self.dlg.run() == gtk::ResponseType::Apply {
+            let settings = self.settings();
+            match settings.validate() {
+                Ok(_) => {
+                    result = Some(settings);
+                    break;
+                }
+                Err(message) => self.status_label.set_markup(&format!(
+                    "<span foreground=\"red\">{}</span>",
+                    glib::markup_escape_text(&message)
+                )),
+            }
+        }
+        self.dlg.close();
+        result
+    }
+
+    fn settings(&self) -> SubscriptionSettings {
+        let count = |spin: &gtk::SpinButton| spin.get_value() as u32;
+        SubscriptionSettings {
+            publishing_interval: self.publishing_interval_spin.get_value(),
+            lifetime_count: count(&self.lifetime_count_spin),
+            max_keep_alive_count: count(&self.keep_alive_count_spin),
+            max_notifications_per_publish: count(&self.max_notifications_spin),
+            priority: self.priority_spin.get_value_as_int() as u8,
+            publishing_enabled: self.publishing_enabled_check.get_active(),
+        }
+    }
+}
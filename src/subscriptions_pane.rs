@@ -0,0 +1,292 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    monitored_items_pane::MonitoredItemsPane,
+    subscription_dlg::SubscriptionDlg,
+    value_format,
+    watch_list::{Acquisition, RevisedSubscription, SubscriptionSettings},
+};
+
+const COL_WATCH_LIST_ID: u32 = 0;
+const COL_NAME: u32 = 1;
+const COL_SUBSCRIPTION_ID: u32 = 2;
+const COL_PUBLISHING_INTERVAL: u32 = 3;
+const COL_LIFETIME_COUNT: u32 = 4;
+const COL_KEEP_ALIVE_COUNT: u32 = 5;
+const COL_MAX_NOTIFICATIONS: u32 = 6;
+const COL_PRIORITY: u32 = 7;
+const COL_PUBLISHING_ENABLED: u32 = 8;
+const COL_ITEMS: u32 = 9;
+const COL_STATUS: u32 = 10;
+const COL_STATUS_COLOUR: u32 = 11;
+
+struct SubscriptionsPaneImpl {
+    monitored_items_pane: MonitoredItemsPane,
+    subscriptions_tree: gtk::TreeView,
+    subscriptions_model: gtk::ListStore,
+    /// What the server granted to the subscription of each watch list, or why it refused
+    revised: RefCell<HashMap<u32, Result<RevisedSubscription, StatusCode>>>,
+}
+
+/// Lists the subscriptions of the watch lists that use one, with the publishing parameters the
+/// server granted, and creates, changes and deletes them
+pub struct SubscriptionsPane {
+    data: Rc<SubscriptionsPaneImpl>,
+}
+
+impl SubscriptionsPane {
+    pub fn new(builder: Rc<gtk::Builder>, monitored_items_pane: MonitoredItemsPane) -> Self {
+        let new_btn: gtk::Button = builder.get_object("subscription_new_btn").unwrap();
+        let edit_btn: gtk::Button = builder.get_object("subscription_edit_btn").unwrap();
+        let delete_btn: gtk::Button = builder.get_object("subscription_delete_btn").unwrap();
+        let publishing_renderer: gtk::CellRendererToggle = builder
+            .get_object("subscriptions_publishing_renderer")
+            .unwrap();
+
+        let data = Rc::new(SubscriptionsPaneImpl {
+            monitored_items_pane,
+            subscriptions_tree: builder.get_object("subscriptions_tree").unwrap(),
+            subscriptions_model: builder.get_object("subscriptions_model").unwrap(),
+            revised: RefCell::new(HashMap::new()),
+        });
+
+        data.monitored_items_pane
+            .connect_watch_lists_changed(clone!(@weak data => move || {
+                data.refresh();
+            }));
+
+        new_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.new_subscription();
+        }));
+        edit_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(id) = data.selected_watch_list_id() {
+                data.edit_subscription(id);
+            }
+        }));
+        data.subscriptions_tree
+            .connect_row_activated(clone!(@weak data => move |_, _, _| {
+                if let Some(id) = data.selected_watch_list_id() {
+                    data.edit_subscription(id);
+                }
+            }));
+        delete_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(id) = data.selected_watch_list_id() {
+                data.revised.borrow_mut().remove(&id);
+                data.monitored_items_pane.remove_watch_list(id);
+            }
+        }));
+        publishing_renderer.connect_toggled(clone!(@weak data => move |_, path| {
+            data.toggle_publishing(&path);
+        }));
+
+        data.refresh();
+
+        Self { data }
+    }
+
+    pub fn on_subscription_revised(
+        &self,
+        watch_list_id: u32,
+        result: Result<RevisedSubscription, StatusCode>,
+    ) {
+        self.data.revised.borrow_mut().insert(watch_list_id, result);
+        self.data.refresh();
+    }
+
+    /// What the server granted no longer applies. The watch lists ask for their subscriptions
+    /// again when a new session is connected.
+    pub fn on_disconnected(&self) {
+        self.data.revised.borrow_mut().clear();
+        self.data.refresh();
+    }
+}
+
+impl SubscriptionsPaneImpl {
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.subscriptions_tree
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok())
+    }
+
+    fn selected_watch_list_id(&self) -> Option<u32> {
+        let (model, iter) = self.subscriptions_tree.get_selection().get_selected()?;
+        model
+            .get_value(&iter, COL_WATCH_LIST_ID as i32)
+            .get::<u32>()
+            .ok()
+            .flatten()
+    }
+
+    fn new_subscription(&self) {
+        let dlg = SubscriptionDlg::new(
+            self.parent_window().as_ref(),
+            "New Subscription",
+            &SubscriptionSettings::default(),
+        );
+        if let Some(settings) = dlg.run() {
+            self.monitored_items_pane.add_subscription(settings);
+        }
+    }
+
+    fn edit_subscription(&self, watch_list_id: u32) {
+        let watch_list = match self
+            .monitored_items_pane
+            .watch_lists()
+            .into_iter()
+            .find(|w| w.id == watch_list_id)
+        {
+            Some(watch_list) => watch_list,
+            None => return,
+        };
+        let dlg = SubscriptionDlg::new(
+            self.parent_window().as_ref(),
+            &format!("Subscription - {}", watch_list.name),
+            &watch_list.subscription,
+        );
+        if let Some(settings) = dlg.run() {
+            self.monitored_items_pane
+                .set_subscription_settings(watch_list_id, settings);
+        }
+    }
+
+    fn toggle_publishing(&self, path: &gtk::TreePath) {
+        let iter = match self.subscriptions_model.get_iter(path) {
+            Some(iter) => iter,
+            None => return,
+        };
+        let watch_list_id = self
+            .subscriptions_model
+            .get_value(&iter, COL_WATCH_LIST_ID as i32)
+            .get::<u32>()
+            .ok()
+            .flatten();
+        let watch_list = watch_list_id.and_then(|id| {
+            self.monitored_items_pane
+                .watch_lists()
+                .into_iter()
+                .find(|w| w.id == id)
+        });
+        if let Some(watch_list) = watch_list {
+            let settings = SubscriptionSettings {
+                publishing_enabled: !watch_list.subscription.publishing_enabled,
+                ..watch_list.subscription
+            };
+            self.monitored_items_pane
+                .set_subscription_settings(watch_list.id, settings);
+        }
+    }
+
+    /// Shows a row for each watch list that uses a subscription, keeping the selection
+    fn refresh(&self) {
+        let selected = self.selected_watch_list_id();
+        self.subscriptions_model.clear();
+        let revised = self.revised.borrow();
+        self.monitored_items_pane
+            .watch_lists()
+            .iter()
+            .filter(|w| w.acquisition == Acquisition::Subscription)
+            .for_each(|watch_list| {
+                let settings = &watch_list.subscription;
+                // Requested values are shown until the server says what it granted
+                let (subscription_id, interval, lifetime, keep_alive, status, colour) =
+                    match revised.get(&watch_list.id) {
+                        Some(Ok(r)) => (
+                            r.subscription_id.to_string(),
+                            revised_text(
+                                format!("{} ms", r.publishing_interval),
+                                r.publishing_interval != settings.publishing_interval,
+                                format!("{} ms", settings.publishing_interval),
+                            ),
+                            revised_text(
+                                r.lifetime_count.to_string(),
+                                r.lifetime_count != settings.lifetime_count,
+                                settings.lifetime_count.to_string(),
+                            ),
+                            revised_text(
+                                r.max_keep_alive_count.to_string(),
+                                r.max_keep_alive_count != settings.max_keep_alive_count,
+                                settings.max_keep_alive_count.to_string(),
+                            ),
+                            if settings.publishing_enabled {
+                                "Publishing".to_string()
+                            } else {
+                                "Publishing disabled".to_string()
+                            },
+                            None,
+                        ),
+                        Some(Err(status_code)) => (
+                            String::new(),
+                            format!("{} ms", settings.publishing_interval),
+                            settings.lifetime_count.to_string(),
+                            settings.max_keep_alive_count.to_string(),
+                            format!(
+                                "Rejected, {}",
+                                value_format::status_code_to_string(*status_code)
+                            ),
+                            Some("red"),
+                        ),
+                        None => (
+                            String::new(),
+                            format!("{} ms", settings.publishing_interval),
+                            settings.lifetime_count.to_string(),
+                            settings.max_keep_alive_count.to_string(),
+                            "Not created".to_string(),
+                            Some("grey"),
+                        ),
+                    };
+                let max_notifications = if settings.max_notifications_per_publish == 0 {
+                    "No limit".to_string()
+                } else {
+                    settings.max_notifications_per_publish.to_string()
+                };
+                let iter = self.subscriptions_model.insert_with_values(
+                    None,
+                    &[
+                        COL_WATCH_LIST_ID,
+                        COL_NAME,
+                        COL_SUBSCRIPTION_ID,
+                        COL_PUBLISHING_INTERVAL,
+                        COL_LIFETIME_COUNT,
+                        COL_KEEP_ALIVE_COUNT,
+                        COL_MAX_NOTIFICATIONS,
+                        COL_PRIORITY,
+                        COL_PUBLISHING_ENABLED,
+                        COL_ITEMS,
+                        COL_STATUS,
+                        COL_STATUS_COLOUR,
+                    ],
+                    &[
+                        &watch_list.id,
+                        &watch_list.name,
+                        &subscription_id,
+                        &interval,
+                        &lifetime,
+                        &keep_alive,
+                        &max_notifications,
+                        &(settings.priority as u32),
+                        &settings.publishing_enabled,
+                        &(watch_list.items.len() as u32),
+                        &status,
+                        &colour,
+                    ],
+                );
+                if selected == Some(watch_list.id) {
+                    self.subscriptions_tree.get_selection().select_iter(&iter);
+                }
+            });
+    }
+}
+
+/// The text of a revised parameter, with what was asked for if the server changed it
+fn revised_text(revised: String, is_revised: bool, requested: String) -> String {
+    if is_revised {
+        format!("{} (asked {})", revised, requested)
+    } else {
+        revised
+    }
+}
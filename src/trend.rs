@@ -0,0 +1,299 @@
+use std::{collections::VecDeque, fmt::Write};
+
+use chrono::{TimeZone, Utc};
+
+use opcua_client::prelude::*;
+
+use crate::{engineering_units::variant_to_f64, value_edit, value_format};
+
+/// Samples older than this, relative to the newest sample of their series, are discarded
+const MAX_HISTORY: f64 = 24.0 * 60.0 * 60.0;
+/// The most samples kept for one series
+const MAX_SAMPLES: usize = 100_000;
+
+/// Colours given to series in the order they are added
+const PALETTE: [(f64, f64, f64); 8] = [
+    (0.12, 0.47, 0.71),
+    (0.84, 0.15, 0.16),
+    (0.17, 0.63, 0.17),
+    (0.58, 0.40, 0.74),
+    (1.0, 0.50, 0.05),
+    (0.55, 0.34, 0.29),
+    (0.89, 0.47, 0.76),
+    (0.09, 0.75, 0.81),
+];
+
+/// How far a sample can be trusted, from the severity of its status code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quality {
+    Good,
+    Uncertain,
+    Bad,
+}
+
+impl Quality {
+    pub fn from_status_code(status_code: StatusCode) -> Self {
+        if status_code.is_bad() {
+            Quality::Bad
+        } else if status_code.is_uncertain() {
+            Quality::Uncertain
+        } else {
+            Quality::Good
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrendSample {
+    /// Seconds since the Unix epoch
+    pub time: f64,
+    /// The value, or none when the value isn't numeric or the server sent none
+    pub value: Option<f64>,
+    pub status_code: StatusCode,
+}
+
+impl TrendSample {
+    pub fn quality(&self) -> Quality {
+        Quality::from_status_code(self.status_code)
+    }
+}
+
+/// The values of one watch item over time
+#[derive(Debug, Clone)]
+pub struct TrendSeries {
+    pub item_id: u32,
+    pub name: String,
+    pub colour: (f64, f64, f64),
+    /// The symbol of the engineering units of the item, if it is an analog item with units
+    pub units: Option<String>,
+    /// The members of the enumeration the item's DataType is, if it is one
+    pub enum_members: Vec<(i64, String)>,
+    /// Samples in time order
+    pub samples: VecDeque<TrendSample>,
+}
+
+impl TrendSeries {
+    /// Formats a value with the name of the enumeration member it stands for and the units
+    pub fn format_value(&self, v: f64) -> String {
+        let text = if !self.enum_members.is_empty() && v.fract() == 0.0 {
+            value_edit::enum_value_to_string(&self.enum_members, v as i64)
+        } else {
+            v.to_string()
+        };
+        match self.units {
+            Some(ref units) => format!("{} {}", text, units),
+            None => text,
+        }
+    }
+
+    fn push(&mut self, sample: TrendSample) {
+        // Values that arrive out of order are placed by their timestamp
+        let index = self
+            .samples
+            .iter()
+            .rposition(|s| s.time <= sample.time)
+            .map_or(0, |i| i + 1);
+        self.samples.insert(index, sample);
+
+        let newest = self.samples.back().map_or(0.0, |s| s.time);
+        while self.samples.len() > MAX_SAMPLES
+            || self
+                .samples
+                .front()
+                .map_or(false, |s| s.time < newest - MAX_HISTORY)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The sample that holds at the time, i.e. the last one at or before it
+    pub fn sample_at(&self, time: f64) -> Option<&TrendSample> {
+        self.samples.iter().rev().find(|s| s.time <= time)
+    }
+
+    /// The samples that are drawn between the times, which include the one holding at the start
+    pub fn samples_between(&self, start: f64, end: f64) -> impl Iterator<Item = &TrendSample> {
+        let first = self
+            .samples
+            .iter()
+            .rposition(|s| s.time <= start)
+            .unwrap_or(0);
+        self.samples
+            .iter()
+            .skip(first)
+            .take_while(move |s| s.time <= end)
+    }
+
+    /// The lowest and highest values between the times
+    pub fn value_range(&self, start: f64, end: f64) -> Option<(f64, f64)> {
+        self.samples_between(start, end)
+            .filter_map(|s| s.value)
+            .fold(None, |range, v| match range {
+                None => Some((v, v)),
+                Some((low, high)) => Some((low.min(v), high.max(v))),
+            })
+    }
+}
+
+/// The series plotted by the trend chart
+#[derive(Debug, Default)]
+pub struct Trend {
+    pub series: Vec<TrendSeries>,
+    /// How many series have been added, which picks the colour of the next
+    added: usize,
+}
+
+impl Trend {
+    /// Adds a series for the item unless there is one already. Returns true if it was added.
+    pub fn add_series(&mut self, item_id: u32, name: &str) -> bool {
+        if self.series.iter().any(|s| s.item_id == item_id) {
+            return false;
+        }
+        self.series.push(TrendSeries {
+            item_id,
+            name: name.to_string(),
+            colour: PALETTE[self.added % PALETTE.len()],
+            units: None,
+            enum_members: Vec::new(),
+            samples: VecDeque::new(),
+        });
+        self.added += 1;
+        true
+    }
+
+    pub fn remove_series(&mut self, item_id: u32) {
+        self.series.retain(|s| s.item_id != item_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.series.clear();
+        self.added = 0;
+    }
+
+    /// Adds the value of an item if it has a series. The source timestamp is preferred, then
+    /// the server timestamp, then the time it was received. Returns true if it was added.
+    pub fn add_value(&mut self, item_id: u32, data_value: &DataValue, received: f64) -> bool {
+        let series = match self.series.iter_mut().find(|s| s.item_id == item_id) {
+            Some(series) => series,
+            None => return false,
+        };
+        let time = data_value
+            .source_timestamp
+            .as_ref()
+            .or_else(|| data_value.server_timestamp.as_ref())
+            .map_or(received, date_time_to_seconds);
+        let value = data_value.value.as_ref().and_then(|v| match v {
+            Variant::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            v => variant_to_f64(v),
+        });
+        series.push(TrendSample {
+            time,
+            value,
+            status_code: data_value.status.unwrap_or(StatusCode::Good),
+        });
+        true
+    }
+
+    /// The samples of every series between the times as CSV, one row per sample. The value is
+    /// followed by its units and, for enumerations, the member it stands for.
+    pub fn to_csv(&self, start: f64, end: f64) -> String {
+        let mut csv = String::from("Series,Timestamp,Value,Units,Member,Status\n");
+        self.series.iter().for_each(|series| {
+            series
+                .samples
+                .iter()
+                .filter(|s| s.time >= start && s.time <= end)
+                .for_each(|s| {
+                    let member = s
+                        .value
+                        .filter(|v| !series.enum_members.is_empty() && v.fract() == 0.0)
+                        .and_then(|v| {
+                            series
+                                .enum_members
+                                .iter()
+                                .find(|(value, _)| *value == v as i64)
+                        })
+                        .map_or("", |(_, name)| name.as_str());
+                    let _ = writeln!(
+                        csv,
+                        "{},{},{},{},{},{}",
+                        csv_field(&series.name),
+                        seconds_to_rfc3339(s.time),
+                        s.value.map_or_else(String::new, |v| v.to_string()),
+                        csv_field(series.units.as_deref().unwrap_or_default()),
+                        csv_field(member),
+                        csv_field(&value_format::status_code_to_string(s.status_code))
+                    );
+                });
+        });
+        csv
+    }
+}
+
+pub fn date_time_to_seconds(v: &DateTime) -> f64 {
+    v.as_chrono().timestamp_millis() as f64 / 1000.0
+}
+
+pub fn seconds_to_rfc3339(time: f64) -> String {
+    Utc.timestamp_millis((time * 1000.0) as i64).to_rfc3339()
+}
+
+/// The time now in seconds since the Unix epoch
+pub fn now() -> f64 {
+    Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+/// Quotes a CSV field if it holds a separator, quote or line break
+pub fn csv_field(text: &str) -> String {
+    if text.contains(|c: char| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Evenly spaced values of 1, 2 or 5 times a power of ten covering the range, aiming for
+/// about the number of ticks asked for
+pub fn ticks(low: f64, high: f64, count: usize) -> Vec<f64> {
+    if !(high - low).is_finite() || high <= low || count == 0 {
+        return vec![];
+    }
+    let rough = (high - low) / count as f64;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    let mut tick = (low / step).ceil() * step;
+    let mut ticks = Vec::new();
+    while tick <= high {
+        ticks.push(tick);
+        tick += step;
+    }
+    ticks
+}
+
+/// Time ticks at whole seconds, minutes or hours covering the range
+pub fn time_ticks(start: f64, end: f64, count: usize) -> Vec<f64> {
+    const STEPS: [f64; 16] = [
+        0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 900.0, 1800.0,
+        3600.0,
+    ];
+    if !(end - start).is_finite() || end <= start || count == 0 {
+        return vec![];
+    }
+    let rough = (end - start) / count as f64;
+    let step = STEPS
+        .iter()
+        .copied()
+        .find(|step| *step >= rough)
+        .unwrap_or_else(|| (rough / 3600.0).ceil() * 3600.0);
+    let mut tick = (start / step).ceil() * step;
+    let mut ticks = Vec::new();
+    while tick <= end {
+        ticks.push(tick);
+        tick += step;
+    }
+    ticks
+}
@@ -0,0 +1,930 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    f64::consts::PI,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use chrono::{Local, TimeZone};
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    monitored_items_pane::MonitoredItemsPane,
+    trend::{self, Quality, Trend},
+    value_format,
+};
+
+const FONT_SIZE: f64 = 10.0;
+const MARGIN: f64 = 10.0;
+/// Width of each value axis, which holds its tick labels
+const AXIS_WIDTH: f64 = 56.0;
+const LEGEND_HEIGHT: f64 = 20.0;
+const TIME_AXIS_HEIGHT: f64 = 20.0;
+/// Milliseconds between redraws while the chart follows the present
+const REFRESH_INTERVAL: u32 = 250;
+const MIN_WINDOW: f64 = 1.0;
+const MAX_WINDOW: f64 = 24.0 * 60.0 * 60.0;
+const MIN_Y_ZOOM: f64 = 0.01;
+const MAX_Y_ZOOM: f64 = 1000.0;
+/// The size of exported images when the chart has not been shown
+const DEFAULT_EXPORT_SIZE: (i32, i32) = (800, 500);
+
+/// The part of the trend that is drawn
+#[derive(Debug, Clone, Copy)]
+struct Viewport {
+    start: f64,
+    end: f64,
+    /// The present, which the last value of a series is drawn up to
+    now: f64,
+    shared_axis: bool,
+    /// The automatic range of the values is divided by this
+    y_zoom: f64,
+    /// How far the values are moved down, as a fraction of the range shown
+    y_pan: f64,
+}
+
+impl Viewport {
+    fn axis_count(&self, trend: &Trend) -> usize {
+        if self.shared_axis {
+            1
+        } else {
+            trend.series.len().max(1)
+        }
+    }
+}
+
+/// The bounds of the plot inside the axes and legend, as left, top, right and bottom
+fn plot_area((width, height): (f64, f64), axis_count: usize) -> (f64, f64, f64, f64) {
+    (
+        MARGIN + AXIS_WIDTH * axis_count as f64,
+        LEGEND_HEIGHT + MARGIN / 2.0,
+        width - MARGIN,
+        height - TIME_AXIS_HEIGHT,
+    )
+}
+
+struct TrendViewState {
+    trend: Trend,
+    /// Seconds shown across the chart
+    window: f64,
+    /// The time at the right edge while paused or scrolled back, otherwise the chart follows
+    /// the present
+    end: Option<f64>,
+    y_zoom: f64,
+    y_pan: f64,
+    drag_start: Option<(f64, f64)>,
+    cursor: Option<(f64, f64)>,
+    /// Where each series is in the legend, to find the one clicked on
+    legend: Vec<(u32, (f64, f64, f64, f64))>,
+}
+
+struct TrendViewImpl {
+    main_window: Rc<gtk::ApplicationWindow>,
+    drawing_area: gtk::DrawingArea,
+    window_spin: gtk::SpinButton,
+    pause_btn: gtk::ToggleButton,
+    shared_axis_check: gtk::CheckButton,
+    status_label: gtk::Label,
+    monitored_items_pane: MonitoredItemsPane,
+    state: RefCell<TrendViewState>,
+}
+
+/// Plots the numeric values of watch items over time. The chart follows the present until it is
+/// paused, panned or zoomed, and shows the value of each series under the cursor.
+pub struct TrendView {
+    data: Rc<TrendViewImpl>,
+}
+
+impl TrendView {
+    pub fn new(builder: Rc<gtk::Builder>, monitored_items_pane: MonitoredItemsPane) -> Self {
+        let live_btn: gtk::Button = builder.get_object("trend_live_btn").unwrap();
+        let clear_btn: gtk::Button = builder.get_object("trend_clear_btn").unwrap();
+        let export_png_btn: gtk::Button = builder.get_object("trend_export_png_btn").unwrap();
+        let export_svg_btn: gtk::Button = builder.get_object("trend_export_svg_btn").unwrap();
+        let export_csv_btn: gtk::Button = builder.get_object("trend_export_csv_btn").unwrap();
+        let window_spin: gtk::SpinButton = builder.get_object("trend_window_spin").unwrap();
+
+        let data = Rc::new(TrendViewImpl {
+            main_window: Rc::new(builder.get_object("main_window").unwrap()),
+            drawing_area: builder.get_object("trend_drawing_area").unwrap(),
+            pause_btn: builder.get_object("trend_pause_btn").unwrap(),
+            shared_axis_check: builder.get_object("trend_shared_axis_check").unwrap(),
+            status_label: builder.get_object("trend_status_label").unwrap(),
+            monitored_items_pane,
+            state: RefCell::new(TrendViewState {
+                trend: Trend::default(),
+                window: window_spin.get_value(),
+                end: None,
+                y_zoom: 1.0,
+                y_pan: 0.0,
+                drag_start: None,
+                cursor: None,
+                legend: Vec::new(),
+            }),
+            window_spin,
+        });
+
+        data.drawing_area.add_events(
+            gdk::EventMask::BUTTON_PRESS_MASK
+                | gdk::EventMask::BUTTON_RELEASE_MASK
+                | gdk::EventMask::POINTER_MOTION_MASK
+                | gdk::EventMask::LEAVE_NOTIFY_MASK
+                | gdk::EventMask::SCROLL_MASK,
+        );
+
+        data.drawing_area.connect_draw(
+            clone!(@weak data => @default-return Inhibit(false), move |area, cr| {
+                data.draw(area, cr);
+                Inhibit(false)
+            }),
+        );
+
+        data.drawing_area.connect_button_press_event(
+            clone!(@weak data => @default-return Inhibit(false), move |_, event| {
+                match event.get_button() {
+                    1 => data.state.borrow_mut().drag_start = Some(event.get_position()),
+                    3 => TrendViewImpl::popup_menu(&data, event.get_position()),
+                    _ => {}
+                }
+                Inhibit(true)
+            }),
+        );
+
+        data.drawing_area.connect_motion_notify_event(
+            clone!(@weak data => @default-return Inhibit(false), move |area, event| {
+                data.on_motion(area, event.get_position());
+                Inhibit(true)
+            }),
+        );
+
+        data.drawing_area.connect_button_release_event(
+            clone!(@weak data => @default-return Inhibit(false), move |_, _| {
+                data.state.borrow_mut().drag_start = None;
+                Inhibit(true)
+            }),
+        );
+
+        data.drawing_area.connect_leave_notify_event(
+            clone!(@weak data => @default-return Inhibit(false), move |area, _| {
+                data.state.borrow_mut().cursor = None;
+                area.queue_draw();
+                Inhibit(false)
+            }),
+        );
+
+        data.drawing_area.connect_scroll_event(
+            clone!(@weak data => @default-return Inhibit(false), move |area, event| {
+                let factor = match event.get_direction() {
+                    gdk::ScrollDirection::Up => 1.0 / 1.2,
+                    gdk::ScrollDirection::Down => 1.2,
+                    gdk::ScrollDirection::Smooth => {
+                        let (_, dy) = event.get_delta();
+                        if dy < 0.0 { 1.0 / 1.2 } else if dy > 0.0 { 1.2 } else { 1.0 }
+                    }
+                    _ => 1.0,
+                };
+                // Ctrl zooms the values, otherwise the time is zoomed about the cursor
+                if event.get_state().contains(gdk::ModifierType::CONTROL_MASK) {
+                    {
+                        let mut state = data.state.borrow_mut();
+                        state.y_zoom = (state.y_zoom / factor).max(MIN_Y_ZOOM).min(MAX_Y_ZOOM);
+                    }
+                    area.queue_draw();
+                } else {
+                    data.zoom_time_about(area, event.get_position(), factor);
+                }
+                Inhibit(true)
+            }),
+        );
+
+        data.window_spin
+            .connect_value_changed(clone!(@weak data => move |spin| {
+                data.state.borrow_mut().window = spin.get_value();
+                data.drawing_area.queue_draw();
+            }));
+
+        data.pause_btn
+            .connect_toggled(clone!(@weak data => move |btn| {
+                {
+                    let mut state = data.state.borrow_mut();
+                    if !btn.get_active() {
+                        state.end = None;
+                    } else if state.end.is_none() {
+                        state.end = Some(trend::now());
+                    }
+                }
+                data.drawing_area.queue_draw();
+            }));
+
+        data.shared_axis_check
+            .connect_toggled(clone!(@weak data => move |_| {
+                data.drawing_area.queue_draw();
+            }));
+
+        live_btn.connect_clicked(clone!(@weak data => move |_| {
+            {
+                let mut state = data.state.borrow_mut();
+                state.end = None;
+                state.y_zoom = 1.0;
+                state.y_pan = 0.0;
+            }
+            data.pause_btn.set_active(false);
+            data.drawing_area.queue_draw();
+        }));
+
+        clear_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.state.borrow_mut().trend.clear();
+            data.drawing_area.queue_draw();
+        }));
+
+        export_png_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.on_export_png_clicked();
+        }));
+
+        export_svg_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.on_export_svg_clicked();
+        }));
+
+        export_csv_btn.connect_clicked(clone!(@weak data => move |_| {
+            data.on_export_csv_clicked();
+        }));
+
+        data.monitored_items_pane
+            .connect_add_to_trend(clone!(@weak data => move |items| {
+                data.add_series(items);
+            }));
+
+        // Series of items that have been removed from every watch list get no more values
+        data.monitored_items_pane
+            .connect_watch_lists_changed(clone!(@weak data => move || {
+                data.remove_missing_series();
+            }));
+
+        // Move on with the present even when no values arrive
+        glib::timeout_add_local(
+            REFRESH_INTERVAL,
+            clone!(@weak data => @default-return glib::Continue(false), move || {
+                let following = {
+                    let state = data.state.borrow();
+                    state.end.is_none() && !state.trend.series.is_empty()
+                };
+                if following && data.drawing_area.get_mapped() {
+                    data.drawing_area.queue_draw();
+                }
+                glib::Continue(true)
+            }),
+        );
+
+        Self { data }
+    }
+
+    /// Adds the values of items that have a series
+    pub fn on_item_values(&self, values: &[(u32, DataValue)]) {
+        let received = trend::now();
+        self.data.update_series_info();
+        let added = {
+            let mut state = self.data.state.borrow_mut();
+            values.iter().fold(false, |added, (item_id, value)| {
+                state.trend.add_value(*item_id, value, received) || added
+            })
+        };
+        if added && self.data.state.borrow().end.is_none() {
+            self.data.drawing_area.queue_draw();
+        }
+    }
+}
+
+impl TrendViewImpl {
+    /// The notebook page hosting the chart
+    fn page(&self) -> Option<gtk::Widget> {
+        self.drawing_area.get_parent()
+    }
+
+    fn add_series(&self, items: Vec<(u32, String)>) {
+        {
+            let mut state = self.state.borrow_mut();
+            items.iter().for_each(|(item_id, name)| {
+                state.trend.add_series(*item_id, name);
+            });
+        }
+        self.update_series_info();
+        self.drawing_area.queue_draw();
+        // Show the chart
+        if let Some(page) = self.page() {
+            let notebook = page
+                .get_parent()
+                .and_then(|p| p.downcast::<gtk::Notebook>().ok());
+            if let Some(notebook) = notebook {
+                if let Some(page_num) = notebook.page_num(&page) {
+                    notebook.set_current_page(Some(page_num));
+                }
+            }
+        }
+    }
+
+    /// Takes the units and enumeration members of the series from the watch items, which may
+    /// only become known after the series was added
+    fn update_series_info(&self) {
+        let mut state = self.state.borrow_mut();
+        state.trend.series.iter_mut().for_each(|series| {
+            series.units = self.monitored_items_pane.item_units(series.item_id);
+            series.enum_members = self.monitored_items_pane.item_enum_members(series.item_id);
+        });
+    }
+
+    fn remove_missing_series(&self) {
+        let item_ids = self
+            .monitored_items_pane
+            .watch_lists()
+            .iter()
+            .flat_map(|w| w.items.iter().map(|i| i.item_id))
+            .collect::<HashSet<_>>();
+        {
+            let mut state = self.state.borrow_mut();
+            let missing = state
+                .trend
+                .series
+                .iter()
+                .map(|s| s.item_id)
+                .filter(|item_id| !item_ids.contains(item_id))
+                .collect::<Vec<_>>();
+            missing
+                .into_iter()
+                .for_each(|item_id| state.trend.remove_series(item_id));
+        }
+        self.drawing_area.queue_draw();
+    }
+
+    fn viewport(&self, state: &TrendViewState) -> Viewport {
+        let now = trend::now();
+        let end = state.end.unwrap_or(now);
+        Viewport {
+            start: end - state.window,
+            end,
+            now,
+            shared_axis: self.shared_axis_check.get_active(),
+            y_zoom: state.y_zoom,
+            y_pan: state.y_pan,
+        }
+    }
+
+    fn area_size(area: &gtk::DrawingArea) -> (f64, f64) {
+        (
+            area.get_allocated_width() as f64,
+            area.get_allocated_height() as f64,
+        )
+    }
+
+    /// Tracks the cursor for the readout, and pans the chart while dragging. Panning the time
+    /// stops the chart following the present.
+    fn on_motion(&self, area: &gtk::DrawingArea, (x, y): (f64, f64)) {
+        let pause = {
+            let mut state = self.state.borrow_mut();
+            state.cursor = Some((x, y));
+            match state.drag_start {
+                Some((start_x, start_y)) => {
+                    let viewport = self.viewport(&state);
+                    let (left, top, right, bottom) =
+                        plot_area(Self::area_size(area), viewport.axis_count(&state.trend));
+                    let time_shift = (x - start_x) / (right - left).max(1.0) * state.window;
+                    if time_shift != 0.0 {
+                        state.end = Some((viewport.end - time_shift).min(viewport.now));
+                    }
+                    state.y_pan += (y - start_y) / (bottom - top).max(1.0);
+                    state.drag_start = Some((x, y));
+                    time_shift != 0.0
+                }
+                None => false,
+            }
+        };
+        if pause {
+            self.pause_btn.set_active(true);
+        }
+        area.queue_draw();
+    }
+
+    fn zoom_time_about(&self, area: &gtk::DrawingArea, (x, _): (f64, f64), factor: f64) {
+        let window = {
+            let mut state = self.state.borrow_mut();
+            let viewport = self.viewport(&state);
+            let window = (state.window * factor).max(MIN_WINDOW).min(MAX_WINDOW);
+            // The right edge stays on the present while following it, otherwise the time under
+            // the cursor stays where it is
+            if let Some(end) = state.end {
+                let (left, _, right, _) =
+                    plot_area(Self::area_size(area), viewport.axis_count(&state.trend));
+                let fraction = ((x - left) / (right - left).max(1.0)).max(0.0).min(1.0);
+                let time = viewport.start + fraction * state.window;
+                state.end = Some(time + (end - time) * window / state.window);
+            }
+            state.window = window;
+            window
+        };
+        self.window_spin.set_value(window);
+        area.queue_draw();
+    }
+
+    /// Offers to remove the series whose legend entry is under the position
+    fn popup_menu(data: &Rc<Self>, (x, y): (f64, f64)) {
+        let series = {
+            let state = data.state.borrow();
+            state
+                .legend
+                .iter()
+                .find(|(_, (lx, ly, w, h))| x >= *lx && x <= lx + w && y >= *ly && y <= ly + h)
+                .and_then(|(item_id, _)| {
+                    state
+                        .trend
+                        .series
+                        .iter()
+                        .find(|s| s.item_id == *item_id)
+                        .map(|s| (s.item_id, s.name.clone()))
+                })
+        };
+        let (item_id, name) = match series {
+            Some(series) => series,
+            None => return,
+        };
+        let menu = gtk::Menu::new();
+        let remove_item = gtk::MenuItem::with_label(&format!("Remove \"{}\" from Trend", name));
+        remove_item.connect_activate(clone!(@weak data => move |_| {
+            data.state.borrow_mut().trend.remove_series(item_id);
+            data.drawing_area.queue_draw();
+        }));
+        menu.append(&remove_item);
+        menu.show_all();
+        menu.popup_at_pointer(None);
+    }
+
+    fn draw(&self, area: &gtk::DrawingArea, cr: &cairo::Context) {
+        let mut state = self.state.borrow_mut();
+        let viewport = self.viewport(&state);
+        let legend = render(
+            cr,
+            &state.trend,
+            &viewport,
+            Self::area_size(area),
+            state.cursor,
+        );
+        state.legend = legend;
+    }
+
+    fn choose_export_file(&self, title: &str, extension: &str) -> Option<PathBuf> {
+        let dlg = gtk::FileChooserDialog::with_buttons(
+            Some(title),
+            Some(&*self.main_window),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("_Save", gtk::ResponseType::Accept),
+            ],
+        );
+        dlg.set_do_overwrite_confirmation(true);
+        dlg.set_current_name(&format!("trend.{}", extension));
+        let filter = gtk::FileFilter::new();
+        filter.add_pattern(&format!("*.{}", extension));
+        dlg.set_filter(&filter);
+        let filename = if dlg.run() == gtk::ResponseType::Accept {
+            dlg.get_filename()
+        } else {
+            None
+        };
+        dlg.close();
+        filename
+    }
+
+    /// The size of an exported image, which is the size of the chart on screen
+    fn export_size(&self) -> (i32, i32) {
+        let (width, height) = (
+            self.drawing_area.get_allocated_width(),
+            self.drawing_area.get_allocated_height(),
+        );
+        if width > 1 && height > 1 {
+            (width, height)
+        } else {
+            DEFAULT_EXPORT_SIZE
+        }
+    }
+
+    fn on_export_png_clicked(&self) {
+        if let Some(path) = self.choose_export_file("Export PNG", "png") {
+            let (width, height) = self.export_size();
+            let state = self.state.borrow();
+            let viewport = self.viewport(&state);
+            let result = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
+                .map_err(|err| format!("{:?}", err))
+                .and_then(|surface| {
+                    let cr = cairo::Context::new(&surface);
+                    render(
+                        &cr,
+                        &state.trend,
+                        &viewport,
+                        (width as f64, height as f64),
+                        None,
+                    );
+                    let mut file = File::create(&path).map_err(|err| err.to_string())?;
+                    surface
+                        .write_to_png(&mut file)
+                        .map_err(|err| format!("{:?}", err))
+                });
+            self.show_export_result(&path, "PNG", result);
+        }
+    }
+
+    fn on_export_svg_clicked(&self) {
+        if let Some(path) = self.choose_export_file("Export SVG", "svg") {
+            let (width, height) = self.export_size();
+            let state = self.state.borrow();
+            let viewport = self.viewport(&state);
+            let result = cairo::SvgSurface::new(width as f64, height as f64, Some(&path))
+                .and_then(|surface| {
+                    let cr = cairo::Context::new(&surface);
+                    render(
+                        &cr,
+                        &state.trend,
+                        &viewport,
+                        (width as f64, height as f64),
+                        None,
+                    );
+                    surface.finish();
+                    // Errors writing the file only show in the status of the finished surface
+                    match surface.status() {
+                        cairo::Status::Success => Ok(()),
+                        status => Err(status),
+                    }
+                })
+                .map_err(|err| format!("{:?}", err));
+            self.show_export_result(&path, "SVG", result);
+        }
+    }
+
+    /// Exports the samples in the time shown
+    fn on_export_csv_clicked(&self) {
+        if let Some(path) = self.choose_export_file("Export CSV", "csv") {
+            let csv = {
+                let state = self.state.borrow();
+                let viewport = self.viewport(&state);
+                state.trend.to_csv(viewport.start, viewport.end)
+            };
+            let result = fs::write(&path, csv).map_err(|err| err.to_string());
+            self.show_export_result(&path, "CSV", result);
+        }
+    }
+
+    fn show_export_result(&self, path: &Path, kind: &str, result: Result<(), String>) {
+        let text = match result {
+            Ok(_) => format!("Exported the trend to {}", path.display()),
+            Err(err) => format!("Cannot write {} file {}, {}", kind, path.display(), err),
+        };
+        self.status_label.set_text(&text);
+    }
+}
+
+/// The range of values an axis shows for the series, from the values in the time shown and the
+/// zoom and pan
+fn axis_range(trend: &Trend, series: &[usize], viewport: &Viewport) -> (f64, f64) {
+    let range = series
+        .iter()
+        .filter_map(|i| trend.series[*i].value_range(viewport.start, viewport.end))
+        .fold(None, |range: Option<(f64, f64)>, (low, high)| match range {
+            None => Some((low, high)),
+            Some((l, h)) => Some((l.min(low), h.max(high))),
+        });
+    let (low, high) = match range {
+        Some((low, high)) if high - low > f64::EPSILON * high.abs().max(1.0) => {
+            let margin = (high - low) * 0.05;
+            (low - margin, high + margin)
+        }
+        Some((v, _)) => {
+            let margin = (v.abs() * 0.1).max(1.0);
+            (v - margin, v + margin)
+        }
+        None => (0.0, 1.0),
+    };
+    let span = (high - low) / viewport.y_zoom;
+    let centre = (low + high) / 2.0 + viewport.y_pan * span;
+    (centre - span / 2.0, centre + span / 2.0)
+}
+
+/// The units of the series plotted against an axis, if they have any. Series with different
+/// units sharing an axis list each of them.
+fn axis_units(trend: &Trend, series: &[usize]) -> Option<String> {
+    let mut units = Vec::new();
+    series
+        .iter()
+        .filter_map(|i| trend.series[*i].units.as_ref())
+        .for_each(|u| {
+            if !units.contains(&u) {
+                units.push(u);
+            }
+        });
+    if units.is_empty() {
+        None
+    } else {
+        Some(
+            units
+                .iter()
+                .map(|u| u.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
+
+/// Formats a tick with as many decimal places as the spacing of the ticks needs
+fn tick_label(value: f64, ticks: &[f64]) -> String {
+    let step = match ticks {
+        [a, b, ..] => b - a,
+        _ => return format!("{}", value),
+    };
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+fn time_label(time: f64, with_fraction: bool) -> String {
+    let local = Local.timestamp_millis((time * 1000.0) as i64);
+    if with_fraction {
+        local.format("%H:%M:%S%.1f").to_string()
+    } else {
+        local.format("%H:%M:%S").to_string()
+    }
+}
+
+fn draw_cross(cr: &cairo::Context, x: f64, y: f64, size: f64) {
+    cr.move_to(x - size, y - size);
+    cr.line_to(x + size, y + size);
+    cr.move_to(x - size, y + size);
+    cr.line_to(x + size, y - size);
+    cr.stroke();
+}
+
+/// Renders the chart onto the context and returns where each series is in the legend. The same
+/// code is used for the screen and for PNG and SVG export.
+fn render(
+    cr: &cairo::Context,
+    trend: &Trend,
+    viewport: &Viewport,
+    size: (f64, f64),
+    cursor: Option<(f64, f64)>,
+) -> Vec<(u32, (f64, f64, f64, f64))> {
+    cr.set_source_rgb(1.0, 1.0, 1.0);
+    cr.paint();
+    cr.set_font_size(FONT_SIZE);
+    cr.set_line_width(1.0);
+
+    if trend.series.is_empty() {
+        cr.set_source_rgb(0.5, 0.5, 0.5);
+        cr.set_font_size(FONT_SIZE + 2.0);
+        cr.move_to(10.0, 20.0);
+        cr.show_text("Choose \"Add to Trend\" on items in the watch list");
+        return vec![];
+    }
+
+    let (left, top, right, bottom) = plot_area(size, viewport.axis_count(trend));
+    if right - left < 10.0 || bottom - top < 10.0 {
+        return vec![];
+    }
+    let duration = viewport.end - viewport.start;
+    let to_x = |time: f64| left + (time - viewport.start) / duration * (right - left);
+
+    // Each axis and the series that are plotted against it
+    let axes = if viewport.shared_axis {
+        vec![(0..trend.series.len()).collect::<Vec<_>>()]
+    } else {
+        (0..trend.series.len()).map(|i| vec![i]).collect()
+    };
+    let ranges = axes
+        .iter()
+        .map(|series| axis_range(trend, series, viewport))
+        .collect::<Vec<_>>();
+    let axis_of = |series: usize| {
+        if viewport.shared_axis {
+            0
+        } else {
+            series
+        }
+    };
+    let to_y = |series: usize, value: f64| {
+        let (low, high) = ranges[axis_of(series)];
+        bottom - (value - low) / (high - low) * (bottom - top)
+    };
+
+    // Time axis and grid
+    let time_ticks = trend::time_ticks(
+        viewport.start,
+        viewport.end,
+        ((right - left) / 100.0).max(1.0) as usize,
+    );
+    let with_fraction = matches!(time_ticks.as_slice(), [a, b, ..] if b - a < 1.0);
+    time_ticks.iter().for_each(|t| {
+        let x = to_x(*t);
+        cr.set_source_rgb(0.9, 0.9, 0.9);
+        cr.move_to(x, top);
+        cr.line_to(x, bottom);
+        cr.stroke();
+        let label = time_label(*t, with_fraction);
+        let extents = cr.text_extents(&label);
+        cr.set_source_rgb(0.2, 0.2, 0.2);
+        cr.move_to(x - extents.width / 2.0, bottom + 4.0 + extents.height);
+        cr.show_text(&label);
+    });
+
+    // Value axes, with the grid from the innermost
+    ranges.iter().enumerate().for_each(|(i, (low, high))| {
+        let x = left - i as f64 * AXIS_WIDTH;
+        let (r, g, b) = if viewport.shared_axis {
+            (0.2, 0.2, 0.2)
+        } else {
+            trend.series[i].colour
+        };
+        let ticks = trend::ticks(*low, *high, ((bottom - top) / 40.0).max(1.0) as usize);
+        ticks.iter().for_each(|v| {
+            let y = bottom - (v - low) / (high - low) * (bottom - top);
+            if i == 0 {
+                cr.set_source_rgb(0.9, 0.9, 0.9);
+                cr.move_to(left, y);
+                cr.line_to(right, y);
+                cr.stroke();
+            }
+            let label = tick_label(*v, &ticks);
+            let extents = cr.text_extents(&label);
+            cr.set_source_rgb(r, g, b);
+            cr.move_to(x - 3.0, y);
+            cr.line_to(x, y);
+            cr.stroke();
+            cr.move_to(x - 5.0 - extents.width, y + extents.height / 2.0);
+            cr.show_text(&label);
+        });
+        cr.set_source_rgb(r, g, b);
+        cr.move_to(x, top);
+        cr.line_to(x, bottom);
+        cr.stroke();
+        // The units, written up the outer edge of the axis
+        if let Some(units) = axis_units(trend, &axes[i]) {
+            let extents = cr.text_extents(&units);
+            cr.save();
+            cr.move_to(
+                x - AXIS_WIDTH + FONT_SIZE,
+                (top + bottom) / 2.0 + extents.width / 2.0,
+            );
+            cr.rotate(-PI / 2.0);
+            cr.show_text(&units);
+            cr.restore();
+        }
+    });
+    cr.set_source_rgb(0.6, 0.6, 0.6);
+    cr.rectangle(left, top, right - left, bottom - top);
+    cr.stroke();
+
+    // Series, which hold their value until the next sample. Uncertain values are dashed with
+    // hollow markers, bad values break the line, shade the time they last and are crossed.
+    cr.save();
+    cr.rectangle(left, top, right - left, bottom - top);
+    cr.clip();
+    trend.series.iter().enumerate().for_each(|(i, series)| {
+        let (r, g, b) = series.colour;
+        let samples = series
+            .samples_between(viewport.start, viewport.end)
+            .collect::<Vec<_>>();
+        samples.iter().enumerate().for_each(|(n, sample)| {
+            let next = samples.get(n + 1);
+            let until = next.map_or(viewport.end.min(viewport.now), |s| s.time);
+            let x = to_x(sample.time);
+            match (sample.quality(), sample.value) {
+                (Quality::Bad, value) => {
+                    cr.set_source_rgba(1.0, 0.0, 0.0, 0.08);
+                    cr.rectangle(x, top, to_x(until) - x, bottom - top);
+                    cr.fill();
+                    cr.set_source_rgb(0.85, 0.0, 0.0);
+                    let y = value.map_or(bottom - 5.0, |v| to_y(i, v));
+                    draw_cross(cr, x, y, 4.0);
+                }
+                (quality, Some(value)) => {
+                    let y = to_y(i, value);
+                    cr.set_source_rgb(r, g, b);
+                    cr.set_line_width(1.5);
+                    if quality == Quality::Uncertain {
+                        cr.set_dash(&[4.0, 3.0], 0.0);
+                    }
+                    cr.move_to(x, y);
+                    cr.line_to(to_x(until), y);
+                    if let Some(next_value) = next
+                        .filter(|s| s.quality() != Quality::Bad)
+                        .and_then(|s| s.value)
+                    {
+                        cr.line_to(to_x(until), to_y(i, next_value));
+                    }
+                    cr.stroke();
+                    cr.set_dash(&[], 0.0);
+                    cr.set_line_width(1.0);
+                    if quality == Quality::Uncertain {
+                        cr.set_source_rgb(1.0, 1.0, 1.0);
+                        cr.arc(x, y, 3.0, 0.0, 2.0 * PI);
+                        cr.fill_preserve();
+                        cr.set_source_rgb(0.9, 0.5, 0.0);
+                        cr.stroke();
+                    }
+                }
+                (_, None) => {}
+            }
+        });
+    });
+    cr.restore();
+
+    // Legend across the top, with the latest value shown
+    let mut legend = Vec::new();
+    let mut x = left;
+    trend.series.iter().for_each(|series| {
+        let latest = series
+            .sample_at(viewport.end)
+            .map_or_else(String::new, |s| {
+                s.value.map_or_else(
+                    || format!(" ({})", value_format::status_code_to_string(s.status_code)),
+                    |v| format!(" = {}", series.format_value(v)),
+                )
+            });
+        let label = format!("{}{}", series.name, latest);
+        let extents = cr.text_extents(&label);
+        let (r, g, b) = series.colour;
+        cr.set_source_rgb(r, g, b);
+        cr.rectangle(x, 5.0, 10.0, 10.0);
+        cr.fill();
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        cr.move_to(x + 14.0, 14.0);
+        cr.show_text(&label);
+        let width = 14.0 + extents.x_advance;
+        legend.push((series.item_id, (x, 0.0, width, LEGEND_HEIGHT)));
+        x += width + 16.0;
+    });
+
+    // Readout of every series at the time under the cursor
+    if let Some((cx, cy)) = cursor {
+        if cx >= left && cx <= right && cy >= top && cy <= bottom {
+            let time = viewport.start + (cx - left) / (right - left) * duration;
+            cr.set_source_rgb(0.3, 0.3, 0.3);
+            cr.set_dash(&[2.0, 2.0], 0.0);
+            cr.move_to(cx, top);
+            cr.line_to(cx, bottom);
+            cr.stroke();
+            cr.set_dash(&[], 0.0);
+
+            let mut lines = vec![(time_label(time, duration < 60.0), (0.0, 0.0, 0.0))];
+            trend.series.iter().enumerate().for_each(|(i, series)| {
+                if let Some(sample) = series.sample_at(time) {
+                    if let Some(value) = sample.value {
+                        let (r, g, b) = series.colour;
+                        cr.set_source_rgb(r, g, b);
+                        cr.arc(cx, to_y(i, value), 3.0, 0.0, 2.0 * PI);
+                        cr.fill();
+                    }
+                    let value = sample
+                        .value
+                        .map_or_else(String::new, |v| series.format_value(v));
+                    let status = if sample.quality() == Quality::Good {
+                        String::new()
+                    } else {
+                        format!(
+                            " ({})",
+                            value_format::status_code_to_string(sample.status_code)
+                        )
+                    };
+                    lines.push((
+                        format!("{}: {}{}", series.name, value, status),
+                        series.colour,
+                    ));
+                }
+            });
+
+            let line_height = FONT_SIZE + 4.0;
+            let width = lines
+                .iter()
+                .map(|(text, _)| cr.text_extents(text).x_advance)
+                .fold(0.0, f64::max)
+                + 8.0;
+            let height = lines.len() as f64 * line_height + 4.0;
+            let bx = if cx + 8.0 + width > right {
+                cx - 8.0 - width
+            } else {
+                cx + 8.0
+            };
+            let by = cy.min(bottom - height).max(top);
+            cr.set_source_rgba(1.0, 1.0, 0.9, 0.9);
+            cr.rectangle(bx, by, width, height);
+            cr.fill_preserve();
+            cr.set_source_rgb(0.5, 0.5, 0.5);
+            cr.stroke();
+            lines.iter().enumerate().for_each(|(n, (text, (r, g, b)))| {
+                cr.set_source_rgb(*r, *g, *b);
+                cr.move_to(bx + 4.0, by + (n + 1) as f64 * line_height);
+                cr.show_text(text);
+            });
+        }
+    }
+
+    legend
+}
@@ -0,0 +1,1245 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Cursor, Read, Write},
+};
+
+use serde_json::{Map, Value as JsonValue};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    value_edit::{self, BuiltinType},
+    value_format::{self, TimeDisplay},
+};
+
+/// Namespace of the built-in types in an OPC Binary schema
+const BINARY_SCHEMA_NAMESPACE: &str = "http://opcfoundation.org/BinarySchema/";
+/// Namespace of the types defined by the OPC UA specification
+const UA_NAMESPACE: &str = "http://opcfoundation.org/UA/";
+
+/// Structures nested deeper than this are assumed to be malformed
+const MAX_DEPTH: usize = 16;
+
+/// How a field is encoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Builtin(BuiltinType),
+    /// An enumeration, encoded as an Int32
+    Enumeration(NodeId),
+    /// A structure which is encoded inline, without an ExtensionObject around it
+    Structure(NodeId),
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldLayout {
+    pub name: String,
+    pub field_type: FieldType,
+    /// Fields with a ValueRank of 1 or more are arrays
+    pub value_rank: i32,
+    /// For an optional field, the bit of the encoding mask saying it is present. For a union,
+    /// the value of the switch field that selects it.
+    pub switch: Option<u32>,
+}
+
+impl FieldLayout {
+    pub fn is_array(&self) -> bool {
+        self.value_rank >= 1
+    }
+
+    pub fn type_name(&self, type_dictionary: &TypeDictionary) -> String {
+        let name = match self.field_type {
+            FieldType::Builtin(t) => format!("{:?}", t),
+            FieldType::Enumeration(ref id) => format!("Enumeration {}", id),
+            FieldType::Structure(ref id) => type_dictionary
+                .structure(id)
+                .map_or_else(|| id.to_string(), |s| s.name.clone()),
+        };
+        if self.is_array() {
+            format!("{}[]", name)
+        } else {
+            name
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StructureKind {
+    Structure,
+    /// Starts with a mask of this many bits saying which optional fields are present
+    WithOptionalFields(u32),
+    /// Starts with a UInt32 saying which one of the fields is present
+    Union,
+}
+
+/// The layout of a structured DataType, from its DataTypeDefinition or a type dictionary
+#[derive(Debug, Clone)]
+pub struct StructureLayout {
+    pub data_type_id: NodeId,
+    pub name: String,
+    /// The DataTypeEncoding node for the binary encoding, used as the ExtensionObject's TypeId
+    pub encoding_id: NodeId,
+    pub kind: StructureKind,
+    pub fields: Vec<FieldLayout>,
+}
+
+/// The members of an enumerated DataType, from its EnumDefinition, EnumValues or EnumStrings
+#[derive(Debug, Clone)]
+pub struct EnumLayout {
+    pub data_type_id: NodeId,
+    pub name: String,
+    /// The value and name of each member
+    pub members: Vec<(i64, String)>,
+}
+
+/// The value of a field of a decoded structure
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Scalar(Variant),
+    Structure(DecodedStructure),
+    Array(Vec<FieldValue>),
+    /// An optional field which isn't present, or a member of a union which isn't selected
+    Absent,
+}
+
+/// A structure decoded into named fields. Every field of the layout is present in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedStructure {
+    pub data_type_id: NodeId,
+    pub type_name: String,
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+/// The layouts of the structured and enumerated DataTypes on a server, loaded when connecting
+#[derive(Debug, Clone, Default)]
+pub struct TypeDictionary {
+    structures: HashMap<NodeId, StructureLayout>,
+    /// Maps binary encoding ids to DataType ids
+    encodings: HashMap<NodeId, NodeId>,
+    enumerations: HashMap<NodeId, EnumLayout>,
+}
+
+impl TypeDictionary {
+    pub fn insert(&mut self, layout: StructureLayout) {
+        if !layout.encoding_id.is_null() {
+            self.encodings
+                .insert(layout.encoding_id.clone(), layout.data_type_id.clone());
+        }
+        self.structures.insert(layout.data_type_id.clone(), layout);
+    }
+
+    pub fn insert_enumeration(&mut self, layout: EnumLayout) {
+        self.enumerations
+            .insert(layout.data_type_id.clone(), layout);
+    }
+
+    pub fn structure_count(&self) -> usize {
+        self.structures.len()
+    }
+
+    pub fn enumeration_count(&self) -> usize {
+        self.enumerations.len()
+    }
+
+    pub fn enumeration(&self, data_type_id: &NodeId) -> Option<&EnumLayout> {
+        self.enumerations.get(data_type_id)
+    }
+
+    /// Formats a value of the DataType, showing the names of enumeration members
+    pub fn format_value(
+        &self,
+        data_type_id: Option<&NodeId>,
+        v: &Variant,
+        time_display: TimeDisplay,
+    ) -> String {
+        match data_type_id.and_then(|id| self.enumeration(id)) {
+            Some(enumeration) => match v {
+                Variant::Int32(v) => {
+                    value_edit::enum_value_to_string(&enumeration.members, *v as i64)
+                }
+                Variant::Array(array) => {
+                    let values = array
+                        .values
+                        .iter()
+                        .map(|v| self.format_value(data_type_id, v, time_display))
+                        .collect::<Vec<_>>();
+                    format!("[{}]", values.join(", "))
+                }
+                v => value_format::variant_to_string(v, time_display),
+            },
+            None => value_format::variant_to_string(v, time_display),
+        }
+    }
+
+    pub fn structure(&self, data_type_id: &NodeId) -> Option<&StructureLayout> {
+        self.structures.get(data_type_id)
+    }
+
+    pub fn structure_by_name(&self, name: &str) -> Option<&StructureLayout> {
+        self.structures.values().find(|s| s.name == name)
+    }
+
+    /// Finds the layout of the structure held by an ExtensionObject
+    pub fn structure_for_encoding(&self, encoding_id: &NodeId) -> Option<&StructureLayout> {
+        self.encodings
+            .get(encoding_id)
+            .and_then(|data_type_id| self.structures.get(data_type_id))
+    }
+
+    /// Decodes the body of an ExtensionObject. Returns None if the type of the structure is
+    /// not known or it isn't binary encoded.
+    pub fn decode(
+        &self,
+        extension_object: &ExtensionObject,
+    ) -> Option<Result<DecodedStructure, String>> {
+        self.decode_at_depth(extension_object, 0)
+    }
+
+    /// Encodes a structure into an ExtensionObject ready to be written
+    pub fn encode(&self, structure: &DecodedStructure) -> Result<ExtensionObject, String> {
+        self.encode_at_depth(structure, 0)
+    }
+
+    /// Decodes an ExtensionObject found at a depth of nesting, so structures that hold
+    /// themselves can't recurse without end
+    fn decode_at_depth(
+        &self,
+        extension_object: &ExtensionObject,
+        depth: usize,
+    ) -> Option<Result<DecodedStructure, String>> {
+        let layout = self.structure_for_encoding(&extension_object.node_id)?;
+        match extension_object.body {
+            ExtensionObjectEncoding::ByteString(ref body) => {
+                let bytes = body.value.as_ref().map_or(&[][..], |b| b.as_slice());
+                let mut stream = Cursor::new(bytes);
+                Some(self.decode_structure(layout, &mut stream, depth))
+            }
+            _ => None,
+        }
+    }
+
+    fn encode_at_depth(
+        &self,
+        structure: &DecodedStructure,
+        depth: usize,
+    ) -> Result<ExtensionObject, String> {
+        let layout = self
+            .structure(&structure.data_type_id)
+            .ok_or_else(|| format!("The layout of {} is not known", structure.type_name))?;
+        let mut stream = Cursor::new(Vec::new());
+        self.encode_structure(layout, structure, &mut stream, depth)?;
+        Ok(ExtensionObject {
+            node_id: layout.encoding_id.clone(),
+            body: ExtensionObjectEncoding::ByteString(ByteString::from(stream.into_inner())),
+        })
+    }
+
+    /// Makes a structure with default values, with optional fields absent and no member of a
+    /// union selected
+    pub fn default_structure(&self, data_type_id: &NodeId) -> Option<DecodedStructure> {
+        self.default_structure_at(data_type_id, 0)
+    }
+
+    fn default_structure_at(
+        &self,
+        data_type_id: &NodeId,
+        depth: usize,
+    ) -> Option<DecodedStructure> {
+        let layout = self.structure(data_type_id)?;
+        let fields = layout
+            .fields
+            .iter()
+            .map(|f| {
+                let value = if f.switch.is_some() {
+                    FieldValue::Absent
+                } else {
+                    self.default_field_value(f, depth + 1)
+                };
+                (f.name.clone(), value)
+            })
+            .collect();
+        Some(DecodedStructure {
+            data_type_id: layout.data_type_id.clone(),
+            type_name: layout.name.clone(),
+            fields,
+        })
+    }
+
+    /// The value a field starts with when it is created
+    pub fn default_field_value(&self, field: &FieldLayout, depth: usize) -> FieldValue {
+        if field.is_array() {
+            return FieldValue::Array(Vec::new());
+        }
+        self.default_element_value(&field.field_type, depth)
+    }
+
+    pub fn default_element_value(&self, field_type: &FieldType, depth: usize) -> FieldValue {
+        match field_type {
+            FieldType::Builtin(t) => FieldValue::Scalar(default_variant(*t)),
+            FieldType::Enumeration(_) => FieldValue::Scalar(Variant::Int32(0)),
+            FieldType::Structure(data_type_id) if depth < MAX_DEPTH => self
+                .default_structure_at(data_type_id, depth)
+                .map_or(FieldValue::Absent, FieldValue::Structure),
+            FieldType::Structure(_) => FieldValue::Absent,
+        }
+    }
+
+    /// Converts a value to JSON, decoding any structures it contains
+    pub fn variant_to_json(&self, v: &Variant) -> JsonValue {
+        match v {
+            Variant::ExtensionObject(extension_object) => match self.decode(extension_object) {
+                Some(Ok(structure)) => structure_to_json(&structure),
+                _ => value_format::variant_to_json(v),
+            },
+            Variant::Array(array)
+                if array
+                    .values
+                    .iter()
+                    .any(|v| matches!(v, Variant::ExtensionObject(_))) =>
+            {
+                JsonValue::Array(
+                    array
+                        .values
+                        .iter()
+                        .map(|v| self.variant_to_json(v))
+                        .collect(),
+                )
+            }
+            v => value_format::variant_to_json(v),
+        }
+    }
+
+    fn decode_structure<S: Read>(
+        &self,
+        layout: &StructureLayout,
+        stream: &mut S,
+        depth: usize,
+    ) -> Result<DecodedStructure, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("{} is nested too deeply", layout.name));
+        }
+        let decoding_options = DecodingOptions::default();
+
+        let fields = match layout.kind {
+            StructureKind::Structure => layout
+                .fields
+                .iter()
+                .map(|f| Ok((f.name.clone(), self.decode_field(f, stream, depth)?)))
+                .collect::<Result<Vec<_>, String>>()?,
+            StructureKind::WithOptionalFields(mask_bits) => {
+                check_mask_bits(layout, mask_bits)?;
+                let mut mask_bytes = vec![0u8; ((mask_bits + 7) / 8) as usize];
+                stream
+                    .read_exact(&mut mask_bytes)
+                    .map_err(|_| format!("{} ends before its encoding mask", layout.name))?;
+                let mask = mask_bytes
+                    .iter()
+                    .rev()
+                    .fold(0u64, |mask, b| (mask << 8) | *b as u64);
+                layout
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let present = match f.switch {
+                            Some(bit) => mask & switch_bit(bit)? != 0,
+                            None => true,
+                        };
+                        let value = if present {
+                            self.decode_field(f, stream, depth)?
+                        } else {
+                            FieldValue::Absent
+                        };
+                        Ok((f.name.clone(), value))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?
+            }
+            StructureKind::Union => {
+                let switch = u32::decode(stream, &decoding_options)
+                    .map_err(|err| format!("{} has no switch field, {}", layout.name, err))?;
+                layout
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let value = if f.switch == Some(switch) {
+                            self.decode_field(f, stream, depth)?
+                        } else {
+                            FieldValue::Absent
+                        };
+                        Ok((f.name.clone(), value))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?
+            }
+        };
+
+        Ok(DecodedStructure {
+            data_type_id: layout.data_type_id.clone(),
+            type_name: layout.name.clone(),
+            fields,
+        })
+    }
+
+    fn decode_field<S: Read>(
+        &self,
+        field: &FieldLayout,
+        stream: &mut S,
+        depth: usize,
+    ) -> Result<FieldValue, String> {
+        let result = if field.is_array() {
+            let decoding_options = DecodingOptions::default();
+            let len = i32::decode(stream, &decoding_options).map_err(|err| err.to_string())?;
+            if len > 0 && len as usize > decoding_options.max_array_length {
+                return Err(format!("Field {} has too many elements", field.name));
+            }
+            (0..len.max(0))
+                .map(|_| self.decode_element(&field.field_type, stream, depth))
+                .collect::<Result<Vec<_>, String>>()
+                .map(FieldValue::Array)
+        } else {
+            self.decode_element(&field.field_type, stream, depth)
+        };
+        result.map_err(|err| format!("Field {}: {}", field.name, err))
+    }
+
+    fn decode_element<S: Read>(
+        &self,
+        field_type: &FieldType,
+        stream: &mut S,
+        depth: usize,
+    ) -> Result<FieldValue, String> {
+        match field_type {
+            FieldType::Builtin(BuiltinType::ExtensionObject) => {
+                let extension_object = ExtensionObject::decode(stream, &DecodingOptions::default())
+                    .map_err(|err| err.to_string())?;
+                // Decode what's inside if possible
+                match self.decode_at_depth(&extension_object, depth + 1) {
+                    Some(Ok(structure)) => Ok(FieldValue::Structure(structure)),
+                    _ => Ok(FieldValue::Scalar(Variant::from(extension_object))),
+                }
+            }
+            FieldType::Builtin(t) => decode_builtin(*t, stream).map(FieldValue::Scalar),
+            FieldType::Enumeration(_) => {
+                decode_builtin(BuiltinType::Int32, stream).map(FieldValue::Scalar)
+            }
+            FieldType::Structure(data_type_id) => {
+                let layout = self
+                    .structure(data_type_id)
+                    .ok_or_else(|| format!("The layout of {} is not known", data_type_id))?;
+                self.decode_structure(layout, stream, depth + 1)
+                    .map(FieldValue::Structure)
+            }
+        }
+    }
+
+    fn encode_structure<S: Write>(
+        &self,
+        layout: &StructureLayout,
+        structure: &DecodedStructure,
+        stream: &mut S,
+        depth: usize,
+    ) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("{} is nested too deeply", layout.name));
+        }
+        let value = |f: &FieldLayout| {
+            structure
+                .fields
+                .iter()
+                .find(|(name, _)| *name == f.name)
+                .map_or(&FieldValue::Absent, |(_, v)| v)
+        };
+        let is_present = |f: &FieldLayout| *value(f) != FieldValue::Absent;
+
+        match layout.kind {
+            StructureKind::Structure => {}
+            StructureKind::WithOptionalFields(mask_bits) => {
+                check_mask_bits(layout, mask_bits)?;
+                let mask = layout
+                    .fields
+                    .iter()
+                    .filter(|f| is_present(f))
+                    .filter_map(|f| f.switch)
+                    .try_fold(0u64, |mask, bit| Ok::<_, String>(mask | switch_bit(bit)?))?;
+                let mask_bytes = mask.to_le_bytes();
+                stream
+                    .write_all(&mask_bytes[..((mask_bits + 7) / 8) as usize])
+                    .map_err(|err| err.to_string())?;
+            }
+            StructureKind::Union => {
+                let switch = layout
+                    .fields
+                    .iter()
+                    .find(|f| is_present(f))
+                    .and_then(|f| f.switch)
+                    .unwrap_or(0);
+                switch.encode(stream).map_err(|err| err.to_string())?;
+            }
+        }
+
+        layout.fields.iter().try_for_each(|f| {
+            let v = value(f);
+            match v {
+                FieldValue::Absent if f.switch.is_some() => Ok(()),
+                FieldValue::Absent => Err(format!("Field {} must have a value", f.name)),
+                v => self
+                    .encode_field(f, v, stream, depth)
+                    .map_err(|err| format!("Field {}: {}", f.name, err)),
+            }
+        })
+    }
+
+    fn encode_field<S: Write>(
+        &self,
+        field: &FieldLayout,
+        value: &FieldValue,
+        stream: &mut S,
+        depth: usize,
+    ) -> Result<(), String> {
+        if field.is_array() {
+            match value {
+                FieldValue::Array(values) => {
+                    (values.len() as i32)
+                        .encode(stream)
+                        .map_err(|err| err.to_string())?;
+                    values
+                        .iter()
+                        .try_for_each(|v| self.encode_element(&field.field_type, v, stream, depth))
+                }
+                _ => Err("An array was expected".to_string()),
+            }
+        } else {
+            self.encode_element(&field.field_type, value, stream, depth)
+        }
+    }
+
+    fn encode_element<S: Write>(
+        &self,
+        field_type: &FieldType,
+        value: &FieldValue,
+        stream: &mut S,
+        depth: usize,
+    ) -> Result<(), String> {
+        match (field_type, value) {
+            (FieldType::Builtin(BuiltinType::ExtensionObject), FieldValue::Structure(s)) => self
+                .encode_at_depth(s, depth + 1)?
+                .encode(stream)
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            (FieldType::Builtin(t), FieldValue::Scalar(v)) => encode_builtin(*t, v, stream),
+            (FieldType::Enumeration(_), FieldValue::Scalar(v)) => {
+                encode_builtin(BuiltinType::Int32, v, stream)
+            }
+            (FieldType::Structure(data_type_id), FieldValue::Structure(s)) => {
+                let layout = self
+                    .structure(data_type_id)
+                    .ok_or_else(|| format!("The layout of {} is not known", data_type_id))?;
+                self.encode_structure(layout, s, stream, depth + 1)
+            }
+            _ => Err("The value does not match the type of the field".to_string()),
+        }
+    }
+}
+
+/// The bit of an encoding mask for an optional field. Masks are at most 64 bits.
+fn switch_bit(bit: u32) -> Result<u64, String> {
+    1u64.checked_shl(bit)
+        .ok_or_else(|| format!("Bit {} of the encoding mask is out of range", bit))
+}
+
+fn check_mask_bits(layout: &StructureLayout, mask_bits: u32) -> Result<(), String> {
+    if mask_bits > 64 {
+        Err(format!(
+            "{} has an encoding mask of {} bits, more than 64",
+            layout.name, mask_bits
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The value of a built-in type that a new field starts with
+pub fn default_variant(builtin_type: BuiltinType) -> Variant {
+    match builtin_type {
+        BuiltinType::String => Variant::String(UAString::null()),
+        BuiltinType::XmlElement => Variant::XmlElement(UAString::null()),
+        BuiltinType::DateTime => Variant::from(DateTime::null()),
+        BuiltinType::Guid => Variant::from(Guid::null()),
+        BuiltinType::ByteString => Variant::from(ByteString::null()),
+        BuiltinType::NodeId => Variant::from(NodeId::null()),
+        BuiltinType::ExpandedNodeId => Variant::from(ExpandedNodeId::null()),
+        BuiltinType::QualifiedName => Variant::from(QualifiedName::null()),
+        BuiltinType::LocalizedText => Variant::from(LocalizedText::null()),
+        BuiltinType::ExtensionObject => Variant::from(ExtensionObject::null()),
+        BuiltinType::DataValue => Variant::from(DataValue::null()),
+        BuiltinType::DiagnosticInfo => Variant::from(DiagnosticInfo::null()),
+        BuiltinType::StatusCode => Variant::StatusCode(StatusCode::Good),
+        t => value_edit::parse_scalar(t, t.default_text()).unwrap_or(Variant::Empty),
+    }
+}
+
+fn decode_builtin<S: Read>(builtin_type: BuiltinType, stream: &mut S) -> Result<Variant, String> {
+    let o = DecodingOptions::default();
+    let v = match builtin_type {
+        BuiltinType::Boolean => bool::decode(stream, &o).map(Variant::from),
+        BuiltinType::SByte => i8::decode(stream, &o).map(Variant::from),
+        BuiltinType::Byte => u8::decode(stream, &o).map(Variant::from),
+        BuiltinType::Int16 => i16::decode(stream, &o).map(Variant::from),
+        BuiltinType::UInt16 => u16::decode(stream, &o).map(Variant::from),
+        BuiltinType::Int32 => i32::decode(stream, &o).map(Variant::from),
+        BuiltinType::UInt32 => u32::decode(stream, &o).map(Variant::from),
+        BuiltinType::Int64 => i64::decode(stream, &o).map(Variant::from),
+        BuiltinType::UInt64 => u64::decode(stream, &o).map(Variant::from),
+        BuiltinType::Float => f32::decode(stream, &o).map(Variant::from),
+        BuiltinType::Double => f64::decode(stream, &o).map(Variant::from),
+        BuiltinType::String => UAString::decode(stream, &o).map(Variant::String),
+        BuiltinType::DateTime => DateTime::decode(stream, &o).map(Variant::from),
+        BuiltinType::Guid => Guid::decode(stream, &o).map(Variant::from),
+        BuiltinType::ByteString => ByteString::decode(stream, &o).map(Variant::from),
+        BuiltinType::XmlElement => UAString::decode(stream, &o).map(Variant::XmlElement),
+        BuiltinType::NodeId => NodeId::decode(stream, &o).map(Variant::from),
+        BuiltinType::ExpandedNodeId => ExpandedNodeId::decode(stream, &o).map(Variant::from),
+        BuiltinType::StatusCode => StatusCode::decode(stream, &o).map(Variant::StatusCode),
+        BuiltinType::QualifiedName => QualifiedName::decode(stream, &o).map(Variant::from),
+        BuiltinType::LocalizedText => LocalizedText::decode(stream, &o).map(Variant::from),
+        BuiltinType::ExtensionObject => ExtensionObject::decode(stream, &o).map(Variant::from),
+        BuiltinType::DataValue => DataValue::decode(stream, &o).map(Variant::from),
+        BuiltinType::Variant => Variant::decode(stream, &o),
+        BuiltinType::DiagnosticInfo => DiagnosticInfo::decode(stream, &o).map(Variant::from),
+    };
+    v.map_err(|err| err.to_string())
+}
+
+fn encode_builtin<S: Write>(
+    builtin_type: BuiltinType,
+    v: &Variant,
+    stream: &mut S,
+) -> Result<(), String> {
+    let result = match (builtin_type, v) {
+        (BuiltinType::Boolean, Variant::Boolean(v)) => v.encode(stream),
+        (BuiltinType::SByte, Variant::SByte(v)) => v.encode(stream),
+        (BuiltinType::Byte, Variant::Byte(v)) => v.encode(stream),
+        (BuiltinType::Int16, Variant::Int16(v)) => v.encode(stream),
+        (BuiltinType::UInt16, Variant::UInt16(v)) => v.encode(stream),
+        (BuiltinType::Int32, Variant::Int32(v)) => v.encode(stream),
+        (BuiltinType::UInt32, Variant::UInt32(v)) => v.encode(stream),
+        (BuiltinType::Int64, Variant::Int64(v)) => v.encode(stream),
+        (BuiltinType::UInt64, Variant::UInt64(v)) => v.encode(stream),
+        (BuiltinType::Float, Variant::Float(v)) => v.encode(stream),
+        (BuiltinType::Double, Variant::Double(v)) => v.encode(stream),
+        (BuiltinType::String, Variant::String(v)) => v.encode(stream),
+        (BuiltinType::DateTime, Variant::DateTime(v)) => v.encode(stream),
+        (BuiltinType::Guid, Variant::Guid(v)) => v.encode(stream),
+        (BuiltinType::ByteString, Variant::ByteString(v)) => v.encode(stream),
+        (BuiltinType::XmlElement, Variant::XmlElement(v)) => v.encode(stream),
+        (BuiltinType::NodeId, Variant::NodeId(v)) => v.encode(stream),
+        (BuiltinType::ExpandedNodeId, Variant::ExpandedNodeId(v)) => v.encode(stream),
+        (BuiltinType::StatusCode, Variant::StatusCode(v)) => v.encode(stream),
+        (BuiltinType::QualifiedName, Variant::QualifiedName(v)) => v.encode(stream),
+        (BuiltinType::LocalizedText, Variant::LocalizedText(v)) => v.encode(stream),
+        (BuiltinType::ExtensionObject, Variant::ExtensionObject(v)) => v.encode(stream),
+        (BuiltinType::DataValue, Variant::DataValue(v)) => v.encode(stream),
+        (BuiltinType::DiagnosticInfo, Variant::DiagnosticInfo(v)) => v.encode(stream),
+        (BuiltinType::Variant, v) => v.encode(stream),
+        (t, v) => {
+            return Err(format!(
+                "A {:?} was expected but the value is a {}",
+                t,
+                value_format::variant_type_name(v)
+            ))
+        }
+    };
+    result.map(|_| ()).map_err(|err| err.to_string())
+}
+
+/// Converts a decoded structure to a JSON object of its present fields
+pub fn structure_to_json(structure: &DecodedStructure) -> JsonValue {
+    let mut object = Map::new();
+    structure.fields.iter().for_each(|(name, value)| {
+        if *value != FieldValue::Absent {
+            object.insert(name.clone(), field_value_to_json(value));
+        }
+    });
+    JsonValue::Object(object)
+}
+
+fn field_value_to_json(value: &FieldValue) -> JsonValue {
+    match value {
+        FieldValue::Scalar(v) => value_format::variant_to_json(v),
+        FieldValue::Structure(s) => structure_to_json(s),
+        FieldValue::Array(values) => {
+            JsonValue::Array(values.iter().map(field_value_to_json).collect())
+        }
+        FieldValue::Absent => JsonValue::Null,
+    }
+}
+
+/// A field of a structured type in an OPC Binary schema
+#[derive(Debug, Clone)]
+struct SchemaField {
+    name: String,
+    /// The namespace URI and name of the field's type
+    type_name: (String, String),
+    length_field: Option<String>,
+    switch_field: Option<String>,
+    switch_value: Option<u32>,
+    /// The number of bits, for fields of type opc:Bit
+    length: u32,
+}
+
+/// The structured and enumerated types of a legacy DataTypeDictionary, which is an OPC Binary
+/// schema (Part 3, Annex C).
+#[derive(Debug, Clone, Default)]
+pub struct BinarySchema {
+    pub target_namespace: String,
+    structured_types: HashMap<String, Vec<SchemaField>>,
+    enumerated_types: HashSet<String>,
+}
+
+impl BinarySchema {
+    pub fn parse(xml: &str) -> Result<BinarySchema, String> {
+        let document = roxmltree::Document::parse(xml).map_err(|err| err.to_string())?;
+        let root = document.root_element();
+        let mut schema = BinarySchema {
+            target_namespace: root.attribute("TargetNamespace").unwrap_or("").to_string(),
+            ..Default::default()
+        };
+
+        root.children()
+            .filter(|n| n.is_element())
+            .for_each(|n| match n.tag_name().name() {
+                "StructuredType" => {
+                    let name = n.attribute("Name").unwrap_or("").to_string();
+                    let fields = n
+                        .children()
+                        .filter(|f| f.is_element() && f.tag_name().name() == "Field")
+                        .map(|f| {
+                            // Type names are qualified with a prefix for their namespace
+                            let type_name = f.attribute("TypeName").unwrap_or("");
+                            let (prefix, local_name) = match type_name.find(':') {
+                                Some(pos) => (Some(&type_name[..pos]), &type_name[pos + 1..]),
+                                None => (None, type_name),
+                            };
+                            let namespace = f.lookup_namespace_uri(prefix).unwrap_or("");
+                            SchemaField {
+                                name: f.attribute("Name").unwrap_or("").to_string(),
+                                type_name: (namespace.to_string(), local_name.to_string()),
+                                length_field: f.attribute("LengthField").map(|v| v.to_string()),
+                                switch_field: f.attribute("SwitchField").map(|v| v.to_string()),
+                                switch_value: f
+                                    .attribute("SwitchValue")
+                                    .and_then(|v| v.parse().ok()),
+                                length: f
+                                    .attribute("Length")
+                                    .and_then(|v| v.parse().ok())
+                                    .unwrap_or(1),
+                            }
+                        })
+                        .collect();
+                    schema.structured_types.insert(name, fields);
+                }
+                "EnumeratedType" => {
+                    schema
+                        .enumerated_types
+                        .insert(n.attribute("Name").unwrap_or("").to_string());
+                }
+                _ => {}
+            });
+        Ok(schema)
+    }
+
+    pub fn has_structure(&self, name: &str) -> bool {
+        self.structured_types.contains_key(name)
+    }
+
+    /// Makes the layout of one of the schema's structured types. Types from other structures
+    /// are found with the resolver, which is passed the namespace URI and name of the type.
+    pub fn layout<F>(
+        &self,
+        name: &str,
+        data_type_id: NodeId,
+        encoding_id: NodeId,
+        resolve_structure: F,
+    ) -> Result<StructureLayout, String>
+    where
+        F: Fn(&str, &str) -> Option<NodeId>,
+    {
+        let fields = self
+            .structured_types
+            .get(name)
+            .ok_or_else(|| format!("{} is not in the type dictionary", name))?;
+
+        // Length fields are implied by the array encoding, and the bits are the encoding mask
+        let length_fields = fields
+            .iter()
+            .filter_map(|f| f.length_field.as_ref())
+            .collect::<HashSet<_>>();
+        let switch_fields = fields
+            .iter()
+            .filter(|f| f.switch_value.is_some())
+            .filter_map(|f| f.switch_field.as_ref())
+            .collect::<HashSet<_>>();
+        let is_bit =
+            |f: &SchemaField| f.type_name.0 == BINARY_SCHEMA_NAMESPACE && f.type_name.1 == "Bit";
+
+        let mut bits = HashMap::new();
+        let mut mask_bits = 0;
+        fields.iter().filter(|f| is_bit(f)).for_each(|f| {
+            bits.insert(f.name.clone(), mask_bits);
+            mask_bits += f.length;
+        });
+
+        let kind = if !switch_fields.is_empty() {
+            StructureKind::Union
+        } else if mask_bits > 0 {
+            StructureKind::WithOptionalFields(mask_bits)
+        } else {
+            StructureKind::Structure
+        };
+
+        let fields = fields
+            .iter()
+            .filter(|f| {
+                !is_bit(f) && !length_fields.contains(&f.name) && !switch_fields.contains(&f.name)
+            })
+            .map(|f| {
+                let (ref namespace, ref type_name) = f.type_name;
+                let field_type =
+                    if namespace == BINARY_SCHEMA_NAMESPACE || namespace == UA_NAMESPACE {
+                        let builtin_name = match type_name.as_str() {
+                            "CharArray" => "String",
+                            "Char" => "Byte",
+                            name => name,
+                        };
+                        BuiltinType::from_name(builtin_name).map(FieldType::Builtin)
+                    } else {
+                        None
+                    };
+                let field_type = match field_type {
+                    Some(field_type) => field_type,
+                    None if *namespace == self.target_namespace
+                        && self.enumerated_types.contains(type_name) =>
+                    {
+                        FieldType::Builtin(BuiltinType::Int32)
+                    }
+                    None => resolve_structure(namespace, type_name)
+                        .map(FieldType::Structure)
+                        .ok_or_else(|| {
+                            format!("Field {} has unknown type {}", f.name, type_name)
+                        })?,
+                };
+                let switch = match f.switch_value {
+                    Some(switch_value) => Some(switch_value),
+                    None => f.switch_field.as_ref().and_then(|s| bits.get(s).cloned()),
+                };
+                Ok(FieldLayout {
+                    name: f.name.clone(),
+                    field_type,
+                    value_rank: if f.length_field.is_some() { 1 } else { -1 },
+                    switch,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(StructureLayout {
+            data_type_id,
+            name: name.to_string(),
+            encoding_id,
+            kind,
+            fields,
+        })
+    }
+}
+
+/// Makes the layout of a structure from its DataTypeDefinition attribute. The resolver says how
+/// fields of each DataType are encoded.
+pub fn layout_from_definition<F>(
+    data_type_id: NodeId,
+    name: &str,
+    definition: &StructureDefinition,
+    mut resolve_field_type: F,
+) -> StructureLayout
+where
+    F: FnMut(&NodeId) -> FieldType,
+{
+    let is_optional = definition.structure_type == StructureType::StructureWithOptionalFields;
+    let is_union = definition.structure_type == StructureType::Union;
+    let mut next_bit = 0;
+    let fields = definition
+        .fields
+        .as_ref()
+        .map_or(&[][..], |f| f.as_slice())
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let switch = if is_union {
+                Some(i as u32 + 1)
+            } else if is_optional && f.is_optional {
+                next_bit += 1;
+                Some(next_bit - 1)
+            } else {
+                None
+            };
+            FieldLayout {
+                name: f.name.to_string(),
+                field_type: resolve_field_type(&f.data_type),
+                value_rank: f.value_rank,
+                switch,
+            }
+        })
+        .collect();
+    let kind = if is_union {
+        StructureKind::Union
+    } else if is_optional {
+        StructureKind::WithOptionalFields(32)
+    } else {
+        StructureKind::Structure
+    };
+    StructureLayout {
+        data_type_id,
+        name: name.to_string(),
+        encoding_id: definition.default_encoding_id.clone(),
+        kind,
+        fields,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"<opc:TypeDictionary
+    xmlns:opc="http://opcfoundation.org/BinarySchema/"
+    xmlns:ua="http://opcfoundation.org/UA/"
+    xmlns:tns="urn:example"
+    TargetNamespace="urn:example">
+  <opc:EnumeratedType Name="Mode" LengthInBits="32">
+    <opc:EnumeratedValue Name="Off" Value="0"/>
+    <opc:EnumeratedValue Name="On" Value="1"/>
+  </opc:EnumeratedType>
+  <opc:StructuredType Name="Point" BaseType="ua:ExtensionObject">
+    <opc:Field Name="X" TypeName="opc:Double"/>
+    <opc:Field Name="Y" TypeName="opc:Double"/>
+  </opc:StructuredType>
+  <opc:StructuredType Name="Path" BaseType="ua:ExtensionObject">
+    <opc:Field Name="NameSpecified" TypeName="opc:Bit"/>
+    <opc:Field Name="Reserved1" TypeName="opc:Bit" Length="31"/>
+    <opc:Field Name="Mode" TypeName="tns:Mode"/>
+    <opc:Field Name="NoOfPoints" TypeName="opc:Int32"/>
+    <opc:Field Name="Points" TypeName="tns:Point" LengthField="NoOfPoints"/>
+    <opc:Field Name="Name" TypeName="opc:CharArray" SwitchField="NameSpecified"/>
+    <opc:Field Name="Id" TypeName="ua:NodeId"/>
+  </opc:StructuredType>
+  <opc:StructuredType Name="Choice" BaseType="ua:Union">
+    <opc:Field Name="SwitchField" TypeName="opc:UInt32"/>
+    <opc:Field Name="Number" TypeName="opc:Int32" SwitchField="SwitchField" SwitchValue="1"/>
+    <opc:Field Name="Text" TypeName="opc:CharArray" SwitchField="SwitchField" SwitchValue="2"/>
+  </opc:StructuredType>
+</opc:TypeDictionary>"#;
+
+    fn point_id() -> NodeId {
+        NodeId::new(2, "Point")
+    }
+
+    fn path_id() -> NodeId {
+        NodeId::new(2, "Path")
+    }
+
+    fn choice_id() -> NodeId {
+        NodeId::new(2, "Choice")
+    }
+
+    /// The dictionary of the structures in the schema
+    fn type_dictionary() -> TypeDictionary {
+        let schema = BinarySchema::parse(SCHEMA).unwrap();
+        let resolve_structure = |namespace: &str, name: &str| {
+            if namespace == "urn:example" && name == "Point" {
+                Some(point_id())
+            } else {
+                None
+            }
+        };
+        let mut type_dictionary = TypeDictionary::default();
+        [
+            ("Point", point_id(), 1001u32),
+            ("Path", path_id(), 1002),
+            ("Choice", choice_id(), 1003),
+        ]
+        .iter()
+        .for_each(|(name, data_type_id, encoding_id)| {
+            let layout = schema
+                .layout(
+                    name,
+                    data_type_id.clone(),
+                    NodeId::new(2, *encoding_id),
+                    resolve_structure,
+                )
+                .unwrap();
+            type_dictionary.insert(layout);
+        });
+        type_dictionary
+    }
+
+    fn point(x: f64, y: f64) -> FieldValue {
+        FieldValue::Structure(DecodedStructure {
+            data_type_id: point_id(),
+            type_name: "Point".to_string(),
+            fields: vec![
+                ("X".to_string(), FieldValue::Scalar(Variant::Double(x))),
+                ("Y".to_string(), FieldValue::Scalar(Variant::Double(y))),
+            ],
+        })
+    }
+
+    fn path(name: Option<&str>) -> DecodedStructure {
+        DecodedStructure {
+            data_type_id: path_id(),
+            type_name: "Path".to_string(),
+            fields: vec![
+                ("Mode".to_string(), FieldValue::Scalar(Variant::Int32(1))),
+                (
+                    "Points".to_string(),
+                    FieldValue::Array(vec![point(0.0, 1.5), point(-2.0, 3.25)]),
+                ),
+                (
+                    "Name".to_string(),
+                    name.map_or(FieldValue::Absent, |name| {
+                        FieldValue::Scalar(Variant::String(UAString::from(name)))
+                    }),
+                ),
+                (
+                    "Id".to_string(),
+                    FieldValue::Scalar(Variant::from(NodeId::new(3, 42u32))),
+                ),
+            ],
+        }
+    }
+
+    fn body(extension_object: &ExtensionObject) -> Vec<u8> {
+        match extension_object.body {
+            ExtensionObjectEncoding::ByteString(ref body) => body.value.clone().unwrap(),
+            _ => panic!("The body is not binary encoded"),
+        }
+    }
+
+    fn extension_object(encoding_id: u32, body: Vec<u8>) -> ExtensionObject {
+        ExtensionObject {
+            node_id: NodeId::new(2, encoding_id),
+            body: ExtensionObjectEncoding::ByteString(ByteString::from(body)),
+        }
+    }
+
+    #[test]
+    fn parse_binary_schema() {
+        let schema = BinarySchema::parse(SCHEMA).unwrap();
+        assert_eq!(schema.target_namespace, "urn:example");
+        assert!(schema.has_structure("Point"));
+        assert!(schema.has_structure("Path"));
+        assert!(!schema.has_structure("Mode"));
+        assert!(BinarySchema::parse("<opc:TypeDictionary").is_err());
+    }
+
+    #[test]
+    fn binary_schema_layouts() {
+        let type_dictionary = type_dictionary();
+        let fields = |layout: &StructureLayout| {
+            layout
+                .fields
+                .iter()
+                .map(|f| (f.name.clone(), f.field_type.clone(), f.value_rank, f.switch))
+                .collect::<Vec<_>>()
+        };
+
+        // Bits make up the encoding mask and the length field is part of the array encoding
+        let path_layout = type_dictionary.structure(&path_id()).unwrap();
+        assert_eq!(path_layout.kind, StructureKind::WithOptionalFields(32));
+        assert_eq!(path_layout.encoding_id, NodeId::new(2, 1002u32));
+        assert_eq!(
+            fields(path_layout),
+            vec![
+                (
+                    "Mode".to_string(),
+                    FieldType::Builtin(BuiltinType::Int32),
+                    -1,
+                    None
+                ),
+                (
+                    "Points".to_string(),
+                    FieldType::Structure(point_id()),
+                    1,
+                    None
+                ),
+                (
+                    "Name".to_string(),
+                    FieldType::Builtin(BuiltinType::String),
+                    -1,
+                    Some(0)
+                ),
+                (
+                    "Id".to_string(),
+                    FieldType::Builtin(BuiltinType::NodeId),
+                    -1,
+                    None
+                ),
+            ]
+        );
+
+        let choice_layout = type_dictionary.structure(&choice_id()).unwrap();
+        assert_eq!(choice_layout.kind, StructureKind::Union);
+        assert_eq!(
+            fields(choice_layout),
+            vec![
+                (
+                    "Number".to_string(),
+                    FieldType::Builtin(BuiltinType::Int32),
+                    -1,
+                    Some(1)
+                ),
+                (
+                    "Text".to_string(),
+                    FieldType::Builtin(BuiltinType::String),
+                    -1,
+                    Some(2)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_schema_layout_with_unknown_type() {
+        let schema = BinarySchema::parse(SCHEMA).unwrap();
+        let result = schema.layout("Path", path_id(), NodeId::new(2, 1002u32), |_, _| None);
+        assert!(result.is_err());
+        let result = schema.layout("Missing", NodeId::new(2, 1u32), NodeId::null(), |_, _| None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn structure_round_trip() {
+        let type_dictionary = type_dictionary();
+        [Some("Route 66"), None].iter().for_each(|name| {
+            let structure = path(*name);
+            let encoded = type_dictionary.encode(&structure).unwrap();
+            assert_eq!(encoded.node_id, NodeId::new(2, 1002u32));
+            // The encoding mask says whether the name is present
+            let mask = if name.is_some() { 1 } else { 0 };
+            assert_eq!(body(&encoded)[..4], [mask, 0, 0, 0]);
+            assert_eq!(type_dictionary.decode(&encoded), Some(Ok(structure)));
+        });
+    }
+
+    #[test]
+    fn union_round_trip() {
+        let type_dictionary = type_dictionary();
+        let structure = DecodedStructure {
+            data_type_id: choice_id(),
+            type_name: "Choice".to_string(),
+            fields: vec![
+                ("Number".to_string(), FieldValue::Absent),
+                (
+                    "Text".to_string(),
+                    FieldValue::Scalar(Variant::String(UAString::from("abc"))),
+                ),
+            ],
+        };
+        let encoded = type_dictionary.encode(&structure).unwrap();
+        assert_eq!(
+            body(&encoded),
+            vec![2, 0, 0, 0, 3, 0, 0, 0, b'a', b'b', b'c']
+        );
+        assert_eq!(type_dictionary.decode(&encoded), Some(Ok(structure)));
+
+        // A switch of 0 selects none of the fields
+        let decoded = type_dictionary
+            .decode(&extension_object(1003, vec![0, 0, 0, 0]))
+            .unwrap()
+            .unwrap();
+        assert!(decoded.fields.iter().all(|(_, v)| *v == FieldValue::Absent));
+    }
+
+    #[test]
+    fn decode_rejects_truncated_body() {
+        let type_dictionary = type_dictionary();
+        let encoded = type_dictionary.encode(&path(Some("Route 66"))).unwrap();
+        let mut bytes = body(&encoded);
+        bytes.pop();
+        assert!(matches!(
+            type_dictionary.decode(&extension_object(1002, bytes)),
+            Some(Err(_))
+        ));
+        assert!(matches!(
+            type_dictionary.decode(&extension_object(1002, Vec::new())),
+            Some(Err(_))
+        ));
+        // Structures of unknown types aren't decoded
+        assert_eq!(
+            type_dictionary.decode(&extension_object(9999, Vec::new())),
+            None
+        );
+    }
+
+    #[test]
+    fn encode_rejects_invalid_values() {
+        let type_dictionary = type_dictionary();
+        let mut structure = path(None);
+        structure.fields[0].1 = FieldValue::Absent;
+        assert!(type_dictionary.encode(&structure).is_err());
+
+        let mut structure = path(None);
+        structure.fields[0].1 = FieldValue::Scalar(Variant::Double(1.0));
+        assert!(type_dictionary.encode(&structure).is_err());
+
+        let mut structure = path(None);
+        structure.data_type_id = NodeId::new(2, "Unknown");
+        assert!(type_dictionary.encode(&structure).is_err());
+    }
+
+    #[test]
+    fn decode_stops_at_nesting_limit() {
+        let loop_id = NodeId::new(2, "Loop");
+        let mut type_dictionary = TypeDictionary::default();
+        type_dictionary.insert(StructureLayout {
+            data_type_id: loop_id.clone(),
+            name: "Loop".to_string(),
+            encoding_id: NodeId::new(2, 2001u32),
+            kind: StructureKind::Structure,
+            fields: vec![FieldLayout {
+                name: "Child".to_string(),
+                field_type: FieldType::Structure(loop_id),
+                value_rank: -1,
+                switch: None,
+            }],
+        });
+        assert!(matches!(
+            type_dictionary.decode(&extension_object(2001, Vec::new())),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn encoding_masks_are_at_most_64_bits() {
+        let wide_id = NodeId::new(2, "Wide");
+        let mut type_dictionary = TypeDictionary::default();
+        type_dictionary.insert(StructureLayout {
+            data_type_id: wide_id.clone(),
+            name: "Wide".to_string(),
+            encoding_id: NodeId::new(2, 2002u32),
+            kind: StructureKind::WithOptionalFields(72),
+            fields: vec![FieldLayout {
+                name: "Value".to_string(),
+                field_type: FieldType::Builtin(BuiltinType::Int32),
+                value_rank: -1,
+                switch: Some(70),
+            }],
+        });
+        assert!(matches!(
+            type_dictionary.decode(&extension_object(2002, vec![0xff; 13])),
+            Some(Err(_))
+        ));
+        let structure = DecodedStructure {
+            data_type_id: wide_id,
+            type_name: "Wide".to_string(),
+            fields: vec![("Value".to_string(), FieldValue::Scalar(Variant::Int32(1)))],
+        };
+        assert!(type_dictionary.encode(&structure).is_err());
+        assert!(switch_bit(63).is_ok());
+        assert!(switch_bit(64).is_err());
+    }
+}
@@ -0,0 +1,690 @@
+use std::{convert::TryFrom, str::FromStr};
+
+use opcua_client::prelude::*;
+
+use crate::value_format::{self, TimeDisplay};
+
+/// Bit of the AccessLevel / UserAccessLevel attributes that says the value can be written
+pub const ACCESS_LEVEL_CURRENT_WRITE: u8 = 0x02;
+
+/// ValueRank of a scalar
+pub const VALUE_RANK_SCALAR: i32 = -1;
+
+/// The built-in types of OPC UA. The discriminant is the numeric id of the type's DataType node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltinType {
+    Boolean = 1,
+    SByte = 2,
+    Byte = 3,
+    Int16 = 4,
+    UInt16 = 5,
+    Int32 = 6,
+    UInt32 = 7,
+    Int64 = 8,
+    UInt64 = 9,
+    Float = 10,
+    Double = 11,
+    String = 12,
+    DateTime = 13,
+    Guid = 14,
+    ByteString = 15,
+    XmlElement = 16,
+    NodeId = 17,
+    ExpandedNodeId = 18,
+    StatusCode = 19,
+    QualifiedName = 20,
+    LocalizedText = 21,
+    ExtensionObject = 22,
+    DataValue = 23,
+    Variant = 24,
+    DiagnosticInfo = 25,
+}
+
+/// The scalar types which can be typed in as text
+pub const TEXT_TYPES: &[BuiltinType] = &[
+    BuiltinType::Boolean,
+    BuiltinType::SByte,
+    BuiltinType::Byte,
+    BuiltinType::Int16,
+    BuiltinType::UInt16,
+    BuiltinType::Int32,
+    BuiltinType::UInt32,
+    BuiltinType::Int64,
+    BuiltinType::UInt64,
+    BuiltinType::Float,
+    BuiltinType::Double,
+    BuiltinType::String,
+    BuiltinType::DateTime,
+    BuiltinType::Guid,
+    BuiltinType::ByteString,
+    BuiltinType::XmlElement,
+    BuiltinType::NodeId,
+    BuiltinType::ExpandedNodeId,
+    BuiltinType::StatusCode,
+    BuiltinType::QualifiedName,
+    BuiltinType::LocalizedText,
+];
+
+impl BuiltinType {
+    /// Returns the built-in type for a DataType node id. Structure (i=22) is the DataType of
+    /// ExtensionObject and BaseDataType (i=24) that of Variant, so they map naturally.
+    pub fn from_data_type(data_type: &NodeId) -> Option<BuiltinType> {
+        if data_type.namespace != 0 {
+            return None;
+        }
+        match data_type.identifier {
+            Identifier::Numeric(id) => Self::from_id(id),
+            _ => None,
+        }
+    }
+
+    pub fn from_id(id: u32) -> Option<BuiltinType> {
+        TEXT_TYPES
+            .iter()
+            .chain(
+                [
+                    BuiltinType::ExtensionObject,
+                    BuiltinType::DataValue,
+                    BuiltinType::Variant,
+                    BuiltinType::DiagnosticInfo,
+                ]
+                .iter(),
+            )
+            .find(|t| **t as u32 == id)
+            .cloned()
+    }
+
+    pub fn from_name(name: &str) -> Option<BuiltinType> {
+        TEXT_TYPES
+            .iter()
+            .find(|t| format!("{:?}", t) == name)
+            .cloned()
+    }
+
+    /// The bounds of integer types
+    pub fn integer_range(self) -> Option<(i128, i128)> {
+        match self {
+            BuiltinType::SByte => Some((i8::MIN as i128, i8::MAX as i128)),
+            BuiltinType::Byte => Some((0, u8::MAX as i128)),
+            BuiltinType::Int16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            BuiltinType::UInt16 => Some((0, u16::MAX as i128)),
+            BuiltinType::Int32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            BuiltinType::UInt32 => Some((0, u32::MAX as i128)),
+            BuiltinType::Int64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            BuiltinType::UInt64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
+
+    pub fn variant_type_id(self) -> VariantTypeId {
+        match self {
+            BuiltinType::Boolean => VariantTypeId::Boolean,
+            BuiltinType::SByte => VariantTypeId::SByte,
+            BuiltinType::Byte => VariantTypeId::Byte,
+            BuiltinType::Int16 => VariantTypeId::Int16,
+            BuiltinType::UInt16 => VariantTypeId::UInt16,
+            BuiltinType::Int32 => VariantTypeId::Int32,
+            BuiltinType::UInt32 => VariantTypeId::UInt32,
+            BuiltinType::Int64 => VariantTypeId::Int64,
+            BuiltinType::UInt64 => VariantTypeId::UInt64,
+            BuiltinType::Float => VariantTypeId::Float,
+            BuiltinType::Double => VariantTypeId::Double,
+            BuiltinType::String => VariantTypeId::String,
+            BuiltinType::DateTime => VariantTypeId::DateTime,
+            BuiltinType::Guid => VariantTypeId::Guid,
+            BuiltinType::ByteString => VariantTypeId::ByteString,
+            BuiltinType::XmlElement => VariantTypeId::XmlElement,
+            BuiltinType::NodeId => VariantTypeId::NodeId,
+            BuiltinType::ExpandedNodeId => VariantTypeId::ExpandedNodeId,
+            BuiltinType::StatusCode => VariantTypeId::StatusCode,
+            BuiltinType::QualifiedName => VariantTypeId::QualifiedName,
+            BuiltinType::LocalizedText => VariantTypeId::LocalizedText,
+            BuiltinType::ExtensionObject => VariantTypeId::ExtensionObject,
+            _ => VariantTypeId::Empty,
+        }
+    }
+
+    /// The text of a new element, e.g. when an array is made longer
+    pub fn default_text(self) -> &'static str {
+        match self {
+            BuiltinType::Boolean => "false",
+            BuiltinType::Float | BuiltinType::Double => "0.0",
+            BuiltinType::DateTime => "1601-01-01 00:00:00.0000000Z",
+            BuiltinType::Guid => "00000000-0000-0000-0000-000000000000",
+            BuiltinType::NodeId | BuiltinType::ExpandedNodeId => "i=0",
+            BuiltinType::StatusCode => "0x00000000",
+            BuiltinType::String
+            | BuiltinType::ByteString
+            | BuiltinType::XmlElement
+            | BuiltinType::QualifiedName
+            | BuiltinType::LocalizedText => "",
+            _ => "0",
+        }
+    }
+
+    /// Tests if the type can be edited precisely with a spin button, which holds an f64
+    pub fn is_spinnable(self) -> bool {
+        match self.integer_range() {
+            Some((min, max)) => min >= i32::MIN as i128 && max <= u32::MAX as i128,
+            None => false,
+        }
+    }
+
+    /// Makes a variant of this type from an integer, checking it is in range
+    pub fn integer_variant(self, v: i128) -> Result<Variant, String> {
+        let (min, max) = self
+            .integer_range()
+            .ok_or_else(|| format!("{:?} is not an integer type", self))?;
+        if v < min || v > max {
+            return Err(format!(
+                "{} is out of range for {:?} ({} to {})",
+                v, self, min, max
+            ));
+        }
+        Ok(match self {
+            BuiltinType::SByte => Variant::SByte(v as i8),
+            BuiltinType::Byte => Variant::Byte(v as u8),
+            BuiltinType::Int16 => Variant::Int16(v as i16),
+            BuiltinType::UInt16 => Variant::UInt16(v as u16),
+            BuiltinType::Int32 => Variant::Int32(v as i32),
+            BuiltinType::UInt32 => Variant::UInt32(v as u32),
+            BuiltinType::Int64 => Variant::Int64(v as i64),
+            _ => Variant::UInt64(v as u64),
+        })
+    }
+}
+
+/// Parses text typed by the user into a value of the built-in type. The accepted formats match
+/// those produced by the value viewer.
+pub fn parse_scalar(builtin_type: BuiltinType, text: &str) -> Result<Variant, String> {
+    let trimmed = text.trim();
+    let invalid = || format!("\"{}\" is not a valid {:?}", trimmed, builtin_type);
+    match builtin_type {
+        BuiltinType::Boolean => match trimmed.to_lowercase().as_str() {
+            "true" | "1" => Ok(Variant::Boolean(true)),
+            "false" | "0" => Ok(Variant::Boolean(false)),
+            _ => Err(invalid()),
+        },
+        BuiltinType::SByte
+        | BuiltinType::Byte
+        | BuiltinType::Int16
+        | BuiltinType::UInt16
+        | BuiltinType::Int32
+        | BuiltinType::UInt32
+        | BuiltinType::Int64
+        | BuiltinType::UInt64 => {
+            let v = trimmed.parse::<i128>().map_err(|_| invalid())?;
+            builtin_type.integer_variant(v)
+        }
+        BuiltinType::Float => trimmed
+            .parse::<f32>()
+            .map(Variant::Float)
+            .map_err(|_| invalid()),
+        BuiltinType::Double => trimmed
+            .parse::<f64>()
+            .map(Variant::Double)
+            .map_err(|_| invalid()),
+        // Strings are taken as typed, including leading and trailing space
+        BuiltinType::String => Ok(Variant::String(UAString::from(text))),
+        BuiltinType::XmlElement => Ok(Variant::XmlElement(UAString::from(text))),
+        BuiltinType::DateTime => parse_date_time(trimmed).map(Variant::from),
+        BuiltinType::Guid => Guid::from_str(trimmed)
+            .map(Variant::from)
+            .map_err(|_| invalid()),
+        BuiltinType::ByteString => parse_hex(trimmed)
+            .map(|bytes| Variant::from(ByteString::from(bytes)))
+            .ok_or_else(invalid),
+        BuiltinType::NodeId => NodeId::from_str(trimmed)
+            .map(Variant::from)
+            .map_err(|_| invalid()),
+        BuiltinType::ExpandedNodeId => NodeId::from_str(trimmed)
+            .map(|v| Variant::from(ExpandedNodeId::from(v)))
+            .map_err(|_| invalid()),
+        BuiltinType::StatusCode => {
+            let bits = if let Some(hex) = trimmed.strip_prefix("0x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else {
+                trimmed.parse::<u32>().ok()
+            };
+            bits.map(|bits| Variant::from(StatusCode::from_bits_truncate(bits)))
+                .ok_or_else(invalid)
+        }
+        BuiltinType::QualifiedName => {
+            // "ns:name", or just "name" in namespace 0
+            let (namespace_index, name) = match trimmed.find(':') {
+                Some(pos) => match trimmed[..pos].parse::<u16>() {
+                    Ok(ns) => (ns, &trimmed[pos + 1..]),
+                    Err(_) => (0, trimmed),
+                },
+                None => (0, trimmed),
+            };
+            Ok(Variant::from(QualifiedName::new(namespace_index, name)))
+        }
+        BuiltinType::LocalizedText => {
+            // "text [locale]", or just "text" with no locale
+            let (text, locale) = match (trimmed.rfind('['), trimmed.ends_with(']')) {
+                (Some(pos), true) => (
+                    trimmed[..pos].trim_end(),
+                    &trimmed[pos + 1..trimmed.len() - 1],
+                ),
+                _ => (trimmed, ""),
+            };
+            Ok(Variant::from(LocalizedText::new(locale, text)))
+        }
+        _ => Err(format!(
+            "{:?} values cannot be entered as text",
+            builtin_type
+        )),
+    }
+}
+
+/// Formats a value as text in the form that `parse_scalar` accepts back
+pub fn scalar_to_edit_text(v: &Variant) -> String {
+    match v {
+        Variant::StatusCode(v) => format!("0x{:08X}", v.bits()),
+        Variant::ByteString(v) if v.is_null() => String::new(),
+        v => value_format::variant_to_string(v, TimeDisplay::Utc),
+    }
+}
+
+/// Formats an enumeration value with the name of its member, e.g. "Running (3)"
+pub fn enum_value_to_string(members: &[(i64, String)], value: i64) -> String {
+    match members.iter().find(|(v, _)| *v == value) {
+        Some((_, name)) => format!("{} ({})", name, value),
+        None => value.to_string(),
+    }
+}
+
+/// Parses an enumeration value given as a member name, a number or both as formatted by
+/// `enum_value_to_string`
+pub fn parse_enum(members: &[(i64, String)], text: &str) -> Result<Variant, String> {
+    let text = text.trim();
+    // Take the number from "Name (3)"
+    let number = match (text.rfind('('), text.ends_with(')')) {
+        (Some(pos), true) => &text[pos + 1..text.len() - 1],
+        _ => text,
+    };
+    let value = match number.trim().parse::<i64>() {
+        Ok(value) => value,
+        Err(_) => members
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(text))
+            .map(|(value, _)| *value)
+            .ok_or_else(|| format!("\"{}\" is not a member of the enumeration", text))?,
+    };
+    if !members.is_empty() && !members.iter().any(|(v, _)| *v == value) {
+        return Err(format!("{} is not a member of the enumeration", value));
+    }
+    i32::try_from(value)
+        .map(Variant::Int32)
+        .map_err(|_| format!("{} is out of range for an enumeration", value))
+}
+
+/// Makes an array value. Dimensions are only stored for multi-dimensional arrays.
+pub fn make_array(element_type: BuiltinType, values: Vec<Variant>, dimensions: &[u32]) -> Variant {
+    let dimensions = if dimensions.len() > 1 {
+        dimensions.to_vec()
+    } else {
+        Vec::new()
+    };
+    Variant::Array(Box::new(Array {
+        value_type: element_type.variant_type_id(),
+        values,
+        dimensions,
+    }))
+}
+
+/// Parses a NumericRange, e.g. "5", "2:10" or "0:1,3:4" for a matrix, into an inclusive range
+/// per dimension. The range is checked against the dimensions of the array if supplied.
+pub fn parse_index_range(
+    text: &str,
+    dimensions: Option<&[u32]>,
+) -> Result<Vec<(u32, u32)>, String> {
+    let ranges = text
+        .trim()
+        .split(',')
+        .map(|range| {
+            let parse = |v: &str| {
+                v.trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("\"{}\" is not a valid index", v.trim()))
+            };
+            match range.find(':') {
+                Some(pos) => {
+                    let (min, max) = (parse(&range[..pos])?, parse(&range[pos + 1..])?);
+                    if min >= max {
+                        Err(format!(
+                            "In range \"{}\" the first index must be less than the second",
+                            range.trim()
+                        ))
+                    } else {
+                        Ok((min, max))
+                    }
+                }
+                None => parse(range).map(|v| (v, v)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(dimensions) = dimensions {
+        if ranges.len() > dimensions.len() {
+            return Err(format!(
+                "The range has {} dimensions but the array has {}",
+                ranges.len(),
+                dimensions.len()
+            ));
+        }
+        if let Some((i, (_, max))) = ranges
+            .iter()
+            .enumerate()
+            .find(|(i, (_, max))| *max >= dimensions[*i])
+        {
+            return Err(format!(
+                "Index {} is beyond the end of dimension {} which has length {}",
+                max, i, dimensions[i]
+            ));
+        }
+    }
+    Ok(ranges)
+}
+
+/// Formats an inclusive range per dimension as a NumericRange
+pub fn index_range_to_string(ranges: &[(u32, u32)]) -> String {
+    ranges
+        .iter()
+        .map(|(min, max)| {
+            if min == max {
+                min.to_string()
+            } else {
+                format!("{}:{}", min, max)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Splits CSV text into rows of fields. Fields may be separated by commas, semicolons or tabs
+/// and may be quoted with double quotes.
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = Vec::new();
+            let mut field = String::new();
+            let mut quoted = false;
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    '"' => quoted = !quoted,
+                    ',' | ';' | '\t' if !quoted => {
+                        fields.push(field.trim().to_string());
+                        field.clear();
+                    }
+                    c => field.push(c),
+                }
+            }
+            fields.push(field.trim().to_string());
+            fields
+        })
+        .collect()
+}
+
+/// Explains why a write failed in terms a user can act on
+pub fn describe_write_status(status_code: StatusCode) -> String {
+    let explanation = if status_code.is_good() {
+        "The value was written"
+    } else if status_code == StatusCode::BadIndexRangeInvalid {
+        "The server rejected the index range as invalid for this variable"
+    } else if status_code == StatusCode::BadIndexRangeNoData {
+        "The index range lies outside the current array. Some servers cannot grow an array with a partial write"
+    } else if status_code == StatusCode::BadTypeMismatch {
+        "The value or the size of the range does not match what the variable holds"
+    } else if status_code == StatusCode::BadOutOfRange {
+        "The value is outside the range the server accepts"
+    } else if status_code == StatusCode::BadNotWritable {
+        "The variable is not writable"
+    } else if status_code == StatusCode::BadUserAccessDenied {
+        "The user is not allowed to write the variable"
+    } else if status_code == StatusCode::BadWriteNotSupported {
+        "The server does not support writing this combination of value, status and timestamps"
+    } else {
+        "The write failed"
+    };
+    format!(
+        "{}: {}",
+        value_format::status_code_to_string(status_code),
+        explanation
+    )
+}
+
+/// Parses an ISO 8601 / RFC 3339 date time, e.g. 2020-06-01T12:00:00.0000001Z
+pub fn parse_date_time(text: &str) -> Result<DateTime, String> {
+    chrono::DateTime::parse_from_rfc3339(&text.replace(' ', "T"))
+        .map(|v| DateTime::from(v.with_timezone(&chrono::Utc)))
+        .map_err(|err| format!("\"{}\" is not a valid DateTime, {}", text, err))
+}
+
+/// Parses hex bytes, optionally separated by whitespace
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).filter(|_| c.is_ascii_hexdigit()))
+        .collect::<Option<Vec<_>>>()?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| (pair[0] * 16 + pair[1]) as u8)
+            .collect(),
+    )
+}
+
+/// What the explorer needs to know about a Variable to offer a suitable editor for its value
+#[derive(Debug, Clone)]
+pub struct WriteInfo {
+    pub node_id: NodeId,
+    pub display_name: String,
+    pub data_type: NodeId,
+    pub data_type_name: String,
+    /// The built-in type the DataType derives from, if any
+    pub builtin_type: Option<BuiltinType>,
+    /// For enumerations, the value and name of each member
+    pub enum_members: Vec<(i64, String)>,
+    pub value_rank: i32,
+    pub array_dimensions: Option<Vec<u32>>,
+    pub access_level: u8,
+    pub user_access_level: u8,
+    pub value: Option<DataValue>,
+}
+
+impl WriteInfo {
+    pub fn is_writable(&self) -> bool {
+        self.access_level & ACCESS_LEVEL_CURRENT_WRITE != 0
+            && self.user_access_level & ACCESS_LEVEL_CURRENT_WRITE != 0
+    }
+
+    pub fn is_enum(&self) -> bool {
+        !self.enum_members.is_empty()
+    }
+
+    /// Tests if the value is an array. ValueRanks of Any (-2) and ScalarOrOneDimension (-3) go
+    /// by what the variable currently holds.
+    pub fn is_array(&self) -> bool {
+        if self.value_rank >= 0 {
+            true
+        } else if self.value_rank == VALUE_RANK_SCALAR {
+            false
+        } else {
+            match self.value.as_ref().and_then(|v| v.value.as_ref()) {
+                Some(Variant::Array(_)) => true,
+                _ => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scalar_round_trips_edit_text() {
+        let guid = Guid::from_str("72962b91-fa75-4ae6-8d28-b404dc7daf63").unwrap();
+        let values = vec![
+            (BuiltinType::Boolean, Variant::Boolean(true)),
+            (BuiltinType::SByte, Variant::SByte(-128)),
+            (BuiltinType::Byte, Variant::Byte(255)),
+            (BuiltinType::Int32, Variant::Int32(-123_456)),
+            (BuiltinType::UInt64, Variant::UInt64(u64::MAX)),
+            (BuiltinType::Float, Variant::Float(1.5)),
+            (BuiltinType::Double, Variant::Double(-0.25)),
+            (
+                BuiltinType::String,
+                Variant::String(UAString::from("Tank 1")),
+            ),
+            (BuiltinType::Guid, Variant::from(guid)),
+            (
+                BuiltinType::ByteString,
+                Variant::from(ByteString::from(vec![0x00, 0x7f, 0xff])),
+            ),
+            (
+                BuiltinType::NodeId,
+                Variant::from(NodeId::new(2, "Tank.Level")),
+            ),
+            (
+                BuiltinType::StatusCode,
+                Variant::from(StatusCode::BadNodeIdUnknown),
+            ),
+            (
+                BuiltinType::QualifiedName,
+                Variant::from(QualifiedName::new(2, "Temperature")),
+            ),
+            (
+                BuiltinType::LocalizedText,
+                Variant::from(LocalizedText::new("de", "Hallo")),
+            ),
+            (
+                BuiltinType::LocalizedText,
+                Variant::from(LocalizedText::new("", "Hello")),
+            ),
+        ];
+        values.into_iter().for_each(|(builtin_type, value)| {
+            let text = scalar_to_edit_text(&value);
+            assert_eq!(parse_scalar(builtin_type, &text), Ok(value), "{}", text);
+        });
+    }
+
+    #[test]
+    fn parse_scalar_checks_integer_range() {
+        assert_eq!(
+            parse_scalar(BuiltinType::Byte, "255"),
+            Ok(Variant::Byte(255))
+        );
+        assert!(parse_scalar(BuiltinType::Byte, "256").is_err());
+        assert!(parse_scalar(BuiltinType::Byte, "-1").is_err());
+        assert!(parse_scalar(BuiltinType::SByte, "-129").is_err());
+        assert!(parse_scalar(BuiltinType::UInt64, "18446744073709551616").is_err());
+        assert!(parse_scalar(BuiltinType::Int32, "1.5").is_err());
+        assert!(parse_scalar(BuiltinType::Int32, "").is_err());
+    }
+
+    #[test]
+    fn parse_scalar_edge_cases() {
+        assert_eq!(
+            parse_scalar(BuiltinType::Boolean, " TRUE "),
+            Ok(Variant::Boolean(true))
+        );
+        assert_eq!(
+            parse_scalar(BuiltinType::Boolean, "0"),
+            Ok(Variant::Boolean(false))
+        );
+        assert!(parse_scalar(BuiltinType::Boolean, "yes").is_err());
+        // Strings keep their spaces, other types are trimmed
+        assert_eq!(
+            parse_scalar(BuiltinType::String, " a "),
+            Ok(Variant::String(UAString::from(" a ")))
+        );
+        assert_eq!(
+            parse_scalar(BuiltinType::StatusCode, "0"),
+            Ok(Variant::from(StatusCode::Good))
+        );
+        assert_eq!(
+            parse_scalar(BuiltinType::QualifiedName, "x:name"),
+            Ok(Variant::from(QualifiedName::new(0, "x:name")))
+        );
+        assert!(parse_scalar(BuiltinType::ByteString, "0").is_err());
+        assert!(parse_scalar(BuiltinType::Guid, "not a guid").is_err());
+        assert!(parse_scalar(BuiltinType::ExtensionObject, "").is_err());
+    }
+
+    #[test]
+    fn parse_index_range_round_trips() {
+        ["5", "2:10", "0:1,3:4", "0,7"].iter().for_each(|text| {
+            let ranges = parse_index_range(text, None).unwrap();
+            assert_eq!(index_range_to_string(&ranges), *text);
+        });
+        assert_eq!(parse_index_range(" 2 : 10 ", None), Ok(vec![(2, 10)]));
+    }
+
+    #[test]
+    fn parse_index_range_rejects_invalid_ranges() {
+        assert!(parse_index_range("", None).is_err());
+        assert!(parse_index_range("a", None).is_err());
+        assert!(parse_index_range("-1", None).is_err());
+        assert!(parse_index_range("3:3", None).is_err());
+        assert!(parse_index_range("4:2", None).is_err());
+        assert!(parse_index_range("1:", None).is_err());
+        assert!(parse_index_range("1,,2", None).is_err());
+    }
+
+    #[test]
+    fn parse_index_range_checks_dimensions() {
+        assert_eq!(parse_index_range("0:9", Some(&[10][..])), Ok(vec![(0, 9)]));
+        assert!(parse_index_range("0:10", Some(&[10][..])).is_err());
+        assert!(parse_index_range("1,1", Some(&[10][..])).is_err());
+        assert_eq!(parse_index_range("1", Some(&[2, 3][..])), Ok(vec![(1, 1)]));
+        assert!(parse_index_range("0,3", Some(&[2, 3][..])).is_err());
+        assert!(parse_index_range("0", Some(&[][..])).is_err());
+    }
+
+    #[test]
+    fn parse_csv_separators_and_quotes() {
+        assert_eq!(
+            parse_csv("1, 2;3\t4\n\n5"),
+            vec![vec!["1", "2", "3", "4"], vec!["5"]]
+        );
+        assert_eq!(
+            parse_csv("\"a, b\",\"say \"\"hi\"\"\",,c"),
+            vec![vec!["a, b", "say \"hi\"", "", "c"]]
+        );
+        assert_eq!(parse_csv("x,"), vec![vec!["x", ""]]);
+        assert!(parse_csv("").is_empty());
+        assert!(parse_csv("  \n\t\n").is_empty());
+    }
+
+    #[test]
+    fn parse_hex_bytes() {
+        assert_eq!(parse_hex("0A ff\t10"), Some(vec![0x0a, 0xff, 0x10]));
+        assert_eq!(parse_hex("deadBEEF"), Some(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(parse_hex(""), Some(Vec::new()));
+        assert_eq!(parse_hex("   "), Some(Vec::new()));
+    }
+
+    #[test]
+    fn parse_hex_rejects_invalid_text() {
+        assert_eq!(parse_hex("abc"), None);
+        assert_eq!(parse_hex("0g"), None);
+        assert_eq!(parse_hex("-1"), None);
+        assert_eq!(parse_hex("+1"), None);
+        // Non-ASCII characters must not split a multi-byte character or panic
+        assert_eq!(parse_hex("é1"), None);
+        assert_eq!(parse_hex("１２"), None);
+    }
+}
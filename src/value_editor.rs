@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use chrono::{Datelike, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    array_editor::ArrayEditor,
+    structure_editor::StructureEditor,
+    type_dictionary::{DecodedStructure, TypeDictionary},
+    value_edit::{self, BuiltinType, WriteInfo, TEXT_TYPES},
+    value_format::{self, TimeDisplay},
+};
+
+/// The widgets used to enter a value, which depend on the type of the variable or argument
+pub(crate) enum ValueEditor {
+    Toggle(gtk::CheckButton),
+    Spin(gtk::SpinButton, BuiltinType),
+    Text(gtk::Entry, BuiltinType),
+    DateTime(gtk::Calendar, gtk::Entry, gtk::ComboBoxText),
+    Enum(gtk::ComboBoxText),
+    /// For variables of an abstract type, the user chooses the type as well as the value
+    Raw(gtk::ComboBoxText, gtk::Entry),
+    Array(ArrayEditor),
+    Structure(StructureEditor),
+}
+
+impl ValueEditor {
+    pub fn new(write_info: &WriteInfo, type_dictionary: &Arc<TypeDictionary>) -> Self {
+        let current = write_info.value.as_ref().and_then(|v| v.value.as_ref());
+
+        if write_info.is_array() {
+            return ValueEditor::Array(ArrayEditor::new(write_info));
+        }
+
+        if write_info.is_enum() {
+            let combo = gtk::ComboBoxText::new();
+            write_info.enum_members.iter().for_each(|(value, name)| {
+                combo.append(Some(&value.to_string()), &format!("{} ({})", name, value));
+            });
+            if let Some(Variant::Int32(v)) = current {
+                combo.set_active_id(Some(&v.to_string()));
+            }
+            return ValueEditor::Enum(combo);
+        }
+
+        if write_info.builtin_type == Some(BuiltinType::ExtensionObject) {
+            if let Some(structure) = Self::structure(write_info, type_dictionary) {
+                return ValueEditor::Structure(StructureEditor::new(
+                    type_dictionary.clone(),
+                    structure,
+                ));
+            }
+        }
+
+        match write_info.builtin_type {
+            Some(BuiltinType::Boolean) => {
+                let toggle = gtk::CheckButton::with_label("True");
+                if let Some(Variant::Boolean(v)) = current {
+                    toggle.set_active(*v);
+                }
+                ValueEditor::Toggle(toggle)
+            }
+            Some(builtin_type) if builtin_type.is_spinnable() => {
+                let (min, max) = builtin_type.integer_range().unwrap();
+                let spin = gtk::SpinButton::with_range(min as f64, max as f64, 1.0);
+                spin.set_digits(0);
+                spin.set_numeric(true);
+                if let Some(v) = current {
+                    if let Ok(v) = value_edit::scalar_to_edit_text(v).parse::<f64>() {
+                        spin.set_value(v);
+                    }
+                }
+                ValueEditor::Spin(spin, builtin_type)
+            }
+            Some(BuiltinType::DateTime) => {
+                let calendar = gtk::Calendar::new();
+                let time_text = gtk::Entry::new();
+                time_text.set_placeholder_text(Some("HH:MM:SS.fffffff"));
+                let zone_combo = gtk::ComboBoxText::new();
+                zone_combo.append(Some("Utc"), "UTC");
+                zone_combo.append(Some("Local"), "Local");
+                zone_combo.set_active_id(Some("Utc"));
+                if let Some(Variant::DateTime(v)) = current {
+                    let utc = v.as_chrono();
+                    calendar.select_month(utc.month0(), utc.year() as u32);
+                    calendar.select_day(utc.day());
+                    let full = value_format::date_time_to_string(v, TimeDisplay::Utc);
+                    // Take the time of day part of "YYYY-MM-DD HH:MM:SS.fffffffZ"
+                    time_text.set_text(full[11..].trim_end_matches('Z'));
+                } else {
+                    time_text.set_text("00:00:00");
+                }
+                ValueEditor::DateTime(calendar, time_text, zone_combo)
+            }
+            Some(builtin_type) if TEXT_TYPES.contains(&builtin_type) => {
+                let entry = gtk::Entry::new();
+                if let Some(v) = current {
+                    entry.set_text(&value_edit::scalar_to_edit_text(v));
+                }
+                ValueEditor::Text(entry, builtin_type)
+            }
+            _ => {
+                let type_combo = gtk::ComboBoxText::new();
+                TEXT_TYPES.iter().for_each(|t| {
+                    let name = format!("{:?}", t);
+                    type_combo.append(Some(&name), &name);
+                });
+                let entry = gtk::Entry::new();
+                // Start with the type of the current value
+                let current_type = current
+                    .map(value_format::variant_type_name)
+                    .filter(|t| BuiltinType::from_name(t).is_some())
+                    .unwrap_or_else(|| "String".to_string());
+                type_combo.set_active_id(Some(&current_type));
+                if let Some(v) = current {
+                    entry.set_text(&value_edit::scalar_to_edit_text(v));
+                }
+                ValueEditor::Raw(type_combo, entry)
+            }
+        }
+    }
+
+    /// The structure to start editing from - the current value if it can be decoded, otherwise
+    /// a structure of the variable's DataType with default values
+    fn structure(
+        write_info: &WriteInfo,
+        type_dictionary: &TypeDictionary,
+    ) -> Option<DecodedStructure> {
+        let current = match write_info.value.as_ref().and_then(|v| v.value.as_ref()) {
+            Some(Variant::ExtensionObject(v)) => type_dictionary.decode(v).and_then(|r| r.ok()),
+            _ => None,
+        };
+        current.or_else(|| type_dictionary.default_structure(&write_info.data_type))
+    }
+
+    pub fn attach(&self, grid: &gtk::Grid, top: i32) {
+        match self {
+            ValueEditor::Toggle(w) => grid.attach(w, 1, top, 1, 1),
+            ValueEditor::Spin(w, _) => grid.attach(w, 1, top, 1, 1),
+            ValueEditor::Text(w, _) => grid.attach(w, 1, top, 1, 1),
+            ValueEditor::DateTime(calendar, time_text, zone_combo) => {
+                grid.attach(calendar, 1, top, 1, 1);
+                let time_box = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+                time_box.pack_start(time_text, true, true, 0);
+                time_box.pack_start(zone_combo, false, true, 0);
+                grid.attach(&time_box, 1, top + 1, 1, 1);
+            }
+            ValueEditor::Enum(w) => grid.attach(w, 1, top, 1, 1),
+            ValueEditor::Raw(type_combo, entry) => {
+                grid.attach(type_combo, 1, top, 1, 1);
+                grid.attach(entry, 1, top + 1, 1, 1);
+            }
+            ValueEditor::Array(array_editor) => grid.attach(array_editor.widget(), 1, top, 1, 2),
+            ValueEditor::Structure(structure_editor) => {
+                grid.attach(structure_editor.widget(), 1, top, 1, 2)
+            }
+        }
+    }
+
+    /// The number of grid rows the editor takes up when attached
+    pub fn rows(&self) -> i32 {
+        match self {
+            ValueEditor::DateTime(..)
+            | ValueEditor::Raw(..)
+            | ValueEditor::Array(_)
+            | ValueEditor::Structure(_) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn set_tooltip_text(&self, text: Option<&str>) {
+        match self {
+            ValueEditor::Toggle(w) => w.set_tooltip_text(text),
+            ValueEditor::Spin(w, _) => w.set_tooltip_text(text),
+            ValueEditor::Text(w, _) => w.set_tooltip_text(text),
+            ValueEditor::DateTime(calendar, time_text, _) => {
+                calendar.set_tooltip_text(text);
+                time_text.set_tooltip_text(text);
+            }
+            ValueEditor::Enum(w) => w.set_tooltip_text(text),
+            ValueEditor::Raw(_, entry) => entry.set_tooltip_text(text),
+            ValueEditor::Array(array_editor) => array_editor.widget().set_tooltip_text(text),
+            ValueEditor::Structure(structure_editor) => {
+                structure_editor.widget().set_tooltip_text(text)
+            }
+        }
+    }
+
+    /// Validates the input and returns it as a value of the right type, together with the
+    /// index range to write it to
+    pub fn value(&self) -> Result<(Variant, Option<String>), String> {
+        match self {
+            ValueEditor::Array(array_editor) => array_editor.value(),
+            _ => self.scalar_value().map(|v| (v, None)),
+        }
+    }
+
+    fn scalar_value(&self) -> Result<Variant, String> {
+        match self {
+            ValueEditor::Toggle(toggle) => Ok(Variant::Boolean(toggle.get_active())),
+            ValueEditor::Spin(spin, builtin_type) => {
+                spin.update();
+                builtin_type.integer_variant(spin.get_value().round() as i128)
+            }
+            ValueEditor::Text(entry, builtin_type) => {
+                value_edit::parse_scalar(*builtin_type, entry.get_text().as_str())
+            }
+            ValueEditor::DateTime(calendar, time_text, zone_combo) => {
+                let (year, month0, day) = calendar.get_date();
+                let date = NaiveDate::from_ymd_opt(year as i32, month0 + 1, day)
+                    .ok_or_else(|| "The date is not valid".to_string())?;
+                let time_text = time_text.get_text();
+                let time = NaiveTime::parse_from_str(time_text.trim(), "%H:%M:%S%.f")
+                    .or_else(|_| NaiveTime::parse_from_str(time_text.trim(), "%H:%M"))
+                    .map_err(|_| format!("\"{}\" is not a valid time", time_text))?;
+                let date_time = date.and_time(time);
+                let utc = if zone_combo.get_active_id().as_deref() == Some("Local") {
+                    Local
+                        .from_local_datetime(&date_time)
+                        .single()
+                        .ok_or_else(|| "The local time is ambiguous or invalid".to_string())?
+                        .with_timezone(&Utc)
+                } else {
+                    Utc.from_utc_datetime(&date_time)
+                };
+                Ok(Variant::from(DateTime::from(utc)))
+            }
+            ValueEditor::Enum(combo) => match combo.get_active_id() {
+                Some(id) => id
+                    .as_str()
+                    .parse::<i32>()
+                    .map(Variant::Int32)
+                    .map_err(|_| "The enumeration value is not valid".to_string()),
+                None => Err("Choose a value".to_string()),
+            },
+            ValueEditor::Raw(type_combo, entry) => {
+                let builtin_type = type_combo
+                    .get_active_id()
+                    .and_then(|id| BuiltinType::from_name(id.as_str()))
+                    .ok_or_else(|| "Choose a type".to_string())?;
+                value_edit::parse_scalar(builtin_type, entry.get_text().as_str())
+            }
+            ValueEditor::Array(array_editor) => array_editor.value().map(|(v, _)| v),
+            ValueEditor::Structure(structure_editor) => structure_editor.value(),
+        }
+    }
+}
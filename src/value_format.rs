@@ -0,0 +1,324 @@
+use std::fmt::Write;
+
+use chrono::{Local, TimeZone};
+use serde_json::{json, Map, Value as JsonValue};
+
+use opcua_client::prelude::*;
+
+/// The time zone that DateTimes are shown in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeDisplay {
+    Utc,
+    Local,
+}
+
+impl Default for TimeDisplay {
+    fn default() -> Self {
+        TimeDisplay::Utc
+    }
+}
+
+/// Number of 100ns ticks in a second, the resolution of an OPC UA DateTime
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// Formats a DateTime to its full 100ns precision
+pub fn date_time_to_string(v: &DateTime, time_display: TimeDisplay) -> String {
+    let fraction = v.ticks().rem_euclid(TICKS_PER_SECOND);
+    let utc = v.as_chrono();
+    match time_display {
+        TimeDisplay::Utc => format!("{}.{:07}Z", utc.format("%Y-%m-%d %H:%M:%S"), fraction),
+        TimeDisplay::Local => {
+            let local = Local.from_utc_datetime(&utc.naive_utc());
+            format!(
+                "{}.{:07} {}",
+                local.format("%Y-%m-%d %H:%M:%S"),
+                fraction,
+                local.format("%:z")
+            )
+        }
+    }
+}
+
+pub fn qualified_name_to_string(v: &QualifiedName) -> String {
+    format!("{}:{}", v.namespace_index, v.name)
+}
+
+pub fn localized_text_to_string(v: &LocalizedText) -> String {
+    if v.locale.is_empty() {
+        v.text.to_string()
+    } else {
+        format!("{} [{}]", v.text, v.locale)
+    }
+}
+
+pub fn status_code_to_string(v: StatusCode) -> String {
+    format!("{} (0x{:08X})", v.name(), v.bits())
+}
+
+pub fn byte_string_to_string(v: &ByteString) -> String {
+    match v.value {
+        Some(ref bytes) => bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => "null".to_string(),
+    }
+}
+
+/// Produces a classic hex dump, 16 bytes to a line, with offsets and printable ASCII
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    bytes.chunks(16).enumerate().for_each(|(line, chunk)| {
+        let hex = (0..16)
+            .map(|i| match chunk.get(i) {
+                Some(b) => format!("{:02X}", b),
+                None => "  ".to_string(),
+            })
+            .collect::<Vec<_>>();
+        let ascii = chunk
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        let _ = writeln!(
+            dump,
+            "{:08X}  {}  {}  |{}|",
+            line * 16,
+            hex[..8].join(" "),
+            hex[8..].join(" "),
+            ascii
+        );
+    });
+    dump
+}
+
+/// The name of the type held by the variant
+pub fn variant_type_name(v: &Variant) -> String {
+    match v {
+        Variant::Empty => "Empty".into(),
+        Variant::Boolean(_) => "Boolean".into(),
+        Variant::SByte(_) => "SByte".into(),
+        Variant::Byte(_) => "Byte".into(),
+        Variant::Int16(_) => "Int16".into(),
+        Variant::UInt16(_) => "UInt16".into(),
+        Variant::Int32(_) => "Int32".into(),
+        Variant::UInt32(_) => "UInt32".into(),
+        Variant::Int64(_) => "Int64".into(),
+        Variant::UInt64(_) => "UInt64".into(),
+        Variant::Float(_) => "Float".into(),
+        Variant::Double(_) => "Double".into(),
+        Variant::String(_) => "String".into(),
+        Variant::DateTime(_) => "DateTime".into(),
+        Variant::Guid(_) => "Guid".into(),
+        Variant::StatusCode(_) => "StatusCode".into(),
+        Variant::ByteString(_) => "ByteString".into(),
+        Variant::XmlElement(_) => "XmlElement".into(),
+        Variant::QualifiedName(_) => "QualifiedName".into(),
+        Variant::LocalizedText(_) => "LocalizedText".into(),
+        Variant::NodeId(_) => "NodeId".into(),
+        Variant::ExpandedNodeId(_) => "ExpandedNodeId".into(),
+        Variant::ExtensionObject(_) => "ExtensionObject".into(),
+        Variant::Array(array) => {
+            if array.dimensions.len() > 1 {
+                let dimensions = array
+                    .dimensions
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{:?}[{}]", array.value_type, dimensions)
+            } else {
+                format!("{:?}[{}]", array.value_type, array.values.len())
+            }
+        }
+        _ => "Unknown".into(),
+    }
+}
+
+/// Formats a scalar value as text. Arrays are written out in full.
+pub fn variant_to_string(v: &Variant, time_display: TimeDisplay) -> String {
+    match v {
+        Variant::Empty => String::new(),
+        Variant::Boolean(v) => v.to_string(),
+        Variant::SByte(v) => v.to_string(),
+        Variant::Byte(v) => v.to_string(),
+        Variant::Int16(v) => v.to_string(),
+        Variant::UInt16(v) => v.to_string(),
+        Variant::Int32(v) => v.to_string(),
+        Variant::UInt32(v) => v.to_string(),
+        Variant::Int64(v) => v.to_string(),
+        Variant::UInt64(v) => v.to_string(),
+        Variant::Float(v) => v.to_string(),
+        Variant::Double(v) => v.to_string(),
+        Variant::String(v) => v.to_string(),
+        Variant::DateTime(v) => date_time_to_string(v, time_display),
+        Variant::Guid(v) => v.to_string(),
+        Variant::StatusCode(v) => status_code_to_string(*v),
+        Variant::ByteString(v) => byte_string_to_string(v),
+        Variant::XmlElement(v) => v.to_string(),
+        Variant::QualifiedName(v) => qualified_name_to_string(v),
+        Variant::LocalizedText(v) => localized_text_to_string(v),
+        Variant::NodeId(v) => v.to_string(),
+        Variant::ExpandedNodeId(v) => v.to_string(),
+        Variant::ExtensionObject(v) => format!("ExtensionObject {}", v.node_id),
+        Variant::Array(array) => {
+            let values = array
+                .values
+                .iter()
+                .map(|v| variant_to_string(v, time_display))
+                .collect::<Vec<_>>();
+            format!("[{}]", values.join(", "))
+        }
+        v => format!("{:?}", v),
+    }
+}
+
+/// Converts the variant to JSON, loosely following the non-reversible form of the OPC UA JSON
+/// encoding. Multi-dimensional arrays become nested arrays.
+pub fn variant_to_json(v: &Variant) -> JsonValue {
+    match v {
+        Variant::Empty => JsonValue::Null,
+        Variant::Boolean(v) => json!(v),
+        Variant::SByte(v) => json!(v),
+        Variant::Byte(v) => json!(v),
+        Variant::Int16(v) => json!(v),
+        Variant::UInt16(v) => json!(v),
+        Variant::Int32(v) => json!(v),
+        Variant::UInt32(v) => json!(v),
+        // 64-bit integers are strings in OPC UA JSON so they survive JavaScript
+        Variant::Int64(v) => json!(v.to_string()),
+        Variant::UInt64(v) => json!(v.to_string()),
+        Variant::Float(v) => json!(v),
+        Variant::Double(v) => json!(v),
+        Variant::String(v) => {
+            if v.is_null() {
+                JsonValue::Null
+            } else {
+                json!(v.as_ref())
+            }
+        }
+        Variant::DateTime(v) => json!(date_time_to_string(v, TimeDisplay::Utc).replace(' ', "T")),
+        Variant::Guid(v) => json!(v.to_string()),
+        Variant::StatusCode(v) => json!({ "Code": v.bits(), "Symbol": v.name() }),
+        Variant::ByteString(v) => {
+            if v.is_null() {
+                JsonValue::Null
+            } else {
+                json!(v.as_base64())
+            }
+        }
+        Variant::XmlElement(v) => json!(v.to_string()),
+        Variant::QualifiedName(v) => json!({
+            "NamespaceIndex": v.namespace_index,
+            "Name": v.name.to_string(),
+        }),
+        Variant::LocalizedText(v) => json!({
+            "Locale": v.locale.to_string(),
+            "Text": v.text.to_string(),
+        }),
+        Variant::NodeId(v) => json!(v.to_string()),
+        Variant::ExpandedNodeId(v) => json!(v.to_string()),
+        Variant::ExtensionObject(v) => {
+            let mut object = Map::new();
+            object.insert("TypeId".into(), json!(v.node_id.to_string()));
+            match v.body {
+                ExtensionObjectEncoding::None => {}
+                ExtensionObjectEncoding::ByteString(ref body) => {
+                    object.insert("Encoding".into(), json!("Binary"));
+                    object.insert("Body".into(), json!(body.as_base64()));
+                }
+                ExtensionObjectEncoding::XmlElement(ref body) => {
+                    object.insert("Encoding".into(), json!("Xml"));
+                    object.insert("Body".into(), json!(body.to_string()));
+                }
+            }
+            JsonValue::Object(object)
+        }
+        Variant::Array(array) => {
+            let values = array.values.iter().map(variant_to_json).collect::<Vec<_>>();
+            if array.dimensions.len() > 1 {
+                nest_json(&values, &array.dimensions)
+            } else {
+                JsonValue::Array(values)
+            }
+        }
+        v => json!(format!("{:?}", v)),
+    }
+}
+
+/// Turns a flat row-major list into nested arrays with the given dimensions
+fn nest_json(values: &[JsonValue], dimensions: &[u32]) -> JsonValue {
+    if dimensions.len() <= 1 {
+        JsonValue::Array(values.to_vec())
+    } else {
+        let stride = dimensions[1..].iter().product::<u32>().max(1) as usize;
+        JsonValue::Array(
+            values
+                .chunks(stride)
+                .map(|chunk| nest_json(chunk, &dimensions[1..]))
+                .collect(),
+        )
+    }
+}
+
+/// Returns the dimensions of an array value. The dimensions held by the value take precedence
+/// over the ArrayDimensions attribute of the node, which only applies if the lengths agree.
+pub fn array_dimensions(array: &Array, array_dimensions: Option<&[u32]>) -> Vec<u32> {
+    if array.dimensions.len() > 1 {
+        array.dimensions.clone()
+    } else if let Some(dimensions) = array_dimensions {
+        let len = dimensions.iter().product::<u32>() as usize;
+        if dimensions.len() > 1 && !dimensions.contains(&0) && len == array.values.len() {
+            dimensions.to_vec()
+        } else {
+            vec![array.values.len() as u32]
+        }
+    } else {
+        vec![array.values.len() as u32]
+    }
+}
+
+/// Formats the multi-dimensional index of the element at the offset in a row-major array
+pub fn array_index_to_string(offset: usize, dimensions: &[u32]) -> String {
+    let mut remainder = offset;
+    let mut index = dimensions
+        .iter()
+        .rev()
+        .map(|d| {
+            let d = (*d).max(1) as usize;
+            let i = remainder % d;
+            remainder /= d;
+            i.to_string()
+        })
+        .collect::<Vec<_>>();
+    index.reverse();
+    format!("[{}]", index.join(","))
+}
+
+/// Formats a value as text for copying. Matrices become tab separated rows.
+pub fn value_to_text(v: &Variant, dimensions: &[u32], time_display: TimeDisplay) -> String {
+    match v {
+        Variant::Array(array) if dimensions.len() > 1 => {
+            let columns = (*dimensions.last().unwrap()).max(1) as usize;
+            array
+                .values
+                .chunks(columns)
+                .map(|row| {
+                    row.iter()
+                        .map(|v| variant_to_string(v, time_display))
+                        .collect::<Vec<_>>()
+                        .join("\t")
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        Variant::ByteString(v) => hex_dump(v.value.as_ref().map_or(&[], |v| v.as_slice())),
+        v => variant_to_string(v, time_display),
+    }
+}
@@ -0,0 +1,502 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    engineering_units::{self, AnalogInfo},
+    type_dictionary::{DecodedStructure, FieldType, FieldValue, TypeDictionary},
+    value_format::{self, TimeDisplay},
+};
+
+const COL_NAME: u32 = 0;
+const COL_TYPE: u32 = 1;
+const COL_VALUE: u32 = 2;
+
+#[derive(Default)]
+struct ValueViewerState {
+    value: Option<DataValue>,
+    /// The ArrayDimensions attribute of the node the value came from
+    array_dimensions: Option<Vec<u32>>,
+    /// The DataType of the node, used to name enumeration values
+    data_type: Option<NodeId>,
+    /// The units and ranges of an analog item
+    analog_info: Option<AnalogInfo>,
+}
+
+struct ValueViewerImpl {
+    time_combo: Rc<gtk::ComboBoxText>,
+    stack: Rc<gtk::Stack>,
+    tree_model: Rc<gtk::TreeStore>,
+    tree: Rc<gtk::TreeView>,
+    grid: Rc<gtk::TreeView>,
+    hex_text: Rc<gtk::TextView>,
+    state: RefCell<ValueViewerState>,
+    /// Used to decode structures into their fields
+    type_dictionary: RefCell<Arc<TypeDictionary>>,
+}
+
+/// Shows a value according to its type - scalars and structures as a tree, arrays and matrices
+/// in a grid and byte strings as a hex dump.
+pub struct ValueViewer {
+    data: Rc<ValueViewerImpl>,
+}
+
+impl ValueViewer {
+    pub fn new(builder: Rc<gtk::Builder>) -> Self {
+        let copy_text_btn: Rc<gtk::Button> =
+            Rc::new(builder.get_object("value_copy_text_btn").unwrap());
+        let copy_json_btn: Rc<gtk::Button> =
+            Rc::new(builder.get_object("value_copy_json_btn").unwrap());
+
+        let data = Rc::new(ValueViewerImpl {
+            time_combo: Rc::new(builder.get_object("value_time_combo").unwrap()),
+            stack: Rc::new(builder.get_object("value_stack").unwrap()),
+            tree_model: Rc::new(builder.get_object("value_tree_model").unwrap()),
+            tree: Rc::new(builder.get_object("value_tree").unwrap()),
+            grid: Rc::new(builder.get_object("value_grid").unwrap()),
+            hex_text: Rc::new(builder.get_object("value_hex_text").unwrap()),
+            state: RefCell::new(ValueViewerState::default()),
+            type_dictionary: RefCell::new(Arc::new(TypeDictionary::default())),
+        });
+
+        data.time_combo
+            .connect_changed(clone!(@weak data => move |_| {
+                data.refresh();
+            }));
+
+        copy_text_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(text) = data.value_as_text() {
+                data.copy_to_clipboard(&text);
+            }
+        }));
+
+        copy_json_btn.connect_clicked(clone!(@weak data => move |_| {
+            if let Some(json) = data.value_as_json() {
+                data.copy_to_clipboard(&json);
+            }
+        }));
+
+        Self { data }
+    }
+
+    pub fn show_value(
+        &self,
+        value: Option<DataValue>,
+        array_dimensions: Option<Vec<u32>>,
+        data_type: Option<NodeId>,
+        analog_info: Option<AnalogInfo>,
+    ) {
+        {
+            let mut state = self.data.state.borrow_mut();
+            state.value = value;
+            state.array_dimensions = array_dimensions;
+            state.data_type = data_type;
+            state.analog_info = analog_info;
+        }
+        self.data.refresh();
+    }
+
+    pub fn clear(&self) {
+        self.show_value(None, None, None, None);
+    }
+
+    pub fn set_type_dictionary(&self, type_dictionary: Arc<TypeDictionary>) {
+        *self.data.type_dictionary.borrow_mut() = type_dictionary;
+        self.data.refresh();
+    }
+}
+
+impl ValueViewerImpl {
+    fn time_display(&self) -> TimeDisplay {
+        match self.time_combo.get_active_id() {
+            Some(ref id) if id.as_str() == "Local" => TimeDisplay::Local,
+            _ => TimeDisplay::Utc,
+        }
+    }
+
+    fn dimensions(&self, state: &ValueViewerState) -> Vec<u32> {
+        match state.value.as_ref().and_then(|v| v.value.as_ref()) {
+            Some(Variant::Array(array)) => {
+                value_format::array_dimensions(array, state.array_dimensions.as_deref())
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn refresh(&self) {
+        let state = self.state.borrow();
+        let time_display = self.time_display();
+
+        self.tree_model.clear();
+        self.hex_text.get_buffer().unwrap().set_text("");
+
+        let data_value = match state.value {
+            Some(ref data_value) => data_value,
+            None => {
+                self.stack.set_visible_child_name("tree");
+                return;
+            }
+        };
+
+        match data_value.value {
+            Some(Variant::ByteString(ref v)) if !v.is_null() => {
+                let dump = value_format::hex_dump(v.value.as_ref().unwrap());
+                self.hex_text.get_buffer().unwrap().set_text(&dump);
+                self.stack.set_visible_child_name("hex");
+            }
+            Some(Variant::Array(ref array)) if Self::is_grid_array(array) => {
+                self.populate_grid(
+                    array,
+                    &self.dimensions(&state),
+                    state.data_type.as_ref(),
+                    state.analog_info.as_ref(),
+                    time_display,
+                );
+                self.stack.set_visible_child_name("grid");
+            }
+            _ => {
+                self.populate_tree(
+                    data_value,
+                    state.data_type.as_ref(),
+                    state.analog_info.as_ref(),
+                    time_display,
+                );
+                self.stack.set_visible_child_name("tree");
+            }
+        }
+    }
+
+    /// Arrays of simple values go in the grid, anything else is better off in the tree
+    fn is_grid_array(array: &Array) -> bool {
+        !array.values.iter().any(|v| match v {
+            Variant::Array(_) | Variant::ExtensionObject(_) | Variant::ByteString(_) => true,
+            _ => false,
+        })
+    }
+
+    fn populate_tree(
+        &self,
+        data_value: &DataValue,
+        data_type: Option<&NodeId>,
+        analog_info: Option<&AnalogInfo>,
+        time_display: TimeDisplay,
+    ) {
+        match (&data_value.value, analog_info) {
+            (Some(ref value), Some(analog_info))
+                if engineering_units::variant_to_f64(value).is_some() =>
+            {
+                let text = value_format::variant_to_string(value, time_display);
+                self.insert_row(
+                    None,
+                    "Value",
+                    &value_format::variant_type_name(value),
+                    &analog_info.format_value(&text, value),
+                );
+            }
+            (Some(ref value), _) => {
+                self.insert_variant(None, "Value", value, data_type, time_display)
+            }
+            (None, _) => {}
+        }
+        if let Some(analog_info) = analog_info {
+            if let Some(units) = analog_info.units_description() {
+                self.insert_row(None, "EngineeringUnits", "EUInformation", &units);
+            }
+            if let Some(ref range) = analog_info.eu_range {
+                let text = analog_info.with_units(&range.to_string());
+                self.insert_row(None, "EURange", "Range", &text);
+            }
+            if let Some(ref range) = analog_info.instrument_range {
+                let text = analog_info.with_units(&range.to_string());
+                self.insert_row(None, "InstrumentRange", "Range", &text);
+            }
+        }
+        let status = data_value.status.unwrap_or(StatusCode::Good);
+        self.insert_row(
+            None,
+            "Status",
+            "StatusCode",
+            &value_format::status_code_to_string(status),
+        );
+        if let Some(ref v) = data_value.source_timestamp {
+            self.insert_row(
+                None,
+                "SourceTimestamp",
+                "DateTime",
+                &value_format::date_time_to_string(v, time_display),
+            );
+        }
+        if let Some(v) = data_value.source_picoseconds {
+            self.insert_row(None, "SourcePicoseconds", "UInt16", &v.to_string());
+        }
+        if let Some(ref v) = data_value.server_timestamp {
+            self.insert_row(
+                None,
+                "ServerTimestamp",
+                "DateTime",
+                &value_format::date_time_to_string(v, time_display),
+            );
+        }
+        if let Some(v) = data_value.server_picoseconds {
+            self.insert_row(None, "ServerPicoseconds", "UInt16", &v.to_string());
+        }
+        self.tree.expand_all();
+    }
+
+    fn insert_row(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        name: &str,
+        type_name: &str,
+        value: &str,
+    ) -> gtk::TreeIter {
+        self.tree_model.insert_with_values(
+            parent,
+            None,
+            &[COL_NAME, COL_TYPE, COL_VALUE],
+            &[&name, &type_name, &value],
+        )
+    }
+
+    /// Inserts a value into the tree with child rows for the parts of composite values. The
+    /// DataType is supplied for values of an enumerated type.
+    fn insert_variant(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        name: &str,
+        value: &Variant,
+        data_type: Option<&NodeId>,
+        time_display: TimeDisplay,
+    ) {
+        let type_name = value_format::variant_type_name(value);
+        match value {
+            Variant::Array(array) => {
+                let iter = self.insert_row(parent, name, &type_name, "");
+                let dimensions = value_format::array_dimensions(array, None);
+                array.values.iter().enumerate().for_each(|(i, v)| {
+                    let index = value_format::array_index_to_string(i, &dimensions);
+                    self.insert_variant(Some(&iter), &index, v, data_type, time_display);
+                });
+            }
+            Variant::ExtensionObject(v) => {
+                let decoded = self.type_dictionary.borrow().decode(v);
+                if let Some(Ok(structure)) = decoded {
+                    self.insert_structure(parent, name, &structure, time_display);
+                    return;
+                }
+                let iter = self.insert_row(parent, name, &type_name, "");
+                if let Some(Err(err)) = decoded {
+                    self.insert_row(Some(&iter), "Decode Error", "", &err);
+                }
+                self.insert_row(Some(&iter), "TypeId", "NodeId", &v.node_id.to_string());
+                match v.body {
+                    ExtensionObjectEncoding::None => {
+                        self.insert_row(Some(&iter), "Encoding", "", "None");
+                    }
+                    ExtensionObjectEncoding::ByteString(ref body) => {
+                        self.insert_row(Some(&iter), "Encoding", "", "Binary");
+                        self.insert_row(
+                            Some(&iter),
+                            "Body",
+                            "ByteString",
+                            &value_format::byte_string_to_string(body),
+                        );
+                    }
+                    ExtensionObjectEncoding::XmlElement(ref body) => {
+                        self.insert_row(Some(&iter), "Encoding", "", "Xml");
+                        self.insert_row(Some(&iter), "Body", "XmlElement", body.as_ref());
+                    }
+                }
+            }
+            Variant::QualifiedName(v) => {
+                let iter = self.insert_row(
+                    parent,
+                    name,
+                    &type_name,
+                    &value_format::qualified_name_to_string(v),
+                );
+                self.insert_row(
+                    Some(&iter),
+                    "NamespaceIndex",
+                    "UInt16",
+                    &v.namespace_index.to_string(),
+                );
+                self.insert_row(Some(&iter), "Name", "String", v.name.as_ref());
+            }
+            Variant::LocalizedText(v) => {
+                let iter = self.insert_row(
+                    parent,
+                    name,
+                    &type_name,
+                    &value_format::localized_text_to_string(v),
+                );
+                self.insert_row(Some(&iter), "Locale", "String", v.locale.as_ref());
+                self.insert_row(Some(&iter), "Text", "String", v.text.as_ref());
+            }
+            v => {
+                let text = self
+                    .type_dictionary
+                    .borrow()
+                    .format_value(data_type, v, time_display);
+                self.insert_row(parent, name, &type_name, &text);
+            }
+        }
+    }
+
+    /// Inserts a decoded structure with a child row for each field
+    fn insert_structure(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        name: &str,
+        structure: &DecodedStructure,
+        time_display: TimeDisplay,
+    ) {
+        let iter = self.insert_row(parent, name, &structure.type_name, "");
+        // Enumeration fields are named from their DataType
+        let enum_types = self
+            .type_dictionary
+            .borrow()
+            .structure(&structure.data_type_id)
+            .map_or_else(Vec::new, |layout| {
+                layout
+                    .fields
+                    .iter()
+                    .map(|f| match f.field_type {
+                        FieldType::Enumeration(ref data_type) => Some(data_type.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            });
+        structure
+            .fields
+            .iter()
+            .enumerate()
+            .for_each(|(i, (field_name, value))| {
+                let data_type = enum_types.get(i).and_then(|t| t.as_ref());
+                self.insert_field_value(Some(&iter), field_name, value, data_type, time_display);
+            });
+    }
+
+    fn insert_field_value(
+        &self,
+        parent: Option<&gtk::TreeIter>,
+        name: &str,
+        value: &FieldValue,
+        data_type: Option<&NodeId>,
+        time_display: TimeDisplay,
+    ) {
+        match value {
+            FieldValue::Scalar(v) => self.insert_variant(parent, name, v, data_type, time_display),
+            FieldValue::Structure(s) => self.insert_structure(parent, name, s, time_display),
+            FieldValue::Array(values) => {
+                let iter = self.insert_row(parent, name, "Array", &format!("[{}]", values.len()));
+                values.iter().enumerate().for_each(|(i, v)| {
+                    let index = format!("[{}]", i);
+                    self.insert_field_value(Some(&iter), &index, v, data_type, time_display);
+                });
+            }
+            // Absent optional fields and unselected union members aren't shown
+            FieldValue::Absent => {}
+        }
+    }
+
+    /// Shows an array in a grid. One dimensional arrays are shown as a column, matrices as rows
+    /// and columns. Higher dimensions are flattened into rows labelled with their index.
+    fn populate_grid(
+        &self,
+        array: &Array,
+        dimensions: &[u32],
+        data_type: Option<&NodeId>,
+        analog_info: Option<&AnalogInfo>,
+        time_display: TimeDisplay,
+    ) {
+        self.grid.get_columns().iter().for_each(|c| {
+            self.grid.remove_column(c);
+        });
+
+        let columns = if dimensions.len() > 1 {
+            (*dimensions.last().unwrap()).max(1) as usize
+        } else {
+            1
+        };
+        let row_dimensions = if dimensions.len() > 1 {
+            &dimensions[..dimensions.len() - 1]
+        } else {
+            dimensions
+        };
+
+        let column_types = vec![glib::Type::String; columns + 1];
+        let grid_model = gtk::ListStore::new(&column_types);
+
+        let add_column = |title: &str, i: u32| {
+            let renderer = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            self.grid.append_column(&column);
+        };
+        add_column("Index", 0);
+        if dimensions.len() > 1 {
+            (0..columns).for_each(|c| add_column(&c.to_string(), c as u32 + 1));
+        } else {
+            add_column("Value", 1);
+        }
+
+        let column_ids = (0..=columns as u32).collect::<Vec<_>>();
+        let type_dictionary = self.type_dictionary.borrow();
+        array
+            .values
+            .chunks(columns)
+            .enumerate()
+            .for_each(|(row, values)| {
+                let mut texts = vec![value_format::array_index_to_string(row, row_dimensions)];
+                texts.extend(values.iter().map(|v| {
+                    let text = type_dictionary.format_value(data_type, v, time_display);
+                    match analog_info {
+                        Some(analog_info) => analog_info.format_value(&text, v),
+                        None => text,
+                    }
+                }));
+                let values = texts.iter().map(|t| t as &dyn ToValue).collect::<Vec<_>>();
+                grid_model.insert_with_values(None, &column_ids[..values.len()], &values);
+            });
+
+        self.grid.set_model(Some(&grid_model));
+    }
+
+    fn value_as_text(&self) -> Option<String> {
+        let state = self.state.borrow();
+        let value = state.value.as_ref()?.value.as_ref()?;
+        Some(value_format::value_to_text(
+            value,
+            &self.dimensions(&state),
+            self.time_display(),
+        ))
+    }
+
+    fn value_as_json(&self) -> Option<String> {
+        let state = self.state.borrow();
+        let value = state.value.as_ref()?.value.as_ref()?;
+        let json = match value {
+            Variant::Array(array) => {
+                let mut array = array.clone();
+                array.dimensions = self.dimensions(&state);
+                self.type_dictionary
+                    .borrow()
+                    .variant_to_json(&Variant::Array(array))
+            }
+            value => self.type_dictionary.borrow().variant_to_json(value),
+        };
+        serde_json::to_string_pretty(&json).ok()
+    }
+
+    fn copy_to_clipboard(&self, text: &str) {
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        clipboard.set_text(text);
+    }
+}
@@ -0,0 +1,374 @@
+use std::{fmt, time::Duration};
+
+use opcua_client::prelude::*;
+
+use crate::{alert_rules::AlertRule, engineering_units::ValueRange};
+
+/// How the values of the items in a watch list are acquired from the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Acquisition {
+    /// Monitored items on a subscription, the server reports the changes
+    Subscription,
+    /// Cyclic Read at an interval in milliseconds, for servers whose subscriptions are missing
+    /// or can't be relied on
+    Polling(u32),
+}
+
+impl fmt::Display for Acquisition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Acquisition::Subscription => write!(f, "Subscription"),
+            Acquisition::Polling(interval) => write!(f, "Polling {} ms", interval),
+        }
+    }
+}
+
+/// The publishing parameters requested for the subscription of a watch list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubscriptionSettings {
+    /// In milliseconds
+    pub publishing_interval: f64,
+    /// Publishing intervals without a Publish request before the server deletes the subscription
+    pub lifetime_count: u32,
+    /// Publishing intervals without a notification before the server sends a keep-alive
+    pub max_keep_alive_count: u32,
+    /// The most notifications in a publish, 0 for no limit
+    pub max_notifications_per_publish: u32,
+    /// Subscriptions with a higher priority are published first
+    pub priority: u8,
+    pub publishing_enabled: bool,
+}
+
+impl Default for SubscriptionSettings {
+    fn default() -> Self {
+        Self {
+            publishing_interval: 1000.0,
+            lifetime_count: 30,
+            max_keep_alive_count: 10,
+            max_notifications_per_publish: 0,
+            priority: 0,
+            publishing_enabled: true,
+        }
+    }
+}
+
+impl SubscriptionSettings {
+    /// Tests if the settings differ in anything besides the publishing mode, which is changed
+    /// by a different service
+    pub fn publishing_parameters_differ(&self, other: &SubscriptionSettings) -> bool {
+        Self {
+            publishing_enabled: other.publishing_enabled,
+            ..*self
+        } != *other
+    }
+
+    /// Checks the settings are ones a server should accept. The lifetime must be at least three
+    /// keep-alives so the subscription survives a few missed publishes.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.publishing_interval <= 0.0 {
+            Err("The publishing interval must be more than 0".to_string())
+        } else if self.max_keep_alive_count == 0 {
+            Err("The keep-alive count must be at least 1".to_string())
+        } else if self.lifetime_count < self.max_keep_alive_count.saturating_mul(3) {
+            Err(format!(
+                "The lifetime count must be at least three times the keep-alive count, {}",
+                self.max_keep_alive_count.saturating_mul(3)
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The publishing parameters the server granted for a subscription, which may differ from those
+/// requested
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RevisedSubscription {
+    pub subscription_id: u32,
+    pub publishing_interval: f64,
+    pub lifetime_count: u32,
+    pub max_keep_alive_count: u32,
+}
+
+/// How much a value must change before the server reports it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deadband {
+    None,
+    /// A change of at least this much, in the units of the value
+    Absolute(f64),
+    /// A change of at least this percentage of the item's EURange
+    Percent(f64),
+}
+
+impl fmt::Display for Deadband {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deadband::None => write!(f, "no deadband"),
+            Deadband::Absolute(v) => write!(f, "deadband {}", v),
+            Deadband::Percent(v) => write!(f, "deadband {} %", v),
+        }
+    }
+}
+
+/// The parameters requested for the monitored item of a watch item. They only apply to watch
+/// lists that use a subscription.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitoringSettings {
+    /// In milliseconds, -1 to sample at the publishing interval of the subscription
+    pub sampling_interval: f64,
+    pub queue_size: u32,
+    pub discard_oldest: bool,
+    pub trigger: DataChangeTrigger,
+    pub deadband: Deadband,
+}
+
+impl Default for MonitoringSettings {
+    fn default() -> Self {
+        Self {
+            sampling_interval: -1.0,
+            queue_size: 1,
+            discard_oldest: true,
+            trigger: DataChangeTrigger::StatusValue,
+            deadband: Deadband::None,
+        }
+    }
+}
+
+impl MonitoringSettings {
+    /// The filter to request, if the settings differ from what a server does without one,
+    /// which is to report changes of status or value with no deadband
+    pub fn data_change_filter(&self) -> Option<DataChangeFilter> {
+        if self.trigger == DataChangeTrigger::StatusValue && self.deadband == Deadband::None {
+            return None;
+        }
+        let (deadband_type, deadband_value) = match self.deadband {
+            Deadband::None => (DeadbandType::None, 0.0),
+            Deadband::Absolute(v) => (DeadbandType::Absolute, v),
+            Deadband::Percent(v) => (DeadbandType::Percent, v),
+        };
+        Some(DataChangeFilter {
+            trigger: self.trigger,
+            deadband_type: deadband_type as u32,
+            deadband_value,
+        })
+    }
+
+    pub fn monitoring_parameters(&self, client_handle: u32) -> MonitoringParameters {
+        let filter = self
+            .data_change_filter()
+            .map_or_else(ExtensionObject::null, |filter| {
+                ExtensionObject::from_encodable(
+                    ObjectId::DataChangeFilter_Encoding_DefaultBinary,
+                    &filter,
+                )
+            });
+        MonitoringParameters {
+            client_handle,
+            sampling_interval: self.sampling_interval,
+            filter,
+            queue_size: self.queue_size,
+            discard_oldest: self.discard_oldest,
+        }
+    }
+
+    /// Checks the settings make sense for an item with the EURange. A percent deadband is a
+    /// percentage of the EURange so the item must have one.
+    pub fn validate(&self, eu_range: Option<ValueRange>) -> Result<(), String> {
+        if self.sampling_interval < 0.0 && self.sampling_interval != -1.0 {
+            return Err("The sampling interval must be -1 or more".to_string());
+        }
+        match self.deadband {
+            Deadband::None => Ok(()),
+            Deadband::Absolute(v) if v < 0.0 => {
+                Err("An absolute deadband can't be negative".to_string())
+            }
+            Deadband::Absolute(_) => Ok(()),
+            Deadband::Percent(v) if !(0.0..=100.0).contains(&v) => {
+                Err("A percent deadband must be from 0 to 100".to_string())
+            }
+            Deadband::Percent(_) => match eu_range {
+                Some(range) if range.high > range.low => Ok(()),
+                Some(range) => Err(format!(
+                    "A percent deadband needs an EURange whose high is above its low, not {}",
+                    range
+                )),
+                None => Err("A percent deadband needs an item with an EURange".to_string()),
+            },
+        }
+    }
+
+    /// The change in the units of the value that a deadband amounts to
+    pub fn absolute_deadband(&self, eu_range: Option<ValueRange>) -> Option<f64> {
+        match self.deadband {
+            Deadband::None => None,
+            Deadband::Absolute(v) => Some(v),
+            Deadband::Percent(v) => eu_range.map(|range| v / 100.0 * (range.high - range.low)),
+        }
+    }
+}
+
+impl fmt::Display for MonitoringSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.sampling_interval < 0.0 {
+            write!(f, "publishing interval")?;
+        } else {
+            write!(f, "{} ms", self.sampling_interval)?;
+        }
+        write!(
+            f,
+            ", queue {}, {:?}, {}",
+            self.queue_size, self.trigger, self.deadband
+        )
+    }
+}
+
+/// The sampling interval and queue size the server granted for a monitored item, which may
+/// differ from those requested
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RevisedMonitoring {
+    pub sampling_interval: f64,
+    pub queue_size: u32,
+}
+
+/// A node being watched. The item id is allocated by the app and identifies the item's values
+/// whichever way they are acquired.
+#[derive(Debug, Clone)]
+pub struct WatchItem {
+    pub item_id: u32,
+    pub node_id: NodeId,
+    pub display_name: String,
+    pub monitoring: MonitoringSettings,
+    /// True if other items of the watch list can be linked to this one to be reported when it
+    /// changes
+    pub is_trigger: bool,
+    /// The id of the trigger item this one is linked to. A linked item is monitored in
+    /// Sampling mode, so its values are only reported when the trigger reports a change.
+    pub triggered_by: Option<u32>,
+    /// The mode asked for the monitored item. Disabled items keep their monitored item and
+    /// settings on the server but are neither sampled nor reported. An item linked to a trigger
+    /// is sampled unless it is disabled.
+    pub monitoring_mode: MonitoringMode,
+    /// Rules that raise alerts on the item's values
+    pub alert_rules: Vec<AlertRule>,
+}
+
+impl WatchItem {
+    pub fn new(item_id: u32, node_id: NodeId, display_name: String) -> Self {
+        Self {
+            item_id,
+            node_id,
+            display_name,
+            monitoring: MonitoringSettings::default(),
+            is_trigger: false,
+            triggered_by: None,
+            monitoring_mode: MonitoringMode::Reporting,
+            alert_rules: Vec::new(),
+        }
+    }
+}
+
+/// A named group of nodes whose values are acquired together
+#[derive(Debug, Clone)]
+pub struct WatchList {
+    pub id: u32,
+    pub name: String,
+    pub acquisition: Acquisition,
+    /// The publishing parameters of the subscription when the acquisition is by subscription
+    pub subscription: SubscriptionSettings,
+    pub items: Vec<WatchItem>,
+}
+
+impl WatchList {
+    pub fn new(id: u32, name: &str, acquisition: Acquisition) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            acquisition,
+            subscription: SubscriptionSettings::default(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Unlinks items from triggers that are no longer in the watch list or no longer triggers
+    pub fn unlink_missing_triggers(&mut self) {
+        let trigger_ids = self
+            .items
+            .iter()
+            .filter(|i| i.is_trigger)
+            .map(|i| i.item_id)
+            .collect::<Vec<_>>();
+        self.items.iter_mut().for_each(|item| {
+            if let Some(trigger_id) = item.triggered_by {
+                if !trigger_ids.contains(&trigger_id) {
+                    item.triggered_by = None;
+                }
+            }
+        });
+    }
+}
+
+/// The timing of the reads of a polled watch list. Latency is the time from the start of a
+/// poll until its values arrive, including any wait for the connection. Jitter is how far the
+/// time between the starts of consecutive polls strays from the interval.
+#[derive(Debug, Clone, Default)]
+pub struct PollStatistics {
+    pub polls: u64,
+    /// Polls that took longer than the interval
+    pub overruns: u64,
+    pub last_latency: Duration,
+    pub mean_latency: Duration,
+    pub max_latency: Duration,
+    pub last_jitter: Duration,
+    pub mean_jitter: Duration,
+    pub max_jitter: Duration,
+}
+
+impl PollStatistics {
+    /// Records a poll. The period is the time since the previous poll started, if there was one.
+    pub fn record(&mut self, latency: Duration, period: Option<Duration>, interval: Duration) {
+        self.polls += 1;
+        if latency > interval {
+            self.overruns += 1;
+        }
+        self.last_latency = latency;
+        self.max_latency = self.max_latency.max(latency);
+        self.mean_latency = running_mean(self.mean_latency, latency, self.polls);
+        if let Some(period) = period {
+            let jitter = if period > interval {
+                period - interval
+            } else {
+                interval - period
+            };
+            self.last_jitter = jitter;
+            self.max_jitter = self.max_jitter.max(jitter);
+            // The first poll has no period
+            self.mean_jitter = running_mean(self.mean_jitter, jitter, self.polls - 1);
+        }
+    }
+}
+
+impl fmt::Display for PollStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{} polls, latency {:.1} ms (mean {:.1}, max {:.1}), jitter {:.1} ms (mean {:.1}, max {:.1}), {} overruns",
+            self.polls,
+            ms(self.last_latency),
+            ms(self.mean_latency),
+            ms(self.max_latency),
+            ms(self.last_jitter),
+            ms(self.mean_jitter),
+            ms(self.max_jitter),
+            self.overruns
+        )
+    }
+}
+
+fn running_mean(mean: Duration, sample: Duration, count: u64) -> Duration {
+    if count == 0 {
+        return sample;
+    }
+    let mean = mean.as_secs_f64();
+    Duration::from_secs_f64(mean + (sample.as_secs_f64() - mean) / count as f64)
+}
@@ -0,0 +1,338 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde_json::{json, Value as JsonValue};
+
+use opcua_client::prelude::*;
+
+use crate::{
+    alert_rules::{AlertCondition, AlertRule},
+    watch_list::{
+        Acquisition, Deadband, MonitoringSettings, SubscriptionSettings, WatchItem, WatchList,
+    },
+};
+
+/// Where the links from watch list files to connections are kept
+const LINKS_FILE: &str = "watch_list_links.json";
+
+/// Writes a node id with the URI of its namespace instead of the index, e.g.
+/// "nsu=urn:example;s=Tank1", since indexes can differ between servers and sessions. Nodes of
+/// namespace 0 keep the index as their URI is fixed.
+pub fn node_id_to_text(node_id: &NodeId, namespaces: &[String]) -> Result<String, String> {
+    if node_id.namespace == 0 {
+        return Ok(node_id.to_string());
+    }
+    let uri = namespaces
+        .get(node_id.namespace as usize)
+        .ok_or_else(|| format!("Node {} has a namespace the server doesn't list", node_id))?;
+    let text = node_id.to_string();
+    let identifier = text.splitn(2, ';').nth(1).unwrap_or(&text);
+    Ok(format!("nsu={};{}", uri, identifier))
+}
+
+/// Reads a node id written by `node_id_to_text`, finding the index of its namespace URI
+pub fn node_id_from_text(text: &str, namespaces: &[String]) -> Result<NodeId, String> {
+    let node_id = match text.strip_prefix("nsu=") {
+        Some(rest) => {
+            let (uri, identifier) = match rest.find(';') {
+                Some(i) => (&rest[..i], &rest[i + 1..]),
+                None => return Err(format!("{} has no identifier", text)),
+            };
+            let index = namespaces
+                .iter()
+                .position(|n| n == uri)
+                .ok_or_else(|| format!("The server has no namespace {}", uri))?;
+            format!("ns={};{}", index, identifier)
+        }
+        None => text.to_string(),
+    };
+    NodeId::from_str(&node_id).map_err(|_| format!("{} is not a valid node id", text))
+}
+
+/// A watch list as saved to a file, with node ids that use namespace URIs
+#[derive(Debug, Clone)]
+pub struct WatchListFile {
+    pub name: String,
+    /// The endpoint of the connection the watch list was saved from
+    pub endpoint_url: Option<String>,
+    pub acquisition: Acquisition,
+    pub subscription: SubscriptionSettings,
+    /// The node id text of each item and the item with a null node id. Items are numbered
+    /// from 1 in the order of the file, which is how trigger links refer to them.
+    pub items: Vec<(String, WatchItem)>,
+}
+
+impl WatchListFile {
+    /// Takes the parts of a watch list to save. Items whose namespace isn't known are an
+    /// error, since they couldn't be found again.
+    pub fn from_watch_list(
+        watch_list: &WatchList,
+        endpoint_url: Option<String>,
+        namespaces: &[String],
+    ) -> Result<Self, String> {
+        let items = watch_list
+            .items
+            .iter()
+            .map(|item| Ok((node_id_to_text(&item.node_id, namespaces)?, item.clone())))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            name: watch_list.name.clone(),
+            endpoint_url,
+            acquisition: watch_list.acquisition,
+            subscription: watch_list.subscription,
+            items,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(&self.to_json()).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Cannot write {}, {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}, {}", path.display(), e))?;
+        let json = serde_json::from_str::<JsonValue>(&text)
+            .map_err(|e| format!("{} is not JSON, {}", path.display(), e))?;
+        Self::from_json(&json).map_err(|e| format!("{} is not a watch list, {}", path.display(), e))
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let acquisition = match self.acquisition {
+            Acquisition::Subscription => json!({ "mode": "Subscription" }),
+            Acquisition::Polling(interval) => json!({ "mode": "Polling", "interval": interval }),
+        };
+        let s = &self.subscription;
+        let items = self
+            .items
+            .iter()
+            .map(|(node_id, item)| {
+                // Links are saved as the index of the trigger, since item ids aren't kept
+                let triggered_by = item
+                    .triggered_by
+                    .and_then(|id| self.items.iter().position(|(_, i)| i.item_id == id));
+                let m = &item.monitoring;
+                let deadband = match m.deadband {
+                    Deadband::None => JsonValue::Null,
+                    Deadband::Absolute(v) => json!({ "type": "Absolute", "value": v }),
+                    Deadband::Percent(v) => json!({ "type": "Percent", "value": v }),
+                };
+                let alert_rules = item
+                    .alert_rules
+                    .iter()
+                    .map(|rule| {
+                        json!({
+                            "kind": rule.condition.kind(),
+                            "limit": rule.condition.limit(),
+                            "hysteresis": rule.condition.hysteresis(),
+                            "notify": rule.notify,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "node_id": node_id,
+                    "display_name": item.display_name,
+                    "monitoring": {
+                        "sampling_interval": m.sampling_interval,
+                        "queue_size": m.queue_size,
+                        "discard_oldest": m.discard_oldest,
+                        "trigger": format!("{:?}", m.trigger),
+                        "deadband": deadband,
+                    },
+                    "is_trigger": item.is_trigger,
+                    "triggered_by": triggered_by,
+                    "alert_rules": alert_rules,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "name": self.name,
+            "endpoint_url": self.endpoint_url,
+            "acquisition": acquisition,
+            "subscription": {
+                "publishing_interval": s.publishing_interval,
+                "lifetime_count": s.lifetime_count,
+                "max_keep_alive_count": s.max_keep_alive_count,
+                "max_notifications_per_publish": s.max_notifications_per_publish,
+                "priority": s.priority,
+                "publishing_enabled": s.publishing_enabled,
+            },
+            "items": items,
+        })
+    }
+
+    /// Reads a watch list, using the defaults for settings that are missing
+    fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let name = json["name"].as_str().unwrap_or("Watch List").to_string();
+        let endpoint_url = json["endpoint_url"].as_str().map(|s| s.to_string());
+        let acquisition = match json["acquisition"]["mode"].as_str() {
+            Some("Polling") => Acquisition::Polling(
+                json["acquisition"]["interval"]
+                    .as_u64()
+                    .ok_or("the polling interval is missing")? as u32,
+            ),
+            _ => Acquisition::Subscription,
+        };
+        let s = &json["subscription"];
+        let defaults = SubscriptionSettings::default();
+        let count = |key: &str, default: u32| s[key].as_u64().map_or(default, |v| v as u32);
+        let subscription = SubscriptionSettings {
+            publishing_interval: s["publishing_interval"]
+                .as_f64()
+                .unwrap_or(defaults.publishing_interval),
+            lifetime_count: count("lifetime_count", defaults.lifetime_count),
+            max_keep_alive_count: count("max_keep_alive_count", defaults.max_keep_alive_count),
+            max_notifications_per_publish: count(
+                "max_notifications_per_publish",
+                defaults.max_notifications_per_publish,
+            ),
+            priority: s["priority"]
+                .as_u64()
+                .map_or(defaults.priority, |v| v as u8),
+            publishing_enabled: s["publishing_enabled"]
+                .as_bool()
+                .unwrap_or(defaults.publishing_enabled),
+        };
+        let items = json["items"]
+            .as_array()
+            .ok_or("the items are missing")?
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let node_id = item["node_id"]
+                    .as_str()
+                    .ok_or("an item has no node id")?
+                    .to_string();
+                let display_name = item["display_name"]
+                    .as_str()
+                    .unwrap_or(&node_id)
+                    .to_string();
+                Ok((
+                    node_id,
+                    WatchItem {
+                        item_id: i as u32 + 1,
+                        node_id: NodeId::null(),
+                        display_name,
+                        monitoring: monitoring_from_json(&item["monitoring"]),
+                        is_trigger: item["is_trigger"].as_bool().unwrap_or(false),
+                        triggered_by: item["triggered_by"].as_u64().map(|i| i as u32 + 1),
+                        // Items are paused for a while rather than saved that way
+                        monitoring_mode: MonitoringMode::Reporting,
+                        alert_rules: alert_rules_from_json(&item["alert_rules"]),
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            name,
+            endpoint_url,
+            acquisition,
+            subscription,
+            items,
+        })
+    }
+}
+
+fn monitoring_from_json(json: &JsonValue) -> MonitoringSettings {
+    let defaults = MonitoringSettings::default();
+    let deadband_value = json["deadband"]["value"].as_f64().unwrap_or(0.0);
+    MonitoringSettings {
+        sampling_interval: json["sampling_interval"]
+            .as_f64()
+            .unwrap_or(defaults.sampling_interval),
+        queue_size: json["queue_size"]
+            .as_u64()
+            .map_or(defaults.queue_size, |v| v as u32),
+        discard_oldest: json["discard_oldest"]
+            .as_bool()
+            .unwrap_or(defaults.discard_oldest),
+        trigger: match json["trigger"].as_str() {
+            Some("Status") => DataChangeTrigger::Status,
+            Some("StatusValueTimestamp") => DataChangeTrigger::StatusValueTimestamp,
+            _ => DataChangeTrigger::StatusValue,
+        },
+        deadband: match json["deadband"]["type"].as_str() {
+            Some("Absolute") => Deadband::Absolute(deadband_value),
+            Some("Percent") => Deadband::Percent(deadband_value),
+            _ => Deadband::None,
+        },
+    }
+}
+
+/// Reads the alert rules of an item, skipping rules of kinds that aren't known
+fn alert_rules_from_json(json: &JsonValue) -> Vec<AlertRule> {
+    json.as_array()
+        .map(|rules| {
+            rules
+                .iter()
+                .filter_map(|rule| {
+                    let condition = AlertCondition::from_kind(
+                        rule["kind"].as_str()?,
+                        rule["limit"].as_f64().unwrap_or(0.0),
+                        rule["hysteresis"].as_f64().unwrap_or(0.0),
+                    )?;
+                    Some(AlertRule {
+                        condition,
+                        notify: rule["notify"].as_bool().unwrap_or(false),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A watch list file that is opened whenever the app connects to an endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchListLink {
+    pub endpoint_url: String,
+    pub path: PathBuf,
+}
+
+/// The directory the app keeps its settings in, e.g. ~/.config/opcua-explorer
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("opcua-explorer"))
+}
+
+/// The links from watch list files to endpoints. Missing or unreadable links are no links.
+pub fn load_links() -> Vec<WatchListLink> {
+    let text = match config_dir().and_then(|dir| fs::read_to_string(dir.join(LINKS_FILE)).ok()) {
+        Some(text) => text,
+        None => return Vec::new(),
+    };
+    serde_json::from_str::<JsonValue>(&text)
+        .ok()
+        .and_then(|json| json.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|link| {
+            Some(WatchListLink {
+                endpoint_url: link["endpoint_url"].as_str()?.to_string(),
+                path: PathBuf::from(link["path"].as_str()?),
+            })
+        })
+        .collect()
+}
+
+pub fn save_links(links: &[WatchListLink]) -> Result<(), String> {
+    let dir = config_dir().ok_or("There is no directory for settings")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}, {}", dir.display(), e))?;
+    let json = links
+        .iter()
+        .map(|link| {
+            json!({
+                "endpoint_url": link.endpoint_url,
+                "path": link.path.to_string_lossy(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let path = dir.join(LINKS_FILE);
+    fs::write(&path, JsonValue::Array(json).to_string())
+        .map_err(|e| format!("Cannot write {}, {}", path.display(), e))
+}
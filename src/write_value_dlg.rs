@@ -0,0 +1,172 @@
+use std::{cell::Cell, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk::{self, prelude::*};
+use riker::actors::*;
+
+use opcua_client::prelude::*;
+
+use crate::{
+    model::ModelMessage,
+    type_dictionary::TypeDictionary,
+    value_edit::{self, WriteInfo},
+    value_editor::ValueEditor,
+    value_format::{self, TimeDisplay},
+};
+
+struct WriteValueDlgImpl {
+    model: ActorRef<ModelMessage>,
+    write_info: WriteInfo,
+    dlg: gtk::Dialog,
+    editor: ValueEditor,
+    status_label: gtk::Label,
+    closed: Cell<bool>,
+}
+
+/// A dialog which writes the value of a variable. The dialog stays open after writing so the
+/// result can be shown and further values written.
+pub(crate) struct WriteValueDlg {
+    data: Rc<WriteValueDlgImpl>,
+}
+
+impl WriteValueDlg {
+    pub fn new(
+        model: ActorRef<ModelMessage>,
+        parent: &gtk::ApplicationWindow,
+        write_info: WriteInfo,
+        type_dictionary: Arc<TypeDictionary>,
+    ) -> Self {
+        let dlg = gtk::Dialog::with_buttons(
+            Some(&format!("Write Value - {}", write_info.display_name)),
+            Some(parent),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Close", gtk::ResponseType::Close),
+                ("Write", gtk::ResponseType::Apply),
+            ],
+        );
+        dlg.set_default_response(gtk::ResponseType::Apply);
+
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(8);
+        grid.set_row_spacing(4);
+        grid.set_border_width(8);
+
+        let add_row = |top: i32, name: &str, value: &str| {
+            let name_label = gtk::Label::new(Some(name));
+            name_label.set_halign(gtk::Align::End);
+            let value_label = gtk::Label::new(Some(value));
+            value_label.set_halign(gtk::Align::Start);
+            value_label.set_selectable(true);
+            grid.attach(&name_label, 0, top, 1, 1);
+            grid.attach(&value_label, 1, top, 1, 1);
+        };
+
+        // Arrays can be thousands of elements long so only their type and size are shown
+        let current_value = write_info
+            .value
+            .as_ref()
+            .and_then(|v| v.value.as_ref())
+            .map(|v| match v {
+                Variant::Array(_) => value_format::variant_type_name(v),
+                v => value_format::variant_to_string(v, TimeDisplay::Utc),
+            })
+            .unwrap_or_default();
+        add_row(0, "Node:", &write_info.node_id.to_string());
+        add_row(
+            1,
+            "Data Type:",
+            &format!("{} ({})", write_info.data_type_name, write_info.data_type),
+        );
+        add_row(2, "Value Rank:", &write_info.value_rank.to_string());
+        add_row(3, "Current Value:", &current_value);
+
+        let editor = ValueEditor::new(&write_info, &type_dictionary);
+        let new_value_label = gtk::Label::new(Some("New Value:"));
+        new_value_label.set_halign(gtk::Align::End);
+        new_value_label.set_valign(gtk::Align::Start);
+        grid.attach(&new_value_label, 0, 4, 1, 1);
+        editor.attach(&grid, 4);
+
+        let status_label = gtk::Label::new(None);
+        status_label.set_halign(gtk::Align::Start);
+        status_label.set_line_wrap(true);
+        grid.attach(&status_label, 0, 6, 2, 1);
+
+        dlg.get_content_area().pack_start(&grid, true, true, 0);
+
+        let data = Rc::new(WriteValueDlgImpl {
+            model,
+            write_info,
+            dlg,
+            editor,
+            status_label,
+            closed: Cell::new(false),
+        });
+
+        if !data.write_info.is_writable() {
+            data.set_status(
+                false,
+                "The variable's AccessLevel / UserAccessLevel do not permit writing",
+            );
+        }
+
+        data.dlg
+            .connect_response(clone!(@weak data => move |dlg, response| {
+                if response == gtk::ResponseType::Apply {
+                    data.on_write_clicked();
+                } else {
+                    data.closed.set(true);
+                    dlg.close();
+                }
+            }));
+
+        Self { data }
+    }
+
+    pub fn show(&self) {
+        self.data.dlg.show_all();
+    }
+
+    pub fn close(&self) {
+        self.data.closed.set(true);
+        self.data.dlg.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.data.closed.get()
+    }
+
+    pub fn on_write_result(&self, node_id: &NodeId, status_code: StatusCode) {
+        if *node_id == self.data.write_info.node_id {
+            self.data.set_status(
+                status_code.is_good(),
+                &value_edit::describe_write_status(status_code),
+            );
+        }
+    }
+}
+
+impl WriteValueDlgImpl {
+    fn set_status(&self, good: bool, message: &str) {
+        let colour = if good { "darkgreen" } else { "red" };
+        self.status_label.set_markup(&format!(
+            "<span foreground=\"{}\">{}</span>",
+            colour,
+            glib::markup_escape_text(message)
+        ));
+    }
+
+    fn on_write_clicked(&self) {
+        match self.editor.value() {
+            Ok((value, index_range)) => {
+                self.set_status(true, "Writing...");
+                self.model.tell(
+                    ModelMessage::WriteValue(self.write_info.node_id.clone(), value, index_range),
+                    None,
+                );
+            }
+            Err(err) => self.set_status(false, &err),
+        }
+    }
+}